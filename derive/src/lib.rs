@@ -0,0 +1,115 @@
+//! Derive macro for [`tower-otel-http-metrics`] attribute extractors.
+//!
+//! Use through the `derive` feature of [`tower-otel-http-metrics`], which re-exports
+//! [`HttpMetricsAttributes`](macro@HttpMetricsAttributes).
+//!
+//! [`tower-otel-http-metrics`]: https://docs.rs/tower-otel-http-metrics
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr, Type};
+
+/// Derive `HttpMetricsAttributes`, recording each field of a struct as an attribute.
+///
+/// Field values must be `Clone` and convertible into an OTEL `Value`; `Option` fields are only
+/// recorded when they are `Some`. Fields are configured with the `http_metrics` attribute:
+///
+/// - `#[http_metrics(key = "app.plan")]` records the field under the given key instead of the field name
+/// - `#[http_metrics(skip)]` does not record the field
+#[proc_macro_derive(HttpMetricsAttributes, attributes(http_metrics))]
+pub fn derive_http_metrics_attributes(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "HttpMetricsAttributes can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "HttpMetricsAttributes can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut pushes = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().expect("named fields have identifiers");
+        let mut key = LitStr::new(&ident.to_string(), ident.span());
+        let mut skip = false;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("http_metrics"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("key") {
+                    key = meta.value()?.parse()?;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `key = \"...\"` or `skip`"))
+                }
+            })?;
+        }
+        if skip {
+            continue;
+        }
+
+        pushes.push(if is_option(&field.ty) {
+            quote! {
+                if let ::std::option::Option::Some(value) = &self.#ident {
+                    attributes.push(::tower_otel_http_metrics::__private::KeyValue::new(
+                        #key,
+                        ::std::clone::Clone::clone(value),
+                    ));
+                }
+            }
+        } else {
+            quote! {
+                attributes.push(::tower_otel_http_metrics::__private::KeyValue::new(
+                    #key,
+                    ::std::clone::Clone::clone(&self.#ident),
+                ));
+            }
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::tower_otel_http_metrics::HttpMetricsAttributes for #name #ty_generics #where_clause {
+            fn attributes(
+                &self,
+                attributes: &mut ::std::vec::Vec<::tower_otel_http_metrics::__private::KeyValue>,
+            ) {
+                #(#pushes)*
+            }
+        }
+    })
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
@@ -0,0 +1,93 @@
+//! Connection accept time propagation.
+//!
+//! Under backpressure, connections can wait in the listen backlog or for a free worker long
+//! before the first request on them reaches the middleware, and a duration measured from
+//! [`Service::call`] never sees that wait. [`AcceptTimeService`] wraps the per-connection service
+//! and stamps the time the connection was accepted into the request extensions, which the layer
+//! can use as the start of the request duration instead.
+
+use std::fmt;
+use std::result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use tower_service::Service;
+
+#[derive(Clone, Copy, Debug)]
+/// Request extension holding the time the request's connection was accepted.
+///
+/// Inserted by [`AcceptTimeService`] into the first request of each connection only: later
+/// requests on a keep-alive connection did not arrive at accept time.
+pub struct AcceptTime(Instant);
+
+impl AcceptTime {
+    /// The time the connection was accepted.
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+}
+
+#[derive(Clone)]
+/// Per-connection [`Service`] wrapper inserting [`AcceptTime`] into the connection's first request.
+///
+/// Create it with the accept time right after accepting the connection, and before
+/// handing the service to the connection:
+///
+/// ```
+/// use std::time::Instant;
+///
+/// use tower_otel_http_metrics::AcceptTimeService;
+///
+/// // called with the service for each connection, as soon as the connection is accepted
+/// fn on_accept<S>(service: S) -> AcceptTimeService<S> {
+///     AcceptTimeService::new(service, Instant::now())
+/// }
+/// ```
+pub struct AcceptTimeService<S> {
+    accept_time: AcceptTime,
+    // shared between clones, as connections may clone the service for each request
+    stamped: Arc<AtomicBool>,
+    inner_service: S,
+}
+
+impl<S> AcceptTimeService<S> {
+    /// Wrap the service of a connection accepted at `accepted_at`.
+    pub fn new(inner_service: S, accepted_at: Instant) -> Self {
+        AcceptTimeService {
+            accept_time: AcceptTime(accepted_at),
+            stamped: Arc::new(AtomicBool::new(false)),
+            inner_service,
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for AcceptTimeService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AcceptTimeService")
+            .field("accept_time", &self.accept_time)
+            .field("inner_service", &self.inner_service)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for AcceptTimeService<S>
+where
+    S: Service<http::Request<ReqBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        if !self.stamped.swap(true, Ordering::Relaxed) {
+            req.extensions_mut().insert(self.accept_time);
+        }
+        self.inner_service.call(req)
+    }
+}
@@ -0,0 +1,174 @@
+//! Alias names for instruments, recording the same measurements under additional names.
+//!
+//! During a dashboards migration, measurements may be needed under legacy names as well as the
+//! semconv ones. Instruments of the layer are created through a meter which builds each aliased
+//! instrument once per name and fans recordings out to all of them.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use opentelemetry::metrics::{
+    AsyncInstrument, AsyncInstrumentBuilder, Counter, Gauge, Histogram, HistogramBuilder,
+    InstrumentBuilder, InstrumentProvider, Meter, ObservableCounter, ObservableGauge,
+    ObservableUpDownCounter, SyncInstrument, UpDownCounter,
+};
+use opentelemetry::KeyValue;
+
+pub(crate) type MetricAliases = HashMap<Cow<'static, str>, Vec<Cow<'static, str>>>;
+
+/// Create a meter which records the instruments of `meter` under their aliases as well.
+pub(crate) fn aliased_meter(meter: Meter, aliases: MetricAliases) -> Meter {
+    Meter::new(Arc::new(AliasInstrumentProvider { meter, aliases }))
+}
+
+struct AliasInstrumentProvider {
+    meter: Meter,
+    aliases: MetricAliases,
+}
+
+impl AliasInstrumentProvider {
+    /// The name of an instrument followed by its aliases.
+    fn names(&self, name: Cow<'static, str>) -> Vec<Cow<'static, str>> {
+        let aliases = self.aliases.get(&name).cloned().unwrap_or_default();
+        std::iter::once(name).chain(aliases).collect()
+    }
+}
+
+/// Sync instrument recording each measurement into several instruments.
+struct FanOut<I>(Vec<I>);
+
+macro_rules! sync_instruments {
+    ($($method:ident: $builder:ident<$inst:ident<$value:ty>>, $record:ident;)*) => {
+        $(
+            impl SyncInstrument<$value> for FanOut<$inst<$value>> {
+                fn measure(&self, measurement: $value, attributes: &[KeyValue]) {
+                    for instrument in &self.0 {
+                        instrument.$record(measurement, attributes);
+                    }
+                }
+            }
+        )*
+
+        impl AliasInstrumentProvider {
+            $(
+                fn $method(&self, builder: $builder<'_, $inst<$value>>) -> $inst<$value> {
+                    let instruments = self
+                        .names(builder.name.clone())
+                        .into_iter()
+                        .map(|name| {
+                            let mut alias = self.meter.$method(name);
+                            if let Some(description) = builder.description.clone() {
+                                alias = alias.with_description(description);
+                            }
+                            if let Some(unit) = builder.unit.clone() {
+                                alias = alias.with_unit(unit);
+                            }
+                            sync_instruments!(@boundaries alias, builder, $builder).build()
+                        })
+                        .collect();
+                    $inst::new(Arc::new(FanOut(instruments)))
+                }
+            )*
+        }
+    };
+    (@boundaries $alias:ident, $builder:ident, HistogramBuilder) => {
+        match $builder.boundaries.clone() {
+            Some(boundaries) => $alias.with_boundaries(boundaries),
+            None => $alias,
+        }
+    };
+    (@boundaries $alias:ident, $builder:ident, InstrumentBuilder) => {
+        $alias
+    };
+}
+
+sync_instruments! {
+    u64_counter: InstrumentBuilder<Counter<u64>>, add;
+    f64_counter: InstrumentBuilder<Counter<f64>>, add;
+    i64_up_down_counter: InstrumentBuilder<UpDownCounter<i64>>, add;
+    f64_up_down_counter: InstrumentBuilder<UpDownCounter<f64>>, add;
+    u64_gauge: InstrumentBuilder<Gauge<u64>>, record;
+    f64_gauge: InstrumentBuilder<Gauge<f64>>, record;
+    i64_gauge: InstrumentBuilder<Gauge<i64>>, record;
+    f64_histogram: HistogramBuilder<Histogram<f64>>, record;
+    u64_histogram: HistogramBuilder<Histogram<u64>>, record;
+}
+
+macro_rules! async_instruments {
+    ($($method:ident: $inst:ident<$value:ty>;)*) => {
+        impl AliasInstrumentProvider {
+            $(
+                fn $method(
+                    &self,
+                    builder: AsyncInstrumentBuilder<'_, $inst<$value>, $value>,
+                ) -> $inst<$value> {
+                    // every instrument observes the values of all callbacks
+                    let callbacks = Arc::new(builder.callbacks);
+                    let mut instruments = self.names(builder.name).into_iter().map(|name| {
+                        let callbacks = callbacks.clone();
+                        let mut alias = self.meter.$method(name).with_callback(
+                            move |observer: &dyn AsyncInstrument<$value>| {
+                                for callback in callbacks.iter() {
+                                    callback(observer);
+                                }
+                            },
+                        );
+                        if let Some(description) = builder.description.clone() {
+                            alias = alias.with_description(description);
+                        }
+                        if let Some(unit) = builder.unit.clone() {
+                            alias = alias.with_unit(unit);
+                        }
+                        alias.build()
+                    });
+                    // observable instruments report through their callbacks alone
+                    let instrument = instruments.next().expect("names include the instrument name");
+                    instruments.for_each(drop);
+                    instrument
+                }
+            )*
+        }
+    };
+}
+
+async_instruments! {
+    u64_observable_counter: ObservableCounter<u64>;
+    f64_observable_counter: ObservableCounter<f64>;
+    i64_observable_up_down_counter: ObservableUpDownCounter<i64>;
+    f64_observable_up_down_counter: ObservableUpDownCounter<f64>;
+    u64_observable_gauge: ObservableGauge<u64>;
+    i64_observable_gauge: ObservableGauge<i64>;
+    f64_observable_gauge: ObservableGauge<f64>;
+}
+
+macro_rules! forward_instruments {
+    ($($method:ident: $builder:ty => $inst:ty;)*) => {
+        impl InstrumentProvider for AliasInstrumentProvider {
+            $(
+                fn $method(&self, builder: $builder) -> $inst {
+                    AliasInstrumentProvider::$method(self, builder)
+                }
+            )*
+        }
+    };
+}
+
+forward_instruments! {
+    u64_counter: InstrumentBuilder<'_, Counter<u64>> => Counter<u64>;
+    f64_counter: InstrumentBuilder<'_, Counter<f64>> => Counter<f64>;
+    i64_up_down_counter: InstrumentBuilder<'_, UpDownCounter<i64>> => UpDownCounter<i64>;
+    f64_up_down_counter: InstrumentBuilder<'_, UpDownCounter<f64>> => UpDownCounter<f64>;
+    u64_gauge: InstrumentBuilder<'_, Gauge<u64>> => Gauge<u64>;
+    f64_gauge: InstrumentBuilder<'_, Gauge<f64>> => Gauge<f64>;
+    i64_gauge: InstrumentBuilder<'_, Gauge<i64>> => Gauge<i64>;
+    f64_histogram: HistogramBuilder<'_, Histogram<f64>> => Histogram<f64>;
+    u64_histogram: HistogramBuilder<'_, Histogram<u64>> => Histogram<u64>;
+    u64_observable_counter: AsyncInstrumentBuilder<'_, ObservableCounter<u64>, u64> => ObservableCounter<u64>;
+    f64_observable_counter: AsyncInstrumentBuilder<'_, ObservableCounter<f64>, f64> => ObservableCounter<f64>;
+    i64_observable_up_down_counter: AsyncInstrumentBuilder<'_, ObservableUpDownCounter<i64>, i64> => ObservableUpDownCounter<i64>;
+    f64_observable_up_down_counter: AsyncInstrumentBuilder<'_, ObservableUpDownCounter<f64>, f64> => ObservableUpDownCounter<f64>;
+    u64_observable_gauge: AsyncInstrumentBuilder<'_, ObservableGauge<u64>, u64> => ObservableGauge<u64>;
+    i64_observable_gauge: AsyncInstrumentBuilder<'_, ObservableGauge<i64>, i64> => ObservableGauge<i64>;
+    f64_observable_gauge: AsyncInstrumentBuilder<'_, ObservableGauge<f64>, f64> => ObservableGauge<f64>;
+}
@@ -0,0 +1,253 @@
+//! Async attribute extractors, awaited before requests are forwarded to the inner service.
+//!
+//! Some attributes need async work before the request is handled, e.g. a lookup in a local cache
+//! keyed by an auth header. [`HTTPMetricsService`] forwards requests as soon as it is called, so
+//! async extractors are instead run by [`AsyncExtractorLayer`], created with
+//! [`HTTPMetricsLayer::async_extractor_layer`] and placed around the [`HTTPMetricsLayer`]. It
+//! awaits the extractors of each request concurrently, then hands their attributes on to
+//! `http.server.request.duration` through a request extension.
+//!
+//! Extractors share a time budget per request. Attributes of extractors still pending once it is
+//! spent are dropped and counted in `http.server.extractor.timeouts`, so a slow lookup delays
+//! requests by at most the budget. The budget is timed with tokio, which must drive the service.
+//!
+//! [`HTTPMetricsService`]: crate::HTTPMetricsService
+//! [`HTTPMetricsLayer`]: crate::HTTPMetricsLayer
+//! [`HTTPMetricsLayer::async_extractor_layer`]: crate::HTTPMetricsLayer::async_extractor_layer
+
+use std::fmt;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::result;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use opentelemetry::KeyValue;
+use pin_project_lite::pin_project;
+use tokio::time::{sleep, Sleep};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::binding::LayerBinding;
+use crate::extractor::{catch_extractor_panic, EXTRACTOR_KIND_REQUEST};
+use crate::HTTPMetricsLayerState;
+
+pub(crate) const HTTP_SERVER_EXTRACTOR_TIMEOUTS_METRIC: &str = "http.server.extractor.timeouts";
+pub(crate) const HTTP_SERVER_EXTRACTOR_TIMEOUTS_UNIT: &str = "{timeout}";
+
+pub(crate) const DEFAULT_ASYNC_EXTRACTOR_BUDGET: Duration = Duration::from_millis(10);
+
+type ExtractFuture = Pin<Box<dyn Future<Output = Vec<KeyValue>> + Send>>;
+pub(crate) type AsyncRequestAttributeExtractor =
+    dyn Fn(&http::request::Parts) -> ExtractFuture + Send + Sync;
+
+/// Request extension through which [`AsyncExtractorService`] hands the attributes of its
+/// extractors to [`HTTPMetricsService`].
+///
+/// [`HTTPMetricsService`]: crate::HTTPMetricsService
+#[derive(Clone)]
+pub(crate) struct AsyncAttributes(pub(crate) Vec<KeyValue>);
+
+#[derive(Clone)]
+/// [`Layer`] which runs async attribute extractors, created with
+/// [`HTTPMetricsLayer::async_extractor_layer`].
+///
+/// [`HTTPMetricsLayer::async_extractor_layer`]: crate::HTTPMetricsLayer::async_extractor_layer
+pub struct AsyncExtractorLayer {
+    binding: Arc<LayerBinding>,
+}
+
+#[derive(Clone)]
+/// [`Service`] used by [`AsyncExtractorLayer`]
+pub struct AsyncExtractorService<S> {
+    state: Arc<HTTPMetricsLayerState>,
+    binding: Arc<LayerBinding>,
+    generation: u64,
+    inner_service: S,
+}
+
+pin_project! {
+    /// Response [`Future`] for [`AsyncExtractorService`].
+    pub struct AsyncExtractorFuture<S, B, F> {
+        #[pin]
+        state: AsyncExtractorState<S, B, F>,
+    }
+}
+
+pin_project! {
+    #[project = AsyncExtractorStateProj]
+    enum AsyncExtractorState<S, B, F> {
+        Extracting {
+            // ready service taken from AsyncExtractorService::call
+            service: Option<S>,
+            request: Option<(http::request::Parts, B)>,
+            pending: Vec<Option<ExtractFuture>>,
+            labels: Vec<KeyValue>,
+            #[pin]
+            deadline: Sleep,
+            layer_state: Arc<HTTPMetricsLayerState>,
+        },
+        Calling {
+            #[pin]
+            future: F,
+        },
+    }
+}
+
+impl AsyncExtractorLayer {
+    pub(crate) fn new(binding: Arc<LayerBinding>) -> Self {
+        AsyncExtractorLayer { binding }
+    }
+}
+
+impl fmt::Debug for AsyncExtractorLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncExtractorLayer")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for AsyncExtractorService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncExtractorService")
+            .field("inner_service", &self.inner_service)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, B, F> fmt::Debug for AsyncExtractorFuture<S, B, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncExtractorFuture")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for AsyncExtractorLayer {
+    type Service = AsyncExtractorService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        let (generation, state) = self.binding.current();
+        AsyncExtractorService {
+            state,
+            binding: self.binding.clone(),
+            generation,
+            inner_service: service,
+        }
+    }
+}
+
+impl<S, B> Service<http::Request<B>> for AsyncExtractorService<S>
+where
+    S: Service<http::Request<B>> + Clone,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = AsyncExtractorFuture<S, B, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        self.binding.refresh(&mut self.generation, &mut self.state);
+
+        if self.state.async_attribute_extractors.is_empty() {
+            return AsyncExtractorFuture {
+                state: AsyncExtractorState::Calling {
+                    future: self.inner_service.call(req),
+                },
+            };
+        }
+
+        let (parts, body) = req.into_parts();
+        let pending = self
+            .state
+            .async_attribute_extractors
+            .iter()
+            .map(|extractor| {
+                catch_extractor_panic(
+                    &self.state.server_extractor_panics,
+                    EXTRACTOR_KIND_REQUEST,
+                    || extractor(&parts),
+                )
+            })
+            .collect();
+
+        // the service polled ready is the one called once extraction completes
+        let clone = self.inner_service.clone();
+        let service = mem::replace(&mut self.inner_service, clone);
+
+        AsyncExtractorFuture {
+            state: AsyncExtractorState::Extracting {
+                service: Some(service),
+                request: Some((parts, body)),
+                pending,
+                labels: Vec::new(),
+                deadline: sleep(self.state.async_extractor_budget),
+                layer_state: self.state.clone(),
+            },
+        }
+    }
+}
+
+impl<S, B> Future for AsyncExtractorFuture<S, B, S::Future>
+where
+    S: Service<http::Request<B>>,
+{
+    type Output = result::Result<S::Response, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.project().state;
+        loop {
+            match state.as_mut().project() {
+                AsyncExtractorStateProj::Extracting {
+                    service,
+                    request,
+                    pending,
+                    labels,
+                    deadline,
+                    layer_state,
+                } => {
+                    let mut still_pending = 0;
+                    for slot in pending.iter_mut() {
+                        let Some(future) = slot else {
+                            continue;
+                        };
+                        let poll = catch_extractor_panic(
+                            &layer_state.server_extractor_panics,
+                            EXTRACTOR_KIND_REQUEST,
+                            || future.as_mut().poll(cx),
+                        );
+                        match poll {
+                            Some(Poll::Pending) => still_pending += 1,
+                            Some(Poll::Ready(attributes)) => {
+                                labels.extend(attributes);
+                                *slot = None;
+                            }
+                            None => *slot = None,
+                        }
+                    }
+                    if still_pending > 0 {
+                        if deadline.poll(cx).is_pending() {
+                            return Poll::Pending;
+                        }
+                        layer_state
+                            .server_extractor_timeouts
+                            .add(still_pending, &[]);
+                    }
+
+                    let (mut parts, body) = request.take().expect("request is only forwarded once");
+                    parts.extensions.insert(AsyncAttributes(mem::take(labels)));
+                    let future = service
+                        .take()
+                        .expect("service is only called once")
+                        .call(http::Request::from_parts(parts, body));
+                    state.set(AsyncExtractorState::Calling { future });
+                }
+                AsyncExtractorStateProj::Calling { future } => return future.poll(cx),
+            }
+        }
+    }
+}
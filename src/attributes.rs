@@ -0,0 +1,529 @@
+//! Opt-in attributes and values derived from request and response data.
+//!
+//! These are not part of the OTEL HTTP semantic conventions; each one is enabled
+//! separately on the [`HTTPMetricsLayerBuilder`].
+//!
+//! [`HTTPMetricsLayerBuilder`]: crate::HTTPMetricsLayerBuilder
+
+use std::borrow::Cow;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::uri::Authority;
+use http::{Extensions, HeaderMap, Uri};
+use opentelemetry::{Key, KeyValue};
+
+pub(crate) const HTTP_CACHE_STATUS_LABEL: &str = "http.cache.status";
+
+pub(crate) const URL_PATH_LABEL: &str = "url.path";
+
+pub(crate) const HTTP_THROTTLE_POLICY_LABEL: &str = "http.server.throttle.policy";
+
+const URL_QUERY_LABEL_PREFIX: &str = "url.query.";
+
+pub(crate) const SERVER_ADDRESS_LABEL: &str = "server.address";
+pub(crate) const SERVER_PORT_LABEL: &str = "server.port";
+
+pub(crate) const NETWORK_PEER_ADDRESS_LABEL: &str = "network.peer.address";
+
+pub(crate) const GEO_COUNTRY_ISO_CODE_LABEL: &str = "geo.country.iso_code";
+pub(crate) const GEO_REGION_ISO_CODE_LABEL: &str = "geo.region.iso_code";
+
+/// Country headers set by CDNs and edge proxies, in order of precedence
+const GEO_COUNTRY_HEADERS: [&str; 5] = [
+    "cf-ipcountry",
+    "cloudfront-viewer-country",
+    "fastly-client-country",
+    "x-vercel-ip-country",
+    "x-appengine-country",
+];
+
+/// Region (ISO 3166-2 subdivision) headers set by CDNs and edge proxies, in order of precedence
+const GEO_REGION_HEADERS: [&str; 4] = [
+    "cf-region-code",
+    "cloudfront-viewer-country-region",
+    "fastly-client-region",
+    "x-vercel-ip-country-region",
+];
+
+#[cfg(feature = "user-agent")]
+pub(crate) const USER_AGENT_DEVICE_CATEGORY_LABEL: &str = "user_agent.device.category";
+
+pub(crate) const DEPLOYMENT_VARIANT_LABEL: &str = "deployment.variant";
+
+pub(crate) const ERROR_TYPE_LABEL: &str = "error.type";
+
+/// `error.type` of requests whose response future was dropped before the response
+pub(crate) const ERROR_TYPE_CANCELLED: &str = "cancelled";
+
+/// `error.type` of requests abandoned by the client, rather than failed by the server
+pub(crate) const ERROR_TYPE_CLIENT_ABORT: &str = "client_abort";
+
+pub(crate) const HTTP_AUTH_OUTCOME_LABEL: &str = "http.auth.outcome";
+pub(crate) const HTTP_AUTH_SCHEME_LABEL: &str = "http.auth.scheme";
+
+/// Attribute value used in place of values outside a bounded set, per semconv
+pub(crate) const OTHER_LABEL_VALUE: &str = "_OTHER";
+
+const CACHE_STATUS_HIT: &str = "hit";
+const CACHE_STATUS_MISS: &str = "miss";
+const CACHE_STATUS_STALE: &str = "stale";
+
+/// Normalize CDN and proxy cache headers into `hit`, `miss`, or `stale`.
+///
+/// Headers are consulted in order of specificity: `CF-Cache-Status`, then `X-Cache`,
+/// then `Age`. Returns `None` when no header identifies the cache status.
+pub(crate) fn cache_status(headers: &HeaderMap) -> Option<&'static str> {
+    if let Some(value) = header_str(headers, "cf-cache-status") {
+        return match value.trim().to_ascii_uppercase().as_str() {
+            "HIT" | "REVALIDATED" => Some(CACHE_STATUS_HIT),
+            "STALE" | "UPDATING" => Some(CACHE_STATUS_STALE),
+            "MISS" | "EXPIRED" | "BYPASS" | "DYNAMIC" => Some(CACHE_STATUS_MISS),
+            _ => None,
+        };
+    }
+
+    if let Some(value) = header_str(headers, "x-cache") {
+        // Multi-tier caches (e.g. Fastly shielding) list one status per tier,
+        // with the tier closest to the client last.
+        let edge = value
+            .rsplit(',')
+            .next()
+            .unwrap_or(value)
+            .to_ascii_lowercase();
+        if edge.contains("stale") {
+            return Some(CACHE_STATUS_STALE);
+        } else if edge.contains("hit") {
+            return Some(CACHE_STATUS_HIT);
+        } else if edge.contains("miss") {
+            return Some(CACHE_STATUS_MISS);
+        }
+    }
+
+    // A non-zero Age means the response was served from a cache
+    match header_str(headers, "age")?.trim().parse::<u64>() {
+        Ok(age) if age > 0 => Some(CACHE_STATUS_HIT),
+        _ => None,
+    }
+}
+
+/// Parse the quota limit and remaining quota from rate limit response headers.
+///
+/// Supports the `RateLimit-Limit` / `RateLimit-Remaining` fields of the IETF RateLimit header
+/// drafts as well as the widespread `X-RateLimit-*` variants. Quota policies following the
+/// limit (e.g. `100, 100;w=60`) are ignored.
+pub(crate) fn rate_limit(headers: &HeaderMap) -> (Option<u64>, Option<u64>) {
+    let parse = |name: &str, legacy_name: &str| {
+        let value = header_str(headers, name).or_else(|| header_str(headers, legacy_name))?;
+        value.split([',', ';']).next()?.trim().parse::<u64>().ok()
+    };
+    (
+        parse("ratelimit-limit", "x-ratelimit-limit"),
+        parse("ratelimit-remaining", "x-ratelimit-remaining"),
+    )
+}
+
+/// The `Content-Length` of a message.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ContentLength {
+    Absent,
+    Valid(u64),
+    Malformed,
+}
+
+/// Parse the `Content-Length` of a message, treating lengths above `max` as malformed.
+///
+/// Per RFC 9110 the field is a decimal length, repeated as a list or across fields only with
+/// identical values. Signs, whitespace within the digits, differing values and lengths which
+/// overflow a `u64` are malformed.
+pub(crate) fn content_length(headers: &HeaderMap, max: Option<u64>) -> ContentLength {
+    let mut length = None;
+    for value in headers.get_all(http::header::CONTENT_LENGTH) {
+        for item in value.as_bytes().split(|&b| b == b',') {
+            let item = item.trim_ascii();
+            if item.is_empty() || !item.iter().all(u8::is_ascii_digit) {
+                return ContentLength::Malformed;
+            }
+            let parsed = item.iter().try_fold(0u64, |length, &digit| {
+                length.checked_mul(10)?.checked_add(u64::from(digit - b'0'))
+            });
+            match parsed {
+                Some(parsed) if length.is_none_or(|length| length == parsed) => {
+                    length = Some(parsed);
+                }
+                _ => return ContentLength::Malformed,
+            }
+        }
+    }
+    match length {
+        None => ContentLength::Absent,
+        Some(length) if max.is_some_and(|max| length > max) => ContentLength::Malformed,
+        Some(length) => ContentLength::Valid(length),
+    }
+}
+
+/// Size of the byte range of a `206 Partial Content` response from its `Content-Range`, e.g. `500`
+/// for `bytes 0-499/1234`.
+///
+/// Multipart responses to requests for several ranges describe each range in its part instead,
+/// so they have no size here.
+pub(crate) fn content_range_size(headers: &HeaderMap) -> Option<u64> {
+    let range = header_str(headers, "content-range")?
+        .strip_prefix("bytes ")?
+        .split('/')
+        .next()?;
+    let (first, last) = range.split_once('-')?;
+    let first = first.trim().parse::<u64>().ok()?;
+    let last = last.trim().parse::<u64>().ok()?;
+    last.checked_sub(first)?.checked_add(1)
+}
+
+/// Whether a response throttled the request: a 429, or a 503 telling the client to retry later.
+pub(crate) fn throttled(status: http::StatusCode, headers: &HeaderMap) -> bool {
+    status == http::StatusCode::TOO_MANY_REQUESTS
+        || (status == http::StatusCode::SERVICE_UNAVAILABLE
+            && headers.contains_key(http::header::RETRY_AFTER))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Response extension naming the rate limiting policy which throttled a request.
+///
+/// Recorded as the `http.server.throttle.policy` attribute of `http.server.request.throttled`,
+/// enabled with [`with_throttled_requests_counter`]. Policy names should come from a small fixed
+/// set, such as the names of the limiter's configured policies.
+///
+/// ```
+/// use tower_otel_http_metrics::ThrottlePolicy;
+///
+/// let mut response = http::Response::new(());
+/// *response.status_mut() = http::StatusCode::TOO_MANY_REQUESTS;
+/// response.extensions_mut().insert(ThrottlePolicy::new("per-tenant"));
+/// ```
+///
+/// [`with_throttled_requests_counter`]: crate::HTTPMetricsLayerBuilder::with_throttled_requests_counter
+pub struct ThrottlePolicy(pub Cow<'static, str>);
+
+impl ThrottlePolicy {
+    /// Name the policy which throttled the request.
+    pub fn new(policy: impl Into<Cow<'static, str>>) -> Self {
+        ThrottlePolicy(policy.into())
+    }
+}
+
+/// Classify authentication failures as `unauthenticated` (401) or `forbidden` (403),
+/// along with the lowercased auth scheme challenged in `WWW-Authenticate`, if any.
+///
+/// Unrecognized schemes are reported as `_OTHER` to keep the attribute bounded.
+pub(crate) fn auth_outcome(
+    status: http::StatusCode,
+    headers: &HeaderMap,
+) -> Option<(&'static str, Option<&'static str>)> {
+    let outcome = match status {
+        http::StatusCode::UNAUTHORIZED => "unauthenticated",
+        http::StatusCode::FORBIDDEN => "forbidden",
+        _ => return None,
+    };
+
+    let scheme = header_str(headers, http::header::WWW_AUTHENTICATE.as_str())
+        .and_then(|challenge| challenge.split_whitespace().next())
+        .map(|scheme| {
+            let scheme = scheme.trim_end_matches(',').to_ascii_lowercase();
+            match scheme.as_str() {
+                "basic" => "basic",
+                "bearer" => "bearer",
+                "digest" => "digest",
+                "negotiate" => "negotiate",
+                "ntlm" => "ntlm",
+                "hoba" => "hoba",
+                "mutual" => "mutual",
+                "aws4-hmac-sha256" => "aws4-hmac-sha256",
+                "dpop" => "dpop",
+                _ => OTHER_LABEL_VALUE,
+            }
+        });
+
+    Some((outcome, scheme))
+}
+
+/// Push `geo.country.iso_code` and `geo.region.iso_code` labels from CDN geolocation headers.
+///
+/// Values must look like ISO 3166 codes (short and alphanumeric) and are uppercased;
+/// anything else is recorded as `_OTHER` so a misbehaving or spoofed header cannot
+/// inflate cardinality.
+pub(crate) fn push_client_geo_labels(headers: &HeaderMap, labels: &mut Vec<KeyValue>) {
+    let iso_code = |header_names: &[&str], max_len: usize| {
+        let value = header_names
+            .iter()
+            .find_map(|name| header_str(headers, name))?
+            .trim();
+        if !value.is_empty()
+            && value.len() <= max_len
+            && value.bytes().all(|b| b.is_ascii_alphanumeric())
+        {
+            Some(Cow::Owned(value.to_ascii_uppercase()))
+        } else {
+            Some(Cow::Borrowed(OTHER_LABEL_VALUE))
+        }
+    };
+    if let Some(country) = iso_code(&GEO_COUNTRY_HEADERS, 2) {
+        labels.push(KeyValue::new(GEO_COUNTRY_ISO_CODE_LABEL, country));
+    }
+    if let Some(region) = iso_code(&GEO_REGION_HEADERS, 3) {
+        labels.push(KeyValue::new(GEO_REGION_ISO_CODE_LABEL, region));
+    }
+}
+
+/// Push `server.address`, and `server.port` when given, from the authority of the request URI
+/// or else the `Host` header.
+pub(crate) fn push_server_address_labels(
+    uri: &Uri,
+    headers: &HeaderMap,
+    labels: &mut Vec<KeyValue>,
+) {
+    let host_header = || {
+        header_str(headers, http::header::HOST.as_str())?
+            .parse::<Authority>()
+            .ok()
+    };
+    let Some(authority) = uri.authority().cloned().or_else(host_header) else {
+        return;
+    };
+    labels.push(KeyValue::new(
+        SERVER_ADDRESS_LABEL,
+        authority.host().to_ascii_lowercase(),
+    ));
+    if let Some(port) = authority.port_u16() {
+        labels.push(KeyValue::new(SERVER_PORT_LABEL, i64::from(port)));
+    }
+}
+
+/// Push `network.peer.address` from the address of the client connection, when a request
+/// extension carries it.
+pub(crate) fn push_network_peer_label(extensions: &Extensions, labels: &mut Vec<KeyValue>) {
+    if let Some(peer) = extensions.get::<SocketAddr>() {
+        labels.push(KeyValue::new(
+            NETWORK_PEER_ADDRESS_LABEL,
+            peer.ip().to_string(),
+        ));
+    }
+}
+
+/// Classify the `User-Agent` request header as `desktop`, `mobile`, or `bot`.
+///
+/// Appliances, unrecognized agents, and requests without a `User-Agent` are classified as `other`.
+#[cfg(feature = "user-agent")]
+pub(crate) fn device_category(headers: &HeaderMap) -> &'static str {
+    let category = header_str(headers, http::header::USER_AGENT.as_str())
+        .and_then(|agent| woothee::parser::Parser::new().parse(agent))
+        .map(|result| result.category);
+    match category {
+        Some("pc") => "desktop",
+        Some("smartphone" | "mobilephone") => "mobile",
+        Some("crawler") => "bot",
+        _ => "other",
+    }
+}
+
+/// Parse the time a request spent queued upstream from the `X-Request-Start` header.
+///
+/// The header holds the Unix time the request arrived at the proxy, optionally prefixed with
+/// `t=` as set by nginx and Heroku. Proxies disagree on the unit, so it is inferred from the
+/// magnitude: seconds (with an optional fraction), milliseconds, microseconds, or nanoseconds.
+/// Returns `None` when the header is missing or unparseable, or when the start time is in the
+/// future (clock skew between the proxy and this host).
+pub(crate) fn queue_time(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = header_str(headers, "x-request-start")?.trim();
+    let value = value.strip_prefix("t=").unwrap_or(value);
+    let timestamp = value
+        .parse::<f64>()
+        .ok()
+        .filter(|t| t.is_finite() && *t > 0.0)?;
+    let seconds = if timestamp >= 1e17 {
+        timestamp / 1e9
+    } else if timestamp >= 1e14 {
+        timestamp / 1e6
+    } else if timestamp >= 1e11 {
+        timestamp / 1e3
+    } else {
+        timestamp
+    };
+    let start = UNIX_EPOCH + Duration::try_from_secs_f64(seconds).ok()?;
+    now.duration_since(start).ok()
+}
+
+/// Built-in `url.path` sanitizer which masks identifiers in path segments.
+///
+/// Segments made up entirely of digits are replaced with `{id}` and UUIDs are replaced
+/// with `{uuid}`, so `/users/42/files/6ba7b810-9dad-11d1-80b4-00c04fd430c8` is recorded as
+/// `/users/{id}/files/{uuid}`. Pass to [`with_url_path_attribute`] when paths only vary by
+/// such identifiers; otherwise provide a sanitizer which bounds the paths of your service.
+///
+/// [`with_url_path_attribute`]: crate::HTTPMetricsLayerBuilder::with_url_path_attribute
+pub fn mask_path_ids(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            if !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) {
+                "{id}"
+            } else if is_uuid(segment) {
+                "{uuid}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// An allowlisted query parameter recorded as a `url.query.<param>` attribute.
+#[derive(Clone, Debug)]
+pub(crate) struct QueryParamAttribute {
+    key: Key,
+    param: Cow<'static, str>,
+    allowed_values: Vec<Cow<'static, str>>,
+}
+
+impl QueryParamAttribute {
+    pub(crate) fn new(param: Cow<'static, str>, allowed_values: Vec<Cow<'static, str>>) -> Self {
+        QueryParamAttribute {
+            key: Key::new(format!("{URL_QUERY_LABEL_PREFIX}{param}")),
+            param,
+            allowed_values,
+        }
+    }
+}
+
+/// Where to read the traffic split variant of a request from.
+#[derive(Clone, Debug)]
+pub enum TrafficSplitSource {
+    /// A request header set by the load balancer or service mesh, e.g. `x-canary`.
+    Header(http::HeaderName),
+    /// An entry of the W3C `baggage` request header, e.g. `deployment.variant`.
+    Baggage(Cow<'static, str>),
+}
+
+/// The configured traffic split source with its allowed variants.
+#[derive(Clone, Debug)]
+pub(crate) struct TrafficSplitAttribute {
+    source: TrafficSplitSource,
+    allowed_values: Vec<Cow<'static, str>>,
+}
+
+impl TrafficSplitAttribute {
+    pub(crate) fn new(source: TrafficSplitSource, allowed_values: Vec<Cow<'static, str>>) -> Self {
+        TrafficSplitAttribute {
+            source,
+            allowed_values,
+        }
+    }
+}
+
+/// Push a `deployment.variant` label when the request carries a traffic split variant.
+///
+/// Variants outside the allowlist are recorded as `_OTHER`.
+pub(crate) fn push_traffic_split_label(
+    attribute: &TrafficSplitAttribute,
+    headers: &HeaderMap,
+    labels: &mut Vec<KeyValue>,
+) {
+    let value = match &attribute.source {
+        TrafficSplitSource::Header(name) => header_str(headers, name.as_str()).map(str::trim),
+        TrafficSplitSource::Baggage(key) => headers
+            .get_all("baggage")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(|member| {
+                // properties following the value (`key=value;prop`) are not part of the value
+                let member = member.split(';').next()?;
+                let (name, value) = member.split_once('=')?;
+                (name.trim() == key).then_some(value.trim())
+            }),
+    };
+    if let Some(value) = value {
+        let value = attribute
+            .allowed_values
+            .iter()
+            .find(|allowed| allowed.as_ref() == value)
+            .cloned()
+            .unwrap_or(Cow::Borrowed(OTHER_LABEL_VALUE));
+        labels.push(KeyValue::new(DEPLOYMENT_VARIANT_LABEL, value));
+    }
+}
+
+/// Push a label for each configured parameter present in the query string.
+///
+/// Only the first occurrence of a parameter is considered. Names and values are compared once
+/// percent-decoded, with `+` as a space, as form-encoded by browsers. Values outside the
+/// parameter's allowlist are recorded as `_OTHER`, so the attribute stays bounded whatever clients
+/// send.
+pub(crate) fn push_query_param_labels(
+    attributes: &[QueryParamAttribute],
+    query: Option<&str>,
+    labels: &mut Vec<KeyValue>,
+) {
+    let Some(query) = query else {
+        return;
+    };
+    for attribute in attributes {
+        let value = query.split('&').find_map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode_query_component(name) == attribute.param.as_ref())
+                .then(|| decode_query_component(value))
+        });
+        if let Some(value) = value {
+            let value = attribute
+                .allowed_values
+                .iter()
+                .find(|allowed| allowed.as_ref() == value)
+                .cloned()
+                .unwrap_or(Cow::Borrowed(OTHER_LABEL_VALUE));
+            labels.push(KeyValue::new(attribute.key.clone(), value));
+        }
+    }
+}
+
+/// Percent-decode a query string component, with `+` as a space.
+///
+/// Malformed escapes are kept as they are, and bytes which do not decode to UTF-8 are replaced,
+/// never matching an allowlisted value.
+fn decode_query_component(component: &str) -> Cow<'_, str> {
+    if !component.contains(['%', '+']) {
+        return Cow::Borrowed(component);
+    }
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = bytes
+                    .get(i + 1..i + 3)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+                match hex {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    Cow::Owned(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+fn is_uuid(segment: &str) -> bool {
+    segment.len() == 36
+        && segment.bytes().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name)?.to_str().ok()
+}
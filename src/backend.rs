@@ -0,0 +1,140 @@
+//! Backends of routed stacks, recorded as the `http.server.backend` attribute.
+//!
+//! A router such as `tower::steer::Steer` fans requests out to several inner services, so the
+//! metrics of the layer wrapping it mix all of them. The backend which served a request is named
+//! either up front, by building a layer per branch with
+//! [`HTTPMetricsLayerBuilder::with_backend`], or by the branch itself through the [`Backend`]
+//! response extension, which [`BackendLayer`] inserts into every response of the branch it wraps.
+//!
+//! [`HTTPMetricsLayerBuilder::with_backend`]: crate::HTTPMetricsLayerBuilder::with_backend
+
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use std::result;
+use std::task::{Context, Poll};
+
+use futures_util::ready;
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+pub(crate) const HTTP_SERVER_BACKEND_LABEL: &str = "http.server.backend";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Response extension naming the backend which served a request.
+///
+/// Recorded as the `http.server.backend` attribute of `http.server.request.duration`, taking
+/// precedence over the backend set with [`with_backend`]. Backend names should come from a small
+/// fixed set, as routes do.
+///
+/// ```
+/// use tower_otel_http_metrics::Backend;
+///
+/// let mut response = http::Response::new(());
+/// response.extensions_mut().insert(Backend::new("replica"));
+/// ```
+///
+/// [`with_backend`]: crate::HTTPMetricsLayerBuilder::with_backend
+pub struct Backend(pub Cow<'static, str>);
+
+impl Backend {
+    /// Name the backend which served the request.
+    pub fn new(backend: impl Into<Cow<'static, str>>) -> Self {
+        Backend(backend.into())
+    }
+}
+
+#[derive(Clone, Debug)]
+/// [`Layer`] inserting the [`Backend`] extension into the responses of the service it wraps.
+///
+/// Applied to each branch of a router, below the metrics layer wrapping the router:
+///
+/// ```
+/// use tower::Layer;
+/// use tower_otel_http_metrics::{BackendLayer, BackendService};
+///
+/// // the branches are then routed with e.g. `tower::steer::Steer::new(branches, pick)`
+/// fn branches<S>(primary: S, replica: S) -> Vec<BackendService<S>> {
+///     vec![
+///         BackendLayer::new("primary").layer(primary),
+///         BackendLayer::new("replica").layer(replica),
+///     ]
+/// }
+/// ```
+pub struct BackendLayer {
+    backend: Backend,
+}
+
+impl BackendLayer {
+    /// Create the layer naming the backend of the service it wraps.
+    pub fn new(backend: impl Into<Cow<'static, str>>) -> Self {
+        BackendLayer {
+            backend: Backend::new(backend),
+        }
+    }
+}
+
+impl<S> Layer<S> for BackendLayer {
+    type Service = BackendService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        BackendService {
+            backend: self.backend.clone(),
+            inner_service: service,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// [`Service`] used by [`BackendLayer`]
+pub struct BackendService<S> {
+    backend: Backend,
+    inner_service: S,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for BackendService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BackendResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        BackendResponseFuture {
+            inner_response_future: self.inner_service.call(req),
+            backend: Some(self.backend.clone()),
+        }
+    }
+}
+
+pin_project! {
+    /// Response [`Future`] for [`BackendService`].
+    #[derive(Debug)]
+    pub struct BackendResponseFuture<F> {
+        #[pin]
+        inner_response_future: F,
+        backend: Option<Backend>,
+    }
+}
+
+impl<F, ResBody, E> Future for BackendResponseFuture<F>
+where
+    F: Future<Output = result::Result<http::Response<ResBody>, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut result = ready!(this.inner_response_future.poll(cx));
+        if let (Ok(response), Some(backend)) = (&mut result, this.backend.take()) {
+            response.extensions_mut().insert(backend);
+        }
+        Poll::Ready(result)
+    }
+}
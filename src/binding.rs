@@ -0,0 +1,146 @@
+//! Re-binding the instruments of a layer to a new meter at runtime.
+//!
+//! Instruments are created once when the layer is built, so replacing the meter provider, e.g.
+//! on a hot-reload of the telemetry configuration, would otherwise leave services recording into
+//! the instruments of the old provider. The layer keeps its builder around to create new
+//! instruments, and services pick up the new instruments on their next request.
+//!
+//! When the layer was given a weak handle to its meter provider, services also detect the
+//! provider being dropped, after which the SDK no longer exports anything, and then switch to
+//! no-op instruments rather than keep recording into the dropped provider. A provider shut down
+//! explicitly while still referenced, e.g. with `SdkMeterProvider::shutdown`, cannot be told
+//! apart from a live one, so the caller unbinds the layer from it instead.
+//!
+//! With no-op meter detection enabled, meters which are no-ops from the start, such as the global
+//! meter taken before a provider was installed, are detected as well, and services then pass
+//! requests through without extracting or recording anything.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+
+use opentelemetry::metrics::{InstrumentProvider, Meter, MeterProvider};
+
+use crate::{HTTPMetricsLayerBuilder, HTTPMetricsLayerState};
+
+pub(crate) type WeakMeterProvider = Weak<dyn MeterProvider + Send + Sync>;
+
+/// The current instruments of a layer, shared by the layer and its services.
+pub(crate) struct LayerBinding {
+    builder: HTTPMetricsLayerBuilder,
+    state: RwLock<Arc<HTTPMetricsLayerState>>,
+    // bumped whenever the state is replaced, so services only take the lock after a rebind
+    generation: AtomicU64,
+}
+
+/// Meter whose instruments record nothing, bound once the meter provider is gone.
+pub(crate) struct NoopInstruments;
+
+impl InstrumentProvider for NoopInstruments {}
+
+const HTTP_SERVER_METER_PROBE_METRIC: &str = "http.server.meter.probe";
+
+/// Whether the instruments of `meter` record nothing.
+///
+/// The OTEL API offers no way to tell a no-op meter apart, so the meter is probed with an
+/// observable instrument whose callback holds a reference observed here: no-op meters, and SDK
+/// meters without any reader, drop the callbacks of observable instruments right away. The API
+/// cannot unregister the probe, but its callback never observes a value, so SDKs never export it.
+/// Meters dropping the probe otherwise, e.g. through an SDK view, are taken for no-ops as well.
+pub(crate) fn is_noop_meter(meter: &Meter) -> bool {
+    let probe = Arc::new(());
+    let callback_probe = probe.clone();
+    let _gauge = meter
+        .u64_observable_gauge(HTTP_SERVER_METER_PROBE_METRIC)
+        .with_callback(move |_| {
+            let _ = &callback_probe;
+        })
+        .build();
+    Arc::strong_count(&probe) == 1
+}
+
+impl LayerBinding {
+    pub(crate) fn new(builder: HTTPMetricsLayerBuilder, state: HTTPMetricsLayerState) -> Self {
+        LayerBinding {
+            builder,
+            state: RwLock::new(Arc::new(state)),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// The builder the layer was built from.
+    #[cfg(feature = "diagnostics")]
+    pub(crate) fn builder(&self) -> &HTTPMetricsLayerBuilder {
+        &self.builder
+    }
+
+    /// Current generation and state.
+    pub(crate) fn current(&self) -> (u64, Arc<HTTPMetricsLayerState>) {
+        let state = self.state.read().unwrap_or_else(|err| err.into_inner());
+        (self.generation.load(Ordering::Acquire), state.clone())
+    }
+
+    /// Replace the state held by a service when the layer has been rebound since.
+    pub(crate) fn refresh(&self, generation: &mut u64, state: &mut Arc<HTTPMetricsLayerState>) {
+        if state
+            .meter_provider
+            .as_ref()
+            .is_some_and(|provider| provider.strong_count() == 0)
+        {
+            let noop_state = HTTPMetricsLayerState {
+                #[cfg(feature = "diagnostics")]
+                meter_provider_dropped: true,
+                ..self.noop_state()
+            };
+            self.replace(Some(state), noop_state);
+        }
+        if self.generation.load(Ordering::Acquire) != *generation {
+            (*generation, *state) = self.current();
+        }
+    }
+
+    /// Create the instruments of the layer from `meter`, tracking `provider` when given.
+    pub(crate) fn rebind(&self, meter: Meter, provider: Option<WeakMeterProvider>) {
+        // dry runs record into their summary regardless of the meter
+        if self.builder.dry_run {
+            return;
+        }
+        let mut state = self
+            .builder
+            .make_state(&self.builder.aliased(meter.clone()));
+        state.pass_through = self.builder.passes_through(&meter);
+        state.meter_provider = provider;
+        self.replace(None, state);
+    }
+
+    /// Switch to no-op instruments, e.g. once the meter provider has been shut down.
+    pub(crate) fn unbind(&self) {
+        if self.builder.dry_run {
+            return;
+        }
+        let noop_state = HTTPMetricsLayerState {
+            #[cfg(feature = "diagnostics")]
+            meter_provider_unbound: true,
+            ..self.noop_state()
+        };
+        self.replace(None, noop_state);
+    }
+
+    /// State of the layer with no-op instruments.
+    fn noop_state(&self) -> HTTPMetricsLayerState {
+        let mut state = self
+            .builder
+            .make_state(&Meter::new(Arc::new(NoopInstruments)));
+        state.pass_through = self.builder.may_pass_through();
+        state
+    }
+
+    /// Replace the state, unless `expected` is given and no longer the current state.
+    fn replace(&self, expected: Option<&Arc<HTTPMetricsLayerState>>, state: HTTPMetricsLayerState) {
+        let mut current = self.state.write().unwrap_or_else(|err| err.into_inner());
+        if expected.is_some_and(|expected| !Arc::ptr_eq(expected, &current)) {
+            return;
+        }
+        *current = Arc::new(state);
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+}
@@ -0,0 +1,409 @@
+//! Response body wrapper used by [`ResponseBodyMetricsLayer`] to observe responses as they are
+//! streamed.
+//!
+//! [`HTTPMetricsService`] leaves the response type of the inner service untouched. Metrics which
+//! need the response body, to read its exact size hint or to observe it as it is streamed, are
+//! instead applied by [`ResponseBodyMetricsLayer`], which wraps response bodies in
+//! [`HTTPMetricsResponseBody`] and belongs around an [`HTTPMetricsLayer`] built from the same
+//! builder, e.g. around the whole router or service.
+//!
+//! The [`HTTPMetricsService`] inside hands the attributes of each response and the metrics it
+//! needs observed to the body layer through a request extension. Responses which no body metric
+//! needs to observe are passed through untouched.
+//!
+//! [`HTTPMetricsLayer`]: crate::HTTPMetricsLayer
+//! [`HTTPMetricsService`]: crate::HTTPMetricsService
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::result;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use bytes::Buf;
+use futures_util::ready;
+use http_body::{Body, Frame, SizeHint};
+use opentelemetry::KeyValue;
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::grpc::GrpcMessageCounter;
+use crate::labels::Labels;
+use crate::{HTTPMetricsLayerState, NETWORK_IO_DIRECTION_LABEL, NETWORK_IO_DIRECTION_TRANSMIT};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// Where the size recorded into `http.server.response.body.size` comes from,
+/// set with [`HTTPMetricsLayerBuilder::with_response_body_size_source`].
+///
+/// The exact size reported by the body through [`Body::size_hint`] is used whenever
+/// the primary source is missing, as it costs nothing either. Sizes other than those read from
+/// the `Content-Length` require the layer returned by [`HTTPMetricsLayer::response_body_layer`].
+///
+/// [`HTTPMetricsLayerBuilder::with_response_body_size_source`]: crate::HTTPMetricsLayerBuilder::with_response_body_size_source
+/// [`HTTPMetricsLayer::response_body_layer`]: crate::HTTPMetricsLayer::response_body_layer
+pub enum ResponseBodySizeSource {
+    /// The exact size hint of the body, else bytes counted by wrapping the body as it is streamed.
+    #[default]
+    Body,
+    /// The response `Content-Length` header, else the exact size hint of the body. Responses
+    /// with neither are not recorded, so the body is never wrapped to count its size.
+    ContentLength,
+    /// The response `Content-Length` header, else the exact size hint of the body, else bytes
+    /// counted by wrapping the body as it is streamed.
+    ContentLengthOrBody,
+}
+
+#[derive(Clone, Debug)]
+/// [`Layer`] which applies response body metrics, created with
+/// [`HTTPMetricsLayer::response_body_layer`].
+///
+/// [`HTTPMetricsLayer::response_body_layer`]: crate::HTTPMetricsLayer::response_body_layer
+pub struct ResponseBodyMetricsLayer {
+    _private: (),
+}
+
+#[derive(Clone, Debug)]
+/// [`Service`] used by [`ResponseBodyMetricsLayer`]
+pub struct ResponseBodyMetricsService<S> {
+    inner_service: S,
+}
+
+pin_project! {
+    /// Response future for [`ResponseBodyMetricsService`].
+    pub struct ResponseBodyMetricsFuture<F> {
+        #[pin]
+        inner_response_future: F,
+        link: ResponseBodyLink,
+    }
+}
+
+/// Request extension through which [`HTTPMetricsService`] hands the body metrics state of a
+/// response to [`ResponseBodyMetricsService`].
+///
+/// [`HTTPMetricsService`]: crate::HTTPMetricsService
+#[derive(Clone, Default)]
+pub(crate) struct ResponseBodyLink(Arc<Mutex<Option<ResponseBodyMetricsState>>>);
+
+impl ResponseBodyLink {
+    pub(crate) fn set(&self, metrics_state: ResponseBodyMetricsState) {
+        *self.0.lock().unwrap_or_else(|err| err.into_inner()) = Some(metrics_state);
+    }
+
+    fn take(&self) -> Option<ResponseBodyMetricsState> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).take()
+    }
+}
+
+pin_project! {
+    /// Response body for [`ResponseBodyMetricsService`].
+    ///
+    /// Frames are passed through unchanged; only when a response needs body metrics which
+    /// are not known up front, e.g. the size of a streamed body, are they observed as they are
+    /// polled. Other bodies are passed through as they are, at no cost beyond the wrapper type.
+    pub struct HTTPMetricsResponseBody<B> {
+        #[pin]
+        kind: ResponseBodyKind<B>,
+    }
+}
+
+pin_project! {
+    #[project = ResponseBodyKindProj]
+    enum ResponseBodyKind<B> {
+        PassThrough {
+            #[pin]
+            inner_body: B,
+        },
+        Observed {
+            #[pin]
+            inner_body: B,
+            metrics_state: ResponseBodyMetricsState,
+        },
+    }
+}
+
+/// ResponseBodyMetricsState holds the data needed to record response body metrics
+/// once the response future has completed and handed the body off to be streamed.
+///
+/// The size of the body is taken from its exact size hint, or when it has none, counted here.
+/// Per-body totals are recorded when the state is dropped, which covers both bodies
+/// streamed to completion and bodies abandoned partway through (e.g. client disconnect).
+pub(crate) struct ResponseBodyMetricsState {
+    layer_state: Arc<HTTPMetricsLayerState>,
+    labels: Labels,
+    observers: ResponseBodyObservers,
+    frames: u64,
+    size: Option<u64>,
+    grpc_messages: GrpcMessageCounter,
+}
+
+/// Which response body metrics are observed for a response.
+#[derive(Default)]
+pub(crate) struct ResponseBodyObservers {
+    // record the exact size hint of the body, else count its size when `count_size` is set
+    pub(crate) size_hint: bool,
+    pub(crate) count_size: bool,
+    pub(crate) frame_metrics: bool,
+    // method and route labels of the response bytes counter, when enabled
+    pub(crate) bytes_labels: Option<Labels>,
+    pub(crate) count_network_io: bool,
+    // gRPC call labels of the responses per RPC histogram, when enabled
+    pub(crate) grpc_labels: Option<Labels>,
+}
+
+impl ResponseBodyObservers {
+    /// Whether any metrics need the response body to be observed.
+    pub(crate) fn any(&self) -> bool {
+        self.size_hint
+            || self.count_size
+            || self.frame_metrics
+            || self.bytes_labels.is_some()
+            || self.count_network_io
+            || self.grpc_labels.is_some()
+    }
+}
+
+impl ResponseBodyMetricsLayer {
+    pub(crate) fn new() -> Self {
+        ResponseBodyMetricsLayer { _private: () }
+    }
+}
+
+impl<F> fmt::Debug for ResponseBodyMetricsFuture<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseBodyMetricsFuture")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for ResponseBodyMetricsLayer {
+    type Service = ResponseBodyMetricsService<S>;
+
+    fn layer(&self, inner_service: S) -> Self::Service {
+        ResponseBodyMetricsService { inner_service }
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for ResponseBodyMetricsService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+    ResBody: Body,
+{
+    type Response = http::Response<HTTPMetricsResponseBody<ResBody>>;
+    type Error = S::Error;
+    type Future = ResponseBodyMetricsFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        let link = ResponseBodyLink::default();
+        req.extensions_mut().insert(link.clone());
+        ResponseBodyMetricsFuture {
+            inner_response_future: self.inner_service.call(req),
+            link,
+        }
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseBodyMetricsFuture<F>
+where
+    F: Future<Output = result::Result<http::Response<ResBody>, E>>,
+    ResBody: Body,
+{
+    type Output = result::Result<http::Response<HTTPMetricsResponseBody<ResBody>>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let response = ready!(this.inner_response_future.poll(cx))?;
+        let metrics_state = this
+            .link
+            .take()
+            .and_then(|metrics_state| metrics_state.start(response.body().size_hint()));
+        Poll::Ready(Ok(
+            response.map(|body| HTTPMetricsResponseBody::new(body, metrics_state))
+        ))
+    }
+}
+
+impl<B: fmt::Debug> fmt::Debug for HTTPMetricsResponseBody<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HTTPMetricsResponseBody")
+            .field("inner_body", self.inner_body())
+            .field(
+                "observed",
+                &matches!(self.kind, ResponseBodyKind::Observed { .. }),
+            )
+            .finish()
+    }
+}
+
+impl<B> HTTPMetricsResponseBody<B> {
+    fn new(inner_body: B, metrics_state: Option<ResponseBodyMetricsState>) -> Self {
+        let kind = match metrics_state {
+            Some(metrics_state) => ResponseBodyKind::Observed {
+                inner_body,
+                metrics_state,
+            },
+            None => ResponseBodyKind::PassThrough { inner_body },
+        };
+        HTTPMetricsResponseBody { kind }
+    }
+
+    fn inner_body(&self) -> &B {
+        match &self.kind {
+            ResponseBodyKind::PassThrough { inner_body }
+            | ResponseBodyKind::Observed { inner_body, .. } => inner_body,
+        }
+    }
+}
+
+impl ResponseBodyMetricsState {
+    pub(crate) fn new(
+        layer_state: Arc<HTTPMetricsLayerState>,
+        labels: Labels,
+        observers: ResponseBodyObservers,
+    ) -> Self {
+        ResponseBodyMetricsState {
+            layer_state,
+            labels,
+            size: None,
+            observers,
+            frames: 0,
+            grpc_messages: GrpcMessageCounter::default(),
+        }
+    }
+
+    /// Settle the metrics the exact size hint of the body answers up front, returning the state
+    /// only when metrics are left to observe as the body is streamed.
+    fn start(mut self, size_hint: SizeHint) -> Option<Self> {
+        match size_hint.exact() {
+            Some(size) => {
+                if self.observers.size_hint {
+                    self.size = Some(size);
+                }
+                self.observers.count_size = false;
+                if let (true, Some(server_network_io)) = (
+                    self.observers.count_network_io,
+                    &self.layer_state.server_network_io,
+                ) {
+                    server_network_io.add(
+                        size,
+                        &[KeyValue::new(
+                            NETWORK_IO_DIRECTION_LABEL,
+                            NETWORK_IO_DIRECTION_TRANSMIT,
+                        )],
+                    );
+                }
+                self.observers.count_network_io = false;
+            }
+            None if self.observers.count_size => self.size = Some(0),
+            None => {}
+        }
+        let observers = &self.observers;
+        // a state which is not returned is dropped here, recording the size it was given
+        (observers.count_size
+            || observers.frame_metrics
+            || observers.bytes_labels.is_some()
+            || observers.count_network_io
+            || observers.grpc_labels.is_some())
+        .then_some(self)
+    }
+
+    fn observe_data_frame<D: Buf>(&mut self, data: &D) {
+        let frame_size = data.remaining() as u64;
+        self.frames += 1;
+        if let (true, Some(size)) = (self.observers.count_size, &mut self.size) {
+            *size += frame_size;
+        }
+        if let (true, Some(server_response_body_frame_size)) = (
+            self.observers.frame_metrics,
+            &self.layer_state.server_response_body_frame_size,
+        ) {
+            server_response_body_frame_size.record(frame_size, &self.labels);
+        }
+        if let (Some(bytes_labels), Some(server_response_body_bytes)) = (
+            &self.observers.bytes_labels,
+            &self.layer_state.server_response_body_bytes,
+        ) {
+            server_response_body_bytes.add(frame_size, bytes_labels);
+        }
+        if self.observers.grpc_labels.is_some() {
+            self.grpc_messages.observe(data);
+        }
+        if let (true, Some(server_network_io)) = (
+            self.observers.count_network_io,
+            &self.layer_state.server_network_io,
+        ) {
+            server_network_io.add(
+                frame_size,
+                &[KeyValue::new(
+                    NETWORK_IO_DIRECTION_LABEL,
+                    NETWORK_IO_DIRECTION_TRANSMIT,
+                )],
+            );
+        }
+    }
+}
+
+impl Drop for ResponseBodyMetricsState {
+    fn drop(&mut self) {
+        if let (true, Some(server_response_body_frames)) = (
+            self.observers.frame_metrics,
+            &self.layer_state.server_response_body_frames,
+        ) {
+            server_response_body_frames.record(self.frames, &self.labels);
+        }
+        if let (Some(size), Some(server_response_body_size)) =
+            (self.size, &self.layer_state.server_response_body_size)
+        {
+            server_response_body_size.record(size, &self.labels);
+        }
+        if let (Some(grpc_labels), Some(rpc_server_responses_per_rpc)) = (
+            &self.observers.grpc_labels,
+            &self.layer_state.rpc_server_responses_per_rpc,
+        ) {
+            rpc_server_responses_per_rpc.record(self.grpc_messages.messages(), grpc_labels);
+        }
+    }
+}
+
+impl<B> Body for HTTPMetricsResponseBody<B>
+where
+    B: Body,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        match self.project().kind.project() {
+            ResponseBodyKindProj::PassThrough { inner_body } => inner_body.poll_frame(cx),
+            ResponseBodyKindProj::Observed {
+                inner_body,
+                metrics_state,
+            } => {
+                let frame = ready!(inner_body.poll_frame(cx));
+                if let Some(Ok(frame)) = &frame {
+                    if let Some(data) = frame.data_ref() {
+                        metrics_state.observe_data_frame(data);
+                    }
+                }
+                Poll::Ready(frame)
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner_body().is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner_body().size_hint()
+    }
+}
@@ -0,0 +1,255 @@
+//! Queue depth metrics for [`tower::buffer::BufferLayer`].
+//!
+//! [`Buffer`] does not expose how many requests are waiting on its worker, so
+//! [`BufferMetricsLayer`] tracks the backlog itself: requests are counted as enqueued when
+//! they are handed to the [`Buffer`] and as dequeued when the worker calls the inner service,
+//! or once they can no longer be, because their response future was dropped while waiting or the
+//! worker has shut down.
+//!
+//! [`tower::buffer::BufferLayer`]: tower::buffer::BufferLayer
+
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::result;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_util::ready;
+use opentelemetry::metrics::{Meter, ObservableGauge};
+use pin_project_lite::pin_project;
+use tower::buffer::Buffer;
+use tower::BoxError;
+use tower_layer::Layer;
+use tower_service::Service;
+
+const HTTP_SERVER_BUFFER_QUEUE_DEPTH_METRIC: &str = "http.server.buffer.queue_depth";
+const HTTP_SERVER_BUFFER_QUEUE_DEPTH_UNIT: &str = "{request}";
+
+/// [`Layer`] which applies a [`Buffer`] and reports the number of requests waiting in its queue
+pub struct BufferMetricsLayer<Request> {
+    bound: usize,
+    queue_depth: Arc<AtomicU64>,
+    _queue_depth_gauge: ObservableGauge<u64>,
+    _request: PhantomData<fn(Request)>,
+}
+
+#[derive(Clone)]
+/// [`Service`] used by [`BufferMetricsLayer`]
+pub struct BufferMetricsService<S> {
+    queue_depth: Arc<AtomicU64>,
+    inner_service: S,
+}
+
+/// Service driven by the [`Buffer`] worker, marking requests as dequeued when called.
+struct DequeuedService<S> {
+    inner_service: S,
+}
+
+/// Request waiting in the queue of a [`BufferMetricsService`].
+pub struct QueuedRequest<Request> {
+    request: Request,
+    guard: QueueGuard,
+}
+
+/// Place of a request in the queue, shared by the request and its response future.
+struct QueueSlot {
+    queue_depth: Arc<AtomicU64>,
+    dequeued: AtomicBool,
+}
+
+/// Handle on a [`QueueSlot`] which marks the request as dequeued when dropped.
+struct QueueGuard(Arc<QueueSlot>);
+
+impl QueueGuard {
+    fn enqueue(queue_depth: &Arc<AtomicU64>) -> Self {
+        queue_depth.fetch_add(1, Ordering::Relaxed);
+        QueueGuard(Arc::new(QueueSlot {
+            queue_depth: queue_depth.clone(),
+            dequeued: AtomicBool::new(false),
+        }))
+    }
+
+    /// Mark the request as no longer waiting, once however many times it is called.
+    fn dequeue(&self) {
+        if !self.0.dequeued.swap(true, Ordering::AcqRel) {
+            self.0.queue_depth.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Clone for QueueGuard {
+    fn clone(&self) -> Self {
+        QueueGuard(self.0.clone())
+    }
+}
+
+impl Drop for QueueGuard {
+    fn drop(&mut self) {
+        self.dequeue();
+    }
+}
+
+pin_project! {
+    /// Response [`Future`] for [`BufferMetricsService`].
+    pub struct BufferMetricsFuture<F> {
+        #[pin]
+        inner_response_future: F,
+        guard: QueueGuard,
+    }
+}
+
+impl<Request> BufferMetricsLayer<Request> {
+    /// Create a buffer holding up to `bound` requests,
+    /// registering the `http.server.buffer.queue_depth` gauge with the given meter.
+    ///
+    /// As with [`tower::buffer::BufferLayer`], layering spawns the buffer worker
+    /// onto the current tokio runtime.
+    ///
+    /// [`tower::buffer::BufferLayer`]: tower::buffer::BufferLayer
+    pub fn new(meter: &Meter, bound: usize) -> Self {
+        let queue_depth = Arc::new(AtomicU64::new(0));
+
+        let observed_queue_depth = queue_depth.clone();
+        let queue_depth_gauge = meter
+            .u64_observable_gauge(HTTP_SERVER_BUFFER_QUEUE_DEPTH_METRIC)
+            .with_description("Number of requests waiting in the buffer queue.")
+            .with_unit(HTTP_SERVER_BUFFER_QUEUE_DEPTH_UNIT)
+            .with_callback(move |observer| {
+                observer.observe(observed_queue_depth.load(Ordering::Relaxed), &[]);
+            })
+            .build();
+
+        BufferMetricsLayer {
+            bound,
+            queue_depth,
+            _queue_depth_gauge: queue_depth_gauge,
+            _request: PhantomData,
+        }
+    }
+}
+
+impl<Request> Clone for BufferMetricsLayer<Request> {
+    fn clone(&self) -> Self {
+        BufferMetricsLayer {
+            bound: self.bound,
+            queue_depth: self.queue_depth.clone(),
+            _queue_depth_gauge: self._queue_depth_gauge.clone(),
+            _request: PhantomData,
+        }
+    }
+}
+
+impl<Request> fmt::Debug for BufferMetricsLayer<Request> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferMetricsLayer")
+            .field("bound", &self.bound)
+            .field("queue_depth", &self.queue_depth.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for BufferMetricsService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferMetricsService")
+            .field("queue_depth", &self.queue_depth.load(Ordering::Relaxed))
+            .field("inner_service", &self.inner_service)
+            .finish()
+    }
+}
+
+impl<S, Request> Layer<S> for BufferMetricsLayer<Request>
+where
+    S: Service<Request> + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError> + Send + Sync,
+    Request: Send + 'static,
+{
+    type Service = BufferMetricsService<Buffer<QueuedRequest<Request>, S::Future>>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        let dequeued_service = DequeuedService {
+            inner_service: service,
+        };
+        BufferMetricsService {
+            queue_depth: self.queue_depth.clone(),
+            inner_service: Buffer::new(dequeued_service, self.bound),
+        }
+    }
+}
+
+impl<S, Request> Service<Request> for BufferMetricsService<S>
+where
+    S: Service<QueuedRequest<Request>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BufferMetricsFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        // the request may be dropped unprocessed, by the worker skipping it once its response
+        // future is gone or by a closed buffer, so both of them hold the guard
+        let guard = QueueGuard::enqueue(&self.queue_depth);
+        BufferMetricsFuture {
+            inner_response_future: self.inner_service.call(QueuedRequest {
+                request: req,
+                guard: guard.clone(),
+            }),
+            guard,
+        }
+    }
+}
+
+impl<F, T, E> Future for BufferMetricsFuture<F>
+where
+    F: Future<Output = result::Result<T, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.inner_response_future.poll(cx));
+        this.guard.dequeue();
+        Poll::Ready(result)
+    }
+}
+
+impl<F> fmt::Debug for BufferMetricsFuture<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferMetricsFuture")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<Request: fmt::Debug> fmt::Debug for QueuedRequest<Request> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueuedRequest")
+            .field("request", &self.request)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, Request> Service<QueuedRequest<Request>> for DequeuedService<S>
+where
+    S: Service<Request>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: QueuedRequest<Request>) -> Self::Future {
+        let QueuedRequest { request, guard } = req;
+        guard.dequeue();
+        self.inner_service.call(request)
+    }
+}
@@ -0,0 +1,2668 @@
+//! Builder of [`HTTPMetricsLayer`], and the instruments and state it creates for the layer.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "async-extractor")]
+use std::future::Future;
+use std::string::String;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "diagnostics")]
+use std::time::Instant;
+
+use opentelemetry::metrics::{Meter, MeterProvider};
+#[cfg(feature = "async-extractor")]
+use opentelemetry::KeyValue;
+use opentelemetry::{global, Key, StringValue};
+
+use crate::alias::{aliased_meter, MetricAliases};
+#[cfg(feature = "async-extractor")]
+use crate::async_extractor::{
+    AsyncRequestAttributeExtractor, DEFAULT_ASYNC_EXTRACTOR_BUDGET,
+    HTTP_SERVER_EXTRACTOR_TIMEOUTS_METRIC, HTTP_SERVER_EXTRACTOR_TIMEOUTS_UNIT,
+};
+use crate::attributes::{QueryParamAttribute, TrafficSplitAttribute};
+use crate::binding::{is_noop_meter, LayerBinding, WeakMeterProvider};
+#[cfg(feature = "dashboard")]
+use crate::capture::CapturedInstruments;
+#[cfg(feature = "cardinality")]
+use crate::cardinality::{CardinalityEstimator, CardinalityWarning};
+use crate::checkpoint::{
+    HTTP_SERVER_REQUEST_STAGE_DURATION_METRIC, HTTP_SERVER_REQUEST_STAGE_DURATION_UNIT,
+};
+#[cfg(feature = "tower-http")]
+use crate::classify::{make_failure_classifier, MakeFailureClassifier};
+use crate::clock;
+#[cfg(feature = "dashboard")]
+use crate::dashboard::grafana_dashboard;
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::{LastCollection, HTTP_SERVER_DIAGNOSTICS_COLLECTION_INSTRUMENT};
+use crate::extractor::{
+    split_attribute_extractors, DEFAULT_MAX_EXTRACTOR_ATTRIBUTES,
+    HTTP_SERVER_EXTRACTOR_ATTRIBUTES_DROPPED_METRIC, HTTP_SERVER_EXTRACTOR_ATTRIBUTES_DROPPED_UNIT,
+    HTTP_SERVER_EXTRACTOR_PANICS_METRIC, HTTP_SERVER_EXTRACTOR_PANICS_UNIT,
+};
+#[cfg(feature = "failure-logs")]
+use crate::failure_log::FailureLogger;
+use crate::fast::{
+    MinRecordedDurations, HTTP_SERVER_REQUEST_FAST_METRIC, HTTP_SERVER_REQUEST_FAST_UNIT,
+};
+use crate::grpc::{
+    RPC_SERVER_MESSAGES_PER_RPC_UNIT, RPC_SERVER_REQUESTS_PER_RPC_METRIC,
+    RPC_SERVER_RESPONSES_PER_RPC_METRIC,
+};
+use crate::known_routes::{
+    KnownRoutes, HTTP_SERVER_REQUEST_COUNT_METRIC, HTTP_SERVER_REQUEST_COUNT_UNIT,
+};
+use crate::labels::{method_value, COMMON_STATUS_CODES};
+use crate::latency::{LatencyAlertCallback, LatencyTracker};
+#[cfg(feature = "route-cache")]
+use crate::lru::RouteCache;
+use crate::naming::named_meter;
+use crate::operation::OperationIds;
+#[cfg(feature = "axum")]
+use crate::placement::{PlacementGuard, PlacementWarning};
+use crate::resolution::DurationHistogram;
+use crate::route::{static_route_value, RouteExtractor, RoutePattern, RouteResolver};
+use crate::slo::{SloThresholds, HTTP_SERVER_REQUEST_SLOW_METRIC, HTTP_SERVER_REQUEST_SLOW_UNIT};
+use crate::tunnel::{HTTP_SERVER_OPEN_TUNNELS_METRIC, HTTP_SERVER_OPEN_TUNNELS_UNIT};
+#[cfg(feature = "semconv-validation")]
+use crate::validation::{SemconvValidator, SemconvViolationReport};
+#[cfg(feature = "load")]
+use crate::LoadMeasure;
+#[cfg(feature = "semconv-validation")]
+use crate::SemconvViolation;
+#[cfg(feature = "trace-sampling")]
+use crate::TraceSampling;
+#[cfg(feature = "dashboard")]
+use crate::HTTP_ROUTE_LABEL;
+use crate::{
+    AttributeExtractor, CustomInstrument, DryRunSummary, DurationResolution, DurationRollup, Error,
+    ErrorKind, HTTPMetricsLayer, HTTPMetricsLayerState, LatencyAlert, NamingConvention, Preset,
+    RequestClassifier, ResponseBodySizeSource, Result, TrafficSplitSource,
+    HTTP_SERVER_DURATION_BOUNDARIES, HTTP_SERVER_DURATION_METRIC,
+};
+
+const HTTP_SERVER_DURATION_OVERFLOW_METRIC: &str = "http.server.request.duration.overflow";
+const HTTP_SERVER_DURATION_OVERFLOW_UNIT: &str = "{request}";
+
+const HTTP_SERVER_DURATION_ROLLUP_METRIC: &str = "http.server.request.duration.rollup";
+
+#[cfg(feature = "cardinality")]
+const HTTP_SERVER_DURATION_ATTRIBUTE_SETS_METRIC: &str =
+    "http.server.request.duration.attribute_sets";
+#[cfg(feature = "cardinality")]
+const HTTP_SERVER_DURATION_ATTRIBUTE_SETS_UNIT: &str = "{attribute_set}";
+
+#[cfg(feature = "route-cache")]
+const HTTP_SERVER_ROUTE_CACHE_EVICTIONS_METRIC: &str = "http.server.route_cache.evictions";
+#[cfg(feature = "route-cache")]
+const HTTP_SERVER_ROUTE_CACHE_EVICTIONS_UNIT: &str = "{route}";
+#[cfg(feature = "route-cache")]
+const DEFAULT_ROUTE_CACHE_CAPACITY: usize = 1024;
+
+const HTTP_SERVER_ACTIVE_REQUESTS_METRIC: &str = "http.server.active_requests";
+const HTTP_SERVER_ACTIVE_REQUESTS_UNIT: &str = "{request}";
+
+const HTTP_SERVER_CONCURRENT_REQUESTS_METRIC: &str = "http.server.concurrent_requests";
+const HTTP_SERVER_CONCURRENT_REQUESTS_UNIT: &str = "{request}";
+
+const HTTP_SERVER_CONCURRENT_REQUESTS_BOUNDARIES: [f64; 14] = [
+    1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 75.0, 100.0, 250.0, 500.0, 750.0, 1000.0, 2500.0, 5000.0,
+];
+
+const HTTP_SERVER_REQUEST_BODY_SIZE_METRIC: &str = "http.server.request.body.size";
+const HTTP_SERVER_REQUEST_BODY_SIZE_UNIT: &str = "By";
+
+const HTTP_SERVER_REQUEST_SIZE_METRIC: &str = "http.server.request.size";
+const HTTP_SERVER_REQUEST_SIZE_UNIT: &str = "By";
+
+const HTTP_SERVER_RESPONSE_BODY_SIZE_METRIC: &str = "http.server.response.body.size";
+const HTTP_SERVER_RESPONSE_BODY_SIZE_UNIT: &str = "By";
+
+const HTTP_SERVER_RESPONSE_BODY_FRAME_SIZE_METRIC: &str = "http.server.response.body.frame.size";
+const HTTP_SERVER_RESPONSE_BODY_FRAME_SIZE_UNIT: &str = "By";
+
+const HTTP_SERVER_RESPONSE_BODY_FRAME_SIZE_BOUNDARIES: [f64; 11] = [
+    16.0, 64.0, 256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0, 16777216.0,
+];
+
+const HTTP_SERVER_RESPONSE_BODY_FRAMES_METRIC: &str = "http.server.response.body.frames";
+const HTTP_SERVER_RESPONSE_BODY_FRAMES_UNIT: &str = "{frame}";
+
+const HTTP_SERVER_RESPONSE_BODY_FRAMES_BOUNDARIES: [f64; 13] = [
+    1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+];
+
+const HTTP_SERVER_INFORMATIONAL_RESPONSES_METRIC: &str = "http.server.informational_responses";
+const HTTP_SERVER_INFORMATIONAL_RESPONSES_UNIT: &str = "{response}";
+
+const HTTP_SERVER_REQUEST_CONTINUE_DURATION_METRIC: &str = "http.server.request.continue.duration";
+const HTTP_SERVER_REQUEST_CONTINUE_DURATION_UNIT: &str = "s";
+
+const HTTP_SERVER_REQUEST_BODY_BYTES_METRIC: &str = "http.server.request.body.bytes";
+const HTTP_SERVER_REQUEST_BODY_BYTES_UNIT: &str = "By";
+
+const HTTP_SERVER_RESPONSE_BODY_BYTES_METRIC: &str = "http.server.response.body.bytes";
+const HTTP_SERVER_RESPONSE_BODY_BYTES_UNIT: &str = "By";
+
+const HTTP_SERVER_NETWORK_IO_METRIC: &str = "http.server.network.io";
+const HTTP_SERVER_NETWORK_IO_UNIT: &str = "By";
+
+const HTTP_SERVER_REQUEST_BODY_SIZE_MALFORMED_METRIC: &str =
+    "http.server.request.body.size.malformed";
+const HTTP_SERVER_REQUEST_BODY_SIZE_MALFORMED_UNIT: &str = "{request}";
+
+const HTTP_SERVER_QUEUE_TIME_METRIC: &str = "http.server.queue_time";
+const HTTP_SERVER_QUEUE_TIME_UNIT: &str = "s";
+
+const HTTP_SERVER_REQUEST_POLLS_METRIC: &str = "http.server.request.polls";
+const HTTP_SERVER_REQUEST_POLLS_UNIT: &str = "{poll}";
+
+const HTTP_SERVER_REQUEST_POLLS_BOUNDARIES: [f64; 12] = [
+    1.0, 2.0, 3.0, 5.0, 10.0, 20.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 10000.0,
+];
+
+const HTTP_SERVER_RATE_LIMIT_LIMIT_METRIC: &str = "http.server.rate_limit.limit";
+const HTTP_SERVER_RATE_LIMIT_REMAINING_METRIC: &str = "http.server.rate_limit.remaining";
+const HTTP_SERVER_RATE_LIMIT_UNIT: &str = "{request}";
+
+const HTTP_SERVER_REQUEST_THROTTLED_METRIC: &str = "http.server.request.throttled";
+const HTTP_SERVER_REQUEST_THROTTLED_UNIT: &str = "{request}";
+
+const HTTP_SERVER_RANGE_REQUESTS_METRIC: &str = "http.server.range_requests";
+const HTTP_SERVER_RANGE_REQUESTS_UNIT: &str = "{request}";
+
+const HTTP_SERVER_RESPONSE_RANGE_SIZE_METRIC: &str = "http.server.response.range.size";
+const HTTP_SERVER_RESPONSE_RANGE_SIZE_UNIT: &str = "By";
+
+/// Builder for [`HTTPMetricsLayer`]
+pub struct HTTPMetricsLayerBuilder {
+    meter: Option<Meter>,
+    meter_provider: Option<WeakMeterProvider>,
+    pub(crate) dry_run: bool,
+    noop_meter_detection: bool,
+    metric_aliases: MetricAliases,
+    naming_convention: NamingConvention,
+    default_url_scheme: Cow<'static, str>,
+    string_status_code: bool,
+    status_class: bool,
+    kept_status_codes: Option<Vec<http::StatusCode>>,
+    max_request_duration: Option<Duration>,
+    cancelled_requests: bool,
+    client_abort_error_type: bool,
+    duration_from_accept_time: bool,
+    request_context_extension: bool,
+    active_requests_route: bool,
+    #[cfg(feature = "span-attributes")]
+    span_attributes: bool,
+    duration_rollup_keys: Option<Vec<Key>>,
+    #[cfg(feature = "cardinality")]
+    cardinality_estimate: bool,
+    #[cfg(feature = "route-cache")]
+    route_cache_capacity: usize,
+    matched_path_route: bool,
+    route_extractor: Option<Arc<RouteExtractor>>,
+    #[cfg(feature = "route-patterns")]
+    route_patterns: Vec<Cow<'static, str>>,
+    fallback_route: Option<Cow<'static, str>>,
+    known_routes: Vec<(http::Method, Cow<'static, str>)>,
+    slo_thresholds: HashMap<Cow<'static, str>, Duration>,
+    min_recorded_durations: HashMap<Cow<'static, str>, Duration>,
+    #[cfg(feature = "cardinality")]
+    cardinality_warning: Option<(u64, Duration, Arc<CardinalityWarning>)>,
+    latency_alert: Option<(f64, Duration, Duration, Arc<LatencyAlertCallback>)>,
+    active_requests: bool,
+    concurrent_requests_histogram: bool,
+    request_body_size: bool,
+    request_size: bool,
+    response_body_size: bool,
+    response_body_size_source: ResponseBodySizeSource,
+    duration_resolution: DurationResolution,
+    response_body_frame_metrics: bool,
+    queue_time_histogram: bool,
+    poll_count_histogram: bool,
+    timing_checkpoints: bool,
+    malformed_content_length_counter: bool,
+    max_content_length: Option<u64>,
+    informational_responses_counter: bool,
+    expect_continue_duration: bool,
+    request_body_bytes_counter: bool,
+    response_body_bytes_counter: bool,
+    network_io_counter: bool,
+    grpc_message_counts: bool,
+    grpc_service_attributes: bool,
+    body_metrics_filter: Option<BodyMetricsFilter>,
+    custom_histograms: Vec<(Cow<'static, str>, Cow<'static, str>)>,
+    custom_instruments: Vec<CustomInstrument>,
+    request_classifier: Option<Arc<dyn RequestClassifier>>,
+    attribute_extractors: Vec<AttributeExtractor>,
+    max_extractor_attributes: usize,
+    #[cfg(feature = "async-extractor")]
+    async_attribute_extractors: Vec<Arc<AsyncRequestAttributeExtractor>>,
+    #[cfg(feature = "async-extractor")]
+    async_extractor_budget: Duration,
+    usage_counter: Option<(Cow<'static, str>, Cow<'static, str>)>,
+    usage_tenant_header: Option<http::HeaderName>,
+    cache_status_attribute: bool,
+    auth_outcome_attributes: bool,
+    user_authenticated_attribute: bool,
+    operation_id_attribute: bool,
+    operation_ids: Vec<(http::Method, Cow<'static, str>, Cow<'static, str>)>,
+    backend_attribute: bool,
+    backend: Option<Cow<'static, str>>,
+    principal_buckets: Option<u32>,
+    #[cfg(feature = "tower-http")]
+    failure_classifier: Option<Arc<MakeFailureClassifier>>,
+    url_path_sanitizer: Option<Arc<UrlPathSanitizer>>,
+    query_param_attributes: Vec<QueryParamAttribute>,
+    server_address_attributes: bool,
+    network_peer_address_attribute: bool,
+    client_geo_attributes: bool,
+    faas_attributes: bool,
+    traffic_split_attribute: Option<TrafficSplitAttribute>,
+    #[cfg(feature = "user-agent")]
+    user_agent_device_category: bool,
+    route_fallback_attribute: bool,
+    #[cfg(feature = "axum")]
+    placement_warning: Option<(usize, Arc<PlacementWarning>)>,
+    #[cfg(feature = "axum")]
+    strict_placement: bool,
+    #[cfg(feature = "semconv-validation")]
+    semconv_validation: Option<Arc<SemconvViolationReport>>,
+    #[cfg(feature = "trace-sampling")]
+    trace_sampling: TraceSampling,
+    #[cfg(feature = "failure-logs")]
+    failure_logger: Option<Arc<dyn FailureLogger>>,
+    #[cfg(feature = "load")]
+    load_measure: Option<LoadMeasure>,
+    rate_limit_gauges: bool,
+    throttled_requests_counter: bool,
+    range_request_metrics: bool,
+    connect_tunnels: bool,
+    open_tunnels_counter: bool,
+}
+
+pub(crate) type UrlPathSanitizer = dyn Fn(&str) -> String + Send + Sync;
+
+#[derive(Clone, Debug, Default)]
+/// Selects the requests which get body size instrumentation.
+///
+/// Routes are matched against the `http.route` when one is available and against the request path,
+/// using router-style patterns such as `/files/{id}`, `/files/:id`, or `/files/{*path}`.
+/// When routes are given, a request must match one of them; when methods are given,
+/// a request must use one of them. An empty filter matches every request.
+pub struct BodyMetricsFilter {
+    routes: Vec<RoutePattern>,
+    methods: Vec<http::Method>,
+}
+
+impl fmt::Debug for HTTPMetricsLayerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("HTTPMetricsLayerBuilder");
+        debug
+            .field("meter", &self.meter.is_some())
+            .field("meter_provider", &self.meter_provider.is_some())
+            .field("dry_run", &self.dry_run)
+            .field("noop_meter_detection", &self.noop_meter_detection)
+            .field("metric_aliases", &self.metric_aliases)
+            .field("naming_convention", &self.naming_convention)
+            .field("default_url_scheme", &self.default_url_scheme)
+            .field("string_status_code", &self.string_status_code)
+            .field("status_class", &self.status_class)
+            .field("kept_status_codes", &self.kept_status_codes)
+            .field("max_request_duration", &self.max_request_duration)
+            .field("cancelled_requests", &self.cancelled_requests)
+            .field("client_abort_error_type", &self.client_abort_error_type)
+            .field("duration_from_accept_time", &self.duration_from_accept_time)
+            .field("request_context_extension", &self.request_context_extension)
+            .field("active_requests_route", &self.active_requests_route)
+            .field("duration_rollup_keys", &self.duration_rollup_keys)
+            .field("matched_path_route", &self.matched_path_route)
+            .field("route_extractor", &self.route_extractor.is_some())
+            .field("fallback_route", &self.fallback_route)
+            .field("known_routes", &self.known_routes)
+            .field("slo_thresholds", &self.slo_thresholds)
+            .field("min_recorded_durations", &self.min_recorded_durations)
+            .field(
+                "latency_alert",
+                &self
+                    .latency_alert
+                    .as_ref()
+                    .map(|(quantile, threshold, window, _)| (quantile, threshold, window)),
+            )
+            .field("active_requests", &self.active_requests)
+            .field(
+                "concurrent_requests_histogram",
+                &self.concurrent_requests_histogram,
+            )
+            .field("request_body_size", &self.request_body_size)
+            .field("request_size", &self.request_size)
+            .field("response_body_size", &self.response_body_size)
+            .field("response_body_size_source", &self.response_body_size_source)
+            .field("duration_resolution", &self.duration_resolution)
+            .field(
+                "response_body_frame_metrics",
+                &self.response_body_frame_metrics,
+            )
+            .field("queue_time_histogram", &self.queue_time_histogram)
+            .field("poll_count_histogram", &self.poll_count_histogram)
+            .field("timing_checkpoints", &self.timing_checkpoints)
+            .field(
+                "malformed_content_length_counter",
+                &self.malformed_content_length_counter,
+            )
+            .field("max_content_length", &self.max_content_length)
+            .field(
+                "informational_responses_counter",
+                &self.informational_responses_counter,
+            )
+            .field("expect_continue_duration", &self.expect_continue_duration)
+            .field(
+                "request_body_bytes_counter",
+                &self.request_body_bytes_counter,
+            )
+            .field(
+                "response_body_bytes_counter",
+                &self.response_body_bytes_counter,
+            )
+            .field("network_io_counter", &self.network_io_counter)
+            .field("grpc_message_counts", &self.grpc_message_counts)
+            .field("grpc_service_attributes", &self.grpc_service_attributes)
+            .field("body_metrics_filter", &self.body_metrics_filter)
+            .field("custom_histograms", &self.custom_histograms)
+            .field("custom_instruments", &self.custom_instruments)
+            .field("request_classifier", &self.request_classifier.is_some())
+            .field("attribute_extractors", &self.attribute_extractors)
+            .field("max_extractor_attributes", &self.max_extractor_attributes)
+            .field("usage_counter", &self.usage_counter)
+            .field("usage_tenant_header", &self.usage_tenant_header)
+            .field("cache_status_attribute", &self.cache_status_attribute)
+            .field("auth_outcome_attributes", &self.auth_outcome_attributes)
+            .field(
+                "user_authenticated_attribute",
+                &self.user_authenticated_attribute,
+            )
+            .field("principal_buckets", &self.principal_buckets)
+            .field("operation_id_attribute", &self.operation_id_attribute)
+            .field("operation_ids", &self.operation_ids)
+            .field("backend_attribute", &self.backend_attribute)
+            .field("backend", &self.backend)
+            .field("url_path_attribute", &self.url_path_sanitizer.is_some())
+            .field("query_param_attributes", &self.query_param_attributes)
+            .field("server_address_attributes", &self.server_address_attributes)
+            .field(
+                "network_peer_address_attribute",
+                &self.network_peer_address_attribute,
+            )
+            .field("client_geo_attributes", &self.client_geo_attributes)
+            .field("faas_attributes", &self.faas_attributes)
+            .field("traffic_split_attribute", &self.traffic_split_attribute)
+            .field("route_fallback_attribute", &self.route_fallback_attribute);
+        #[cfg(feature = "cardinality")]
+        debug
+            .field("cardinality_estimate", &self.cardinality_estimate)
+            .field(
+                "cardinality_warning",
+                &self
+                    .cardinality_warning
+                    .as_ref()
+                    .map(|(threshold, interval, _)| (threshold, interval)),
+            );
+        #[cfg(feature = "route-cache")]
+        debug.field("route_cache_capacity", &self.route_cache_capacity);
+        #[cfg(feature = "route-patterns")]
+        debug.field("route_patterns", &self.route_patterns);
+        #[cfg(feature = "user-agent")]
+        debug.field(
+            "user_agent_device_category",
+            &self.user_agent_device_category,
+        );
+        #[cfg(feature = "axum")]
+        debug
+            .field(
+                "placement_warning",
+                &self
+                    .placement_warning
+                    .as_ref()
+                    .map(|(requests, _)| requests),
+            )
+            .field("strict_placement", &self.strict_placement);
+        #[cfg(feature = "tower-http")]
+        debug.field("failure_classifier", &self.failure_classifier.is_some());
+        #[cfg(feature = "semconv-validation")]
+        debug.field("semconv_validation", &self.semconv_validation.is_some());
+        #[cfg(feature = "trace-sampling")]
+        debug.field("trace_sampling", &self.trace_sampling);
+        #[cfg(feature = "failure-logs")]
+        debug.field("failure_logs", &self.failure_logger.is_some());
+        #[cfg(feature = "load")]
+        debug.field("load_measure", &self.load_measure);
+        #[cfg(feature = "span-attributes")]
+        debug.field("span_attributes", &self.span_attributes);
+        #[cfg(feature = "async-extractor")]
+        debug
+            .field(
+                "async_attribute_extractors",
+                &self.async_attribute_extractors.len(),
+            )
+            .field("async_extractor_budget", &self.async_extractor_budget);
+        debug
+            .field("rate_limit_gauges", &self.rate_limit_gauges)
+            .field(
+                "throttled_requests_counter",
+                &self.throttled_requests_counter,
+            )
+            .field("range_request_metrics", &self.range_request_metrics)
+            .field("connect_tunnels", &self.connect_tunnels)
+            .field("open_tunnels_counter", &self.open_tunnels_counter)
+            .finish()
+    }
+}
+
+impl Default for HTTPMetricsLayerBuilder {
+    fn default() -> Self {
+        let meter = global::meter("");
+        HTTPMetricsLayerBuilder::new().with_meter(meter)
+    }
+}
+
+impl HTTPMetricsLayerBuilder {
+    pub fn new() -> Self {
+        HTTPMetricsLayerBuilder {
+            meter: None,
+            meter_provider: None,
+            dry_run: false,
+            noop_meter_detection: false,
+            metric_aliases: HashMap::new(),
+            naming_convention: NamingConvention::OpenTelemetry,
+            default_url_scheme: Cow::Borrowed(""),
+            string_status_code: false,
+            status_class: false,
+            kept_status_codes: None,
+            max_request_duration: None,
+            cancelled_requests: false,
+            client_abort_error_type: false,
+            duration_from_accept_time: false,
+            request_context_extension: false,
+            active_requests_route: false,
+            #[cfg(feature = "span-attributes")]
+            span_attributes: false,
+            duration_rollup_keys: None,
+            #[cfg(feature = "cardinality")]
+            cardinality_estimate: false,
+            #[cfg(feature = "route-cache")]
+            route_cache_capacity: DEFAULT_ROUTE_CACHE_CAPACITY,
+            matched_path_route: true,
+            route_extractor: None,
+            #[cfg(feature = "route-patterns")]
+            route_patterns: Vec::new(),
+            fallback_route: None,
+            known_routes: Vec::new(),
+            slo_thresholds: HashMap::new(),
+            min_recorded_durations: HashMap::new(),
+            #[cfg(feature = "cardinality")]
+            cardinality_warning: None,
+            latency_alert: None,
+            active_requests: true,
+            concurrent_requests_histogram: false,
+            request_body_size: true,
+            request_size: false,
+            response_body_size: false,
+            response_body_size_source: ResponseBodySizeSource::Body,
+            duration_resolution: DurationResolution::Seconds,
+            response_body_frame_metrics: false,
+            queue_time_histogram: false,
+            poll_count_histogram: false,
+            timing_checkpoints: false,
+            malformed_content_length_counter: false,
+            max_content_length: None,
+            informational_responses_counter: false,
+            expect_continue_duration: false,
+            request_body_bytes_counter: false,
+            response_body_bytes_counter: false,
+            network_io_counter: false,
+            grpc_message_counts: false,
+            grpc_service_attributes: false,
+            body_metrics_filter: None,
+            custom_histograms: Vec::new(),
+            custom_instruments: Vec::new(),
+            request_classifier: None,
+            attribute_extractors: Vec::new(),
+            max_extractor_attributes: DEFAULT_MAX_EXTRACTOR_ATTRIBUTES,
+            #[cfg(feature = "async-extractor")]
+            async_attribute_extractors: Vec::new(),
+            #[cfg(feature = "async-extractor")]
+            async_extractor_budget: DEFAULT_ASYNC_EXTRACTOR_BUDGET,
+            usage_counter: None,
+            usage_tenant_header: None,
+            cache_status_attribute: false,
+            auth_outcome_attributes: false,
+            user_authenticated_attribute: false,
+            principal_buckets: None,
+            operation_id_attribute: false,
+            operation_ids: Vec::new(),
+            backend_attribute: false,
+            backend: None,
+            #[cfg(feature = "tower-http")]
+            failure_classifier: None,
+            url_path_sanitizer: None,
+            query_param_attributes: Vec::new(),
+            server_address_attributes: false,
+            network_peer_address_attribute: false,
+            client_geo_attributes: false,
+            faas_attributes: false,
+            traffic_split_attribute: None,
+            #[cfg(feature = "user-agent")]
+            user_agent_device_category: false,
+            route_fallback_attribute: false,
+            #[cfg(feature = "axum")]
+            placement_warning: None,
+            #[cfg(feature = "axum")]
+            strict_placement: false,
+            #[cfg(feature = "semconv-validation")]
+            semconv_validation: None,
+            #[cfg(feature = "trace-sampling")]
+            trace_sampling: TraceSampling::Ignore,
+            #[cfg(feature = "failure-logs")]
+            failure_logger: None,
+            #[cfg(feature = "load")]
+            load_measure: None,
+            rate_limit_gauges: false,
+            throttled_requests_counter: false,
+            range_request_metrics: false,
+            connect_tunnels: false,
+            open_tunnels_counter: false,
+        }
+    }
+
+    pub fn build(self) -> Result<HTTPMetricsLayer> {
+        let (state, dry_run_summary) = if self.dry_run {
+            let summary = DryRunSummary::default();
+            (
+                self.make_state(&self.aliased(summary.meter())),
+                Some(summary),
+            )
+        } else {
+            match &self.meter {
+                Some(meter) => {
+                    let mut state = self.make_state(&self.aliased(meter.clone()));
+                    state.pass_through = self.passes_through(meter);
+                    state.meter_provider = self.meter_provider.clone();
+                    (state, None)
+                }
+                None => {
+                    return Err(Error {
+                        inner: ErrorKind::Config(String::from("no meter provided")),
+                    })
+                }
+            }
+        };
+        clock::calibrate();
+        Ok(HTTPMetricsLayer {
+            binding: Arc::new(LayerBinding::new(self, state)),
+            dry_run_summary,
+        })
+    }
+
+    pub fn with_meter(self, meter: Meter) -> Self {
+        HTTPMetricsLayerBuilder {
+            meter: Some(meter),
+            meter_provider: None,
+            ..self
+        }
+    }
+
+    /// Create the meter from `provider`, holding only a weak handle to the provider itself.
+    ///
+    /// Once the provider is dropped, e.g. when replaced on a reload of the telemetry
+    /// configuration, services switch to no-op instruments instead of recording into the dropped
+    /// provider. A provider shut down while still referenced is not detected; call
+    /// [`HTTPMetricsLayer::unbind`] after shutting it down, or [`HTTPMetricsLayer::rebind`] to
+    /// record into a new provider.
+    pub fn with_meter_provider<P>(self, provider: &Arc<P>) -> Self
+    where
+        P: MeterProvider + Send + Sync + 'static,
+    {
+        let weak: WeakMeterProvider = Arc::downgrade(provider) as _;
+        HTTPMetricsLayerBuilder {
+            meter: Some(provider.meter(env!("CARGO_PKG_NAME"))),
+            meter_provider: Some(weak),
+            ..self
+        }
+    }
+
+    /// Compute everything as usual but record nothing to OTEL, only aggregating a summary
+    /// available from [`HTTPMetricsLayer::dry_run_summary`].
+    ///
+    /// This validates attribute cardinality and extractor behavior in production before turning
+    /// real recording on; the configured meter is not used and need not be set. Disabled by default.
+    pub fn with_dry_run(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            dry_run: enabled,
+            ..self
+        }
+    }
+
+    /// Detect a meter which records nothing, such as the global meter taken before
+    /// `opentelemetry::global::set_meter_provider` was called, and then pass requests through to
+    /// the inner service without extracting or recording anything, nor inserting any request
+    /// extension.
+    ///
+    /// Requests are still handled in full when something in-process consumes their measurements,
+    /// e.g. the request context extension, latency alerts or failure logs.
+    ///
+    /// The OTEL API cannot tell a no-op meter apart, so the meter is probed by registering the
+    /// observable gauge `http.server.meter.probe`, whose callback never observes a value, so SDKs
+    /// never export it. The meter is probed once when the layer is built, and each meter the layer
+    /// is rebound to once more; without detection, or when requests are handled in full anyway,
+    /// no probe is registered. A meter dropping the probe is taken for a no-op, so SDK views which
+    /// drop every instrument but an allowlist must keep it when detection is enabled. Disabled by
+    /// default.
+    pub fn with_noop_meter_detection(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            noop_meter_detection: enabled,
+            ..self
+        }
+    }
+
+    /// Also record the measurements of the instrument `name` under the name `alias`.
+    ///
+    /// Meant for a transition period when dashboards still query legacy custom names, e.g.
+    /// `http_request_duration_seconds` for `http.server.request.duration`. Aliases get the
+    /// description, unit and bucket boundaries of their instrument; an instrument may be given
+    /// any number of aliases by calling this repeatedly. Observable instruments report under their
+    /// aliases through their callbacks as well.
+    pub fn with_metric_alias(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        alias: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.metric_aliases
+            .entry(name.into())
+            .or_default()
+            .push(alias.into());
+        self
+    }
+
+    /// Record metrics and attributes under the names of `convention` instead of OTEL semconv.
+    ///
+    /// Meant for backends whose dashboards and alerts expect their own names. Aliases given with
+    /// [`HTTPMetricsLayerBuilder::with_metric_alias`] still refer to the semconv names. Renaming
+    /// takes an allocation per measurement. Defaults to [`NamingConvention::OpenTelemetry`].
+    pub fn with_naming_convention(self, convention: NamingConvention) -> Self {
+        HTTPMetricsLayerBuilder {
+            naming_convention: convention,
+            ..self
+        }
+    }
+
+    /// Set the `url.scheme` recorded for requests whose URI has no scheme.
+    ///
+    /// Servers generally receive requests in origin form (`/path?query`), without a scheme,
+    /// while the scheme of a listener is almost always statically known; set it to `http` or
+    /// `https` accordingly. Defaults to an empty scheme.
+    pub fn with_default_url_scheme(self, scheme: impl Into<Cow<'static, str>>) -> Self {
+        HTTPMetricsLayerBuilder {
+            default_url_scheme: scheme.into(),
+            ..self
+        }
+    }
+
+    /// Record `http.response.status_code` as `_OTHER` for all but common status codes, bounding the
+    /// status dimension on public endpoints where clients can provoke any status.
+    ///
+    /// The codes kept are 200, 201, 204, 400, 401, 403, 404, 429, 500, 502 and 503; use
+    /// [`with_kept_status_codes`](Self::with_kept_status_codes) for another set. Note `_OTHER` is
+    /// a string, unlike the integer codes. Disabled by default.
+    pub fn with_rare_status_codes_collapsed(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            kept_status_codes: enabled.then(|| COMMON_STATUS_CODES.to_vec()),
+            ..self
+        }
+    }
+
+    /// Record `http.response.status_code` as `_OTHER` for status codes other than `kept`.
+    ///
+    /// ```
+    /// use http::StatusCode;
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let builder = HTTPMetricsLayerBuilder::default().with_kept_status_codes([
+    ///     StatusCode::OK,
+    ///     StatusCode::NOT_FOUND,
+    ///     StatusCode::INTERNAL_SERVER_ERROR,
+    /// ]);
+    /// ```
+    pub fn with_kept_status_codes(self, kept: impl IntoIterator<Item = http::StatusCode>) -> Self {
+        HTTPMetricsLayerBuilder {
+            kept_status_codes: Some(kept.into_iter().collect()),
+            ..self
+        }
+    }
+
+    /// Apply a [`Preset`] combination of options, setting each option it covers either way.
+    ///
+    /// Presets cover [`with_active_requests`](Self::with_active_requests),
+    /// [`with_status_class`](Self::with_status_class),
+    /// [`with_request_body_size`](Self::with_request_body_size),
+    /// [`with_request_size`](Self::with_request_size),
+    /// [`with_response_body_size`](Self::with_response_body_size), and each option adding
+    /// attributes without further configuration: the server address, network peer address, client
+    /// geolocation, FaaS, gRPC service, route fallback, active requests route, cache status,
+    /// authentication, user authenticated, operation id, backend, and with the `user-agent`
+    /// feature, user agent device category attributes. See each [`Preset`] for their values.
+    /// Options set before are overridden where the preset covers them, so apply the preset first
+    /// and adjust it with further options:
+    ///
+    /// ```
+    /// use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, Preset};
+    ///
+    /// let builder = HTTPMetricsLayerBuilder::default()
+    ///     .with_preset(Preset::Full)
+    ///     .with_request_size(false);
+    /// ```
+    pub fn with_preset(self, preset: Preset) -> Self {
+        let minimal = preset == Preset::Minimal;
+        let full = preset == Preset::Full;
+        #[allow(unused_mut)]
+        let mut builder = HTTPMetricsLayerBuilder {
+            active_requests: !minimal,
+            status_class: minimal,
+            request_body_size: !minimal,
+            request_size: full,
+            response_body_size: full,
+            server_address_attributes: full,
+            network_peer_address_attribute: full,
+            client_geo_attributes: full,
+            faas_attributes: full,
+            grpc_service_attributes: full,
+            route_fallback_attribute: full,
+            active_requests_route: full,
+            cache_status_attribute: full,
+            auth_outcome_attributes: full,
+            user_authenticated_attribute: full,
+            operation_id_attribute: full,
+            backend_attribute: full,
+            ..self
+        };
+        #[cfg(feature = "user-agent")]
+        {
+            builder.user_agent_device_category = full;
+        }
+        builder
+    }
+
+    /// Record `http.response.status_code` as a string, as earlier versions did, instead of the
+    /// integer defined by semconv.
+    ///
+    /// For queries and dashboards which still expect strings: `http.server.request.duration` gets
+    /// the code with its reason phrase, e.g. `200 OK`, and other metrics the code alone, e.g. `200`.
+    /// Disabled by default.
+    pub fn with_string_status_code(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            string_status_code: enabled,
+            ..self
+        }
+    }
+
+    /// Record the `http.response.status_class` (`1xx` to `5xx`) instead of `http.response.status_code`
+    /// on `http.server.request.duration`.
+    ///
+    /// This is off-spec, but cuts the series of every route down to five status classes for
+    /// dashboards which only chart success and error rates. Other metrics keep the status code.
+    /// Disabled by default.
+    pub fn with_status_class(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            status_class: enabled,
+            ..self
+        }
+    }
+
+    /// Clamp recorded request durations to `max`, counting each clamped request in
+    /// `http.server.request.duration.overflow`.
+    ///
+    /// Pathological requests, e.g. stuck on an unresponsive upstream, otherwise put absurd values
+    /// into `http.server.request.duration` and distort its sum and average. The overflow counter
+    /// has the same attributes as the duration histogram. Not clamped by default.
+    pub fn with_max_request_duration(self, max: Duration) -> Self {
+        HTTPMetricsLayerBuilder {
+            max_request_duration: Some(max),
+            ..self
+        }
+    }
+
+    /// Record requests whose response future is dropped before the response is ready,
+    /// with an `error.type` of `cancelled`.
+    ///
+    /// Futures are dropped when the client disconnects, but also when an outer timeout expires,
+    /// the server shuts down gracefully or the request loses a `select!`, which cannot be told
+    /// apart here. Cancelled requests are not recorded by default, though they are always
+    /// removed from `http.server.active_requests`.
+    pub fn with_cancelled_requests(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            cancelled_requests: enabled,
+            ..self
+        }
+    }
+
+    /// Record errors caused by the client going away, such as connection resets and broken pipes,
+    /// with an `error.type` of `client_abort` rather than `_OTHER`.
+    ///
+    /// Errors are classified by the layer returned by [`HTTPMetricsLayer::error_type_layer`],
+    /// which must be applied inside this layer and requires the inner service's errors to convert
+    /// into a [`BoxError`]. Disabled by default.
+    ///
+    /// [`BoxError`]: tower::BoxError
+    ///
+    /// ```
+    /// use tower::ServiceBuilder;
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let metrics = HTTPMetricsLayerBuilder::default()
+    ///     .with_client_abort_error_type(true)
+    ///     .build()
+    ///     .unwrap();
+    /// let service = ServiceBuilder::new()
+    ///     .layer(metrics.clone())
+    ///     .layer(metrics.error_type_layer())
+    ///     .service_fn(|_req: http::Request<String>| async {
+    ///         Ok::<_, std::io::Error>(http::Response::new(String::new()))
+    ///     });
+    /// ```
+    pub fn with_client_abort_error_type(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            client_abort_error_type: enabled,
+            ..self
+        }
+    }
+
+    /// Measure `http.server.request.duration` from the time the connection was accepted,
+    /// for requests carrying the [`AcceptTime`] extension inserted by [`AcceptTimeService`].
+    ///
+    /// This includes time spent waiting in the listen backlog or for the server to start
+    /// processing the connection, which a duration measured from the layer's `call` misses
+    /// under backpressure. Requests without the extension are measured from `call` as usual.
+    /// Disabled by default.
+    ///
+    /// [`AcceptTime`]: crate::AcceptTime
+    /// [`AcceptTimeService`]: crate::AcceptTimeService
+    pub fn with_duration_from_accept_time(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            duration_from_accept_time: enabled,
+            ..self
+        }
+    }
+
+    /// Insert a [`RequestMetricsContext`] with the request start time, method and `http.route`
+    /// into the request extensions before calling the inner service.
+    ///
+    /// Middleware inside the layer, e.g. for tracing, rate limiting or logging, can then reuse the
+    /// data the layer has already resolved instead of extracting it again. Disabled by default.
+    ///
+    /// [`RequestMetricsContext`]: crate::RequestMetricsContext
+    pub fn with_request_context_extension(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            request_context_extension: enabled,
+            ..self
+        }
+    }
+
+    /// Add the `http.route` attribute to `http.server.active_requests`, for in-flight counts per
+    /// route.
+    ///
+    /// This is off-spec: semconv keeps the attributes of `http.server.active_requests` to the
+    /// method and scheme, as they are known before routing. Meant for debugging specific endpoints,
+    /// the route adds a series per route to the counter and to
+    /// `http.server.concurrent_requests`. Disabled by default.
+    pub fn with_active_requests_route(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            active_requests_route: enabled,
+            ..self
+        }
+    }
+
+    /// Record the measured duration and outcome of each request on its trace span.
+    ///
+    /// Sets `http.server.request.duration`, in seconds as recorded by the metric, along with
+    /// `http.response.status_code` or `error.type`, so trace and log based analysis sees exactly
+    /// the same numbers as the metric. The span is taken from the [`opentelemetry::Context`] in
+    /// the request extensions, or else the current context when the request is called, so the
+    /// layer must run inside the tracing middleware starting the request's span.
+    /// Disabled by default.
+    #[cfg(feature = "span-attributes")]
+    pub fn with_span_attributes(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            span_attributes: enabled,
+            ..self
+        }
+    }
+
+    /// Record each request duration a second time into `http.server.request.duration.rollup`,
+    /// with a small rollup attribute set.
+    ///
+    /// The rollup has an `http.response.status_class` attribute (`2xx`, `4xx`, ...) plus those
+    /// attributes of `http.server.request.duration` whose keys are listed, so cheap global
+    /// dashboards can coexist with detailed per-route data without aggregating at query time.
+    /// Disabled by default.
+    pub fn with_duration_rollup<I, K>(self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<Key>,
+    {
+        HTTPMetricsLayerBuilder {
+            duration_rollup_keys: Some(keys.into_iter().map(Into::into).collect()),
+            ..self
+        }
+    }
+
+    /// Estimate the number of distinct attribute sets recorded into `http.server.request.duration`,
+    /// reported by the `http.server.request.duration.attribute_sets` gauge.
+    ///
+    /// Each distinct attribute set is a time series in the metrics backend, so this tracks
+    /// the cost of the configured attributes. The estimate is approximate, within a few percent,
+    /// and takes a fixed amount of memory. Disabled by default.
+    #[cfg(feature = "cardinality")]
+    pub fn with_cardinality_estimate(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            cardinality_estimate: enabled,
+            ..self
+        }
+    }
+
+    /// Call `warn` with the estimated number of distinct attribute sets when it reaches
+    /// `threshold`, at most once per `interval` while requests are served.
+    ///
+    /// This enables [`with_cardinality_estimate`](Self::with_cardinality_estimate); `warn` is
+    /// typically used to log a warning that the attributes need to be reined in.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let builder = HTTPMetricsLayerBuilder::default().with_cardinality_warning(
+    ///     10_000,
+    ///     Duration::from_secs(300),
+    ///     |estimate| eprintln!("http.server.request.duration has ~{estimate} attribute sets"),
+    /// );
+    /// ```
+    #[cfg(feature = "cardinality")]
+    pub fn with_cardinality_warning<F>(self, threshold: u64, interval: Duration, warn: F) -> Self
+    where
+        F: Fn(u64) + Send + Sync + 'static,
+    {
+        HTTPMetricsLayerBuilder {
+            cardinality_estimate: true,
+            cardinality_warning: Some((threshold, interval, Arc::new(warn))),
+            ..self
+        }
+    }
+
+    /// Call `alert` when the `quantile` of request durations to a route over a sliding `window`
+    /// crosses `threshold`, and again when it drops back below.
+    ///
+    /// Latencies are tracked in process, per `http.route`, so the application can react right
+    /// away, e.g. by shedding load, without waiting on alerts from the metrics backend. The window
+    /// is evaluated as requests to the route come in, about four times per window; `alert` runs on
+    /// the request path and should return quickly. Durations are tracked within about 6%.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let builder = HTTPMetricsLayerBuilder::default().with_latency_alert(
+    ///     0.99,
+    ///     Duration::from_millis(500),
+    ///     Duration::from_secs(60),
+    ///     |alert| {
+    ///         if alert.firing() {
+    ///             eprintln!("p99 of {:?} is {:?}", alert.route(), alert.latency());
+    ///         }
+    ///     },
+    /// );
+    /// ```
+    pub fn with_latency_alert<F>(
+        self,
+        quantile: f64,
+        threshold: Duration,
+        window: Duration,
+        alert: F,
+    ) -> Self
+    where
+        F: Fn(&LatencyAlert) + Send + Sync + 'static,
+    {
+        HTTPMetricsLayerBuilder {
+            latency_alert: Some((quantile, threshold, window, Arc::new(alert))),
+            ..self
+        }
+    }
+
+    /// Set the number of `http.route` values kept in the route cache, or `0` to disable it.
+    ///
+    /// Requests to a cached route share its attribute value instead of allocating it. The cache
+    /// evicts the least recently used route when full, counting evictions in
+    /// `http.server.route_cache.evictions`; a steady rate of evictions means the application has
+    /// more active routes than the cache holds. Defaults to 1024 routes.
+    #[cfg(feature = "route-cache")]
+    pub fn with_route_cache_capacity(self, capacity: usize) -> Self {
+        HTTPMetricsLayerBuilder {
+            route_cache_capacity: capacity,
+            ..self
+        }
+    }
+
+    /// Take the `http.route` from the axum `MatchedPath` request extension.
+    ///
+    /// This is the first stage of route resolution, followed by the route extractor, the route
+    /// patterns and the fallback route, each of which only applies to requests the stages before
+    /// it did not resolve. Requests to services mounted with `Router::nest_service`, which have no
+    /// `MatchedPath`, get the mount path from the `NestedPath` extension instead, e.g.
+    /// `/assets/*path`, when no route extractor or pattern resolved them. Enabled by default.
+    #[cfg(feature = "axum")]
+    pub fn with_matched_path_route(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            matched_path_route: enabled,
+            ..self
+        }
+    }
+
+    /// Resolve the `http.route` of requests with `extractor`, e.g. from the extension of
+    /// another router, for requests without a `MatchedPath`.
+    ///
+    /// Returning `None` leaves the request to the route patterns and the fallback route.
+    /// Routes must be templates such as `/users/{id}` rather than concrete paths, to keep
+    /// the cardinality of `http.route` bounded.
+    pub fn with_route_extractor<F, R>(self, extractor: F) -> Self
+    where
+        F: Fn(&http::request::Parts) -> Option<R> + Send + Sync + 'static,
+        R: Into<Cow<'static, str>>,
+    {
+        HTTPMetricsLayerBuilder {
+            route_extractor: Some(Arc::new(move |parts: &http::request::Parts| {
+                extractor(parts).map(Into::into)
+            })),
+            ..self
+        }
+    }
+
+    /// Record the route pattern as the `http.route` of requests whose path matches it, e.g.
+    /// `/users/{id}` for `/users/123`, when no earlier stage resolved their route.
+    ///
+    /// Patterns use the syntax of [`BodyMetricsFilter::with_route`] and are tried in the order
+    /// they were added. This gives routes to services without a router reporting them. With the
+    /// `axum` feature, the full path of requests to nested services is matched, mount path included.
+    ///
+    /// Typed paths, e.g. axum-extra's `TypedPath`, carry their route template in a `PATH`
+    /// constant; registering it, as in `.with_route_pattern(UserPath::PATH)`, keeps the
+    /// `http.route` of typed routes wherever no `MatchedPath` reports it.
+    #[cfg(feature = "route-patterns")]
+    pub fn with_route_pattern(mut self, pattern: impl Into<Cow<'static, str>>) -> Self {
+        self.route_patterns.push(pattern.into());
+        self
+    }
+
+    /// Register a route known up front, so its requests are counted from startup.
+    ///
+    /// Series only appear once first recorded, which makes `rate()` and alerts on rarely used
+    /// routes misbehave. Since histograms cannot report a data point without measurements, known
+    /// routes get their completed requests counted in `http.server.request.count`, reported
+    /// for every known method and route from startup, at zero until requested. Requests to
+    /// other routes are not counted there. Routes are given as recorded in `http.route`, e.g.
+    /// `/users/:id` for axum 0.7. May be called multiple times for multiple routes.
+    ///
+    /// `405 Method Not Allowed` responses to requests without an `http.route` are attributed to
+    /// the known route matching their path, if any, so method mismatches are visible per endpoint.
+    pub fn with_known_route(
+        mut self,
+        method: http::Method,
+        route: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.known_routes.push((method, route.into()));
+        self
+    }
+
+    /// Set the latency objective of a route, counting its requests which take longer than
+    /// `threshold` in `http.server.request.slow`.
+    ///
+    /// Requests to routes with an objective are recorded into `http.server.request.duration`
+    /// with an `slo.violated` boolean attribute, so different endpoints can have different
+    /// objectives within one layer. Requests to other routes are not judged. Routes are given as
+    /// recorded in `http.route`, e.g. `/users/:id` for axum 0.7. May be called multiple times for
+    /// multiple routes.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let builder = HTTPMetricsLayerBuilder::default()
+    ///     .with_slo_threshold("/search", Duration::from_millis(300))
+    ///     .with_slo_threshold("/reports/:id/export", Duration::from_secs(5));
+    /// ```
+    pub fn with_slo_threshold(
+        mut self,
+        route: impl Into<Cow<'static, str>>,
+        threshold: Duration,
+    ) -> Self {
+        self.slo_thresholds.insert(route.into(), threshold);
+        self
+    }
+
+    /// Leave the requests of a route completing faster than `threshold` out of
+    /// `http.server.request.duration`, counting them in `http.server.request.fast` instead.
+    ///
+    /// Meant for very chatty endpoints where only slow requests are of interest. The fast requests
+    /// are counted with the attributes they would have been recorded with, so request rates are
+    /// the sum of the histogram count and the counter; they are left out of the duration rollup as
+    /// well, while other instruments, e.g. body sizes, still record them. Routes are given as
+    /// recorded in `http.route`. May be called multiple times for multiple routes.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let builder = HTTPMetricsLayerBuilder::default()
+    ///     .with_min_recorded_duration("/internal/cache/:key", Duration::from_millis(50));
+    /// ```
+    pub fn with_min_recorded_duration(
+        mut self,
+        route: impl Into<Cow<'static, str>>,
+        threshold: Duration,
+    ) -> Self {
+        self.min_recorded_durations.insert(route.into(), threshold);
+        self
+    }
+
+    /// Record `route` as the `http.route` of requests no other stage resolved a route for,
+    /// e.g. `/unmatched`, rather than recording them without an `http.route`.
+    pub fn with_fallback_route(self, route: impl Into<Cow<'static, str>>) -> Self {
+        HTTPMetricsLayerBuilder {
+            fallback_route: Some(route.into()),
+            ..self
+        }
+    }
+
+    /// Add an `http.route.fallback` boolean attribute to `http.server.request.duration`, `true` for
+    /// requests served by the router's fallback, e.g. `Router::fallback`.
+    ///
+    /// The fallback handler marks its responses with the [`RouteFallback`] extension, so "no such
+    /// route" can be told apart from 404s of real endpoints wherever the layer is applied. Routers
+    /// answering without a fallback handler, such as axum's default 404, are not marked; give them
+    /// one to count them. Disabled by default.
+    ///
+    /// [`RouteFallback`]: crate::RouteFallback
+    pub fn with_route_fallback_attribute(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            route_fallback_attribute: enabled,
+            ..self
+        }
+    }
+
+    /// Call `warn` once, with a message explaining the problem, when the first `requests` requests
+    /// all lack an axum `MatchedPath`.
+    ///
+    /// axum only reports the matched route to layers applied with `Router::layer` or
+    /// `Router::route_layer`; wrapped around the whole router, the layer runs before routing and
+    /// never records an `http.route`. The check stops at the first request with a matched route.
+    ///
+    /// ```
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let builder = HTTPMetricsLayerBuilder::default()
+    ///     .with_placement_warning(16, |message| eprintln!("warning: {message}"));
+    /// ```
+    #[cfg(feature = "axum")]
+    pub fn with_placement_warning<F>(self, requests: usize, warn: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        HTTPMetricsLayerBuilder {
+            placement_warning: Some((requests.max(1), Arc::new(warn))),
+            ..self
+        }
+    }
+
+    /// Panic when a request lacks an axum `MatchedPath` before any request had one, failing tests
+    /// which exercise a misplaced layer.
+    ///
+    /// Meant for tests: a test whose first request is not routed by the router, e.g. to check its
+    /// 404s, trips the check as well. Disabled by default.
+    #[cfg(feature = "axum")]
+    pub fn with_strict_placement(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            strict_placement: enabled,
+            ..self
+        }
+    }
+
+    /// Record the number of concurrently active requests into a histogram at each request completion.
+    ///
+    /// The `http.server.active_requests` UpDownCounter only reports the value at collection time,
+    /// so bursts of concurrency between collections are invisible; this histogram captures them.
+    /// This metric is not part of the OTEL semantic conventions and is disabled by default.
+    pub fn with_concurrent_requests_histogram(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            concurrent_requests_histogram: enabled,
+            ..self
+        }
+    }
+
+    /// Record the `http.server.active_requests` metric. Enabled by default.
+    pub fn with_active_requests(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            active_requests: enabled,
+            ..self
+        }
+    }
+
+    /// Record the `http.server.request.body.size` metric. Enabled by default.
+    pub fn with_request_body_size(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            request_body_size: enabled,
+            ..self
+        }
+    }
+
+    /// Record the `http.server.request.size` metric: the size of the request line and header
+    /// fields serialized as HTTP/1.1, plus the size of the body.
+    ///
+    /// The total size is what proxy buffer sizes and request limits apply to. The body size is
+    /// taken from the `Content-Length`, or else from the exact size reported by the body through
+    /// [`http_body::Body::size_hint`], e.g. `0` for bodiless requests, which requires the layer
+    /// returned by [`HTTPMetricsLayer::request_body_layer`] to be applied as well. Requests
+    /// streaming a body of unknown size, as with an invalid `Content-Length`, are not recorded.
+    /// This metric is not part of the OTEL semantic conventions and is disabled by default.
+    pub fn with_request_size(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            request_size: enabled,
+            ..self
+        }
+    }
+
+    /// Record the `http.server.response.body.size` metric.
+    ///
+    /// When the response body reports its exact size via [`http_body::Body::size_hint`],
+    /// the size is recorded as soon as the response is returned. Otherwise, bytes are counted
+    /// as the body is streamed and the size is recorded once the body is dropped; a body which
+    /// is not streamed to completion is recorded with the number of bytes actually produced.
+    /// Both require the layer returned by [`HTTPMetricsLayer::response_body_layer`] to be
+    /// applied as well, unless the size is read from the `Content-Length` as chosen with
+    /// [`with_response_body_size_source`]. Disabled by default.
+    ///
+    /// [`with_response_body_size_source`]: HTTPMetricsLayerBuilder::with_response_body_size_source
+    pub fn with_response_body_size(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            response_body_size: enabled,
+            ..self
+        }
+    }
+
+    /// Choose where the size recorded into `http.server.response.body.size` comes from.
+    ///
+    /// [`ResponseBodySizeSource::ContentLength`] reads the response `Content-Length` header, so
+    /// fixed-size responses whose body does not report its exact size pay no body wrapping cost;
+    /// the header is ignored for responses without a body, i.e. to `HEAD` requests and with
+    /// status `1xx`, `204` or `304`. Defaults to [`ResponseBodySizeSource::Body`].
+    pub fn with_response_body_size_source(self, source: ResponseBodySizeSource) -> Self {
+        HTTPMetricsLayerBuilder {
+            response_body_size_source: source,
+            ..self
+        }
+    }
+
+    /// Choose the resolution of `http.server.request.duration` and its rollup.
+    ///
+    /// The semconv bucket boundaries start at 5ms, which leaves no resolution for services
+    /// answering in microseconds. [`DurationResolution::FineSeconds`] keeps recording seconds into
+    /// boundaries from 1µs up to 10s; [`DurationResolution::Nanoseconds`] records integer
+    /// nanoseconds with unit `ns`, which dashboards built for the semconv unit do not expect.
+    /// Defaults to [`DurationResolution::Seconds`].
+    pub fn with_duration_resolution(self, resolution: DurationResolution) -> Self {
+        HTTPMetricsLayerBuilder {
+            duration_resolution: resolution,
+            ..self
+        }
+    }
+
+    /// Record the size of each response body data frame and the number of frames per response.
+    ///
+    /// Intended for streaming endpoints, to help tune buffering and spot responses
+    /// streamed as many tiny chunks. Requires the layer returned by
+    /// [`HTTPMetricsLayer::response_body_layer`] to be applied as well. These metrics are not
+    /// part of the OTEL semantic conventions and are disabled by default.
+    pub fn with_response_body_frame_metrics(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            response_body_frame_metrics: enabled,
+            ..self
+        }
+    }
+
+    /// Export the `http.server.queue_time` histogram, recording how long requests waited
+    /// between arriving at an upstream proxy and reaching the application.
+    ///
+    /// The arrival time is read from the `X-Request-Start` header set by Heroku's router or
+    /// nginx (`proxy_set_header X-Request-Start "t=${msec}";`); second, millisecond,
+    /// and microsecond timestamps are all accepted. Requests without the header are not recorded.
+    /// Disabled by default.
+    pub fn with_queue_time_histogram(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            queue_time_histogram: enabled,
+            ..self
+        }
+    }
+
+    /// Record how many times the response future of each request was polled in
+    /// `http.server.request.polls`, with the request method and `http.route`.
+    ///
+    /// Each poll after the first follows a wakeup, so requests polled far more often than the
+    /// work they await would explain point at executor contention or wakers firing spuriously,
+    /// which is otherwise invisible. This metric is not part of the OTEL semantic conventions and
+    /// is disabled by default.
+    pub fn with_poll_count_histogram(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            poll_count_histogram: enabled,
+            ..self
+        }
+    }
+
+    /// Insert [`TimingCheckpoints`] into the request extensions, on which middleware and handlers
+    /// inside the layer mark the end of each stage of the request.
+    ///
+    /// The duration of each stage is recorded in `http.server.request.stage.duration` with the
+    /// request method, `http.route` and the stage name as `http.server.request.stage`, for
+    /// per-stage latency without a tracing backend. This metric is not part of the OTEL semantic
+    /// conventions and is disabled by default.
+    ///
+    /// [`TimingCheckpoints`]: crate::TimingCheckpoints
+    pub fn with_timing_checkpoints(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            timing_checkpoints: enabled,
+            ..self
+        }
+    }
+
+    /// Count requests with a malformed `Content-Length` in `http.server.request.body.size.malformed`.
+    ///
+    /// Lengths which are not decimal digits, overflow a `u64`, or differ between repeated
+    /// values are malformed, as are lengths above [`with_max_content_length`]. Their body size is
+    /// not recorded either way; the counter makes such requests, often abusive, visible.
+    /// Disabled by default.
+    ///
+    /// [`with_max_content_length`]: HTTPMetricsLayerBuilder::with_max_content_length
+    pub fn with_malformed_content_length_counter(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            malformed_content_length_counter: enabled,
+            ..self
+        }
+    }
+
+    /// Treat request `Content-Length` values above `max` bytes as malformed, so they are neither
+    /// recorded into the body size metrics nor network I/O. Unlimited by default.
+    pub fn with_max_content_length(self, max: u64) -> Self {
+        HTTPMetricsLayerBuilder {
+            max_content_length: Some(max),
+            ..self
+        }
+    }
+
+    /// Count informational (1xx) responses in `http.server.informational_responses`.
+    ///
+    /// Interim responses such as `100 Continue` and `103 Early Hints` are written by the server
+    /// ahead of the final response and never pass through the layer, so status and duration
+    /// attributes always describe the final response. Informational responses which are final
+    /// as far as the service is concerned, e.g. `101 Switching Protocols` for upgrades, are counted
+    /// here (and still recorded in `http.server.request.duration` as usual). Disabled by default.
+    pub fn with_informational_responses_counter(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            informational_responses_counter: enabled,
+            ..self
+        }
+    }
+
+    /// Record `http.server.request.continue.duration` for `Expect: 100-continue` requests:
+    /// the time between sending `100 Continue` and receiving the full request body.
+    ///
+    /// For large uploads this is where upload latency actually lives. Requires the layer
+    /// returned by [`HTTPMetricsLayer::request_body_layer`] to be applied as well; when the
+    /// informational responses counter is enabled, the `100 Continue` responses are counted too.
+    /// Disabled by default.
+    pub fn with_expect_continue_duration(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            expect_continue_duration: enabled,
+            ..self
+        }
+    }
+
+    /// Count request body bytes in `http.server.request.body.bytes` as each body frame is read.
+    ///
+    /// Unlike `http.server.request.body.size`, which is only recorded once the body is complete,
+    /// this shows the progress and throughput of very large uploads in real time. Requires the
+    /// layer returned by [`HTTPMetricsLayer::request_body_layer`] to be applied as well.
+    /// Disabled by default.
+    pub fn with_request_body_bytes_counter(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            request_body_bytes_counter: enabled,
+            ..self
+        }
+    }
+
+    /// Count response body bytes in `http.server.response.body.bytes` as each body frame is sent,
+    /// with only the method and route attributes.
+    ///
+    /// Size histograms do not sum cleanly across exports; a monotonic counter of bytes sent per
+    /// route is what egress cost dashboards need. Requires the layer returned by
+    /// [`HTTPMetricsLayer::response_body_layer`] to be applied as well. Disabled by default.
+    pub fn with_response_body_bytes_counter(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            response_body_bytes_counter: enabled,
+            ..self
+        }
+    }
+
+    /// Count header and body bytes in `http.server.network.io`, with only the
+    /// `network.io.direction` attribute (`receive` or `transmit`).
+    ///
+    /// A single pair of series captures the bandwidth of the service. Header bytes are counted
+    /// as the size of the uncompressed HTTP/1.1 header section, which HTTP/2 and HTTP/3 compress
+    /// on the wire. Bodies without a `Content-Length` are counted as they are read or sent,
+    /// which requires the layers returned by [`HTTPMetricsLayer::request_body_layer`] and
+    /// [`HTTPMetricsLayer::response_body_layer`] to be applied as well. The body metrics filter
+    /// does not apply. Disabled by default.
+    pub fn with_network_io_counter(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            network_io_counter: enabled,
+            ..self
+        }
+    }
+
+    /// Record the number of messages of each gRPC call into `rpc.server.requests_per_rpc` and
+    /// `rpc.server.responses_per_rpc`, with the `rpc.system`, `rpc.service` and `rpc.method`
+    /// attributes.
+    ///
+    /// Duration alone says little about long-lived streaming calls; messages are counted from
+    /// the gRPC message framing as the bodies are streamed, for requests with an
+    /// `application/grpc` content type. Counting request and response messages requires the
+    /// layers returned by [`HTTPMetricsLayer::request_body_layer`] and
+    /// [`HTTPMetricsLayer::response_body_layer`] respectively to be applied as well. Disabled by
+    /// default.
+    pub fn with_grpc_message_counts(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            grpc_message_counts: enabled,
+            ..self
+        }
+    }
+
+    /// Add the `rpc.system` and `rpc.service` attributes of gRPC calls, e.g.
+    /// `rpc.service=helloworld.Greeter`, to `http.server.request.duration`.
+    ///
+    /// Servers hosting many gRPC services, e.g. a tonic `Routes`, get their metrics organized
+    /// per service without wiring a layer for each one. Requests are identified as gRPC calls by
+    /// their `application/grpc` content type. Disabled by default.
+    pub fn with_grpc_service_attributes(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            grpc_service_attributes: enabled,
+            ..self
+        }
+    }
+
+    /// Only apply body size metrics to requests selected by the filter.
+    ///
+    /// Affects `http.server.request.body.size` and, when enabled, the request and response body
+    /// bytes counters and the response body size and frame metrics. Responses to requests which
+    /// are not selected are passed through without any per-frame work.
+    pub fn with_body_metrics_filter(self, filter: BodyMetricsFilter) -> Self {
+        HTTPMetricsLayerBuilder {
+            body_metrics_filter: Some(filter),
+            ..self
+        }
+    }
+
+    /// Register a custom histogram fed by handler-supplied [`RecordValues`].
+    ///
+    /// Handlers insert [`RecordValues`] into their response extensions, and each value
+    /// under `name` is recorded into this histogram with the same attributes as
+    /// `http.server.request.duration`. Bucket boundaries can be configured with an SDK View.
+    ///
+    /// [`RecordValues`]: crate::RecordValues
+    pub fn with_custom_histogram(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        unit: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.custom_histograms.push((name.into(), unit.into()));
+        self
+    }
+
+    /// Register a custom instrument whose value is extracted from each response.
+    ///
+    /// Intended for domain metrics such as items returned or cache hits, which are recorded
+    /// with the same attributes as `http.server.request.duration`.
+    pub fn with_custom_instrument(mut self, instrument: CustomInstrument) -> Self {
+        self.custom_instruments.push(instrument);
+        self
+    }
+
+    /// Add an `http.request.class` attribute to `http.server.request.duration`, set by `classifier`.
+    ///
+    /// See [`RequestClassifier`] for mapping requests to a small fixed set of classes, for traffic
+    /// mix dashboards without route-level granularity. The attribute does not count towards the
+    /// maximum number of extractor attributes.
+    pub fn with_request_classifier(self, classifier: impl RequestClassifier) -> Self {
+        HTTPMetricsLayerBuilder {
+            request_classifier: Some(Arc::new(classifier)),
+            ..self
+        }
+    }
+
+    /// Register an extractor adding attributes to `http.server.request.duration`.
+    ///
+    /// See [`AttributeExtractor`] for extracting attributes from request and response parts
+    /// and from typed extensions. May be called multiple times for multiple extractors.
+    pub fn with_attribute_extractor(mut self, extractor: AttributeExtractor) -> Self {
+        self.attribute_extractors.push(extractor);
+        self
+    }
+
+    /// Set the maximum number of attributes extractors may add to a request, across request,
+    /// async and response extractors.
+    ///
+    /// This protects the pipeline from extractors returning hundreds of attributes: attributes
+    /// beyond the maximum are dropped and counted in `http.server.extractor.attributes.dropped`.
+    /// Defaults to 64 attributes.
+    pub fn with_max_extractor_attributes(self, max: usize) -> Self {
+        HTTPMetricsLayerBuilder {
+            max_extractor_attributes: max,
+            ..self
+        }
+    }
+
+    /// Register an async extractor adding attributes to `http.server.request.duration`, awaited
+    /// before the request is forwarded to the inner service.
+    ///
+    /// The extractor is given the request parts and returns a future of the attributes, which
+    /// must own whatever it needs from the request. Async extractors are run by the layer from
+    /// [`HTTPMetricsLayer::async_extractor_layer`], which must be placed around this layer.
+    /// May be called multiple times for multiple extractors, which run concurrently.
+    #[cfg(feature = "async-extractor")]
+    pub fn with_async_attribute_extractor<F, Fut, I>(mut self, extractor: F) -> Self
+    where
+        F: Fn(&http::request::Parts) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = I> + Send + 'static,
+        I: IntoIterator<Item = KeyValue>,
+    {
+        self.async_attribute_extractors
+            .push(Arc::new(move |parts: &http::request::Parts| {
+                let attributes = extractor(parts);
+                Box::pin(async move { attributes.await.into_iter().collect() })
+            }));
+        self
+    }
+
+    /// Set the time async extractors of a request are given before it is forwarded without
+    /// the attributes of those still pending, counting them in `http.server.extractor.timeouts`.
+    ///
+    /// Defaults to 10 milliseconds.
+    #[cfg(feature = "async-extractor")]
+    pub fn with_async_extractor_budget(self, budget: Duration) -> Self {
+        HTTPMetricsLayerBuilder {
+            async_extractor_budget: budget,
+            ..self
+        }
+    }
+
+    /// Register a counter metering handler-supplied [`UsageUnits`].
+    ///
+    /// Handlers insert [`UsageUnits`] into their response extensions, and the units are added to
+    /// this counter with the request method, `http.route`, and the `tenant.id` read from the
+    /// header configured with [`with_usage_tenant_header`], giving billing a single consistent
+    /// metering pipeline.
+    ///
+    /// [`with_usage_tenant_header`]: HTTPMetricsLayerBuilder::with_usage_tenant_header
+    ///
+    /// [`UsageUnits`]: crate::UsageUnits
+    pub fn with_usage_counter(
+        self,
+        name: impl Into<Cow<'static, str>>,
+        unit: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        HTTPMetricsLayerBuilder {
+            usage_counter: Some((name.into(), unit.into())),
+            ..self
+        }
+    }
+
+    /// Read the `tenant.id` attribute of the usage counter from the request header.
+    ///
+    /// Requests without the header are metered without a tenant.
+    pub fn with_usage_tenant_header(self, header: http::HeaderName) -> Self {
+        HTTPMetricsLayerBuilder {
+            usage_tenant_header: Some(header),
+            ..self
+        }
+    }
+
+    /// Add an `http.cache.status` attribute of `hit`, `miss`, or `stale` to `http.server.request.duration`.
+    ///
+    /// The status is parsed from the `CF-Cache-Status`, `X-Cache`, or `Age` response headers,
+    /// which lets services behind a caching CDN or proxy separate cached from origin latency.
+    /// Responses without any of these headers do not get the attribute. Disabled by default.
+    pub fn with_cache_status_attribute(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            cache_status_attribute: enabled,
+            ..self
+        }
+    }
+
+    /// Add `http.auth.outcome` and `http.auth.scheme` attributes to `http.server.request.duration`
+    /// for authentication failures.
+    ///
+    /// 401 responses are classified as `unauthenticated` and 403 responses as `forbidden`;
+    /// the scheme is taken from the `WWW-Authenticate` challenge when present.
+    /// Other responses do not get the attributes. Disabled by default.
+    pub fn with_auth_outcome_attributes(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            auth_outcome_attributes: enabled,
+            ..self
+        }
+    }
+
+    /// Add a `user.authenticated` boolean attribute to `http.server.request.duration`, `true` for
+    /// requests auth middleware marked with the [`Authenticated`] extension.
+    ///
+    /// The extension is looked up in the request extensions, for middleware around the layer,
+    /// then in the response extensions, for middleware inside it. Disabled by default.
+    ///
+    /// [`Authenticated`]: crate::Authenticated
+    pub fn with_user_authenticated_attribute(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            user_authenticated_attribute: enabled,
+            ..self
+        }
+    }
+
+    /// Hash the principal given to [`Authenticated::with_principal`] into one of `buckets`
+    /// buckets, recorded as `user.principal.bucket`, along with `user.authenticated`.
+    ///
+    /// The principal itself is never recorded; buckets show whether traffic is spread across
+    /// users or concentrated on a few while keeping the attribute bounded.
+    ///
+    /// [`Authenticated::with_principal`]: crate::Authenticated::with_principal
+    pub fn with_principal_buckets(self, buckets: u32) -> Self {
+        HTTPMetricsLayerBuilder {
+            user_authenticated_attribute: true,
+            principal_buckets: Some(buckets.max(1)),
+            ..self
+        }
+    }
+
+    /// Add an `openapi.operation_id` attribute to `http.server.request.duration`, naming the
+    /// OpenAPI operation which handled the request.
+    ///
+    /// The operation id is taken from the [`OperationId`] response extension, else from those
+    /// registered with [`with_operation_id`](Self::with_operation_id) for the method and
+    /// `http.route`. Requests without either do not get the attribute. Disabled by default.
+    ///
+    /// [`OperationId`]: crate::OperationId
+    pub fn with_operation_id_attribute(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            operation_id_attribute: enabled,
+            ..self
+        }
+    }
+
+    /// Register the OpenAPI operation id of a method and route, recorded as `openapi.operation_id`.
+    ///
+    /// This enables [`with_operation_id_attribute`](Self::with_operation_id_attribute). Routes are
+    /// given as recorded in `http.route`, e.g. `/users/:id` for axum 0.7, and operation ids
+    /// typically come from the OpenAPI document generated by aide or utoipa. May be called
+    /// multiple times for multiple operations.
+    ///
+    /// ```
+    /// use http::Method;
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let builder = HTTPMetricsLayerBuilder::default()
+    ///     .with_operation_id(Method::GET, "/users/:id", "getUser")
+    ///     .with_operation_id(Method::DELETE, "/users/:id", "deleteUser");
+    /// ```
+    pub fn with_operation_id(
+        mut self,
+        method: http::Method,
+        route: impl Into<Cow<'static, str>>,
+        operation_id: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.operation_id_attribute = true;
+        self.operation_ids
+            .push((method, route.into(), operation_id.into()));
+        self
+    }
+
+    /// Add an `http.server.backend` attribute to `http.server.request.duration`, naming the backend
+    /// a router such as `tower::steer::Steer` sent the request to.
+    ///
+    /// The backend is taken from the [`Backend`] response extension, inserted by a
+    /// [`BackendLayer`] around each branch of the router, else from
+    /// [`with_backend`](Self::with_backend). Requests without either do not get the attribute.
+    /// Disabled by default.
+    ///
+    /// [`Backend`]: crate::Backend
+    /// [`BackendLayer`]: crate::BackendLayer
+    pub fn with_backend_attribute(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            backend_attribute: enabled,
+            ..self
+        }
+    }
+
+    /// Name the backend of every request, recorded as `http.server.backend`, when building a layer
+    /// for each branch of a router.
+    ///
+    /// This enables [`with_backend_attribute`](Self::with_backend_attribute). Layers built with
+    /// the same meter share their instruments, so the branches are recorded side by side.
+    ///
+    /// ```
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let meter = opentelemetry::global::meter("my-service");
+    /// let primary = HTTPMetricsLayerBuilder::default()
+    ///     .with_meter(meter.clone())
+    ///     .with_backend("primary")
+    ///     .build()
+    ///     .unwrap();
+    /// let replica = HTTPMetricsLayerBuilder::default()
+    ///     .with_meter(meter)
+    ///     .with_backend("replica")
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn with_backend(self, backend: impl Into<Cow<'static, str>>) -> Self {
+        HTTPMetricsLayerBuilder {
+            backend_attribute: true,
+            backend: Some(backend.into()),
+            ..self
+        }
+    }
+
+    /// Classify responses with the tower-http classifier also given to `TraceLayer`, recording
+    /// the failure class of failed responses as `error.type` on `http.server.request.duration`.
+    ///
+    /// Sharing one classifier keeps the error rate of metrics and traces consistent, e.g. with
+    /// `SharedClassifier::new(ServerErrorsAsFailures::new())`. The failure class is recorded as
+    /// displayed, so it should have few distinct values.
+    #[cfg(feature = "tower-http")]
+    pub fn with_failure_classifier<M>(self, make_classifier: M) -> Self
+    where
+        M: tower_http::classify::MakeClassifier + Send + Sync + 'static,
+        M::Classifier: Send + 'static,
+        M::FailureClass: fmt::Display,
+    {
+        HTTPMetricsLayerBuilder {
+            failure_classifier: Some(make_failure_classifier(make_classifier)),
+            ..self
+        }
+    }
+
+    /// Add a `url.path` attribute to `http.server.request.duration`, passing each path
+    /// through the sanitizer before it is recorded.
+    ///
+    /// Raw paths are an unbounded attribute, so the sanitizer is mandatory: it must map paths
+    /// onto a bounded set of values. [`mask_path_ids`] is provided for paths which only vary by
+    /// numeric IDs and UUIDs. Prefer `http.route` wherever a router provides one.
+    ///
+    /// [`mask_path_ids`]: crate::mask_path_ids
+    pub fn with_url_path_attribute<F>(self, sanitizer: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        HTTPMetricsLayerBuilder {
+            url_path_sanitizer: Some(Arc::new(sanitizer)),
+            ..self
+        }
+    }
+
+    /// Add a `url.query.<param>` attribute to `http.server.request.duration` for the query parameter.
+    ///
+    /// Values are validated against `allowed_values`: any other value is recorded as `_OTHER`,
+    /// so API versions or response formats can be broken down without hand-written URI parsing
+    /// and without letting clients inflate cardinality. Requests without the parameter do not get
+    /// the attribute. Parameters are matched once percent-decoded, with `+` as a space, so
+    /// `allowed_values` are given decoded, e.g. `"text/csv"` for `format=text%2Fcsv`. May be called
+    /// multiple times for multiple parameters.
+    pub fn with_query_param_attribute<I, V>(
+        mut self,
+        param: impl Into<Cow<'static, str>>,
+        allowed_values: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<Cow<'static, str>>,
+    {
+        self.query_param_attributes.push(QueryParamAttribute::new(
+            param.into(),
+            allowed_values.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Add the `server.address` and `server.port` attributes, opt-in in semconv, to
+    /// `http.server.request.duration`, from the authority of the request URI or else the `Host`
+    /// header.
+    ///
+    /// `server.port` is only added when the authority names a port. Clients choose the `Host`
+    /// freely, and each distinct host is a new series, so only enable this where unknown hosts
+    /// are rejected before reaching the layer. Disabled by default.
+    pub fn with_server_address_attributes(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            server_address_attributes: enabled,
+            ..self
+        }
+    }
+
+    /// Add the `network.peer.address` attribute, opt-in in semconv, to
+    /// `http.server.request.duration`, with the IP address of the client connection.
+    ///
+    /// The address is read from a [`SocketAddr`](std::net::SocketAddr) request extension inserted
+    /// by the accept loop; with axum's `ConnectInfo`, copy its address into one in a `map_request`
+    /// middleware. `network.peer.port` is left out, as each connection has a port of its own. Each
+    /// client address is a new series, so this suits services with few, known clients. Disabled by
+    /// default.
+    pub fn with_network_peer_address_attribute(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            network_peer_address_attribute: enabled,
+            ..self
+        }
+    }
+
+    /// Add `geo.country.iso_code` and `geo.region.iso_code` attributes to
+    /// `http.server.request.duration` from CDN geolocation request headers.
+    ///
+    /// Reads `CF-IPCountry`, `CloudFront-Viewer-Country`, `Fastly-Client-Country`,
+    /// `X-Vercel-IP-Country`, and `X-AppEngine-Country` for the country, and the corresponding
+    /// region headers where the CDN provides them. Only enable this behind a CDN which overwrites
+    /// these headers, as clients can otherwise set them freely. Disabled by default.
+    pub fn with_client_geo_attributes(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            client_geo_attributes: enabled,
+            ..self
+        }
+    }
+
+    /// Add `faas.trigger` and `faas.coldstart` attributes to `http.server.request.duration`, for
+    /// services running as serverless functions behind an HTTP trigger, e.g. with `lambda_http`.
+    ///
+    /// `faas.coldstart` is `true` for the first request handled by the process. Requests whose URI
+    /// has no scheme take their `url.scheme` from the `X-Forwarded-Proto` header set by the
+    /// trigger before falling back to the default scheme. Disabled by default.
+    pub fn with_faas_attributes(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            faas_attributes: enabled,
+            ..self
+        }
+    }
+
+    /// Add a `deployment.variant` attribute to `http.server.request.duration`, read from a request
+    /// header or baggage entry, so canary and stable traffic can be compared directly.
+    ///
+    /// Values are validated against `allowed_values` (e.g. `["canary", "stable"]` or
+    /// `["true", "false"]`); any other value is recorded as `_OTHER`. Requests without the
+    /// header or baggage entry do not get the attribute.
+    pub fn with_traffic_split_attribute<I, V>(
+        self,
+        source: TrafficSplitSource,
+        allowed_values: I,
+    ) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<Cow<'static, str>>,
+    {
+        HTTPMetricsLayerBuilder {
+            traffic_split_attribute: Some(TrafficSplitAttribute::new(
+                source,
+                allowed_values.into_iter().map(Into::into).collect(),
+            )),
+            ..self
+        }
+    }
+
+    /// Add a `user_agent.device.category` attribute to `http.server.request.duration`,
+    /// classifying the `User-Agent` as `desktop`, `mobile`, `bot`, or `other`.
+    ///
+    /// The raw user agent is never recorded. Disabled by default.
+    #[cfg(feature = "user-agent")]
+    pub fn with_user_agent_device_category(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            user_agent_device_category: enabled,
+            ..self
+        }
+    }
+
+    /// Check every attribute set recorded into `http.server.request.duration` against the HTTP
+    /// semantic conventions, calling `report` for each violation.
+    ///
+    /// Reports missing required attributes, requests with neither `http.response.status_code` nor
+    /// `error.type`, methods which should have been recorded as `_OTHER`, and attributes, including
+    /// those of extractors, which are duplicated, empty or not named per the semconv naming rules.
+    /// Checks run on the request path; meant for development and tests.
+    ///
+    /// ```
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let builder = HTTPMetricsLayerBuilder::default()
+    ///     .with_semconv_validation(|violation| eprintln!("semconv violation: {violation}"));
+    /// ```
+    #[cfg(feature = "semconv-validation")]
+    pub fn with_semconv_validation<F>(self, report: F) -> Self
+    where
+        F: Fn(&SemconvViolation) + Send + Sync + 'static,
+    {
+        HTTPMetricsLayerBuilder {
+            semconv_validation: Some(Arc::new(report)),
+            ..self
+        }
+    }
+
+    /// Bound the cost of metrics by trace sampling, recording the extractor attributes or the
+    /// measurements of a request only when its trace is sampled.
+    ///
+    /// The sampling decision is read from the [`opentelemetry::Context`] in the request extensions,
+    /// or else the current context, so the layer must run inside the tracing middleware starting
+    /// the request's span. With [`TraceSampling::Measurements`], the counts and rates recorded
+    /// are a sample of the actual traffic. Defaults to [`TraceSampling::Ignore`].
+    ///
+    /// ```
+    /// use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, TraceSampling};
+    ///
+    /// let builder =
+    ///     HTTPMetricsLayerBuilder::default().with_trace_sampling(TraceSampling::ExtractorAttributes);
+    /// ```
+    #[cfg(feature = "trace-sampling")]
+    pub fn with_trace_sampling(self, sampling: TraceSampling) -> Self {
+        HTTPMetricsLayerBuilder {
+            trace_sampling: sampling,
+            ..self
+        }
+    }
+
+    /// Emit an OTEL log event for each failed request, for drilling down into error spikes without
+    /// logging every request.
+    ///
+    /// A request fails when the inner service returns an error, the request is cancelled and
+    /// [cancelled requests] are recorded, the response has a `5xx` status, or the failure
+    /// classifier records an `error.type`. The event `http.server.request.failure` is emitted with
+    /// severity `ERROR` and carries the attributes of `http.server.request.duration`, the duration
+    /// in seconds and the status code. Its trace context is the one current when the response
+    /// completes, as set by the logger. Disabled by default.
+    ///
+    /// [cancelled requests]: HTTPMetricsLayerBuilder::with_cancelled_requests
+    ///
+    /// ```
+    /// use opentelemetry::logs::{LoggerProvider, NoopLoggerProvider};
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let logger = NoopLoggerProvider::new().logger("tower-otel-http-metrics");
+    /// let builder = HTTPMetricsLayerBuilder::default().with_failure_logs(logger);
+    /// ```
+    #[cfg(feature = "failure-logs")]
+    pub fn with_failure_logs<L>(self, logger: L) -> Self
+    where
+        L: opentelemetry::logs::Logger + Send + Sync + 'static,
+    {
+        HTTPMetricsLayerBuilder {
+            failure_logger: Some(Arc::new(logger)),
+            ..self
+        }
+    }
+
+    /// Implement [`tower::load::Load`] on the layer's services from the requests they track, for
+    /// balancers such as `tower::balance::p2c` to pick the least loaded endpoint.
+    ///
+    /// Each service returned by the layer measures its own load, shared with its clones; wrap each
+    /// endpoint separately. Without a measure, services report a load of `0.0`.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, LoadMeasure};
+    ///
+    /// let builder = HTTPMetricsLayerBuilder::default().with_load_measure(LoadMeasure::LatencyEwma {
+    ///     decay: Duration::from_secs(10),
+    /// });
+    /// ```
+    #[cfg(feature = "load")]
+    pub fn with_load_measure(self, measure: LoadMeasure) -> Self {
+        HTTPMetricsLayerBuilder {
+            load_measure: Some(measure),
+            ..self
+        }
+    }
+
+    /// Export the `RateLimit-Limit` and `RateLimit-Remaining` response headers as gauges per route.
+    ///
+    /// Records `http.server.rate_limit.limit` and `http.server.rate_limit.remaining` with the
+    /// request method and `http.route` whenever a response carries the headers
+    /// (or their `X-RateLimit-*` equivalents), so quota exhaustion trends are visible
+    /// without custom code in every handler. Disabled by default.
+    pub fn with_rate_limit_gauges(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            rate_limit_gauges: enabled,
+            ..self
+        }
+    }
+
+    /// Count throttled requests in `http.server.request.throttled`, per route and status code.
+    ///
+    /// Requests are throttled when answered with `429 Too Many Requests`, or with
+    /// `503 Service Unavailable` and a `Retry-After` header. The policy which fired is recorded as
+    /// `http.server.throttle.policy` when the limiter names it in a [`ThrottlePolicy`] response
+    /// extension. Disabled by default.
+    ///
+    /// [`ThrottlePolicy`]: crate::ThrottlePolicy
+    pub fn with_throttled_requests_counter(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            throttled_requests_counter: enabled,
+            ..self
+        }
+    }
+
+    /// Count requests for byte ranges and partial content responses in
+    /// `http.server.range_requests`, and record the size of the ranges served in
+    /// `http.server.response.range.size`.
+    ///
+    /// Requests with a `Range` header and `206 Partial Content` responses are counted per route
+    /// and status code, which tells ranges served (`206`) from ranges ignored (`200`) and
+    /// unsatisfiable ones (`416`). Range sizes are read from the `Content-Range` of `206` responses
+    /// for a single range. Meant for media and download services, where partial content dominates
+    /// bandwidth. Disabled by default.
+    pub fn with_range_request_metrics(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            range_request_metrics: enabled,
+            ..self
+        }
+    }
+
+    /// Handle `CONNECT` requests as forward proxy tunnels, recorded without the `http.route` and
+    /// `url.scheme` attributes.
+    ///
+    /// A `CONNECT` request targets a host and port rather than a path, so neither attribute means
+    /// anything for it; its `http.server.request.duration` measures the establishment of the
+    /// tunnel, up to the response. Disabled by default, recording `CONNECT` requests like any other.
+    pub fn with_connect_tunnels(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            connect_tunnels: enabled,
+            ..self
+        }
+    }
+
+    /// Count the tunnels established by `CONNECT` requests which are still open in
+    /// `http.server.open_tunnels`.
+    ///
+    /// The response to a `CONNECT` request ends the HTTP exchange while the tunnel lives on, so
+    /// handlers hold the [`OpenTunnel`] request extension for as long as the tunnel is open.
+    /// Tunnels are counted from a `2xx` response on. This enables
+    /// [`with_connect_tunnels`](Self::with_connect_tunnels); disabled by default.
+    ///
+    /// [`OpenTunnel`]: crate::OpenTunnel
+    pub fn with_open_tunnels_counter(self, enabled: bool) -> Self {
+        HTTPMetricsLayerBuilder {
+            connect_tunnels: self.connect_tunnels || enabled,
+            open_tunnels_counter: enabled,
+            ..self
+        }
+    }
+
+    /// Generate a Grafana dashboard definition, as JSON, with panels for the metrics of the layer
+    /// as currently configured.
+    ///
+    /// Panels follow the configured metric names, naming convention, aliases, units and histogram
+    /// boundaries: request rates per route and quantiles for histograms, rates for counters and
+    /// current values for gauges and up-down counters. Queries are PromQL for metrics exported
+    /// with the default Prometheus name translation of the OTEL exporter and collector, e.g.
+    /// `http_server_request_duration_seconds`, and use a `datasource` dashboard variable.
+    ///
+    /// ```
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let dashboard = HTTPMetricsLayerBuilder::default()
+    ///     .with_response_body_size(true)
+    ///     .grafana_dashboard("HTTP server");
+    /// assert!(dashboard.contains("http_server_request_duration_seconds_bucket"));
+    /// ```
+    #[cfg(feature = "dashboard")]
+    pub fn grafana_dashboard(&self, title: &str) -> String {
+        let instruments = CapturedInstruments::default();
+        self.make_state(&self.aliased(instruments.meter()));
+        grafana_dashboard(
+            title,
+            &instruments,
+            self.naming_convention.key(HTTP_ROUTE_LABEL),
+        )
+    }
+
+    /// Meter creating the instruments of the layer, recording under metric aliases as well
+    /// and naming them after the configured convention.
+    pub(crate) fn aliased(&self, meter: Meter) -> Meter {
+        let meter = named_meter(meter, self.naming_convention);
+        if self.metric_aliases.is_empty() {
+            meter
+        } else {
+            aliased_meter(meter, self.metric_aliases.clone())
+        }
+    }
+
+    /// Whether services pass requests through without any work, because `meter` records nothing
+    /// and nothing in-process consumes the measurements either.
+    pub(crate) fn passes_through(&self, meter: &Meter) -> bool {
+        self.may_pass_through() && is_noop_meter(meter)
+    }
+
+    /// Whether services may pass requests through when the meter records nothing.
+    pub(crate) fn may_pass_through(&self) -> bool {
+        if !self.noop_meter_detection
+            || self.request_context_extension
+            || self.latency_alert.is_some()
+        {
+            return false;
+        }
+        #[cfg(feature = "cardinality")]
+        if self.cardinality_estimate {
+            return false;
+        }
+        #[cfg(feature = "span-attributes")]
+        if self.span_attributes {
+            return false;
+        }
+        #[cfg(feature = "axum")]
+        if self.placement_warning.is_some() || self.strict_placement {
+            return false;
+        }
+        #[cfg(feature = "semconv-validation")]
+        if self.semconv_validation.is_some() {
+            return false;
+        }
+        #[cfg(feature = "failure-logs")]
+        if self.failure_logger.is_some() {
+            return false;
+        }
+        true
+    }
+
+    pub(crate) fn make_state(&self, meter: &Meter) -> HTTPMetricsLayerState {
+        #[cfg(feature = "cardinality")]
+        let server_request_duration_cardinality = self
+            .cardinality_estimate
+            .then(|| Arc::new(CardinalityEstimator::new(self.cardinality_warning.clone())));
+        #[cfg(feature = "cardinality")]
+        let server_request_duration_attribute_sets =
+            server_request_duration_cardinality.clone().map(|estimator| {
+                meter
+                    .u64_observable_gauge(HTTP_SERVER_DURATION_ATTRIBUTE_SETS_METRIC)
+                    .with_description(
+                        "Estimated number of distinct attribute sets of http.server.request.duration.",
+                    )
+                    .with_unit(HTTP_SERVER_DURATION_ATTRIBUTE_SETS_UNIT)
+                    .with_callback(move |observer| observer.observe(estimator.estimate(), &[]))
+                    .build()
+            });
+
+        let known_routes = (!self.known_routes.is_empty()).then(|| {
+            Arc::new(KnownRoutes::new(self.known_routes.iter().map(
+                |(method, route)| (method_value(method), static_route_value(route.clone())),
+            )))
+        });
+        let server_request_count = known_routes.clone().map(|known_routes| {
+            meter
+                .u64_observable_counter(HTTP_SERVER_REQUEST_COUNT_METRIC)
+                .with_description("Number of completed HTTP server requests to known routes.")
+                .with_unit(HTTP_SERVER_REQUEST_COUNT_UNIT)
+                .with_callback(move |observer| known_routes.observe(observer))
+                .build()
+        });
+
+        #[cfg(feature = "diagnostics")]
+        let last_collection = LastCollection::default();
+        #[cfg(feature = "diagnostics")]
+        let diagnostics_collection = {
+            let last_collection = last_collection.clone();
+            meter
+                .u64_observable_gauge(HTTP_SERVER_DIAGNOSTICS_COLLECTION_INSTRUMENT)
+                .with_description(
+                    "Marks collections for the diagnostics endpoint; reports no values.",
+                )
+                .with_callback(move |_| {
+                    *last_collection
+                        .lock()
+                        .unwrap_or_else(|err| err.into_inner()) = Some(Instant::now());
+                })
+                .build()
+        };
+
+        let slo_thresholds = (!self.slo_thresholds.is_empty()).then(|| {
+            SloThresholds::new(
+                self.slo_thresholds.clone(),
+                meter
+                    .u64_counter(HTTP_SERVER_REQUEST_SLOW_METRIC)
+                    .with_description(
+                        "Number of HTTP server requests slower than the latency objective of their route.",
+                    )
+                    .with_unit(HTTP_SERVER_REQUEST_SLOW_UNIT)
+                    .build(),
+            )
+        });
+
+        let min_recorded_durations = (!self.min_recorded_durations.is_empty()).then(|| {
+            MinRecordedDurations::new(
+                self.min_recorded_durations.clone(),
+                meter
+                    .u64_counter(HTTP_SERVER_REQUEST_FAST_METRIC)
+                    .with_description(
+                        "Number of HTTP server requests faster than the minimum recorded duration of their route.",
+                    )
+                    .with_unit(HTTP_SERVER_REQUEST_FAST_UNIT)
+                    .build(),
+            )
+        });
+
+        let (request_attribute_extractors, response_attribute_extractors) =
+            split_attribute_extractors(&self.attribute_extractors);
+        let server_extractor_panics = meter
+            .u64_counter(HTTP_SERVER_EXTRACTOR_PANICS_METRIC)
+            .with_description("Number of panics caught in user-supplied extractors.")
+            .with_unit(HTTP_SERVER_EXTRACTOR_PANICS_UNIT)
+            .build();
+        HTTPMetricsLayerState {
+            server_request_duration: DurationHistogram::new(
+                meter,
+                self.duration_resolution,
+                HTTP_SERVER_DURATION_METRIC,
+                "Duration of HTTP server requests.",
+            ),
+            max_request_duration: self.max_request_duration,
+            cancelled_requests: self.cancelled_requests,
+            client_abort_error_type: self.client_abort_error_type,
+            duration_from_accept_time: self.duration_from_accept_time,
+            request_context_extension: self.request_context_extension,
+            active_requests_route: self.active_requests_route,
+            #[cfg(feature = "span-attributes")]
+            span_attributes: self.span_attributes,
+            #[cfg(feature = "cardinality")]
+            server_request_duration_cardinality,
+            route_resolver: RouteResolver {
+                matched_path: self.matched_path_route,
+                extractor: self.route_extractor.clone(),
+                #[cfg(feature = "route-patterns")]
+                patterns: self
+                    .route_patterns
+                    .iter()
+                    .map(|pattern| {
+                        (RoutePattern::new(pattern), static_route_value(pattern.clone()))
+                    })
+                    .collect(),
+                fallback: self.fallback_route.clone().map(static_route_value),
+                panics: server_extractor_panics.clone(),
+                #[cfg(feature = "route-cache")]
+                cache: (self.route_cache_capacity > 0).then(|| {
+                    RouteCache::new(
+                        self.route_cache_capacity,
+                        meter
+                            .u64_counter(HTTP_SERVER_ROUTE_CACHE_EVICTIONS_METRIC)
+                            .with_description("Number of routes evicted from the route cache.")
+                            .with_unit(HTTP_SERVER_ROUTE_CACHE_EVICTIONS_UNIT)
+                            .build(),
+                    )
+                }),
+            },
+            known_routes,
+            slo_thresholds,
+            min_recorded_durations,
+            latency_tracker: self.latency_alert.clone().map(
+                |(quantile, threshold, window, alert)| {
+                    LatencyTracker::new(quantile, threshold, window, alert)
+                },
+            ),
+            _server_request_count: server_request_count,
+            #[cfg(feature = "cardinality")]
+            _server_request_duration_attribute_sets: server_request_duration_attribute_sets,
+            server_request_duration_rollup: self.duration_rollup_keys.as_ref().map(|keys| {
+                DurationRollup {
+                    histogram: DurationHistogram::new(
+                        meter,
+                        self.duration_resolution,
+                        HTTP_SERVER_DURATION_ROLLUP_METRIC,
+                        "Duration of HTTP server requests, with rollup attributes.",
+                    ),
+                    keys: keys.clone(),
+                }
+            }),
+            server_request_duration_overflow: self.max_request_duration.map(|_| {
+                meter
+                    .u64_counter(HTTP_SERVER_DURATION_OVERFLOW_METRIC)
+                    .with_description(
+                        "Number of HTTP server requests whose recorded duration was clamped.",
+                    )
+                    .with_unit(HTTP_SERVER_DURATION_OVERFLOW_UNIT)
+                    .build()
+            }),
+            server_active_requests: self.active_requests.then(|| {
+                meter
+                    .i64_up_down_counter(Cow::from(HTTP_SERVER_ACTIVE_REQUESTS_METRIC))
+                    .with_description("Number of active HTTP server requests.")
+                    .with_unit(Cow::from(HTTP_SERVER_ACTIVE_REQUESTS_UNIT))
+                    .build()
+            }),
+            server_request_body_size: self.request_body_size.then(|| {
+                meter
+                    .u64_histogram(HTTP_SERVER_REQUEST_BODY_SIZE_METRIC)
+                    .with_description("Size of HTTP server request bodies.")
+                    .with_unit(HTTP_SERVER_REQUEST_BODY_SIZE_UNIT)
+                    .build()
+            }),
+            server_concurrent_requests: self.concurrent_requests_histogram.then(|| {
+                meter
+                    .u64_histogram(HTTP_SERVER_CONCURRENT_REQUESTS_METRIC)
+                    .with_description(
+                        "Number of concurrently active HTTP server requests at request completion.",
+                    )
+                    .with_unit(HTTP_SERVER_CONCURRENT_REQUESTS_UNIT)
+                    .with_boundaries(HTTP_SERVER_CONCURRENT_REQUESTS_BOUNDARIES.to_vec())
+                    .build()
+            }),
+            server_request_size: self.request_size.then(|| {
+                meter
+                    .u64_histogram(HTTP_SERVER_REQUEST_SIZE_METRIC)
+                    .with_description("Size of HTTP server requests, including headers and body.")
+                    .with_unit(HTTP_SERVER_REQUEST_SIZE_UNIT)
+                    .build()
+            }),
+            response_body_size_source: self.response_body_size_source,
+            server_response_body_size: self.response_body_size.then(|| {
+                meter
+                    .u64_histogram(HTTP_SERVER_RESPONSE_BODY_SIZE_METRIC)
+                    .with_description("Size of HTTP server response bodies.")
+                    .with_unit(HTTP_SERVER_RESPONSE_BODY_SIZE_UNIT)
+                    .build()
+            }),
+            server_response_body_frame_size: self.response_body_frame_metrics.then(|| {
+                meter
+                    .u64_histogram(HTTP_SERVER_RESPONSE_BODY_FRAME_SIZE_METRIC)
+                    .with_description("Size of HTTP server response body data frames.")
+                    .with_unit(HTTP_SERVER_RESPONSE_BODY_FRAME_SIZE_UNIT)
+                    .with_boundaries(HTTP_SERVER_RESPONSE_BODY_FRAME_SIZE_BOUNDARIES.to_vec())
+                    .build()
+            }),
+            server_response_body_frames: self.response_body_frame_metrics.then(|| {
+                meter
+                    .u64_histogram(HTTP_SERVER_RESPONSE_BODY_FRAMES_METRIC)
+                    .with_description("Number of data frames in HTTP server response bodies.")
+                    .with_unit(HTTP_SERVER_RESPONSE_BODY_FRAMES_UNIT)
+                    .with_boundaries(HTTP_SERVER_RESPONSE_BODY_FRAMES_BOUNDARIES.to_vec())
+                    .build()
+            }),
+            server_queue_time: self.queue_time_histogram.then(|| {
+                meter
+                    .f64_histogram(HTTP_SERVER_QUEUE_TIME_METRIC)
+                    .with_description(
+                        "Time HTTP server requests spent queued upstream before reaching the server.",
+                    )
+                    .with_unit(HTTP_SERVER_QUEUE_TIME_UNIT)
+                    .with_boundaries(HTTP_SERVER_DURATION_BOUNDARIES.to_vec())
+                    .build()
+            }),
+            server_request_polls: self.poll_count_histogram.then(|| {
+                meter
+                    .u64_histogram(HTTP_SERVER_REQUEST_POLLS_METRIC)
+                    .with_description("Number of times HTTP server response futures were polled.")
+                    .with_unit(HTTP_SERVER_REQUEST_POLLS_UNIT)
+                    .with_boundaries(HTTP_SERVER_REQUEST_POLLS_BOUNDARIES.to_vec())
+                    .build()
+            }),
+            server_request_stage_duration: self.timing_checkpoints.then(|| {
+                meter
+                    .f64_histogram(HTTP_SERVER_REQUEST_STAGE_DURATION_METRIC)
+                    .with_description("Duration of the stages of HTTP server requests.")
+                    .with_unit(HTTP_SERVER_REQUEST_STAGE_DURATION_UNIT)
+                    .with_boundaries(HTTP_SERVER_DURATION_BOUNDARIES.to_vec())
+                    .build()
+            }),
+            server_request_body_size_malformed: self.malformed_content_length_counter.then(|| {
+                meter
+                    .u64_counter(HTTP_SERVER_REQUEST_BODY_SIZE_MALFORMED_METRIC)
+                    .with_description("Number of HTTP server requests with a malformed Content-Length.")
+                    .with_unit(HTTP_SERVER_REQUEST_BODY_SIZE_MALFORMED_UNIT)
+                    .build()
+            }),
+            max_content_length: self.max_content_length,
+            server_informational_responses: self.informational_responses_counter.then(|| {
+                meter
+                    .u64_counter(HTTP_SERVER_INFORMATIONAL_RESPONSES_METRIC)
+                    .with_description("Number of informational (1xx) HTTP server responses.")
+                    .with_unit(HTTP_SERVER_INFORMATIONAL_RESPONSES_UNIT)
+                    .build()
+            }),
+            server_request_body_bytes: self.request_body_bytes_counter.then(|| {
+                meter
+                    .u64_counter(HTTP_SERVER_REQUEST_BODY_BYTES_METRIC)
+                    .with_description("Number of HTTP server request body bytes received.")
+                    .with_unit(HTTP_SERVER_REQUEST_BODY_BYTES_UNIT)
+                    .build()
+            }),
+            server_response_body_bytes: self.response_body_bytes_counter.then(|| {
+                meter
+                    .u64_counter(HTTP_SERVER_RESPONSE_BODY_BYTES_METRIC)
+                    .with_description("Number of HTTP server response body bytes sent.")
+                    .with_unit(HTTP_SERVER_RESPONSE_BODY_BYTES_UNIT)
+                    .build()
+            }),
+            server_network_io: self.network_io_counter.then(|| {
+                meter
+                    .u64_counter(HTTP_SERVER_NETWORK_IO_METRIC)
+                    .with_description(
+                        "Number of HTTP server header and body bytes received and transmitted.",
+                    )
+                    .with_unit(HTTP_SERVER_NETWORK_IO_UNIT)
+                    .build()
+            }),
+            grpc_service_attributes: self.grpc_service_attributes,
+            rpc_server_requests_per_rpc: self.grpc_message_counts.then(|| {
+                meter
+                    .u64_histogram(RPC_SERVER_REQUESTS_PER_RPC_METRIC)
+                    .with_description("Number of messages received per gRPC call.")
+                    .with_unit(RPC_SERVER_MESSAGES_PER_RPC_UNIT)
+                    .build()
+            }),
+            rpc_server_responses_per_rpc: self.grpc_message_counts.then(|| {
+                meter
+                    .u64_histogram(RPC_SERVER_RESPONSES_PER_RPC_METRIC)
+                    .with_description("Number of messages sent per gRPC call.")
+                    .with_unit(RPC_SERVER_MESSAGES_PER_RPC_UNIT)
+                    .build()
+            }),
+            server_request_continue_duration: self.expect_continue_duration.then(|| {
+                meter
+                    .f64_histogram(HTTP_SERVER_REQUEST_CONTINUE_DURATION_METRIC)
+                    .with_description(
+                        "Time between sending 100 Continue and receiving the full request body.",
+                    )
+                    .with_unit(HTTP_SERVER_REQUEST_CONTINUE_DURATION_UNIT)
+                    .with_boundaries(HTTP_SERVER_DURATION_BOUNDARIES.to_vec())
+                    .build()
+            }),
+            default_url_scheme: match &self.default_url_scheme {
+                Cow::Borrowed(scheme) => StringValue::from(*scheme),
+                Cow::Owned(scheme) => StringValue::from(Arc::<str>::from(scheme.as_str())),
+            },
+            string_status_code: self.string_status_code,
+            status_class: self.status_class,
+            kept_status_codes: self.kept_status_codes.clone(),
+            body_metrics_filter: self.body_metrics_filter.clone(),
+            custom_histograms: self
+                .custom_histograms
+                .iter()
+                .map(|(name, unit)| {
+                    let histogram = meter
+                        .f64_histogram(name.clone())
+                        .with_description("Custom histogram recorded from handler-supplied values.")
+                        .with_unit(unit.clone())
+                        .build();
+                    (name.clone(), histogram)
+                })
+                .collect(),
+            custom_instruments: self
+                .custom_instruments
+                .iter()
+                .map(|instrument| instrument.build(meter))
+                .collect(),
+            request_classifier: self.request_classifier.clone(),
+            request_attribute_extractors,
+            response_attribute_extractors,
+            server_extractor_panics,
+            max_extractor_attributes: self.max_extractor_attributes,
+            server_extractor_attributes_dropped: meter
+                .u64_counter(HTTP_SERVER_EXTRACTOR_ATTRIBUTES_DROPPED_METRIC)
+                .with_description("Number of extractor attributes dropped beyond the maximum.")
+                .with_unit(HTTP_SERVER_EXTRACTOR_ATTRIBUTES_DROPPED_UNIT)
+                .build(),
+            #[cfg(feature = "async-extractor")]
+            async_attribute_extractors: self.async_attribute_extractors.clone(),
+            #[cfg(feature = "async-extractor")]
+            async_extractor_budget: self.async_extractor_budget,
+            #[cfg(feature = "async-extractor")]
+            server_extractor_timeouts: meter
+                .u64_counter(HTTP_SERVER_EXTRACTOR_TIMEOUTS_METRIC)
+                .with_description("Number of async extractors which exceeded their time budget.")
+                .with_unit(HTTP_SERVER_EXTRACTOR_TIMEOUTS_UNIT)
+                .build(),
+            usage_units: self.usage_counter.as_ref().map(|(name, unit)| {
+                meter
+                    .u64_counter(name.clone())
+                    .with_description("Usage units consumed by HTTP server requests.")
+                    .with_unit(unit.clone())
+                    .build()
+            }),
+            usage_tenant_header: self.usage_tenant_header.clone(),
+            cache_status_attribute: self.cache_status_attribute,
+            auth_outcome_attributes: self.auth_outcome_attributes,
+            user_authenticated_attribute: self.user_authenticated_attribute,
+            principal_buckets: self.principal_buckets,
+            operation_ids: self
+                .operation_id_attribute
+                .then(|| OperationIds::new(&self.operation_ids)),
+            backend_attribute: self.backend_attribute,
+            backend: self.backend.clone().map(static_route_value),
+            #[cfg(feature = "tower-http")]
+            failure_classifier: self.failure_classifier.clone(),
+            url_path_sanitizer: self.url_path_sanitizer.clone(),
+            query_param_attributes: self.query_param_attributes.clone(),
+            server_address_attributes: self.server_address_attributes,
+            network_peer_address_attribute: self.network_peer_address_attribute,
+            client_geo_attributes: self.client_geo_attributes,
+            faas_attributes: self.faas_attributes,
+            traffic_split_attribute: self.traffic_split_attribute.clone(),
+            #[cfg(feature = "user-agent")]
+            user_agent_device_category: self.user_agent_device_category,
+            route_fallback_attribute: self.route_fallback_attribute,
+            #[cfg(feature = "axum")]
+            placement_guard: (self.placement_warning.is_some() || self.strict_placement).then(
+                || PlacementGuard::new(self.placement_warning.clone(), self.strict_placement),
+            ),
+            #[cfg(feature = "semconv-validation")]
+            semconv_validator: self
+                .semconv_validation
+                .clone()
+                .map(|report| SemconvValidator::new(report, self.string_status_code)),
+            #[cfg(feature = "trace-sampling")]
+            trace_sampling: self.trace_sampling,
+            #[cfg(feature = "failure-logs")]
+            failure_logger: self.failure_logger.clone(),
+            #[cfg(feature = "load")]
+            load_measure: self.load_measure,
+            server_rate_limit_limit: self.rate_limit_gauges.then(|| {
+                meter
+                    .u64_gauge(HTTP_SERVER_RATE_LIMIT_LIMIT_METRIC)
+                    .with_description("Rate limit quota reported by HTTP server responses.")
+                    .with_unit(HTTP_SERVER_RATE_LIMIT_UNIT)
+                    .build()
+            }),
+            server_rate_limit_remaining: self.rate_limit_gauges.then(|| {
+                meter
+                    .u64_gauge(HTTP_SERVER_RATE_LIMIT_REMAINING_METRIC)
+                    .with_description(
+                        "Remaining rate limit quota reported by HTTP server responses.",
+                    )
+                    .with_unit(HTTP_SERVER_RATE_LIMIT_UNIT)
+                    .build()
+            }),
+            server_request_throttled: self.throttled_requests_counter.then(|| {
+                meter
+                    .u64_counter(HTTP_SERVER_REQUEST_THROTTLED_METRIC)
+                    .with_description("Number of HTTP server requests throttled by rate limiting.")
+                    .with_unit(HTTP_SERVER_REQUEST_THROTTLED_UNIT)
+                    .build()
+            }),
+            server_range_requests: self.range_request_metrics.then(|| {
+                meter
+                    .u64_counter(HTTP_SERVER_RANGE_REQUESTS_METRIC)
+                    .with_description(
+                        "Number of HTTP server requests for byte ranges or answered with partial content.",
+                    )
+                    .with_unit(HTTP_SERVER_RANGE_REQUESTS_UNIT)
+                    .build()
+            }),
+            server_response_range_size: self.range_request_metrics.then(|| {
+                meter
+                    .u64_histogram(HTTP_SERVER_RESPONSE_RANGE_SIZE_METRIC)
+                    .with_description("Size of the byte ranges of HTTP server partial content responses.")
+                    .with_unit(HTTP_SERVER_RESPONSE_RANGE_SIZE_UNIT)
+                    .build()
+            }),
+            connect_tunnels: self.connect_tunnels,
+            server_open_tunnels: self.open_tunnels_counter.then(|| {
+                meter
+                    .i64_up_down_counter(HTTP_SERVER_OPEN_TUNNELS_METRIC)
+                    .with_description("Number of tunnels established by CONNECT requests which are open.")
+                    .with_unit(HTTP_SERVER_OPEN_TUNNELS_UNIT)
+                    .build()
+            }),
+            meter_provider: None,
+            #[cfg(feature = "diagnostics")]
+            meter_provider_dropped: false,
+            #[cfg(feature = "diagnostics")]
+            meter_provider_unbound: false,
+            #[cfg(feature = "diagnostics")]
+            last_collection,
+            #[cfg(feature = "diagnostics")]
+            _diagnostics_collection: diagnostics_collection,
+            pass_through: false,
+            active_requests: AtomicU64::new(0),
+        }
+    }
+}
+
+impl BodyMetricsFilter {
+    pub fn new() -> Self {
+        BodyMetricsFilter::default()
+    }
+
+    /// Select requests matching the route pattern.
+    pub fn with_route(mut self, pattern: &str) -> Self {
+        self.routes.push(RoutePattern::new(pattern));
+        self
+    }
+
+    /// Select requests using the method.
+    pub fn with_method(mut self, method: http::Method) -> Self {
+        self.methods.push(method);
+        self
+    }
+
+    pub(crate) fn matches(&self, method: &http::Method, route: Option<&str>, path: &str) -> bool {
+        let route_matches = self.routes.is_empty()
+            || self.routes.iter().any(|pattern| {
+                route.is_some_and(|route| pattern.matches(route)) || pattern.matches(path)
+            });
+        let method_matches = self.methods.is_empty() || self.methods.contains(method);
+        route_matches && method_matches
+    }
+}
@@ -0,0 +1,136 @@
+//! Capture of the instruments a layer creates, without recording anything.
+//!
+//! Building the layer state against the meter of [`CapturedInstruments`] records how each
+//! instrument was created, after the naming convention and aliases are applied, for tooling
+//! describing the layer's metrics rather than recording them.
+
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use opentelemetry::metrics::{
+    AsyncInstrumentBuilder, Counter, Gauge, Histogram, HistogramBuilder, InstrumentBuilder,
+    InstrumentProvider, Meter, ObservableCounter, ObservableGauge, ObservableUpDownCounter,
+    UpDownCounter,
+};
+
+use crate::binding::NoopInstruments;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "dashboard"), allow(dead_code))]
+pub(crate) enum InstrumentKind {
+    Counter,
+    UpDownCounter,
+    Gauge,
+    Histogram,
+}
+
+#[cfg_attr(not(feature = "dashboard"), allow(dead_code))]
+pub(crate) struct CapturedInstrument {
+    pub(crate) name: Cow<'static, str>,
+    pub(crate) kind: InstrumentKind,
+    pub(crate) description: Option<Cow<'static, str>>,
+    pub(crate) unit: Option<Cow<'static, str>>,
+    pub(crate) boundaries: Option<Vec<f64>>,
+}
+
+/// Instrument provider capturing the instruments created through it, which record nothing.
+#[derive(Clone, Default)]
+pub(crate) struct CapturedInstruments {
+    instruments: Arc<Mutex<Vec<CapturedInstrument>>>,
+}
+
+impl CapturedInstruments {
+    pub(crate) fn meter(&self) -> Meter {
+        Meter::new(Arc::new(self.clone()))
+    }
+
+    /// The instruments captured so far, in order of creation.
+    pub(crate) fn instruments(&self) -> MutexGuard<'_, Vec<CapturedInstrument>> {
+        self.instruments
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn capture(&self, instrument: CapturedInstrument) {
+        let mut instruments = self
+            .instruments
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if !instruments
+            .iter()
+            .any(|captured| captured.name == instrument.name)
+        {
+            instruments.push(instrument);
+        }
+    }
+}
+
+fn noop_meter() -> Meter {
+    Meter::new(Arc::new(NoopInstruments))
+}
+
+macro_rules! captured_instruments {
+    (
+        sync { $($method:ident: $builder:ident<$inst:ident<$value:ty>> => $kind:ident;)* }
+        async { $($async_method:ident: $async_inst:ident<$async_value:ty> => $async_kind:ident;)* }
+    ) => {
+        impl InstrumentProvider for CapturedInstruments {
+            $(
+                fn $method(&self, builder: $builder<'_, $inst<$value>>) -> $inst<$value> {
+                    self.capture(CapturedInstrument {
+                        name: builder.name.clone(),
+                        kind: InstrumentKind::$kind,
+                        description: builder.description.clone(),
+                        unit: builder.unit.clone(),
+                        boundaries: captured_instruments!(@boundaries builder, $builder),
+                    });
+                    noop_meter().$method(builder.name).build()
+                }
+            )*
+            $(
+                fn $async_method(
+                    &self,
+                    builder: AsyncInstrumentBuilder<'_, $async_inst<$async_value>, $async_value>,
+                ) -> $async_inst<$async_value> {
+                    self.capture(CapturedInstrument {
+                        name: builder.name.clone(),
+                        kind: InstrumentKind::$async_kind,
+                        description: builder.description.clone(),
+                        unit: builder.unit.clone(),
+                        boundaries: None,
+                    });
+                    noop_meter().$async_method(builder.name).build()
+                }
+            )*
+        }
+    };
+    (@boundaries $builder:ident, HistogramBuilder) => {
+        $builder.boundaries.clone()
+    };
+    (@boundaries $builder:ident, InstrumentBuilder) => {
+        None
+    };
+}
+
+captured_instruments! {
+    sync {
+        u64_counter: InstrumentBuilder<Counter<u64>> => Counter;
+        f64_counter: InstrumentBuilder<Counter<f64>> => Counter;
+        i64_up_down_counter: InstrumentBuilder<UpDownCounter<i64>> => UpDownCounter;
+        f64_up_down_counter: InstrumentBuilder<UpDownCounter<f64>> => UpDownCounter;
+        u64_gauge: InstrumentBuilder<Gauge<u64>> => Gauge;
+        f64_gauge: InstrumentBuilder<Gauge<f64>> => Gauge;
+        i64_gauge: InstrumentBuilder<Gauge<i64>> => Gauge;
+        f64_histogram: HistogramBuilder<Histogram<f64>> => Histogram;
+        u64_histogram: HistogramBuilder<Histogram<u64>> => Histogram;
+    }
+    async {
+        u64_observable_counter: ObservableCounter<u64> => Counter;
+        f64_observable_counter: ObservableCounter<f64> => Counter;
+        i64_observable_up_down_counter: ObservableUpDownCounter<i64> => UpDownCounter;
+        f64_observable_up_down_counter: ObservableUpDownCounter<f64> => UpDownCounter;
+        u64_observable_gauge: ObservableGauge<u64> => Gauge;
+        i64_observable_gauge: ObservableGauge<i64> => Gauge;
+        f64_observable_gauge: ObservableGauge<f64> => Gauge;
+    }
+}
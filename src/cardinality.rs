@@ -0,0 +1,107 @@
+//! Approximate count of the distinct attribute sets recorded by the layer.
+//!
+//! Distinct attribute sets are counted with a HyperLogLog sketch, which takes a fixed amount of
+//! memory and lock-free updates regardless of the actual cardinality, at the cost of a standard
+//! error of about 3%.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use opentelemetry::KeyValue;
+
+use crate::labels::attribute_set_hash;
+
+// 2^10 registers give a standard error of 1.04 / sqrt(1024), about 3%
+const PRECISION: u32 = 10;
+const REGISTERS: usize = 1 << PRECISION;
+
+pub(crate) type CardinalityWarning = dyn Fn(u64) + Send + Sync;
+
+/// HyperLogLog estimate of the number of distinct attribute sets.
+pub(crate) struct CardinalityEstimator {
+    registers: Box<[AtomicU8]>,
+    warning: Option<ThresholdWarning>,
+}
+
+struct ThresholdWarning {
+    threshold: u64,
+    interval: Duration,
+    created_at: Instant,
+    // nanoseconds since `created_at` after which the threshold is checked again
+    next_check: AtomicU64,
+    warn: Arc<CardinalityWarning>,
+}
+
+impl CardinalityEstimator {
+    pub(crate) fn new(warning: Option<(u64, Duration, Arc<CardinalityWarning>)>) -> Self {
+        CardinalityEstimator {
+            registers: (0..REGISTERS).map(|_| AtomicU8::new(0)).collect(),
+            warning: warning.map(|(threshold, interval, warn)| ThresholdWarning {
+                threshold,
+                interval,
+                created_at: Instant::now(),
+                next_check: AtomicU64::new(0),
+                warn,
+            }),
+        }
+    }
+
+    /// Add an attribute set to the estimate, calling the warning when the estimate is at or above
+    /// its threshold, at most once per interval.
+    pub(crate) fn observe(&self, attributes: &[KeyValue]) {
+        let hash = attribute_set_hash(attributes);
+        let register = (hash >> (u64::BITS - PRECISION)) as usize;
+        // position of the first set bit among the remaining bits, capped when they are all zero
+        let rank = ((hash << PRECISION).leading_zeros() + 1).min(u64::BITS - PRECISION + 1) as u8;
+        self.registers[register].fetch_max(rank, Ordering::Relaxed);
+
+        if let Some(warning) = &self.warning {
+            let now = warning.created_at.elapsed().as_nanos() as u64;
+            let next_check = warning.next_check.load(Ordering::Relaxed);
+            if now < next_check {
+                return;
+            }
+            let following_check = now.saturating_add(warning.interval.as_nanos() as u64);
+            // only the request which moves the next check forward checks the threshold
+            if warning
+                .next_check
+                .compare_exchange(
+                    next_check,
+                    following_check,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                let estimate = self.estimate();
+                if estimate >= warning.threshold {
+                    (warning.warn)(estimate);
+                }
+            }
+        }
+    }
+
+    /// Estimated number of distinct attribute sets observed so far.
+    pub(crate) fn estimate(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let mut sum = 0.0;
+        let mut zeros = 0;
+        for register in self.registers.iter() {
+            let rank = register.load(Ordering::Relaxed);
+            sum += 2f64.powi(-i32::from(rank));
+            if rank == 0 {
+                zeros += 1;
+            }
+        }
+
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let estimate = alpha * m * m / sum;
+        if estimate <= 2.5 * m && zeros > 0 {
+            // linear counting is more accurate for small cardinalities
+            (m * (m / f64::from(zeros)).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Timing checkpoints marked by the middleware and handlers of a request.
+//!
+//! `http.server.request.duration` covers a request end to end; where the time goes within it,
+//! e.g. authentication, a database query or rendering, is usually only visible in traces. Code
+//! anywhere inside the layer marks the end of each stage on the [`TimingCheckpoints`] in the
+//! request extensions, and the layer records the stage durations into a histogram of their own,
+//! giving per-stage latency from metrics alone.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use opentelemetry::metrics::Histogram;
+use opentelemetry::KeyValue;
+
+use crate::clock::Instant;
+
+pub(crate) const HTTP_SERVER_REQUEST_STAGE_DURATION_METRIC: &str =
+    "http.server.request.stage.duration";
+pub(crate) const HTTP_SERVER_REQUEST_STAGE_DURATION_UNIT: &str = "s";
+const HTTP_SERVER_REQUEST_STAGE_LABEL: &str = "http.server.request.stage";
+
+#[derive(Clone)]
+/// Request extension on which middleware and handlers mark the end of each stage of a request.
+///
+/// Inserted when enabled with [`HTTPMetricsLayerBuilder::with_timing_checkpoints`]. Each stage
+/// lasts from the previous checkpoint, or the start of the request, up to its own checkpoint, and
+/// is recorded in `http.server.request.stage.duration` once the response is ready, so checkpoints
+/// marked afterwards, e.g. while the response body streams, are not recorded. Clones share their
+/// checkpoints, so a handler can keep a clone after the request is consumed:
+///
+/// ```
+/// use tower_otel_http_metrics::TimingCheckpoints;
+///
+/// async fn handler(req: http::Request<()>) -> http::Response<()> {
+///     let checkpoints = req.extensions().get::<TimingCheckpoints>().cloned();
+///     // load the user...
+///     if let Some(checkpoints) = &checkpoints {
+///         checkpoints.mark("load_user");
+///     }
+///     // render the page...
+///     if let Some(checkpoints) = &checkpoints {
+///         checkpoints.mark("render");
+///     }
+///     http::Response::new(())
+/// }
+/// ```
+///
+/// [`HTTPMetricsLayerBuilder::with_timing_checkpoints`]: crate::HTTPMetricsLayerBuilder::with_timing_checkpoints
+pub struct TimingCheckpoints(Arc<CheckpointsState>);
+
+struct CheckpointsState {
+    start: Instant,
+    marks: Mutex<Vec<(&'static str, Instant)>>,
+}
+
+impl TimingCheckpoints {
+    pub(crate) fn new(start: Instant) -> Self {
+        TimingCheckpoints(Arc::new(CheckpointsState {
+            start,
+            marks: Mutex::new(Vec::new()),
+        }))
+    }
+
+    /// Mark the end of the stage `name`, which began at the previous checkpoint.
+    ///
+    /// The name is recorded as the `http.server.request.stage` attribute, so it should come from a
+    /// small fixed set, as routes do.
+    pub fn mark(&self, name: &'static str) {
+        let now = Instant::now();
+        self.0
+            .marks
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push((name, now));
+    }
+
+    /// Record the duration of each stage marked so far, along with the given attributes.
+    pub(crate) fn record(&self, histogram: &Histogram<f64>, labels: &[KeyValue]) {
+        let marks = self.0.marks.lock().unwrap_or_else(|err| err.into_inner());
+        let mut previous = self.0.start;
+        let mut stage_labels = labels.to_vec();
+        for &(name, instant) in marks.iter() {
+            stage_labels.truncate(labels.len());
+            stage_labels.push(KeyValue::new(HTTP_SERVER_REQUEST_STAGE_LABEL, name));
+            histogram.record(
+                instant.saturating_duration_since(previous).as_secs_f64(),
+                &stage_labels,
+            );
+            previous = instant;
+        }
+    }
+}
+
+impl fmt::Debug for TimingCheckpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marks = self.0.marks.lock().unwrap_or_else(|err| err.into_inner());
+        f.debug_struct("TimingCheckpoints")
+            .field(
+                "marks",
+                &marks
+                    .iter()
+                    .map(|(name, instant)| (name, instant.saturating_duration_since(self.0.start)))
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
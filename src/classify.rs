@@ -0,0 +1,48 @@
+//! Failure classification shared with tower-http.
+//!
+//! tower-http's `TraceLayer` decides which responses are failures with a [`MakeClassifier`].
+//! Handing the same classifier to the layer records the failure class of each failed response as
+//! the `error.type` of `http.server.request.duration`, so the error rate seen in metrics matches
+//! the one seen in traces.
+//!
+//! Responses are classified from their head. Classifiers which need the end of a streaming
+//! response to decide, e.g. for gRPC statuses sent in trailers, leave those responses unclassified.
+//! Errors returned by the inner service keep the `error.type` the layer determines for them.
+
+use std::fmt::Display;
+use std::sync::Arc;
+
+use tower_http::classify::{ClassifiedResponse, ClassifyResponse, MakeClassifier};
+
+pub(crate) type MakeFailureClassifier =
+    dyn Fn(&http::Request<()>) -> Box<dyn ClassifyFailure> + Send + Sync;
+
+/// Type-erased [`ClassifyResponse`] of a single request.
+pub(crate) trait ClassifyFailure: Send {
+    /// The `error.type` of the response, or `None` when it did not fail.
+    fn classify(self: Box<Self>, res: &http::Response<()>) -> Option<String>;
+}
+
+impl<C> ClassifyFailure for C
+where
+    C: ClassifyResponse + Send,
+    C::FailureClass: Display,
+{
+    fn classify(self: Box<Self>, res: &http::Response<()>) -> Option<String> {
+        match (*self).classify_response(res) {
+            ClassifiedResponse::Ready(Err(class)) => Some(class.to_string()),
+            ClassifiedResponse::Ready(Ok(())) | ClassifiedResponse::RequiresEos(_) => None,
+        }
+    }
+}
+
+pub(crate) fn make_failure_classifier<M>(make_classifier: M) -> Arc<MakeFailureClassifier>
+where
+    M: MakeClassifier + Send + Sync + 'static,
+    M::Classifier: Send + 'static,
+    M::FailureClass: Display,
+{
+    Arc::new(move |req: &http::Request<()>| {
+        Box::new(make_classifier.make_classifier(req)) as Box<dyn ClassifyFailure>
+    })
+}
@@ -0,0 +1,668 @@
+//! Request metrics for tower HTTP clients.
+//!
+//! [`ClientMetricsLayer`] wraps a client service taking [`http::Request`]s, such as hyper-util's
+//! legacy `Client`, recording `http.client.request.duration` until the response head arrives,
+//! along with `http.client.request.body.size` and the opt-in `http.client.response.body.size`.
+//!
+//! Body sizes are taken from the `Content-Length` header, else from the exact size hint of the
+//! body. Other bodies, e.g. streamed uploads or chunked responses, are counted as their frames pass
+//! through [`ClientRequestBody`] and [`ClientResponseBody`], and recorded once they end; bodies
+//! dropped before their end are not recorded, as their size is unknown. A request body still
+//! streaming when the response arrives is recorded with the response attributes once it ends.
+//!
+//! Full URLs would make a series of each requested resource, so none is recorded by default.
+//! [URL templates](ClientMetricsLayer::with_url_template) instead group the paths of outgoing
+//! requests under the `url.template` attribute, e.g. `/users/{id}`, for per-endpoint metrics of
+//! the services called.
+//!
+//! Redirects are followed inside the layer, by a redirect-following service or policy, so the
+//! layer only sees the original request and the final response, and time spent on redirects
+//! silently adds to `http.client.request.duration`. With
+//! [redirect tracking](ClientMetricsLayer::with_redirect_tracking), the redirect-following code
+//! reports each redirect it follows on the [`ClientRedirects`] request extension; the layer then
+//! counts them in `http.client.redirects` and attaches the original and final hosts to both
+//! metrics.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::result;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use std::{fmt, mem};
+
+use bytes::Buf;
+use futures_util::ready;
+use http::uri::Authority;
+use http::Uri;
+use http_body::{Body, Frame, SizeHint};
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::{KeyValue, StringValue};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::attributes::{content_length, ContentLength, SERVER_ADDRESS_LABEL, SERVER_PORT_LABEL};
+use crate::labels::method_value;
+use crate::record::split_and_format_protocol_version;
+use crate::route::RoutePattern;
+use crate::{
+    HTTP_REQUEST_METHOD_LABEL, HTTP_RESPONSE_STATUS_CODE_LABEL, HTTP_SERVER_DURATION_BOUNDARIES,
+    NETWORK_PROTOCOL_NAME_LABEL, NETWORK_PROTOCOL_VERSION_LABEL,
+};
+
+const HTTP_CLIENT_REQUEST_DURATION_METRIC: &str = "http.client.request.duration";
+const HTTP_CLIENT_REQUEST_DURATION_UNIT: &str = "s";
+
+const HTTP_CLIENT_REQUEST_BODY_SIZE_METRIC: &str = "http.client.request.body.size";
+const HTTP_CLIENT_RESPONSE_BODY_SIZE_METRIC: &str = "http.client.response.body.size";
+const HTTP_CLIENT_BODY_SIZE_UNIT: &str = "By";
+
+const HTTP_CLIENT_REDIRECTS_METRIC: &str = "http.client.redirects";
+const HTTP_CLIENT_REDIRECTS_UNIT: &str = "{redirect}";
+
+const URL_TEMPLATE_LABEL: &str = "url.template";
+const HTTP_REDIRECT_ORIGINAL_HOST_LABEL: &str = "http.redirect.original_host";
+const HTTP_REDIRECT_FINAL_HOST_LABEL: &str = "http.redirect.final_host";
+const ERROR_TYPE_LABEL: &str = "error.type";
+const ERROR_TYPE_OTHER: &str = "_OTHER";
+
+#[derive(Clone)]
+struct ClientMetricsLayerState {
+    client_request_duration: Histogram<f64>,
+    client_request_body_size: Histogram<u64>,
+    client_response_body_size: Histogram<u64>,
+    client_redirects: Counter<u64>,
+    request_body_size: bool,
+    response_body_size: bool,
+    url_templates: Vec<(RoutePattern, StringValue)>,
+    redirect_tracking: bool,
+}
+
+#[derive(Clone)]
+/// [`Layer`] which applies OTEL HTTP client request metrics to a client service
+pub struct ClientMetricsLayer {
+    state: Arc<ClientMetricsLayerState>,
+}
+
+#[derive(Clone)]
+/// Client [`Service`] used by [`ClientMetricsLayer`]
+pub struct ClientMetricsService<S> {
+    state: Arc<ClientMetricsLayerState>,
+    inner_service: S,
+}
+
+impl ClientMetricsLayer {
+    /// Create the layer, registering the client request instruments with the given meter.
+    pub fn new(meter: &Meter) -> Self {
+        ClientMetricsLayer {
+            state: Arc::new(ClientMetricsLayerState {
+                client_request_duration: meter
+                    .f64_histogram(HTTP_CLIENT_REQUEST_DURATION_METRIC)
+                    .with_description("Duration of HTTP client requests.")
+                    .with_unit(HTTP_CLIENT_REQUEST_DURATION_UNIT)
+                    .with_boundaries(HTTP_SERVER_DURATION_BOUNDARIES.to_vec())
+                    .build(),
+                client_request_body_size: meter
+                    .u64_histogram(HTTP_CLIENT_REQUEST_BODY_SIZE_METRIC)
+                    .with_description("Size of HTTP client request bodies.")
+                    .with_unit(HTTP_CLIENT_BODY_SIZE_UNIT)
+                    .build(),
+                client_response_body_size: meter
+                    .u64_histogram(HTTP_CLIENT_RESPONSE_BODY_SIZE_METRIC)
+                    .with_description("Size of HTTP client response bodies.")
+                    .with_unit(HTTP_CLIENT_BODY_SIZE_UNIT)
+                    .build(),
+                client_redirects: meter
+                    .u64_counter(HTTP_CLIENT_REDIRECTS_METRIC)
+                    .with_description("Number of redirects followed by HTTP client requests.")
+                    .with_unit(HTTP_CLIENT_REDIRECTS_UNIT)
+                    .build(),
+                request_body_size: true,
+                response_body_size: false,
+                url_templates: Vec::new(),
+                redirect_tracking: false,
+            }),
+        }
+    }
+
+    /// Record `http.client.request.body.size`, the size of the request body sent. Enabled by
+    /// default.
+    pub fn with_request_body_size(self, enabled: bool) -> Self {
+        ClientMetricsLayer {
+            state: Arc::new(ClientMetricsLayerState {
+                request_body_size: enabled,
+                ..(*self.state).clone()
+            }),
+        }
+    }
+
+    /// Record `http.client.response.body.size`, opt-in in semconv, the size of the response body
+    /// received. Disabled by default.
+    pub fn with_response_body_size(self, enabled: bool) -> Self {
+        ClientMetricsLayer {
+            state: Arc::new(ClientMetricsLayerState {
+                response_body_size: enabled,
+                ..(*self.state).clone()
+            }),
+        }
+    }
+
+    /// Add a `url.template` attribute to the requests whose path matches `template`, using
+    /// router-style patterns such as `/users/{id}`, `/users/:id`, or `/files/{*path}`.
+    ///
+    /// The template itself is the attribute value, keeping the cardinality of per-endpoint metrics
+    /// bounded however many resources are requested. Templates are tried in the order they were
+    /// added, so add specific templates before more general ones. Requests matching no template do
+    /// not get the attribute.
+    ///
+    /// ```
+    /// use tower_otel_http_metrics::client::ClientMetricsLayer;
+    ///
+    /// let meter = opentelemetry::global::meter("client");
+    /// let layer = ClientMetricsLayer::new(&meter)
+    ///     .with_url_template("/users/me")
+    ///     .with_url_template("/users/{id}");
+    /// ```
+    pub fn with_url_template(self, template: &'static str) -> Self {
+        let mut state = (*self.state).clone();
+        state
+            .url_templates
+            .push((RoutePattern::new(template), StringValue::from(template)));
+        ClientMetricsLayer {
+            state: Arc::new(state),
+        }
+    }
+
+    /// Track the redirects followed inside the layer, counting them in `http.client.redirects`
+    /// and adding `http.redirect.original_host` and `http.redirect.final_host` attributes to the
+    /// metrics of requests which were redirected.
+    ///
+    /// The layer inserts a [`ClientRedirects`] extension into each request, on which the
+    /// redirect-following code inside the layer reports the redirects it follows. Requests which
+    /// were not redirected do not get the attributes. Disabled by default.
+    pub fn with_redirect_tracking(self, enabled: bool) -> Self {
+        ClientMetricsLayer {
+            state: Arc::new(ClientMetricsLayerState {
+                redirect_tracking: enabled,
+                ..(*self.state).clone()
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for ClientMetricsLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientMetricsLayer")
+            .field("request_body_size", &self.state.request_body_size)
+            .field("response_body_size", &self.state.response_body_size)
+            .field("redirect_tracking", &self.state.redirect_tracking)
+            .field(
+                "url_templates",
+                &self
+                    .state
+                    .url_templates
+                    .iter()
+                    .map(|(_, template)| template.as_str())
+                    .collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for ClientMetricsService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientMetricsService")
+            .field("inner_service", &self.inner_service)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for ClientMetricsLayer {
+    type Service = ClientMetricsService<S>;
+
+    fn layer(&self, inner_service: S) -> Self::Service {
+        ClientMetricsService {
+            state: self.state.clone(),
+            inner_service,
+        }
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for ClientMetricsService<S>
+where
+    S: Service<http::Request<ClientRequestBody<ReqBody>>, Response = http::Response<ResBody>>,
+    ReqBody: Body,
+    ResBody: Body,
+{
+    type Response = http::Response<ClientResponseBody<ResBody>>;
+    type Error = S::Error;
+    type Future = ClientMetricsFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        let request_start = Instant::now();
+        let mut labels = labels_client_request(&req);
+        let path = req.uri().path();
+        if let Some((_, template)) = self
+            .state
+            .url_templates
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path))
+        {
+            labels.push(KeyValue::new(URL_TEMPLATE_LABEL, template.clone()));
+        }
+        let head_request = req.method() == http::Method::HEAD;
+        let redirects = self.state.redirect_tracking.then(|| {
+            let redirects = ClientRedirects::new(req.uri());
+            req.extensions_mut().insert(redirects.clone());
+            redirects
+        });
+
+        let (parts, body) = req.into_parts();
+        let request_body_size = if self.state.request_body_size {
+            Some(match known_body_size(&parts.headers, &body) {
+                Some(size) => RequestBodySize::Known(size),
+                None => RequestBodySize::Streamed(RequestBodyLink::default()),
+            })
+        } else {
+            None
+        };
+        let body = ClientRequestBody {
+            inner_body: body,
+            link: match &request_body_size {
+                Some(RequestBodySize::Streamed(link)) => Some(link.clone()),
+                _ => None,
+            },
+            size: 0,
+        };
+
+        ClientMetricsFuture {
+            inner_response_future: self
+                .inner_service
+                .call(http::Request::from_parts(parts, body)),
+            layer_state: self.state.clone(),
+            labels,
+            request_start,
+            request_body_size,
+            head_request,
+            redirects,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Request extension on which redirect-following code inside a [`ClientMetricsLayer`] reports
+/// the redirects it follows, inserted with
+/// [`ClientMetricsLayer::with_redirect_tracking`].
+///
+/// Redirect-following services usually build the requests to the redirect targets without the
+/// extensions of the original request, so keep the extension of the original request, e.g. in
+/// the per-request state of a redirect policy, and report each redirect on it:
+///
+/// ```
+/// use tower_otel_http_metrics::client::ClientRedirects;
+///
+/// fn on_redirect<B>(original: &http::Request<B>, location: &http::Uri) {
+///     if let Some(redirects) = original.extensions().get::<ClientRedirects>() {
+///         redirects.follow(location);
+///     }
+/// }
+/// ```
+pub struct ClientRedirects(Arc<Mutex<RedirectsState>>);
+
+#[derive(Debug)]
+struct RedirectsState {
+    original_host: Option<String>,
+    final_host: Option<String>,
+    count: u64,
+}
+
+impl ClientRedirects {
+    fn new(uri: &Uri) -> Self {
+        let original_host = uri.host().map(str::to_ascii_lowercase);
+        ClientRedirects(Arc::new(Mutex::new(RedirectsState {
+            final_host: original_host.clone(),
+            original_host,
+            count: 0,
+        })))
+    }
+
+    /// Report a redirect followed to `location`, the target of the `Location` header resolved or
+    /// not; relative locations stay on the current host.
+    pub fn follow(&self, location: &Uri) {
+        let mut state = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        state.count += 1;
+        if let Some(host) = location.host() {
+            state.final_host = Some(host.to_ascii_lowercase());
+        }
+    }
+
+    /// Push the redirect attributes, returning the number of redirects followed, if any.
+    fn push_labels(&self, labels: &mut Vec<KeyValue>) -> Option<u64> {
+        let state = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        if state.count == 0 {
+            return None;
+        }
+        if let Some(original_host) = &state.original_host {
+            labels.push(KeyValue::new(
+                HTTP_REDIRECT_ORIGINAL_HOST_LABEL,
+                original_host.clone(),
+            ));
+        }
+        if let Some(final_host) = &state.final_host {
+            labels.push(KeyValue::new(
+                HTTP_REDIRECT_FINAL_HOST_LABEL,
+                final_host.clone(),
+            ));
+        }
+        Some(state.count)
+    }
+}
+
+/// Size of a request body, known up front or counted as it streams.
+enum RequestBodySize {
+    Known(u64),
+    Streamed(RequestBodyLink),
+}
+
+/// Link between a streamed request body and its response future, recording the body size with the
+/// response attributes once both the body has ended and the response has arrived.
+#[derive(Clone, Default)]
+struct RequestBodyLink(Arc<Mutex<RequestBodyLinkState>>);
+
+#[derive(Default)]
+struct RequestBodyLinkState {
+    // size of the body once it has ended
+    size: Option<u64>,
+    // histogram and attributes to record the size with once the body ends
+    recording: Option<(Histogram<u64>, Vec<KeyValue>)>,
+}
+
+impl RequestBodyLink {
+    /// Record the size of the body once it has ended, else when it ends.
+    fn record(&self, histogram: &Histogram<u64>, labels: Vec<KeyValue>) {
+        let mut state = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        match state.size {
+            Some(size) => histogram.record(size, &labels),
+            None => state.recording = Some((histogram.clone(), labels)),
+        }
+    }
+
+    /// Mark the body as ended after `size` bytes.
+    fn end(&self, size: u64) {
+        let mut state = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        match state.recording.take() {
+            Some((histogram, labels)) => histogram.record(size, &labels),
+            None => state.size = Some(size),
+        }
+    }
+}
+
+pin_project! {
+    /// Response [`Future`] for [`ClientMetricsService`].
+    pub struct ClientMetricsFuture<F> {
+        #[pin]
+        inner_response_future: F,
+        layer_state: Arc<ClientMetricsLayerState>,
+        labels: Vec<KeyValue>,
+        request_start: Instant,
+        request_body_size: Option<RequestBodySize>,
+        head_request: bool,
+        redirects: Option<ClientRedirects>,
+    }
+}
+
+impl<F> fmt::Debug for ClientMetricsFuture<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientMetricsFuture")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F, ResBody, E> Future for ClientMetricsFuture<F>
+where
+    F: Future<Output = result::Result<http::Response<ResBody>, E>>,
+    ResBody: Body,
+{
+    type Output = result::Result<http::Response<ClientResponseBody<ResBody>>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.inner_response_future.poll(cx));
+        let duration = this.request_start.elapsed().as_secs_f64();
+        let state = &**this.layer_state;
+
+        let mut labels = mem::take(this.labels);
+        let redirects = this
+            .redirects
+            .take()
+            .and_then(|redirects| redirects.push_labels(&mut labels));
+        let response = match result {
+            Ok(response) => response,
+            Err(err) => {
+                labels.push(KeyValue::new(ERROR_TYPE_LABEL, ERROR_TYPE_OTHER));
+                state.client_request_duration.record(duration, &labels);
+                if let Some(redirects) = redirects {
+                    state.client_redirects.add(redirects, &labels);
+                }
+                return Poll::Ready(Err(err));
+            }
+        };
+
+        push_response_labels(&response, &mut labels);
+        state.client_request_duration.record(duration, &labels);
+        if let Some(redirects) = redirects {
+            state.client_redirects.add(redirects, &labels);
+        }
+
+        match this.request_body_size.take() {
+            Some(RequestBodySize::Known(size)) => {
+                state.client_request_body_size.record(size, &labels);
+            }
+            Some(RequestBodySize::Streamed(link)) => {
+                link.record(&state.client_request_body_size, labels.clone());
+            }
+            None => {}
+        }
+
+        let (parts, body) = response.into_parts();
+        let mut body = ClientResponseBody {
+            inner_body: body,
+            recording: None,
+            size: 0,
+        };
+        if state.response_body_size {
+            match known_response_body_size(&parts, &body.inner_body, *this.head_request) {
+                Some(size) => state.client_response_body_size.record(size, &labels),
+                None => body.recording = Some((state.client_response_body_size.clone(), labels)),
+            }
+        }
+        Poll::Ready(Ok(http::Response::from_parts(parts, body)))
+    }
+}
+
+pin_project! {
+    /// Request body of [`ClientMetricsService`], counting the bytes of bodies whose size is not
+    /// known up front.
+    pub struct ClientRequestBody<B> {
+        #[pin]
+        inner_body: B,
+        link: Option<RequestBodyLink>,
+        size: u64,
+    }
+}
+
+impl<B> fmt::Debug for ClientRequestBody<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientRequestBody").finish_non_exhaustive()
+    }
+}
+
+impl<B: Body> Body for ClientRequestBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<result::Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let frame = ready!(this.inner_body.as_mut().poll_frame(cx));
+        if this.link.is_some() {
+            if let Some(data) = frame
+                .as_ref()
+                .and_then(|frame| frame.as_ref().ok()?.data_ref())
+            {
+                *this.size += data.remaining() as u64;
+            }
+            if frame.is_none() || this.inner_body.is_end_stream() {
+                if let Some(link) = this.link.take() {
+                    link.end(*this.size);
+                }
+            }
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner_body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner_body.size_hint()
+    }
+}
+
+pin_project! {
+    /// Response body of [`ClientMetricsService`], counting the bytes of bodies whose size is not
+    /// known up front.
+    pub struct ClientResponseBody<B> {
+        #[pin]
+        inner_body: B,
+        recording: Option<(Histogram<u64>, Vec<KeyValue>)>,
+        size: u64,
+    }
+}
+
+impl<B> fmt::Debug for ClientResponseBody<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientResponseBody").finish_non_exhaustive()
+    }
+}
+
+impl<B: Body> Body for ClientResponseBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<result::Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        let frame = ready!(this.inner_body.as_mut().poll_frame(cx));
+        if this.recording.is_some() {
+            if let Some(data) = frame
+                .as_ref()
+                .and_then(|frame| frame.as_ref().ok()?.data_ref())
+            {
+                *this.size += data.remaining() as u64;
+            }
+            if frame.is_none() || this.inner_body.is_end_stream() {
+                if let Some((histogram, labels)) = this.recording.take() {
+                    histogram.record(*this.size, &labels);
+                }
+            }
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner_body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner_body.size_hint()
+    }
+}
+
+/// Size of a body from the `Content-Length` of its message, else its exact size hint.
+fn known_body_size(headers: &http::HeaderMap, body: &impl Body) -> Option<u64> {
+    match content_length(headers, None) {
+        ContentLength::Valid(length) => Some(length),
+        ContentLength::Absent | ContentLength::Malformed => body.size_hint().exact(),
+    }
+}
+
+/// Size of a response body known from its head: empty for responses which have no body, per
+/// RFC 9110, whatever their `Content-Length` says.
+fn known_response_body_size(
+    parts: &http::response::Parts,
+    body: &impl Body,
+    head_request: bool,
+) -> Option<u64> {
+    let status = parts.status;
+    if head_request
+        || status.is_informational()
+        || status == http::StatusCode::NO_CONTENT
+        || status == http::StatusCode::NOT_MODIFIED
+    {
+        return Some(0);
+    }
+    known_body_size(&parts.headers, body)
+}
+
+/// Attributes of a request, known before it is sent.
+fn labels_client_request<B>(req: &http::Request<B>) -> Vec<KeyValue> {
+    let mut labels = Vec::with_capacity(10);
+    labels.push(KeyValue::new(
+        HTTP_REQUEST_METHOD_LABEL,
+        method_value(req.method()),
+    ));
+    let host_header = || {
+        req.headers()
+            .get(http::header::HOST)?
+            .to_str()
+            .ok()?
+            .parse::<Authority>()
+            .ok()
+    };
+    if let Some(authority) = req.uri().authority().cloned().or_else(host_header) {
+        labels.push(KeyValue::new(
+            SERVER_ADDRESS_LABEL,
+            authority.host().to_ascii_lowercase(),
+        ));
+        let port = authority
+            .port_u16()
+            .or_else(|| match req.uri().scheme_str() {
+                Some("http") => Some(80),
+                Some("https") => Some(443),
+                _ => None,
+            });
+        if let Some(port) = port {
+            labels.push(KeyValue::new(SERVER_PORT_LABEL, i64::from(port)));
+        }
+    }
+    labels
+}
+
+/// Push the attributes of a response, classifying 4xx and 5xx responses as errors, per semconv.
+fn push_response_labels<B>(response: &http::Response<B>, labels: &mut Vec<KeyValue>) {
+    let status = response.status();
+    labels.push(KeyValue::new(
+        HTTP_RESPONSE_STATUS_CODE_LABEL,
+        i64::from(status.as_u16()),
+    ));
+    let (protocol_name, protocol_version) = split_and_format_protocol_version(response.version());
+    labels.push(KeyValue::new(NETWORK_PROTOCOL_NAME_LABEL, protocol_name));
+    labels.push(KeyValue::new(
+        NETWORK_PROTOCOL_VERSION_LABEL,
+        protocol_version,
+    ));
+    if status.is_client_error() || status.is_server_error() {
+        labels.push(KeyValue::new(ERROR_TYPE_LABEL, status.as_str().to_owned()));
+    }
+}
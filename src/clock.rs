@@ -0,0 +1,47 @@
+//! The clock request durations are measured with.
+//!
+//! Each measured request reads the clock at least twice, which shows at high request rates. With
+//! the `fast-time` feature, durations are measured with [`quanta`], reading the CPU's TSC where
+//! it is reliable and falling back to the OS clock elsewhere. quanta calibrates the TSC against
+//! the OS clock the first time it is read, which takes a few milliseconds, so the layer
+//! [calibrates](calibrate) it when built rather than on the first request.
+//!
+//! Instants exchanged with the application, e.g. [`AcceptTime`] and
+//! [`RequestMetricsContext::start`], remain [`std::time::Instant`]s, converted through the time
+//! elapsed since or until now.
+//!
+//! [`AcceptTime`]: crate::AcceptTime
+//! [`RequestMetricsContext::start`]: crate::RequestMetricsContext::start
+
+#[cfg(feature = "fast-time")]
+pub(crate) use quanta::Instant;
+#[cfg(not(feature = "fast-time"))]
+pub(crate) use std::time::Instant;
+
+/// Calibrate the clock ahead of the first request.
+pub(crate) fn calibrate() {
+    #[cfg(feature = "fast-time")]
+    Instant::now();
+}
+
+/// The instant of the clock matching `instant`.
+pub(crate) fn from_std(instant: std::time::Instant) -> Instant {
+    #[cfg(feature = "fast-time")]
+    {
+        let now = Instant::now();
+        now.checked_sub(instant.elapsed()).unwrap_or(now)
+    }
+    #[cfg(not(feature = "fast-time"))]
+    instant
+}
+
+/// The [`std::time::Instant`] matching `instant`.
+pub(crate) fn to_std(instant: Instant) -> std::time::Instant {
+    #[cfg(feature = "fast-time")]
+    {
+        let now = std::time::Instant::now();
+        now.checked_sub(instant.elapsed()).unwrap_or(now)
+    }
+    #[cfg(not(feature = "fast-time"))]
+    instant
+}
@@ -0,0 +1,581 @@
+//! Connection metrics for [`hyper_util`] client connectors.
+//!
+//! The hyper-util connection pool does not expose hooks for observing its connections,
+//! so [`ConnectorMetricsLayer`] wraps the connector instead: each connection it establishes
+//! is wrapped in a [`MeteredConnection`] which tracks the connection for as long as it is open.
+//!
+//! [`ResolverMetricsLayer`] wraps a DNS resolver service (such as the one given to
+//! `HttpConnector::new_with_resolver`) to time lookups separately from connection establishment.
+//! Note that when the resolver is part of the connector, the connect duration includes
+//! the lookup; TCP/TLS setup time is the difference between the two.
+//!
+//! Because the pool is opaque, connections are tracked without an `http.connection.state`
+//! attribute; the pool's idle vs. active bookkeeping is not visible from the connector. For the
+//! same reason, the time requests wait to acquire a connection from the pool is not measured:
+//! requests served by a pooled connection never reach the connector, and those waiting on a new
+//! one are covered by `http.client.connect.duration`.
+//!
+//! Failed connections never produce an HTTP status, so their `error.type` on
+//! `http.client.connect.duration` is classified from the I/O errors among the error and its
+//! sources into a bounded set of values: `connection_refused`, `connection_reset`, `tls_error`,
+//! `timeout`, or `_OTHER`. Connector errors are boxed to be inspected, which the hyper-util client
+//! accepts.
+
+use std::collections::HashSet;
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::result;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use futures_util::ready;
+use http::Uri;
+use hyper::rt::{Read, ReadBufCursor, Write};
+use hyper_util::client::legacy::connect::{Connected, Connection};
+use opentelemetry::metrics::{Histogram, Meter, UpDownCounter};
+use opentelemetry::KeyValue;
+use pin_project_lite::pin_project;
+use tower::BoxError;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::URL_SCHEME_LABEL;
+
+const HTTP_CLIENT_OPEN_CONNECTIONS_METRIC: &str = "http.client.open_connections";
+const HTTP_CLIENT_OPEN_CONNECTIONS_UNIT: &str = "{connection}";
+
+const HTTP_CLIENT_CONNECTION_DURATION_METRIC: &str = "http.client.connection.duration";
+const HTTP_CLIENT_CONNECTION_DURATION_UNIT: &str = "s";
+
+const HTTP_CLIENT_CONNECTION_DURATION_BOUNDARIES: [f64; 14] = [
+    0.01, 0.02, 0.05, 0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0,
+];
+
+const HTTP_CLIENT_CONNECT_DURATION_METRIC: &str = "http.client.connect.duration";
+const HTTP_CLIENT_CONNECT_DURATION_UNIT: &str = "s";
+
+const DNS_LOOKUP_DURATION_METRIC: &str = "dns.lookup.duration";
+const DNS_LOOKUP_DURATION_UNIT: &str = "s";
+
+const CONNECT_DURATION_BOUNDARIES: [f64; 14] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0,
+];
+
+/// Attribute value used in place of server addresses beyond the configured limit, per semconv
+const OVERFLOW_LABEL_VALUE: &str = "_OTHER";
+
+const ERROR_TYPE_CONNECTION_REFUSED: &str = "connection_refused";
+const ERROR_TYPE_CONNECTION_RESET: &str = "connection_reset";
+const ERROR_TYPE_TLS: &str = "tls_error";
+const ERROR_TYPE_TIMEOUT: &str = "timeout";
+const ERROR_TYPE_OTHER: &str = "_OTHER";
+
+const DNS_QUESTION_NAME_LABEL: &str = "dns.question.name";
+const ERROR_TYPE_LABEL: &str = "error.type";
+const SERVER_ADDRESS_LABEL: &str = "server.address";
+const SERVER_PORT_LABEL: &str = "server.port";
+
+#[derive(Clone)]
+struct ConnectorMetricsLayerState {
+    client_open_connections: UpDownCounter<i64>,
+    client_connection_duration: Histogram<f64>,
+    client_connect_duration: Histogram<f64>,
+    server_address_limit: Option<Arc<ServerAddressLimit>>,
+}
+
+#[derive(Clone)]
+/// [`Layer`] which applies OTEL HTTP client connection metrics to a connector
+pub struct ConnectorMetricsLayer {
+    state: Arc<ConnectorMetricsLayerState>,
+}
+
+#[derive(Clone)]
+/// Connector [`Service`] used by [`ConnectorMetricsLayer`]
+pub struct ConnectorMetricsService<C> {
+    state: Arc<ConnectorMetricsLayerState>,
+    inner_connector: C,
+}
+
+impl ConnectorMetricsLayer {
+    /// Create the layer, registering the client connection instruments with the given meter.
+    pub fn new(meter: &Meter) -> Self {
+        ConnectorMetricsLayer {
+            state: Arc::new(ConnectorMetricsLayerState {
+                client_open_connections: meter
+                    .i64_up_down_counter(HTTP_CLIENT_OPEN_CONNECTIONS_METRIC)
+                    .with_description(
+                        "Number of outbound HTTP connections that are currently open.",
+                    )
+                    .with_unit(HTTP_CLIENT_OPEN_CONNECTIONS_UNIT)
+                    .build(),
+                client_connection_duration: meter
+                    .f64_histogram(HTTP_CLIENT_CONNECTION_DURATION_METRIC)
+                    .with_description(
+                        "The duration of the successfully established outbound HTTP connections.",
+                    )
+                    .with_unit(HTTP_CLIENT_CONNECTION_DURATION_UNIT)
+                    .with_boundaries(HTTP_CLIENT_CONNECTION_DURATION_BOUNDARIES.to_vec())
+                    .build(),
+                client_connect_duration: meter
+                    .f64_histogram(HTTP_CLIENT_CONNECT_DURATION_METRIC)
+                    .with_description("Duration of establishing outbound HTTP connections.")
+                    .with_unit(HTTP_CLIENT_CONNECT_DURATION_UNIT)
+                    .with_boundaries(CONNECT_DURATION_BOUNDARIES.to_vec())
+                    .build(),
+                server_address_limit: None,
+            }),
+        }
+    }
+
+    /// Track at most `max` distinct `server.address` values, recording connections
+    /// to any further hosts with `server.address` set to `_OTHER`.
+    ///
+    /// Clients which connect to arbitrary, user-provided hosts (e.g. webhook delivery)
+    /// should set a limit to keep the attribute cardinality bounded.
+    pub fn with_max_server_addresses(self, max: usize) -> Self {
+        ConnectorMetricsLayer {
+            state: Arc::new(ConnectorMetricsLayerState {
+                server_address_limit: Some(Arc::new(ServerAddressLimit::new(max))),
+                ..(*self.state).clone()
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for ConnectorMetricsLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectorMetricsLayer")
+            .field(
+                "max_server_addresses",
+                &self
+                    .state
+                    .server_address_limit
+                    .as_ref()
+                    .map(|limit| limit.max),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: fmt::Debug> fmt::Debug for ConnectorMetricsService<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectorMetricsService")
+            .field("inner_connector", &self.inner_connector)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C> Layer<C> for ConnectorMetricsLayer {
+    type Service = ConnectorMetricsService<C>;
+
+    fn layer(&self, connector: C) -> Self::Service {
+        ConnectorMetricsService {
+            state: self.state.clone(),
+            inner_connector: connector,
+        }
+    }
+}
+
+pin_project! {
+    /// Connection [`Future`] for [`ConnectorMetricsService`].
+    pub struct ConnectorMetricsFuture<F> {
+        #[pin]
+        inner_connect_future: F,
+        layer_state: Arc<ConnectorMetricsLayerState>,
+        labels: Vec<KeyValue>,
+        connect_start: Instant,
+    }
+}
+
+impl<F> fmt::Debug for ConnectorMetricsFuture<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectorMetricsFuture")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C> Service<Uri> for ConnectorMetricsService<C>
+where
+    C: Service<Uri>,
+    C::Error: Into<BoxError>,
+{
+    type Response = MeteredConnection<C::Response>;
+    type Error = BoxError;
+    type Future = ConnectorMetricsFuture<C::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_connector.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let connect_start = Instant::now();
+        let labels = labels_client_connection(&dst, self.state.server_address_limit.as_deref());
+        ConnectorMetricsFuture {
+            inner_connect_future: self.inner_connector.call(dst),
+            layer_state: self.state.clone(),
+            labels,
+            connect_start,
+        }
+    }
+}
+
+impl<F, IO, E> Future for ConnectorMetricsFuture<F>
+where
+    F: Future<Output = result::Result<IO, E>>,
+    E: Into<BoxError>,
+{
+    type Output = result::Result<MeteredConnection<IO>, BoxError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.inner_connect_future.poll(cx));
+
+        let connect_duration = this.connect_start.elapsed().as_secs_f64();
+        let io = match result {
+            Ok(io) => {
+                this.layer_state
+                    .client_connect_duration
+                    .record(connect_duration, this.labels);
+                io
+            }
+            Err(err) => {
+                let err = err.into();
+                let mut labels = this.labels.clone();
+                labels.push(KeyValue::new(ERROR_TYPE_LABEL, transport_error_type(&*err)));
+                this.layer_state
+                    .client_connect_duration
+                    .record(connect_duration, &labels);
+                return Poll::Ready(Err(err));
+            }
+        };
+
+        this.layer_state.client_open_connections.add(1, this.labels);
+
+        Poll::Ready(Ok(MeteredConnection {
+            inner_io: io,
+            guard: OpenConnectionGuard {
+                layer_state: this.layer_state.clone(),
+                labels: std::mem::take(this.labels),
+                opened_at: Instant::now(),
+            },
+        }))
+    }
+}
+
+pin_project! {
+    /// Connection established through [`ConnectorMetricsService`],
+    /// tracked as open until it is dropped.
+    pub struct MeteredConnection<IO> {
+        #[pin]
+        inner_io: IO,
+        guard: OpenConnectionGuard,
+    }
+}
+
+struct OpenConnectionGuard {
+    layer_state: Arc<ConnectorMetricsLayerState>,
+    labels: Vec<KeyValue>,
+    opened_at: Instant,
+}
+
+impl<IO: fmt::Debug> fmt::Debug for MeteredConnection<IO> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MeteredConnection")
+            .field("inner_io", &self.inner_io)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for OpenConnectionGuard {
+    fn drop(&mut self) {
+        self.layer_state
+            .client_open_connections
+            .add(-1, &self.labels);
+        self.layer_state
+            .client_connection_duration
+            .record(self.opened_at.elapsed().as_secs_f64(), &self.labels);
+    }
+}
+
+impl<IO: Read> Read for MeteredConnection<IO> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: ReadBufCursor<'_>,
+    ) -> Poll<result::Result<(), io::Error>> {
+        self.project().inner_io.poll_read(cx, buf)
+    }
+}
+
+impl<IO: Write> Write for MeteredConnection<IO> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<result::Result<usize, io::Error>> {
+        self.project().inner_io.poll_write(cx, buf)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<result::Result<(), io::Error>> {
+        self.project().inner_io.poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<result::Result<(), io::Error>> {
+        self.project().inner_io.poll_shutdown(cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner_io.is_write_vectored()
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<result::Result<usize, io::Error>> {
+        self.project().inner_io.poll_write_vectored(cx, bufs)
+    }
+}
+
+impl<IO: Connection> Connection for MeteredConnection<IO> {
+    fn connected(&self) -> Connected {
+        self.inner_io.connected()
+    }
+}
+
+#[derive(Clone)]
+/// [`Layer`] which applies the OTEL `dns.lookup.duration` metric to a DNS resolver service
+pub struct ResolverMetricsLayer {
+    dns_lookup_duration: Histogram<f64>,
+    question_name_limit: Option<Arc<ServerAddressLimit>>,
+}
+
+#[derive(Clone)]
+/// Resolver [`Service`] used by [`ResolverMetricsLayer`]
+pub struct ResolverMetricsService<R> {
+    dns_lookup_duration: Histogram<f64>,
+    question_name_limit: Option<Arc<ServerAddressLimit>>,
+    inner_resolver: R,
+}
+
+impl ResolverMetricsLayer {
+    /// Create the layer, registering the `dns.lookup.duration` histogram with the given meter.
+    pub fn new(meter: &Meter) -> Self {
+        ResolverMetricsLayer {
+            dns_lookup_duration: meter
+                .f64_histogram(DNS_LOOKUP_DURATION_METRIC)
+                .with_description("Measures the time taken to perform a DNS lookup.")
+                .with_unit(DNS_LOOKUP_DURATION_UNIT)
+                .with_boundaries(CONNECT_DURATION_BOUNDARIES.to_vec())
+                .build(),
+            question_name_limit: None,
+        }
+    }
+
+    /// Track at most `max` distinct `dns.question.name` values, recording lookups
+    /// of any further names with `dns.question.name` set to `_OTHER`.
+    pub fn with_max_question_names(self, max: usize) -> Self {
+        ResolverMetricsLayer {
+            question_name_limit: Some(Arc::new(ServerAddressLimit::new(max))),
+            ..self
+        }
+    }
+}
+
+impl fmt::Debug for ResolverMetricsLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResolverMetricsLayer")
+            .field(
+                "max_question_names",
+                &self.question_name_limit.as_ref().map(|limit| limit.max),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for ResolverMetricsService<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResolverMetricsService")
+            .field("inner_resolver", &self.inner_resolver)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F> fmt::Debug for ResolverMetricsFuture<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResolverMetricsFuture")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R> Layer<R> for ResolverMetricsLayer {
+    type Service = ResolverMetricsService<R>;
+
+    fn layer(&self, resolver: R) -> Self::Service {
+        ResolverMetricsService {
+            dns_lookup_duration: self.dns_lookup_duration.clone(),
+            question_name_limit: self.question_name_limit.clone(),
+            inner_resolver: resolver,
+        }
+    }
+}
+
+pin_project! {
+    /// Lookup [`Future`] for [`ResolverMetricsService`].
+    pub struct ResolverMetricsFuture<F> {
+        #[pin]
+        inner_lookup_future: F,
+        dns_lookup_duration: Histogram<f64>,
+        dns_question_name: String,
+        lookup_start: Instant,
+    }
+}
+
+impl<R, N> Service<N> for ResolverMetricsService<R>
+where
+    R: Service<N>,
+    N: fmt::Display,
+{
+    type Response = R::Response;
+    type Error = R::Error;
+    type Future = ResolverMetricsFuture<R::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_resolver.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: N) -> Self::Future {
+        let lookup_start = Instant::now();
+        let mut dns_question_name = name.to_string();
+        if let Some(limit) = &self.question_name_limit {
+            dns_question_name = limit.apply(dns_question_name);
+        }
+        ResolverMetricsFuture {
+            inner_lookup_future: self.inner_resolver.call(name),
+            dns_lookup_duration: self.dns_lookup_duration.clone(),
+            dns_question_name,
+            lookup_start,
+        }
+    }
+}
+
+impl<F, Addrs, E> Future for ResolverMetricsFuture<F>
+where
+    F: Future<Output = result::Result<Addrs, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.inner_lookup_future.poll(cx));
+
+        let mut labels = vec![KeyValue::new(
+            DNS_QUESTION_NAME_LABEL,
+            std::mem::take(this.dns_question_name),
+        )];
+        // resolver errors are of any type, which cannot be inspected
+        if result.is_err() {
+            labels.push(KeyValue::new(ERROR_TYPE_LABEL, ERROR_TYPE_OTHER));
+        }
+        this.dns_lookup_duration
+            .record(this.lookup_start.elapsed().as_secs_f64(), &labels);
+
+        Poll::Ready(result)
+    }
+}
+
+/// Classify a connection failure by the first I/O error in its source chain of a known kind.
+///
+/// hyper-util's connector reports socket failures, including its connect timeout, as I/O errors,
+/// and TLS connectors report handshake failures as I/O errors of kind `InvalidData`. DNS failures
+/// carry no kind of their own and are recorded as `_OTHER`.
+fn transport_error_type(err: &(dyn StdError + 'static)) -> &'static str {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            match err.kind() {
+                io::ErrorKind::ConnectionRefused => return ERROR_TYPE_CONNECTION_REFUSED,
+                io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof => return ERROR_TYPE_CONNECTION_RESET,
+                io::ErrorKind::TimedOut => return ERROR_TYPE_TIMEOUT,
+                io::ErrorKind::InvalidData => return ERROR_TYPE_TLS,
+                _ => {}
+            }
+        }
+        source = err.source();
+    }
+    ERROR_TYPE_OTHER
+}
+
+/// Host from the URI as expected for `server.address`, i.e. without the brackets of IPv6 literals.
+fn server_address(dst: &Uri) -> &str {
+    let host = dst.host().unwrap_or("");
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+/// Bounds the number of distinct host values recorded, collapsing extras into `_OTHER`.
+///
+/// Hosts are admitted first come, first served and are never evicted,
+/// so the set of tracked hosts is stable for the lifetime of the layer.
+struct ServerAddressLimit {
+    max: usize,
+    seen: Mutex<HashSet<String>>,
+}
+
+impl ServerAddressLimit {
+    fn new(max: usize) -> Self {
+        ServerAddressLimit {
+            max,
+            seen: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn apply(&self, address: String) -> String {
+        let mut seen = self.seen.lock().unwrap_or_else(|err| err.into_inner());
+        if seen.contains(&address) {
+            return address;
+        }
+        if seen.len() < self.max {
+            seen.insert(address.clone());
+            return address;
+        }
+        String::from(OVERFLOW_LABEL_VALUE)
+    }
+}
+
+fn labels_client_connection(dst: &Uri, limit: Option<&ServerAddressLimit>) -> Vec<KeyValue> {
+    let scheme = dst.scheme_str().unwrap_or("");
+    let port = dst.port_u16().or(match scheme {
+        "http" => Some(80),
+        "https" => Some(443),
+        _ => None,
+    });
+
+    let mut address = server_address(dst).to_owned();
+    if let Some(limit) = limit {
+        address = limit.apply(address);
+    }
+
+    let mut labels = vec![
+        KeyValue::new(SERVER_ADDRESS_LABEL, address),
+        KeyValue::new(URL_SCHEME_LABEL, scheme.to_owned()),
+    ];
+    if let Some(port) = port {
+        labels.push(KeyValue::new(SERVER_PORT_LABEL, i64::from(port)));
+    }
+    labels
+}
@@ -0,0 +1,63 @@
+//! Request metrics context shared with downstream middleware.
+//!
+//! Middleware inside the layer, such as tracing, rate limiting or logging, often needs the same
+//! request start time, route and method the layer has already resolved. When enabled, the layer
+//! inserts them into the request extensions as a [`RequestMetricsContext`] so they are reused
+//! instead of re-extracted.
+
+use std::time::{Duration, Instant};
+
+use crate::clock;
+
+use opentelemetry::StringValue;
+
+#[derive(Clone, Debug)]
+/// Request extension holding the data resolved by [`HTTPMetricsService`] for a request.
+///
+/// Inserted when enabled with [`HTTPMetricsLayerBuilder::with_request_context_extension`].
+/// Values are shared with the layer's attributes, so cloning the context does not allocate.
+///
+/// [`HTTPMetricsService`]: crate::HTTPMetricsService
+/// [`HTTPMetricsLayerBuilder::with_request_context_extension`]: crate::HTTPMetricsLayerBuilder::with_request_context_extension
+pub struct RequestMetricsContext {
+    start: clock::Instant,
+    method: StringValue,
+    route: Option<StringValue>,
+}
+
+impl RequestMetricsContext {
+    pub(crate) fn new(
+        start: clock::Instant,
+        method: StringValue,
+        route: Option<StringValue>,
+    ) -> Self {
+        RequestMetricsContext {
+            start,
+            method,
+            route,
+        }
+    }
+
+    /// The start of the request duration.
+    ///
+    /// With the `fast-time` feature, the start is converted from the clock durations are measured
+    /// with, so it may be slightly off from the instant the layer read.
+    pub fn start(&self) -> Instant {
+        clock::to_std(self.start)
+    }
+
+    /// The time elapsed since the start of the request duration.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// The `http.request.method` of the request.
+    pub fn method(&self) -> &str {
+        self.method.as_str()
+    }
+
+    /// The `http.route` of the request, if one was resolved.
+    pub fn route(&self) -> Option<&str> {
+        self.route.as_ref().map(StringValue::as_str)
+    }
+}
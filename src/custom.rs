@@ -0,0 +1,206 @@
+//! Application-defined metrics recorded by the layer alongside the HTTP server metrics.
+//!
+//! Custom instruments are registered on the [`HTTPMetricsLayerBuilder`] and recorded with the
+//! same attributes as `http.server.request.duration`, so request-scoped application metrics
+//! can be broken down by route, method, and status without another middleware.
+//!
+//! [`HTTPMetricsLayerBuilder`]: crate::HTTPMetricsLayerBuilder
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use opentelemetry::metrics::{Counter, Histogram, Meter};
+use opentelemetry::KeyValue;
+
+type CustomValueExtractor = dyn Fn(&http::response::Parts) -> Option<f64> + Send + Sync;
+
+#[derive(Clone, Debug)]
+enum CustomInstrumentKind {
+    Counter,
+    Histogram,
+}
+
+#[derive(Clone)]
+/// Definition of a custom instrument whose value is extracted from each response.
+///
+/// The extractor is called with the response parts once the inner service has responded,
+/// and its value, if any, is recorded with the same attributes as `http.server.request.duration`.
+///
+/// ```
+/// use tower_otel_http_metrics::CustomInstrument;
+///
+/// let items_returned = CustomInstrument::histogram("app.items.returned", "{item}", |parts| {
+///     parts.headers.get("x-item-count")?.to_str().ok()?.parse().ok()
+/// });
+/// ```
+pub struct CustomInstrument {
+    kind: CustomInstrumentKind,
+    name: Cow<'static, str>,
+    unit: Cow<'static, str>,
+    extractor: Arc<CustomValueExtractor>,
+}
+
+enum CustomInstrumentHandle {
+    Counter(Counter<f64>),
+    Histogram(Histogram<f64>),
+}
+
+/// Custom instrument registered with the meter, ready to be recorded.
+pub(crate) struct BuiltCustomInstrument {
+    handle: CustomInstrumentHandle,
+    extractor: Arc<CustomValueExtractor>,
+}
+
+impl CustomInstrument {
+    /// Define a counter incremented by the extracted value.
+    pub fn counter<F>(
+        name: impl Into<Cow<'static, str>>,
+        unit: impl Into<Cow<'static, str>>,
+        extractor: F,
+    ) -> Self
+    where
+        F: Fn(&http::response::Parts) -> Option<f64> + Send + Sync + 'static,
+    {
+        CustomInstrument {
+            kind: CustomInstrumentKind::Counter,
+            name: name.into(),
+            unit: unit.into(),
+            extractor: Arc::new(extractor),
+        }
+    }
+
+    /// Define a histogram recording the extracted value.
+    pub fn histogram<F>(
+        name: impl Into<Cow<'static, str>>,
+        unit: impl Into<Cow<'static, str>>,
+        extractor: F,
+    ) -> Self
+    where
+        F: Fn(&http::response::Parts) -> Option<f64> + Send + Sync + 'static,
+    {
+        CustomInstrument {
+            kind: CustomInstrumentKind::Histogram,
+            name: name.into(),
+            unit: unit.into(),
+            extractor: Arc::new(extractor),
+        }
+    }
+
+    pub(crate) fn build(&self, meter: &Meter) -> BuiltCustomInstrument {
+        let handle = match self.kind {
+            CustomInstrumentKind::Counter => CustomInstrumentHandle::Counter(
+                meter
+                    .f64_counter(self.name.clone())
+                    .with_description("Custom counter recorded from HTTP server responses.")
+                    .with_unit(self.unit.clone())
+                    .build(),
+            ),
+            CustomInstrumentKind::Histogram => CustomInstrumentHandle::Histogram(
+                meter
+                    .f64_histogram(self.name.clone())
+                    .with_description("Custom histogram recorded from HTTP server responses.")
+                    .with_unit(self.unit.clone())
+                    .build(),
+            ),
+        };
+        BuiltCustomInstrument {
+            handle,
+            extractor: self.extractor.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for CustomInstrument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomInstrument")
+            .field("kind", &self.kind)
+            .field("name", &self.name)
+            .field("unit", &self.unit)
+            .finish_non_exhaustive()
+    }
+}
+
+pub(crate) fn record_custom_instruments(
+    custom_instruments: &[BuiltCustomInstrument],
+    parts: &http::response::Parts,
+    labels: &[KeyValue],
+) {
+    for instrument in custom_instruments {
+        let Some(value) = (instrument.extractor)(parts) else {
+            continue;
+        };
+        match &instrument.handle {
+            CustomInstrumentHandle::Counter(counter) => counter.add(value, labels),
+            CustomInstrumentHandle::Histogram(histogram) => histogram.record(value, labels),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+/// Response extension carrying handler-supplied values for custom histograms.
+///
+/// Values are recorded into the histogram registered on the builder with
+/// [`with_custom_histogram`] under the same name; values for names which were
+/// not registered are ignored.
+///
+/// ```
+/// use tower_otel_http_metrics::RecordValues;
+///
+/// let mut response = http::Response::new(());
+/// response
+///     .extensions_mut()
+///     .insert(RecordValues::new().with_value("app.db.duration", 0.012));
+/// ```
+///
+/// [`with_custom_histogram`]: crate::HTTPMetricsLayerBuilder::with_custom_histogram
+pub struct RecordValues {
+    values: Vec<(Cow<'static, str>, f64)>,
+}
+
+impl RecordValues {
+    pub fn new() -> Self {
+        RecordValues::default()
+    }
+
+    /// Add a value to be recorded into the custom histogram `name`.
+    pub fn record(&mut self, name: impl Into<Cow<'static, str>>, value: f64) {
+        self.values.push((name.into(), value));
+    }
+
+    /// Add a value to be recorded into the custom histogram `name`.
+    pub fn with_value(mut self, name: impl Into<Cow<'static, str>>, value: f64) -> Self {
+        self.record(name, value);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Response extension carrying the usage units consumed by a request, for metering.
+///
+/// Units are added to the usage counter registered on the builder with [`with_usage_counter`],
+/// along with the request method, `http.route`, and tenant. Responses without the extension
+/// are not metered.
+///
+/// ```
+/// use tower_otel_http_metrics::UsageUnits;
+///
+/// let mut response = http::Response::new(());
+/// response.extensions_mut().insert(UsageUnits(3));
+/// ```
+///
+/// [`with_usage_counter`]: crate::HTTPMetricsLayerBuilder::with_usage_counter
+pub struct UsageUnits(pub u64);
+
+pub(crate) fn record_custom_histograms(
+    custom_histograms: &HashMap<Cow<'static, str>, Histogram<f64>>,
+    record_values: &RecordValues,
+    labels: &[KeyValue],
+) {
+    for (name, value) in &record_values.values {
+        if let Some(histogram) = custom_histograms.get(name) {
+            histogram.record(*value, labels);
+        }
+    }
+}
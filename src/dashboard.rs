@@ -0,0 +1,181 @@
+//! Grafana dashboard generation from the instruments a builder is configured with.
+//!
+//! The instruments are captured by building the layer state against a meter which only records
+//! how each instrument was created, so the dashboard follows the configured naming convention,
+//! aliases, units and histogram boundaries. Queries are written in PromQL for metrics exported
+//! through the OTEL Prometheus exporter or collector with their default name translation: dots
+//! become underscores, units are appended as suffixes (`_seconds`, `_bytes`) and counters end in
+//! `_total`.
+
+use crate::capture::{CapturedInstrument, CapturedInstruments, InstrumentKind};
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::HTTP_SERVER_DIAGNOSTICS_COLLECTION_INSTRUMENT;
+use crate::json::json_string;
+
+const PANEL_WIDTH: u32 = 12;
+const PANEL_HEIGHT: u32 = 8;
+
+/// Name of an instrument once translated by the OTEL Prometheus exporter.
+fn prometheus_name(instrument: &CapturedInstrument) -> String {
+    let mut name: String = instrument
+        .name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let suffix = match instrument.unit.as_deref() {
+        Some("s") => Some("_seconds"),
+        Some("ms") => Some("_milliseconds"),
+        Some("By") => Some("_bytes"),
+        _ => None,
+    };
+    if let Some(suffix) = suffix {
+        if !name.ends_with(suffix) {
+            name.push_str(suffix);
+        }
+    }
+    if instrument.kind == InstrumentKind::Counter && !name.ends_with("_total") {
+        name.push_str("_total");
+    }
+    name
+}
+
+/// Grafana unit of an OTEL unit.
+fn grafana_unit(unit: Option<&str>) -> &'static str {
+    match unit {
+        Some("s") => "s",
+        Some("ms") => "ms",
+        Some("By") => "bytes",
+        _ => "short",
+    }
+}
+
+struct Panel {
+    title: String,
+    description: String,
+    unit: &'static str,
+    /// Legend format and PromQL expression of each query
+    targets: Vec<(String, String)>,
+}
+
+impl Panel {
+    fn json(&self, id: usize) -> String {
+        let x = (id as u32 % 2) * PANEL_WIDTH;
+        let y = (id as u32 / 2) * PANEL_HEIGHT;
+        let targets = self
+            .targets
+            .iter()
+            .enumerate()
+            .map(|(i, (legend, expr))| {
+                format!(
+                    r#"{{"datasource":{{"type":"prometheus","uid":"${{datasource}}"}},"expr":{},"legendFormat":{},"refId":"{}"}}"#,
+                    json_string(expr),
+                    json_string(legend),
+                    char::from(b'A' + i as u8),
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            r#"{{"id":{},"type":"timeseries","title":{},"description":{},"datasource":{{"type":"prometheus","uid":"${{datasource}}"}},"gridPos":{{"h":{PANEL_HEIGHT},"w":{PANEL_WIDTH},"x":{x},"y":{y}}},"fieldConfig":{{"defaults":{{"unit":"{}"}},"overrides":[]}},"targets":[{}]}}"#,
+            id + 1,
+            json_string(&self.title),
+            json_string(&self.description),
+            self.unit,
+            targets,
+        )
+    }
+}
+
+/// Panels showing an instrument, with `route_label` naming the `http.route` attribute.
+fn panels(instrument: &CapturedInstrument, route_label: &str) -> Vec<Panel> {
+    let name = prometheus_name(instrument);
+    let description = instrument.description.as_deref().unwrap_or_default();
+    let unit = grafana_unit(instrument.unit.as_deref());
+    match instrument.kind {
+        InstrumentKind::Histogram => {
+            let boundaries = instrument
+                .boundaries
+                .as_ref()
+                .map(|boundaries| {
+                    let boundaries: Vec<String> =
+                        boundaries.iter().map(ToString::to_string).collect();
+                    format!(" Bucket boundaries: {}.", boundaries.join(", "))
+                })
+                .unwrap_or_default();
+            let quantiles = [("p50", 0.5), ("p95", 0.95), ("p99", 0.99)]
+                .iter()
+                .map(|(legend, quantile)| {
+                    (
+                        legend.to_string(),
+                        format!(
+                            "histogram_quantile({quantile}, sum by (le) (rate({name}_bucket[$__rate_interval])))"
+                        ),
+                    )
+                })
+                .collect();
+            vec![
+                Panel {
+                    title: format!("{} rate by route", instrument.name),
+                    description: format!("Measurements per second of {}.", instrument.name),
+                    unit: "reqps",
+                    targets: vec![(
+                        format!("{{{{{route_label}}}}}"),
+                        format!("sum by ({route_label}) (rate({name}_count[$__rate_interval]))"),
+                    )],
+                },
+                Panel {
+                    title: format!("{} quantiles", instrument.name),
+                    description: format!("{description}{boundaries}"),
+                    unit,
+                    targets: quantiles,
+                },
+            ]
+        }
+        InstrumentKind::Counter => vec![Panel {
+            title: instrument.name.to_string(),
+            description: description.to_owned(),
+            unit: "short",
+            targets: vec![(
+                String::new(),
+                format!("sum(rate({name}[$__rate_interval]))"),
+            )],
+        }],
+        InstrumentKind::UpDownCounter | InstrumentKind::Gauge => vec![Panel {
+            title: instrument.name.to_string(),
+            description: description.to_owned(),
+            unit,
+            targets: vec![(String::new(), format!("sum({name})"))],
+        }],
+    }
+}
+
+/// Grafana dashboard JSON with panels for each captured instrument.
+pub(crate) fn grafana_dashboard(
+    title: &str,
+    instruments: &CapturedInstruments,
+    route_attribute: &str,
+) -> String {
+    let route_label = route_attribute.replace('.', "_");
+    let instruments = instruments.instruments();
+    let mut instruments: Vec<&CapturedInstrument> = instruments.iter().collect();
+    #[cfg(feature = "diagnostics")]
+    instruments
+        .retain(|instrument| instrument.name != HTTP_SERVER_DIAGNOSTICS_COLLECTION_INSTRUMENT);
+    // request histograms first, then current values, then the counters of the layer's internals
+    instruments.sort_by_key(|instrument| match instrument.kind {
+        InstrumentKind::Histogram => 0,
+        InstrumentKind::UpDownCounter | InstrumentKind::Gauge => 1,
+        InstrumentKind::Counter => 2,
+    });
+    let panels: Vec<String> = instruments
+        .into_iter()
+        .flat_map(|instrument| panels(instrument, &route_label))
+        .enumerate()
+        .map(|(id, panel)| panel.json(id))
+        .collect();
+    format!(
+        r#"{{"title":{},"tags":["http","opentelemetry"],"timezone":"browser","schemaVersion":39,"time":{{"from":"now-1h","to":"now"}},"templating":{{"list":[{{"name":"datasource","label":"Data source","type":"datasource","query":"prometheus"}}]}},"panels":[{}]}}"#,
+        json_string(title),
+        panels.join(","),
+    )
+}
@@ -0,0 +1,168 @@
+//! Diagnostics endpoint reporting the state of a layer's metrics, for when no metrics show up.
+//!
+//! The report is a JSON document: whether the meter provider the layer records into is still
+//! live, whether requests pass through unrecorded as the meter is a no-op, the instruments the
+//! layer is configured with, the sizes of its in-process caches, and how long ago the metrics were
+//! last collected. Collection is observed through the callback of an observable instrument which
+//! never reports a value, so it is invoked by every collection of the meter provider, i.e. by every
+//! export of periodic readers and every scrape of pull exporters.
+//!
+//! [`DiagnosticsService`] is a plain [`Service`], mounted e.g. with axum's `route_service`.
+
+use std::convert::Infallible;
+use std::fmt::{self, Write};
+use std::future::{ready, Ready};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use tower_service::Service;
+
+use crate::binding::LayerBinding;
+use crate::capture::CapturedInstruments;
+use crate::json::json_string;
+
+/// Name of the observable instrument whose callback marks each collection; it reports no values.
+pub(crate) const HTTP_SERVER_DIAGNOSTICS_COLLECTION_INSTRUMENT: &str =
+    "http.server.diagnostics.collection";
+
+/// Time of the latest collection of the layer's metrics, if any.
+pub(crate) type LastCollection = Arc<Mutex<Option<Instant>>>;
+
+#[derive(Clone)]
+/// [`Service`] answering every request with a JSON report on the metrics of a layer,
+/// created with [`HTTPMetricsLayer::diagnostics_service`].
+///
+/// [`HTTPMetricsLayer::diagnostics_service`]: crate::HTTPMetricsLayer::diagnostics_service
+pub struct DiagnosticsService {
+    binding: Arc<LayerBinding>,
+    instruments: Arc<[String]>,
+}
+
+impl DiagnosticsService {
+    pub(crate) fn new(binding: Arc<LayerBinding>) -> Self {
+        let captured = CapturedInstruments::default();
+        let builder = binding.builder();
+        builder.make_state(&builder.aliased(captured.meter()));
+        let instruments = captured
+            .instruments()
+            .iter()
+            .filter(|instrument| instrument.name != HTTP_SERVER_DIAGNOSTICS_COLLECTION_INSTRUMENT)
+            .map(|instrument| instrument.name.to_string())
+            .collect();
+        DiagnosticsService {
+            binding,
+            instruments,
+        }
+    }
+
+    /// The JSON report on the current state of the layer.
+    fn report(&self) -> String {
+        let (_, state) = self.binding.current();
+        // layers built from a meter rather than a provider cannot tell whether it is live
+        let meter_provider = if state.meter_provider_dropped
+            || state
+                .meter_provider
+                .as_ref()
+                .is_some_and(|provider| provider.strong_count() == 0)
+        {
+            "dropped"
+        } else if state.meter_provider_unbound {
+            "unbound"
+        } else if state.meter_provider.is_some() {
+            "live"
+        } else {
+            "untracked"
+        };
+        let instruments: Vec<String> = self
+            .instruments
+            .iter()
+            .map(|name| json_string(name))
+            .collect();
+        let last_collection = state
+            .last_collection
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .map(|instant| instant.elapsed().as_secs_f64());
+
+        let mut report = String::new();
+        let _ = write!(
+            report,
+            r#"{{"meter_provider":"{meter_provider}","dry_run":{},"pass_through":{},"instruments":[{}],"active_requests":{}"#,
+            self.binding.builder().dry_run,
+            state.pass_through,
+            instruments.join(","),
+            state.active_requests.load(Ordering::Relaxed),
+        );
+        #[cfg(feature = "route-cache")]
+        let route_cache = state
+            .route_resolver
+            .cache
+            .as_ref()
+            .map(|route_cache| (route_cache.len(), route_cache.capacity()));
+        #[cfg(not(feature = "route-cache"))]
+        let route_cache: Option<(usize, usize)> = None;
+        match route_cache {
+            Some((entries, capacity)) => {
+                let _ = write!(
+                    report,
+                    r#","route_cache":{{"entries":{entries},"capacity":{capacity}}}"#,
+                );
+            }
+            None => report.push_str(r#","route_cache":null"#),
+        }
+        #[cfg(feature = "cardinality")]
+        let attribute_sets_estimate = state
+            .server_request_duration_cardinality
+            .as_ref()
+            .map(|cardinality| cardinality.estimate());
+        #[cfg(not(feature = "cardinality"))]
+        let attribute_sets_estimate: Option<u64> = None;
+        let _ = write!(
+            report,
+            r#","latency_alert_routes":{},"attribute_sets_estimate":{},"last_collection_seconds_ago":{}}}"#,
+            json_option(
+                state
+                    .latency_tracker
+                    .as_ref()
+                    .map(|tracker| tracker.routes())
+            ),
+            json_option(attribute_sets_estimate),
+            json_option(last_collection),
+        );
+        report
+    }
+}
+
+impl fmt::Debug for DiagnosticsService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DiagnosticsService")
+            .field("instruments", &self.instruments)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A JSON number, or `null`.
+fn json_option<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "null".to_owned(), |value| value.to_string())
+}
+
+impl<B> Service<http::Request<B>> for DiagnosticsService {
+    type Response = http::Response<String>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: http::Request<B>) -> Self::Future {
+        let mut response = http::Response::new(self.report());
+        response.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static("application/json"),
+        );
+        ready(Ok(response))
+    }
+}
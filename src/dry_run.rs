@@ -0,0 +1,185 @@
+//! Dry-run recording, which aggregates a summary of what the layer would record instead of
+//! recording to OTEL.
+//!
+//! In dry-run mode, the layer's instruments are created from an in-process meter rather than
+//! the configured one. Routes, attributes and extractors are all computed as usual, but each
+//! measurement only updates a per-instrument count of measurements and distinct attribute sets,
+//! available from [`HTTPMetricsLayer::dry_run_summary`].
+//!
+//! [`HTTPMetricsLayer::dry_run_summary`]: crate::HTTPMetricsLayer::dry_run_summary
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use opentelemetry::metrics::{
+    Counter, Gauge, Histogram, HistogramBuilder, InstrumentBuilder, InstrumentProvider, Meter,
+    SyncInstrument, UpDownCounter,
+};
+use opentelemetry::KeyValue;
+
+use crate::labels::attribute_set_hash;
+
+#[derive(Clone, Default)]
+/// Summary of the measurements a layer in dry-run mode would have recorded.
+///
+/// The summary is shared with the layer and keeps updating as requests are served;
+/// its [`Display`](fmt::Display) output, one line per instrument, is meant for logging.
+pub struct DryRunSummary {
+    instruments: Arc<Mutex<BTreeMap<Cow<'static, str>, InstrumentStats>>>,
+}
+
+#[derive(Default)]
+struct InstrumentStats {
+    measurements: u64,
+    // order-independent hashes of each distinct attribute set
+    attribute_sets: HashSet<u64>,
+}
+
+#[derive(Clone, Debug)]
+/// Measurements a single instrument would have recorded, from [`DryRunSummary::instruments`].
+pub struct InstrumentSummary {
+    name: Cow<'static, str>,
+    measurements: u64,
+    attribute_sets: usize,
+}
+
+impl InstrumentSummary {
+    /// Name of the instrument.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Number of measurements the instrument would have recorded.
+    pub fn measurements(&self) -> u64 {
+        self.measurements
+    }
+
+    /// Number of distinct attribute sets, i.e. time series, the instrument would have produced.
+    pub fn attribute_sets(&self) -> usize {
+        self.attribute_sets
+    }
+}
+
+impl DryRunSummary {
+    /// Summarize each instrument of the layer, ordered by name.
+    pub fn instruments(&self) -> Vec<InstrumentSummary> {
+        self.lock()
+            .iter()
+            .map(|(name, stats)| InstrumentSummary {
+                name: name.clone(),
+                measurements: stats.measurements,
+                attribute_sets: stats.attribute_sets.len(),
+            })
+            .collect()
+    }
+
+    /// Create the meter whose instruments update this summary.
+    pub(crate) fn meter(&self) -> Meter {
+        Meter::new(Arc::new(DryRunInstrumentProvider {
+            summary: self.clone(),
+        }))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<Cow<'static, str>, InstrumentStats>> {
+        self.instruments
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn register(&self, name: Cow<'static, str>) -> Arc<DryRunInstrument> {
+        self.lock().entry(name.clone()).or_default();
+        Arc::new(DryRunInstrument {
+            name,
+            summary: self.clone(),
+        })
+    }
+}
+
+impl fmt::Debug for DryRunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DryRunSummary")
+            .field("instruments", &self.instruments())
+            .finish()
+    }
+}
+
+impl fmt::Display for DryRunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, instrument) in self.instruments().iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{}: {} measurements, {} attribute sets",
+                instrument.name, instrument.measurements, instrument.attribute_sets
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct DryRunInstrumentProvider {
+    summary: DryRunSummary,
+}
+
+impl InstrumentProvider for DryRunInstrumentProvider {
+    fn u64_counter(&self, builder: InstrumentBuilder<'_, Counter<u64>>) -> Counter<u64> {
+        Counter::new(self.summary.register(builder.name))
+    }
+
+    fn f64_counter(&self, builder: InstrumentBuilder<'_, Counter<f64>>) -> Counter<f64> {
+        Counter::new(self.summary.register(builder.name))
+    }
+
+    fn i64_up_down_counter(
+        &self,
+        builder: InstrumentBuilder<'_, UpDownCounter<i64>>,
+    ) -> UpDownCounter<i64> {
+        UpDownCounter::new(self.summary.register(builder.name))
+    }
+
+    fn f64_up_down_counter(
+        &self,
+        builder: InstrumentBuilder<'_, UpDownCounter<f64>>,
+    ) -> UpDownCounter<f64> {
+        UpDownCounter::new(self.summary.register(builder.name))
+    }
+
+    fn u64_gauge(&self, builder: InstrumentBuilder<'_, Gauge<u64>>) -> Gauge<u64> {
+        Gauge::new(self.summary.register(builder.name))
+    }
+
+    fn f64_gauge(&self, builder: InstrumentBuilder<'_, Gauge<f64>>) -> Gauge<f64> {
+        Gauge::new(self.summary.register(builder.name))
+    }
+
+    fn i64_gauge(&self, builder: InstrumentBuilder<'_, Gauge<i64>>) -> Gauge<i64> {
+        Gauge::new(self.summary.register(builder.name))
+    }
+
+    fn f64_histogram(&self, builder: HistogramBuilder<'_, Histogram<f64>>) -> Histogram<f64> {
+        Histogram::new(self.summary.register(builder.name))
+    }
+
+    fn u64_histogram(&self, builder: HistogramBuilder<'_, Histogram<u64>>) -> Histogram<u64> {
+        Histogram::new(self.summary.register(builder.name))
+    }
+}
+
+struct DryRunInstrument {
+    name: Cow<'static, str>,
+    summary: DryRunSummary,
+}
+
+impl<T> SyncInstrument<T> for DryRunInstrument {
+    fn measure(&self, _measurement: T, attributes: &[KeyValue]) {
+        let attribute_set = attribute_set_hash(attributes);
+        let mut instruments = self.summary.lock();
+        let stats = instruments.entry(self.name.clone()).or_default();
+        stats.measurements += 1;
+        stats.attribute_sets.insert(attribute_set);
+    }
+}
@@ -0,0 +1,154 @@
+//! Classification of errors returned by the inner service.
+//!
+//! [`HTTPMetricsService`] accepts inner services with any error type, including errors which are
+//! not `'static` and so cannot be inspected, and records them with an `error.type` of `_OTHER`.
+//! [`ErrorTypeLayer`], applied inside it, converts errors into a [`BoxError`] and walks their
+//! source chain. Errors caused by the client going away, i.e. connection resets, aborted
+//! connections and broken pipes, and with the `hyper` feature, hyper's canceled errors, are
+//! recorded with an `error.type` of `client_abort` rather than counted as server failures.
+//!
+//! The classification is handed to the [`HTTPMetricsService`] outside through a request
+//! extension, which it only inserts when [client abort classification] is enabled.
+//!
+//! [`HTTPMetricsService`]: crate::HTTPMetricsService
+//! [client abort classification]: crate::HTTPMetricsLayerBuilder::with_client_abort_error_type
+
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::result;
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+
+use futures_util::ready;
+use pin_project_lite::pin_project;
+use tower::BoxError;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::attributes::ERROR_TYPE_CLIENT_ABORT;
+
+/// Request extension through which [`ErrorTypeService`] hands the `error.type` of an error to the
+/// [`HTTPMetricsService`] outside.
+///
+/// [`HTTPMetricsService`]: crate::HTTPMetricsService
+#[derive(Clone, Default)]
+pub(crate) struct ErrorTypeLink(Arc<OnceLock<&'static str>>);
+
+impl ErrorTypeLink {
+    /// The `error.type` of the error returned for the request, when it was classified.
+    pub(crate) fn get(&self) -> Option<&'static str> {
+        self.0.get().copied()
+    }
+}
+
+#[derive(Clone, Debug)]
+/// [`Layer`] which classifies errors of the inner service, created with
+/// [`HTTPMetricsLayer::error_type_layer`].
+///
+/// [`HTTPMetricsLayer::error_type_layer`]: crate::HTTPMetricsLayer::error_type_layer
+pub struct ErrorTypeLayer {
+    _private: (),
+}
+
+impl ErrorTypeLayer {
+    pub(crate) fn new() -> Self {
+        ErrorTypeLayer { _private: () }
+    }
+}
+
+impl<S> Layer<S> for ErrorTypeLayer {
+    type Service = ErrorTypeService<S>;
+
+    fn layer(&self, inner_service: S) -> Self::Service {
+        ErrorTypeService { inner_service }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// [`Service`] used by [`ErrorTypeLayer`]
+pub struct ErrorTypeService<S> {
+    inner_service: S,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for ErrorTypeService<S>
+where
+    S: Service<http::Request<ReqBody>>,
+    S::Error: Into<BoxError>,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = ErrorTypeFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_service.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let link = req.extensions().get::<ErrorTypeLink>().cloned();
+        ErrorTypeFuture {
+            inner_response_future: self.inner_service.call(req),
+            link,
+        }
+    }
+}
+
+pin_project! {
+    /// Response future for [`ErrorTypeService`].
+    pub struct ErrorTypeFuture<F> {
+        #[pin]
+        inner_response_future: F,
+        link: Option<ErrorTypeLink>,
+    }
+}
+
+impl<F, T, E> Future for ErrorTypeFuture<F>
+where
+    F: Future<Output = result::Result<T, E>>,
+    E: Into<BoxError>,
+{
+    type Output = result::Result<T, BoxError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.inner_response_future.poll(cx));
+        Poll::Ready(result.map_err(|err| {
+            let err = err.into();
+            if let Some(link) = this.link.take() {
+                if let Some(error_type) = error_type(&*err) {
+                    let _ = link.0.set(error_type);
+                }
+            }
+            err
+        }))
+    }
+}
+
+/// The `error.type` of an error, when its source chain shows a known cause.
+fn error_type(err: &(dyn StdError + 'static)) -> Option<&'static str> {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if is_client_abort(err) {
+            return Some(ERROR_TYPE_CLIENT_ABORT);
+        }
+        source = err.source();
+    }
+    None
+}
+
+fn is_client_abort(err: &(dyn StdError + 'static)) -> bool {
+    if let Some(err) = err.downcast_ref::<io::Error>() {
+        return matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+        );
+    }
+    #[cfg(feature = "hyper")]
+    if let Some(err) = err.downcast_ref::<hyper::Error>() {
+        return err.is_canceled();
+    }
+    false
+}
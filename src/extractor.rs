@@ -0,0 +1,273 @@
+//! User-defined attributes extracted from requests and responses.
+//!
+//! Attribute extractors are registered on the [`HTTPMetricsLayerBuilder`] and add their attributes
+//! to `http.server.request.duration`. Request extractors run before the request is forwarded to
+//! the inner service; response extractors run once the inner service has responded.
+//!
+//! A panicking extractor does not take request handling down with it: the attributes it pushed
+//! are dropped and the panic is counted in `http.server.extractor.panics`. Extractors returning
+//! more attributes than the configured maximum per request get the excess dropped and counted in
+//! `http.server.extractor.attributes.dropped`.
+//!
+//! [`HTTPMetricsLayerBuilder`]: crate::HTTPMetricsLayerBuilder
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+use opentelemetry::metrics::Counter;
+use opentelemetry::{Key, KeyValue, Value};
+
+pub(crate) const HTTP_SERVER_EXTRACTOR_PANICS_METRIC: &str = "http.server.extractor.panics";
+pub(crate) const HTTP_SERVER_EXTRACTOR_PANICS_UNIT: &str = "{panic}";
+
+pub(crate) const HTTP_SERVER_EXTRACTOR_ATTRIBUTES_DROPPED_METRIC: &str =
+    "http.server.extractor.attributes.dropped";
+pub(crate) const HTTP_SERVER_EXTRACTOR_ATTRIBUTES_DROPPED_UNIT: &str = "{attribute}";
+pub(crate) const DEFAULT_MAX_EXTRACTOR_ATTRIBUTES: usize = 64;
+
+const EXTRACTOR_KIND_LABEL: &str = "extractor.kind";
+pub(crate) const EXTRACTOR_KIND_REQUEST: &str = "request";
+pub(crate) const EXTRACTOR_KIND_RESPONSE: &str = "response";
+pub(crate) const EXTRACTOR_KIND_ROUTE: &str = "route";
+
+type PartsAttributeExtractor<P> = dyn Fn(&P, &mut Vec<KeyValue>) + Send + Sync;
+pub(crate) type RequestAttributeExtractor = PartsAttributeExtractor<http::request::Parts>;
+pub(crate) type ResponseAttributeExtractor = PartsAttributeExtractor<http::response::Parts>;
+
+/// Types whose fields are recorded as attributes, e.g. extensions holding a request context.
+///
+/// With the `derive` feature, this is usually derived with
+/// `#[derive(HttpMetricsAttributes)]`, recording each field under its name or the key given
+/// with `#[http_metrics(key = "...")]`. Register implementing extension types with
+/// [`AttributeExtractor::request_extension_attributes`] or
+/// [`AttributeExtractor::response_extension_attributes`].
+///
+/// ```
+/// use opentelemetry::KeyValue;
+/// use tower_otel_http_metrics::HttpMetricsAttributes;
+///
+/// #[derive(Clone)]
+/// struct AuthContext {
+///     plan: &'static str,
+///     region: String,
+/// }
+///
+/// impl HttpMetricsAttributes for AuthContext {
+///     fn attributes(&self, attributes: &mut Vec<KeyValue>) {
+///         attributes.push(KeyValue::new("app.plan", self.plan));
+///         attributes.push(KeyValue::new("app.region", self.region.clone()));
+///     }
+/// }
+/// ```
+pub trait HttpMetricsAttributes {
+    /// Push the attributes of `self`.
+    fn attributes(&self, attributes: &mut Vec<KeyValue>);
+}
+
+#[derive(Clone)]
+enum AttributeExtractorKind {
+    Request(Arc<RequestAttributeExtractor>),
+    Response(Arc<ResponseAttributeExtractor>),
+}
+
+#[derive(Clone)]
+/// Definition of attributes extracted from each request or response.
+///
+/// Extractors return any number of attributes, e.g. an `Option<KeyValue>` or a `Vec<KeyValue>`.
+/// Attributes pulled from typed extensions, such as an auth context inserted by an earlier
+/// middleware, only need the extension type and a mapping to the attribute value:
+///
+/// ```
+/// use tower_otel_http_metrics::AttributeExtractor;
+///
+/// #[derive(Clone)]
+/// struct AuthContext {
+///     plan: &'static str,
+/// }
+///
+/// let plan = AttributeExtractor::request_extension("app.plan", |auth: &AuthContext| auth.plan);
+/// ```
+pub struct AttributeExtractor {
+    kind: AttributeExtractorKind,
+}
+
+impl AttributeExtractor {
+    /// Define attributes extracted from the request parts before the request is forwarded.
+    pub fn request<F, I>(extractor: F) -> Self
+    where
+        F: Fn(&http::request::Parts) -> I + Send + Sync + 'static,
+        I: IntoIterator<Item = KeyValue>,
+    {
+        AttributeExtractor {
+            kind: AttributeExtractorKind::Request(Arc::new(move |parts, labels| {
+                labels.extend(extractor(parts))
+            })),
+        }
+    }
+
+    /// Define attributes extracted from the response parts once the inner service has responded.
+    pub fn response<F, I>(extractor: F) -> Self
+    where
+        F: Fn(&http::response::Parts) -> I + Send + Sync + 'static,
+        I: IntoIterator<Item = KeyValue>,
+    {
+        AttributeExtractor {
+            kind: AttributeExtractorKind::Response(Arc::new(move |parts, labels| {
+                labels.extend(extractor(parts))
+            })),
+        }
+    }
+
+    /// Define an attribute `key` mapped from the request extension of type `T`.
+    ///
+    /// Requests without the extension do not get the attribute.
+    pub fn request_extension<T, F, V>(key: impl Into<Key>, extractor: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&T) -> V + Send + Sync + 'static,
+        V: Into<Value>,
+    {
+        let key = key.into();
+        AttributeExtractor::request(move |parts| {
+            parts
+                .extensions
+                .get::<T>()
+                .map(|extension| KeyValue::new(key.clone(), extractor(extension)))
+        })
+    }
+
+    /// Define an attribute `key` mapped from the response extension of type `T`.
+    ///
+    /// Responses without the extension do not get the attribute.
+    pub fn response_extension<T, F, V>(key: impl Into<Key>, extractor: F) -> Self
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&T) -> V + Send + Sync + 'static,
+        V: Into<Value>,
+    {
+        let key = key.into();
+        AttributeExtractor::response(move |parts| {
+            parts
+                .extensions
+                .get::<T>()
+                .map(|extension| KeyValue::new(key.clone(), extractor(extension)))
+        })
+    }
+
+    /// Define the attributes of the request extension of type `T`.
+    ///
+    /// Requests without the extension do not get the attributes.
+    pub fn request_extension_attributes<T>() -> Self
+    where
+        T: HttpMetricsAttributes + Send + Sync + 'static,
+    {
+        AttributeExtractor {
+            kind: AttributeExtractorKind::Request(Arc::new(|parts, labels| {
+                if let Some(extension) = parts.extensions.get::<T>() {
+                    extension.attributes(labels);
+                }
+            })),
+        }
+    }
+
+    /// Define the attributes of the response extension of type `T`.
+    ///
+    /// Responses without the extension do not get the attributes.
+    pub fn response_extension_attributes<T>() -> Self
+    where
+        T: HttpMetricsAttributes + Send + Sync + 'static,
+    {
+        AttributeExtractor {
+            kind: AttributeExtractorKind::Response(Arc::new(|parts, labels| {
+                if let Some(extension) = parts.extensions.get::<T>() {
+                    extension.attributes(labels);
+                }
+            })),
+        }
+    }
+}
+
+impl fmt::Debug for AttributeExtractor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            AttributeExtractorKind::Request(_) => "request",
+            AttributeExtractorKind::Response(_) => "response",
+        };
+        f.debug_struct("AttributeExtractor")
+            .field("kind", &kind)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Split extractors into those run on requests and those run on responses.
+pub(crate) fn split_attribute_extractors(
+    extractors: &[AttributeExtractor],
+) -> (
+    Vec<Arc<RequestAttributeExtractor>>,
+    Vec<Arc<ResponseAttributeExtractor>>,
+) {
+    let mut request_extractors = Vec::new();
+    let mut response_extractors = Vec::new();
+    for extractor in extractors {
+        match &extractor.kind {
+            AttributeExtractorKind::Request(extractor) => {
+                request_extractors.push(extractor.clone())
+            }
+            AttributeExtractorKind::Response(extractor) => {
+                response_extractors.push(extractor.clone())
+            }
+        }
+    }
+    (request_extractors, response_extractors)
+}
+
+/// Run a user extractor, counting a panic in `panics` rather than unwinding into request handling.
+///
+/// Returns `None` when the extractor panicked.
+pub(crate) fn catch_extractor_panic<T>(
+    panics: &Counter<u64>,
+    kind: &'static str,
+    extract: impl FnOnce() -> T,
+) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(extract)) {
+        Ok(value) => Some(value),
+        Err(_) => {
+            panics.add(1, &[KeyValue::new(EXTRACTOR_KIND_LABEL, kind)]);
+            None
+        }
+    }
+}
+
+/// Run attribute extractors, dropping the attributes of any extractor which panicked.
+pub(crate) fn run_attribute_extractors<P>(
+    extractors: &[Arc<PartsAttributeExtractor<P>>],
+    parts: &P,
+    labels: &mut Vec<KeyValue>,
+    panics: &Counter<u64>,
+    kind: &'static str,
+) {
+    for extractor in extractors {
+        let len = labels.len();
+        if catch_extractor_panic(panics, kind, || extractor(parts, labels)).is_none() {
+            labels.truncate(len);
+        }
+    }
+}
+
+/// Keep at most `remaining` of the extracted attributes `labels[start..]`, counting the attributes
+/// dropped beyond them in `dropped`. Returns the number of attributes kept.
+pub(crate) fn cap_extracted_attributes(
+    labels: &mut Vec<KeyValue>,
+    start: usize,
+    remaining: usize,
+    dropped: &Counter<u64>,
+) -> usize {
+    let extracted = labels.len() - start;
+    if extracted > remaining {
+        labels.truncate(start + remaining);
+        dropped.add((extracted - remaining) as u64, &[]);
+        return remaining;
+    }
+    extracted
+}
@@ -0,0 +1,55 @@
+//! FaaS attributes for services running as serverless functions, e.g. with `lambda_http`.
+//!
+//! Function invocations reach the service as regular HTTP requests, converted from the event of
+//! an HTTP trigger such as API Gateway, an ALB or a function URL. The layer records them with
+//! `faas.trigger` set to `http`, and `faas.coldstart` set on the first request handled by the
+//! process, so cold starts can be told apart in the request duration.
+//!
+//! `faas.invocation_id` is unique to each invocation and is only meant for spans; recording it on
+//! metrics would make every request its own series, so it is left out.
+//!
+//! There is no connection to read the scheme from, and the converted requests may carry none in
+//! their URI; in that case the `X-Forwarded-Proto` header set by the trigger is used, falling back
+//! to the default `url.scheme`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use http::{HeaderMap, Uri};
+use opentelemetry::{KeyValue, StringValue};
+
+use crate::labels::scheme_value;
+
+pub(crate) const FAAS_TRIGGER_LABEL: &str = "faas.trigger";
+pub(crate) const FAAS_COLDSTART_LABEL: &str = "faas.coldstart";
+
+const FAAS_TRIGGER_HTTP: &str = "http";
+
+/// Whether the process has not handled a request yet; shared by all layers, as a cold start
+/// belongs to the function instance rather than to a layer.
+static COLD_START: AtomicBool = AtomicBool::new(true);
+
+/// Push the `faas.trigger` and `faas.coldstart` labels of a request.
+pub(crate) fn push_faas_labels(labels: &mut Vec<KeyValue>) {
+    let cold_start = COLD_START.swap(false, Ordering::Relaxed);
+    labels.push(KeyValue::new(FAAS_TRIGGER_LABEL, FAAS_TRIGGER_HTTP));
+    labels.push(KeyValue::new(FAAS_COLDSTART_LABEL, cold_start));
+}
+
+/// `url.scheme` of a function invocation, taken from `X-Forwarded-Proto` when the URI has none.
+pub(crate) fn faas_scheme_value(
+    uri: &Uri,
+    headers: &HeaderMap,
+    default: &StringValue,
+) -> StringValue {
+    if uri.scheme().is_some() {
+        return scheme_value(uri, default);
+    }
+    match headers
+        .get("x-forwarded-proto")
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(proto) if proto.eq_ignore_ascii_case("https") => StringValue::from("https"),
+        Some(proto) if proto.eq_ignore_ascii_case("http") => StringValue::from("http"),
+        _ => default.clone(),
+    }
+}
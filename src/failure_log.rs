@@ -0,0 +1,87 @@
+//! Log events of failed requests, for drilling down into error spikes without access logging.
+//!
+//! A spike of `5xx` responses or `error.type` values on `http.server.request.duration` shows that
+//! requests fail, but not which ones. Emitting an OTEL log event for each failed request, carrying
+//! the same attributes as the metric along with its duration and status, gives a path from the
+//! spike to the individual requests, much like exemplars, while successful requests are not
+//! logged at all.
+//!
+//! A request fails when the inner service returned an error, the request was cancelled before the
+//! response and cancelled requests are recorded, the response has a `5xx` status, or the failure
+//! classifier recorded an `error.type` for it.
+
+use std::time::{Duration, SystemTime};
+
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, Severity};
+use opentelemetry::{KeyValue, Value};
+
+use crate::attributes::ERROR_TYPE_LABEL;
+use crate::{HTTP_RESPONSE_STATUS_CODE_LABEL, HTTP_SERVER_DURATION_METRIC};
+
+pub(crate) const HTTP_SERVER_REQUEST_FAILURE_EVENT: &str = "http.server.request.failure";
+
+/// Logger emitting the failure events, with the log record type of the logger erased.
+pub(crate) trait FailureLogger: Send + Sync {
+    fn emit_failure(
+        &self,
+        duration: Duration,
+        status: Option<http::StatusCode>,
+        labels: &[KeyValue],
+    );
+}
+
+impl<L> FailureLogger for L
+where
+    L: Logger + Send + Sync,
+{
+    fn emit_failure(
+        &self,
+        duration: Duration,
+        status: Option<http::StatusCode>,
+        labels: &[KeyValue],
+    ) {
+        let mut record = self.create_log_record();
+        record.set_event_name(HTTP_SERVER_REQUEST_FAILURE_EVENT);
+        record.set_timestamp(SystemTime::now());
+        record.set_severity_number(Severity::Error);
+        record.set_severity_text("ERROR");
+        record.add_attributes(
+            labels
+                .iter()
+                .map(|kv| (kv.key.clone(), any_value(&kv.value))),
+        );
+        // the status code may be recorded as its class, or not at all for errors
+        if let Some(status) = status {
+            if !labels
+                .iter()
+                .any(|kv| kv.key.as_str() == HTTP_RESPONSE_STATUS_CODE_LABEL)
+            {
+                record.add_attribute(HTTP_RESPONSE_STATUS_CODE_LABEL, i64::from(status.as_u16()));
+            }
+        }
+        record.add_attribute(HTTP_SERVER_DURATION_METRIC, duration.as_secs_f64());
+        let body = match labels.iter().find(|kv| kv.key.as_str() == ERROR_TYPE_LABEL) {
+            Some(error_type) => error_type.value.to_string(),
+            None => status.map(|status| status.to_string()).unwrap_or_default(),
+        };
+        record.set_body(AnyValue::from(body));
+        self.emit(record);
+    }
+}
+
+/// Log attribute value of a metric attribute value.
+fn any_value(value: &Value) -> AnyValue {
+    match value {
+        Value::Bool(value) => AnyValue::from(*value),
+        Value::I64(value) => AnyValue::from(*value),
+        Value::F64(value) => AnyValue::from(*value),
+        Value::String(value) => AnyValue::from(value.clone()),
+        value => AnyValue::from(value.to_string()),
+    }
+}
+
+/// Whether a request is a failure, by its response status or the recorded `error.type`.
+pub(crate) fn is_failure(status: Option<http::StatusCode>, labels: &[KeyValue]) -> bool {
+    status.is_none_or(|status| status.is_server_error())
+        || labels.iter().any(|kv| kv.key.as_str() == ERROR_TYPE_LABEL)
+}
@@ -0,0 +1,42 @@
+//! Minimum recorded durations per route, counting the fast requests left out of the histogram.
+//!
+//! Very chatty endpoints, e.g. internal health or cache lookups, fill
+//! `http.server.request.duration` with requests nobody looks at, when only their slow requests
+//! matter. Routes are given a minimum duration; their requests completing faster are not recorded
+//! into the duration histogram but counted in `http.server.request.fast`, with the same
+//! attributes, so request rates remain the sum of both.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use opentelemetry::metrics::Counter;
+use opentelemetry::StringValue;
+
+pub(crate) const HTTP_SERVER_REQUEST_FAST_METRIC: &str = "http.server.request.fast";
+pub(crate) const HTTP_SERVER_REQUEST_FAST_UNIT: &str = "{request}";
+
+/// Minimum recorded durations of routes, with the counter of requests below them.
+pub(crate) struct MinRecordedDurations {
+    thresholds: HashMap<Cow<'static, str>, Duration>,
+    pub(crate) fast_requests: Counter<u64>,
+}
+
+impl MinRecordedDurations {
+    pub(crate) fn new(
+        thresholds: HashMap<Cow<'static, str>, Duration>,
+        fast_requests: Counter<u64>,
+    ) -> Self {
+        MinRecordedDurations {
+            thresholds,
+            fast_requests,
+        }
+    }
+
+    /// Whether a request to `route` taking `duration` is too fast to be recorded.
+    pub(crate) fn is_fast(&self, route: Option<&StringValue>, duration: Duration) -> bool {
+        route
+            .and_then(|route| self.thresholds.get(route.as_str()))
+            .is_some_and(|threshold| duration < *threshold)
+    }
+}
@@ -0,0 +1,115 @@
+//! gRPC message counts of streaming calls.
+//!
+//! gRPC calls are HTTP/2 requests with a `application/grpc` content type, whose bodies are
+//! streams of length-prefixed messages. For long-lived streaming calls duration alone says little,
+//! so the number of messages in each direction is counted by parsing the message framing as body
+//! frames pass through, and recorded into `rpc.server.requests_per_rpc` and
+//! `rpc.server.responses_per_rpc` once each body is done.
+//!
+//! The service of gRPC calls can also be added to `http.server.request.duration`, organizing
+//! the metrics of servers hosting many gRPC services without wiring a layer per service.
+
+use std::io::IoSlice;
+
+use bytes::Buf;
+use opentelemetry::{KeyValue, StringValue};
+use smallvec::smallvec;
+
+use crate::labels::Labels;
+
+pub(crate) const RPC_SERVER_REQUESTS_PER_RPC_METRIC: &str = "rpc.server.requests_per_rpc";
+pub(crate) const RPC_SERVER_RESPONSES_PER_RPC_METRIC: &str = "rpc.server.responses_per_rpc";
+pub(crate) const RPC_SERVER_MESSAGES_PER_RPC_UNIT: &str = "{count}";
+
+const RPC_SYSTEM_LABEL: &str = "rpc.system";
+const RPC_SERVICE_LABEL: &str = "rpc.service";
+const RPC_METHOD_LABEL: &str = "rpc.method";
+
+const RPC_SYSTEM_GRPC: &str = "grpc";
+
+// each message is prefixed with a compressed flag byte and a 4-byte big-endian length
+const MESSAGE_PREFIX_SIZE: u8 = 5;
+
+/// Service and method of a gRPC call, or `None` for requests which are not gRPC calls.
+fn grpc_call<'a>(headers: &http::HeaderMap, uri: &'a http::Uri) -> Option<(&'a str, &'a str)> {
+    let content_type = headers.get(http::header::CONTENT_TYPE)?.as_bytes();
+    if !content_type.starts_with(b"application/grpc") {
+        return None;
+    }
+    // gRPC paths are `/{package.Service}/{Method}`
+    uri.path().strip_prefix('/')?.split_once('/')
+}
+
+/// Push the `rpc.system` and `rpc.service` attributes of gRPC calls.
+pub(crate) fn push_grpc_service_labels(
+    headers: &http::HeaderMap,
+    uri: &http::Uri,
+    labels: &mut Vec<KeyValue>,
+) {
+    if let Some((service, _)) = grpc_call(headers, uri) {
+        labels.push(KeyValue::new(RPC_SYSTEM_LABEL, RPC_SYSTEM_GRPC));
+        labels.push(KeyValue::new(RPC_SERVICE_LABEL, service.to_owned()));
+    }
+}
+
+/// Attributes of a gRPC call, or `None` for requests which are not gRPC calls.
+pub(crate) fn grpc_labels(headers: &http::HeaderMap, uri: &http::Uri) -> Option<Labels> {
+    let (service, method) = grpc_call(headers, uri)?;
+    Some(smallvec![
+        KeyValue::new(RPC_SYSTEM_LABEL, RPC_SYSTEM_GRPC),
+        KeyValue::new(RPC_SERVICE_LABEL, StringValue::from(service.to_owned())),
+        KeyValue::new(RPC_METHOD_LABEL, StringValue::from(method.to_owned())),
+    ])
+}
+
+/// Counts the messages of a gRPC body from its data frames.
+#[derive(Default)]
+pub(crate) struct GrpcMessageCounter {
+    messages: u64,
+    // prefix bytes of the current message read so far
+    prefix_read: u8,
+    length: u32,
+    // bytes of the current message still to be skipped
+    remaining: u64,
+}
+
+impl GrpcMessageCounter {
+    pub(crate) fn messages(&self) -> u64 {
+        self.messages
+    }
+
+    pub(crate) fn observe<D: Buf>(&mut self, data: &D) {
+        let mut chunks = [IoSlice::new(&[]); 16];
+        let filled = data.chunks_vectored(&mut chunks);
+        for chunk in &chunks[..filled] {
+            self.observe_chunk(chunk);
+        }
+    }
+
+    fn observe_chunk(&mut self, mut chunk: &[u8]) {
+        while !chunk.is_empty() {
+            if self.remaining > 0 {
+                let skipped = chunk
+                    .len()
+                    .min(self.remaining.try_into().unwrap_or(usize::MAX));
+                self.remaining -= skipped as u64;
+                chunk = &chunk[skipped..];
+                continue;
+            }
+
+            // the first prefix byte is the compressed flag, the rest make up the length
+            if self.prefix_read > 0 {
+                self.length = (self.length << 8) | u32::from(chunk[0]);
+            }
+            self.prefix_read += 1;
+            chunk = &chunk[1..];
+
+            if self.prefix_read == MESSAGE_PREFIX_SIZE {
+                self.messages += 1;
+                self.remaining = u64::from(self.length);
+                self.prefix_read = 0;
+                self.length = 0;
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Request counts of routes known up front, reported from startup.
+//!
+//! Series only appear once their first measurement is recorded, which makes `rate()` and alerts on
+//! rarely used routes misbehave. The OTEL API cannot record a histogram data point without a
+//! measurement, so instead of `http.server.request.duration`, routes registered at build time get
+//! their completed requests counted in `http.server.request.count`, an observable counter which
+//! reports every registered method and route, at zero until requested.
+//!
+//! Known routes also attribute `405 Method Not Allowed` responses of requests no stage resolved a
+//! route for: when the request path matches a known route, the response is recorded with that
+//! route and the actual method, so method mismatches show up per endpoint.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use opentelemetry::metrics::AsyncInstrument;
+use opentelemetry::{KeyValue, StringValue};
+
+use crate::route::RoutePattern;
+use crate::{HTTP_REQUEST_METHOD_LABEL, HTTP_ROUTE_LABEL};
+
+pub(crate) const HTTP_SERVER_REQUEST_COUNT_METRIC: &str = "http.server.request.count";
+pub(crate) const HTTP_SERVER_REQUEST_COUNT_UNIT: &str = "{request}";
+
+/// Completed request counts of the known methods of each known route.
+pub(crate) struct KnownRoutes {
+    routes: HashMap<StringValue, Vec<(StringValue, AtomicU64)>>,
+    // patterns of the known routes, in order of registration
+    patterns: Vec<(RoutePattern, StringValue)>,
+}
+
+impl KnownRoutes {
+    pub(crate) fn new(routes: impl IntoIterator<Item = (StringValue, StringValue)>) -> Self {
+        let mut known: HashMap<StringValue, Vec<(StringValue, AtomicU64)>> = HashMap::new();
+        let mut patterns = Vec::new();
+        for (method, route) in routes {
+            if !known.contains_key(&route) {
+                patterns.push((RoutePattern::new(route.as_str()), route.clone()));
+            }
+            let methods = known.entry(route).or_default();
+            if !methods.iter().any(|(known, _)| *known == method) {
+                methods.push((method, AtomicU64::new(0)));
+            }
+        }
+        KnownRoutes {
+            routes: known,
+            patterns,
+        }
+    }
+
+    /// The known route whose pattern matches `path`, if any.
+    pub(crate) fn matching_route(&self, path: &str) -> Option<&StringValue> {
+        self.patterns
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map(|(_, route)| route)
+    }
+
+    /// Count a completed request, unless its method and route are not known.
+    pub(crate) fn increment(&self, method: &StringValue, route: Option<&StringValue>) {
+        let Some(methods) = route.and_then(|route| self.routes.get(route)) else {
+            return;
+        };
+        if let Some((_, count)) = methods.iter().find(|(known, _)| known == method) {
+            count.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn observe(&self, observer: &dyn AsyncInstrument<u64>) {
+        for (route, methods) in &self.routes {
+            for (method, count) in methods {
+                observer.observe(
+                    count.load(Ordering::Relaxed),
+                    &[
+                        KeyValue::new(HTTP_REQUEST_METHOD_LABEL, method.clone()),
+                        KeyValue::new(HTTP_ROUTE_LABEL, route.clone()),
+                    ],
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Attribute values of the semconv HTTP server attributes.
+//!
+//! Values are shared across requests rather than formatted for each one: standard methods,
+//! schemes and protocol versions are static strings, and status codes are formatted once per code.
+//! Together with attribute sets held in [`Labels`] stack buffers, the default request path of
+//! the layer does not allocate.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, OnceLock};
+
+use http::{Method, StatusCode, Uri};
+use opentelemetry::{KeyValue, StringValue, Value};
+use smallvec::SmallVec;
+
+/// Attribute set of a measurement, kept on the stack for the default attributes.
+pub(crate) type Labels = SmallVec<[KeyValue; 8]>;
+
+/// Hash of an attribute set, independent of the order of its attributes.
+pub(crate) fn attribute_set_hash(attributes: &[KeyValue]) -> u64 {
+    // summing per-attribute hashes makes the set hash independent of attribute order
+    attributes.iter().fold(0u64, |acc, attribute| {
+        let mut hasher = DefaultHasher::new();
+        attribute.hash(&mut hasher);
+        acc.wrapping_add(hasher.finish())
+    })
+}
+
+/// Attribute value of `http.request.method`.
+pub(crate) fn method_value(method: &Method) -> StringValue {
+    let method = match *method {
+        Method::GET => "GET",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::HEAD => "HEAD",
+        Method::OPTIONS => "OPTIONS",
+        Method::CONNECT => "CONNECT",
+        Method::PATCH => "PATCH",
+        Method::TRACE => "TRACE",
+        _ => return StringValue::from(method.as_str().to_owned()),
+    };
+    StringValue::from(method)
+}
+
+/// Attribute value of `url.scheme`, or `default` when the URI has no scheme.
+pub(crate) fn scheme_value(uri: &Uri, default: &StringValue) -> StringValue {
+    match uri.scheme_str() {
+        None => default.clone(),
+        Some("http") => StringValue::from("http"),
+        Some("https") => StringValue::from("https"),
+        Some(scheme) => StringValue::from(scheme.to_owned()),
+    }
+}
+
+/// Value of `http.response.status_code` grouping the codes which are not kept.
+pub(crate) const OTHER_STATUS_CODE: &str = "_OTHER";
+
+/// Status codes kept by default when rare status codes are collapsed.
+pub(crate) const COMMON_STATUS_CODES: [StatusCode; 11] = [
+    StatusCode::OK,
+    StatusCode::CREATED,
+    StatusCode::NO_CONTENT,
+    StatusCode::BAD_REQUEST,
+    StatusCode::UNAUTHORIZED,
+    StatusCode::FORBIDDEN,
+    StatusCode::NOT_FOUND,
+    StatusCode::TOO_MANY_REQUESTS,
+    StatusCode::INTERNAL_SERVER_ERROR,
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+];
+
+/// Attribute value of `http.response.status_code`: the code as an integer, as defined by semconv,
+/// or the string formatted by `legacy` when string status codes are enabled for compatibility.
+/// Codes outside `kept_status_codes`, when given, are recorded as `_OTHER`.
+pub(crate) fn status_code_attribute(
+    status: StatusCode,
+    string_status_code: bool,
+    kept_status_codes: Option<&[StatusCode]>,
+    legacy: fn(StatusCode) -> StringValue,
+) -> Value {
+    if kept_status_codes.is_some_and(|kept| !kept.contains(&status)) {
+        Value::String(StringValue::from(OTHER_STATUS_CODE))
+    } else if string_status_code {
+        Value::String(legacy(status))
+    } else {
+        Value::I64(i64::from(status.as_u16()))
+    }
+}
+
+/// Attribute value of `http.response.status_code` as the code alone, e.g. `200`.
+pub(crate) fn status_code_value(status: StatusCode) -> StringValue {
+    static VALUES: OnceLock<Vec<StringValue>> = OnceLock::new();
+    status_values(&VALUES, status, |status| status.as_str().to_owned())
+}
+
+/// Attribute value of `http.response.status_code` with its reason phrase, e.g. `200 OK`,
+/// as recorded into `http.server.request.duration`.
+pub(crate) fn status_code_reason_value(status: StatusCode) -> StringValue {
+    static VALUES: OnceLock<Vec<StringValue>> = OnceLock::new();
+    status_values(&VALUES, status, |status| status.to_string())
+}
+
+fn status_values(
+    values: &OnceLock<Vec<StringValue>>,
+    status: StatusCode,
+    format: fn(StatusCode) -> String,
+) -> StringValue {
+    let values = values.get_or_init(|| {
+        // StatusCode only admits codes from 100 to 999
+        (100..1000)
+            .map(|code| {
+                let status = StatusCode::from_u16(code).expect("status codes are 100 to 999");
+                StringValue::from(Arc::<str>::from(format(status)))
+            })
+            .collect()
+    });
+    values[usize::from(status.as_u16() - 100)].clone()
+}
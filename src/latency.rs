@@ -0,0 +1,264 @@
+//! In-process latency tracking per route, calling back when a latency quantile crosses a threshold.
+//!
+//! Alerts evaluated by a metrics backend only fire after the export interval, scraping and rule
+//! evaluation have passed. Applications which want to protect themselves right away, e.g. by
+//! shedding load, can instead be called back from the request path when the latency of a route
+//! crosses a threshold.
+//!
+//! Durations are counted in log-linear buckets in the style of HDR histograms: 16 linear
+//! sub-buckets per power of two microseconds, giving quantiles within about 6% of the actual
+//! latency from 1µs to over 19 hours in a fixed 2KiB per slot. The sliding window is divided into
+//! four slots plus the one being filled; when a route's request opens a new slot, the quantile
+//! over the four completed slots is compared with the threshold. Counting is lock-free and
+//! approximate, as requests racing with the opening of a slot may be counted in either one.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use opentelemetry::StringValue;
+
+pub(crate) type LatencyAlertCallback = dyn Fn(&LatencyAlert) + Send + Sync;
+
+const SUB_BUCKET_BITS: u32 = 4;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+/// Durations are capped at 2^36µs, a little over 19 hours
+const MAX_EXPONENT: u32 = 35;
+const BUCKETS: usize = (MAX_EXPONENT as usize - SUB_BUCKET_BITS as usize + 2) * SUB_BUCKETS;
+
+/// Completed slots making up the window, plus the slot being filled
+const SLOTS: usize = 5;
+
+/// Routes tracked at most, so unbounded `http.route` values cannot exhaust memory
+const MAX_ROUTES: usize = 1024;
+
+#[derive(Clone, Debug)]
+/// Latency quantile of a route crossing the threshold of
+/// [`HTTPMetricsLayerBuilder::with_latency_alert`], in either direction.
+///
+/// [`HTTPMetricsLayerBuilder::with_latency_alert`]: crate::HTTPMetricsLayerBuilder::with_latency_alert
+pub struct LatencyAlert {
+    route: Option<StringValue>,
+    quantile: f64,
+    latency: Duration,
+    threshold: Duration,
+    window: Duration,
+}
+
+impl LatencyAlert {
+    /// The `http.route` of the requests, if one was resolved.
+    pub fn route(&self) -> Option<&str> {
+        self.route.as_ref().map(StringValue::as_str)
+    }
+
+    /// The tracked quantile, e.g. `0.99`.
+    pub fn quantile(&self) -> f64 {
+        self.quantile
+    }
+
+    /// The latency at the tracked quantile over the window.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// The threshold the latency is compared with.
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    /// The duration of the sliding window.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Whether the latency crossed above the threshold, or `false` when it dropped back below.
+    pub fn firing(&self) -> bool {
+        self.latency > self.threshold
+    }
+}
+
+/// Bucket of a duration in microseconds.
+fn bucket_index(micros: u64) -> usize {
+    if micros < SUB_BUCKETS as u64 {
+        return micros as usize;
+    }
+    let exponent = (u64::BITS - 1 - micros.leading_zeros()).min(MAX_EXPONENT);
+    let micros = micros.min((1 << (MAX_EXPONENT + 1)) - 1);
+    let sub_bucket = (micros >> (exponent - SUB_BUCKET_BITS)) as usize & (SUB_BUCKETS - 1);
+    (exponent - SUB_BUCKET_BITS + 1) as usize * SUB_BUCKETS + sub_bucket
+}
+
+/// Upper bound of a bucket in microseconds.
+fn bucket_upper_bound(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64 + 1;
+    }
+    let shift = (index / SUB_BUCKETS - 1) as u32;
+    let sub_bucket = (index % SUB_BUCKETS) as u64;
+    (SUB_BUCKETS as u64 + sub_bucket + 1) << shift
+}
+
+struct Slot {
+    /// Index of the slot period the counts belong to
+    period: AtomicU64,
+    counts: Box<[AtomicU32]>,
+}
+
+impl Slot {
+    fn new() -> Self {
+        Slot {
+            period: AtomicU64::new(0),
+            counts: (0..BUCKETS).map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+}
+
+struct RouteLatency {
+    slots: [Slot; SLOTS],
+    firing: AtomicBool,
+}
+
+impl RouteLatency {
+    fn new() -> Self {
+        RouteLatency {
+            slots: std::array::from_fn(|_| Slot::new()),
+            firing: AtomicBool::new(false),
+        }
+    }
+}
+
+pub(crate) struct LatencyTracker {
+    quantile: f64,
+    threshold: Duration,
+    window: Duration,
+    slot_nanos: u64,
+    created_at: Instant,
+    routes: RwLock<HashMap<StringValue, Arc<RouteLatency>>>,
+    unrouted: RouteLatency,
+    alert: Arc<LatencyAlertCallback>,
+}
+
+impl LatencyTracker {
+    pub(crate) fn new(
+        quantile: f64,
+        threshold: Duration,
+        window: Duration,
+        alert: Arc<LatencyAlertCallback>,
+    ) -> Self {
+        LatencyTracker {
+            quantile: quantile.clamp(0.0, 1.0),
+            threshold,
+            window,
+            slot_nanos: (window.as_nanos() as u64 / (SLOTS as u64 - 1)).max(1),
+            created_at: Instant::now(),
+            routes: RwLock::new(HashMap::new()),
+            unrouted: RouteLatency::new(),
+            alert,
+        }
+    }
+
+    /// Number of routes tracked.
+    #[cfg(feature = "diagnostics")]
+    pub(crate) fn routes(&self) -> usize {
+        self.routes
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .len()
+    }
+
+    /// Count the duration of a request to `route`, evaluating the window when a slot opens.
+    pub(crate) fn record(&self, route: Option<&StringValue>, duration: Duration) {
+        let period = self.created_at.elapsed().as_nanos() as u64 / self.slot_nanos + 1;
+        match route {
+            None => self.record_route(&self.unrouted, None, period, duration),
+            Some(route) => {
+                let latency = self
+                    .routes
+                    .read()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .get(route)
+                    .cloned();
+                let latency = match latency {
+                    Some(latency) => latency,
+                    None => {
+                        let mut routes = self.routes.write().unwrap_or_else(|err| err.into_inner());
+                        if routes.len() >= MAX_ROUTES && !routes.contains_key(route) {
+                            return;
+                        }
+                        routes
+                            .entry(route.clone())
+                            .or_insert_with(|| Arc::new(RouteLatency::new()))
+                            .clone()
+                    }
+                };
+                self.record_route(&latency, Some(route), period, duration);
+            }
+        }
+    }
+
+    fn record_route(
+        &self,
+        latency: &RouteLatency,
+        route: Option<&StringValue>,
+        period: u64,
+        duration: Duration,
+    ) {
+        let slot = &latency.slots[period as usize % SLOTS];
+        let slot_period = slot.period.load(Ordering::Relaxed);
+        // only the request which opens the slot clears it and evaluates the window
+        if slot_period != period
+            && slot
+                .period
+                .compare_exchange(slot_period, period, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            for count in slot.counts.iter() {
+                count.store(0, Ordering::Relaxed);
+            }
+            self.evaluate(latency, route, period);
+        }
+        let micros = duration.as_micros().min(u128::from(u64::MAX)) as u64;
+        slot.counts[bucket_index(micros)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Compare the quantile over the completed slots before `period` with the threshold.
+    fn evaluate(&self, latency: &RouteLatency, route: Option<&StringValue>, period: u64) {
+        let window = period.saturating_sub(SLOTS as u64 - 1)..period;
+        let mut counts = [0u64; BUCKETS];
+        for slot in latency
+            .slots
+            .iter()
+            .filter(|slot| window.contains(&slot.period.load(Ordering::Relaxed)))
+        {
+            for (total, count) in counts.iter_mut().zip(slot.counts.iter()) {
+                *total += u64::from(count.load(Ordering::Relaxed));
+            }
+        }
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return;
+        }
+        let rank = ((total as f64) * self.quantile).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        let index = counts
+            .iter()
+            .position(|count| {
+                seen += count;
+                seen >= rank
+            })
+            .unwrap_or(BUCKETS - 1);
+        let quantile_latency = Duration::from_micros(bucket_upper_bound(index));
+
+        let firing = quantile_latency > self.threshold;
+        if latency.firing.swap(firing, Ordering::Relaxed) != firing {
+            (self.alert)(&LatencyAlert {
+                route: route.cloned(),
+                quantile: self.quantile,
+                latency: quantile_latency,
+                threshold: self.threshold,
+                window: self.window,
+            });
+        }
+    }
+}
@@ -3,26 +3,178 @@
 //! [`Layer`]: tower_layer::Layer
 //! [`Service`]: tower_service::Service
 //! [`Future`]: tower_service::Future
+#![warn(missing_debug_implementations)]
 
 use std::borrow::Cow;
-use std::future::Future;
-use std::pin::Pin;
+use std::collections::HashMap;
 use std::string::String;
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
-use std::task::Poll::Ready;
-use std::task::{Context, Poll};
-use std::time::Instant;
+use std::time::Duration;
 use std::{fmt, result};
 
-#[cfg(feature = "axum")]
-use axum::extract::MatchedPath;
-use futures_util::ready;
-use http;
-use opentelemetry::metrics::{Histogram, Meter, UpDownCounter};
-use opentelemetry::{global, KeyValue};
-use pin_project_lite::pin_project;
+#[cfg(any(feature = "cardinality", feature = "diagnostics"))]
+use opentelemetry::metrics::ObservableGauge;
+use opentelemetry::metrics::{
+    Counter, Gauge, Histogram, Meter, MeterProvider, ObservableCounter, UpDownCounter,
+};
+use opentelemetry::{Key, StringValue};
 use tower_layer::Layer;
-use tower_service::Service;
+
+#[cfg(feature = "async-extractor")]
+use crate::async_extractor::{AsyncExtractorLayer, AsyncRequestAttributeExtractor};
+use crate::attributes::{QueryParamAttribute, TrafficSplitAttribute};
+use crate::binding::{LayerBinding, WeakMeterProvider};
+use crate::builder::UrlPathSanitizer;
+#[cfg(feature = "cardinality")]
+use crate::cardinality::CardinalityEstimator;
+#[cfg(feature = "tower-http")]
+use crate::classify::MakeFailureClassifier;
+use crate::custom::BuiltCustomInstrument;
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics::LastCollection;
+use crate::extractor::{RequestAttributeExtractor, ResponseAttributeExtractor};
+#[cfg(feature = "failure-logs")]
+use crate::failure_log::FailureLogger;
+use crate::fast::MinRecordedDurations;
+use crate::known_routes::KnownRoutes;
+use crate::latency::LatencyTracker;
+#[cfg(feature = "load")]
+use crate::load::ServiceLoad;
+use crate::operation::OperationIds;
+#[cfg(feature = "axum")]
+use crate::placement::PlacementGuard;
+use crate::resolution::DurationHistogram;
+use crate::route::RouteResolver;
+use crate::slo::SloThresholds;
+#[cfg(feature = "semconv-validation")]
+use crate::validation::SemconvValidator;
+
+pub use accept::{AcceptTime, AcceptTimeService};
+pub use attributes::{mask_path_ids, ThrottlePolicy, TrafficSplitSource};
+pub use backend::{Backend, BackendLayer, BackendResponseFuture, BackendService};
+pub use body::{
+    HTTPMetricsResponseBody, ResponseBodyMetricsFuture, ResponseBodyMetricsLayer,
+    ResponseBodyMetricsService, ResponseBodySizeSource,
+};
+pub use builder::{BodyMetricsFilter, HTTPMetricsLayerBuilder};
+pub use checkpoint::TimingCheckpoints;
+pub use context::RequestMetricsContext;
+pub use custom::{CustomInstrument, RecordValues, UsageUnits};
+#[cfg(feature = "diagnostics")]
+pub use diagnostics::DiagnosticsService;
+pub use dry_run::{DryRunSummary, InstrumentSummary};
+pub use error_type::{ErrorTypeFuture, ErrorTypeLayer, ErrorTypeService};
+pub use extractor::{AttributeExtractor, HttpMetricsAttributes};
+pub use latency::LatencyAlert;
+#[cfg(feature = "load")]
+pub use load::LoadMeasure;
+#[cfg(feature = "axum")]
+pub use middleware::middleware;
+pub use naming::NamingConvention;
+pub use operation::OperationId;
+pub use preset::Preset;
+pub use request_class::RequestClassifier;
+pub use resolution::DurationResolution;
+pub use route::RouteFallback;
+#[cfg(feature = "trace-sampling")]
+pub use sampling::TraceSampling;
+pub use service::{HTTPMetricsResponseFuture, HTTPMetricsService};
+#[cfg(feature = "service-builder")]
+pub use service_builder::ServiceBuilderExt;
+pub use tls::{TlsHandshake, TlsHandshakeMetrics};
+#[cfg(feature = "derive")]
+pub use tower_otel_http_metrics_derive::HttpMetricsAttributes;
+pub use tunnel::OpenTunnel;
+pub use user::Authenticated;
+#[cfg(feature = "semconv-validation")]
+pub use validation::SemconvViolation;
+
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __private {
+    pub use opentelemetry::KeyValue;
+}
+pub use request_body::{
+    HTTPMetricsRequestBody, RequestBodyMetricsLayer, RequestBodyMetricsService,
+};
+
+mod accept;
+mod alias;
+#[cfg(feature = "async-extractor")]
+pub mod async_extractor;
+mod attributes;
+mod backend;
+mod binding;
+mod body;
+#[cfg(feature = "buffer")]
+pub mod buffer;
+mod builder;
+#[cfg(any(feature = "dashboard", feature = "diagnostics"))]
+mod capture;
+#[cfg(feature = "cardinality")]
+mod cardinality;
+mod checkpoint;
+#[cfg(feature = "tower-http")]
+mod classify;
+#[cfg(feature = "client")]
+pub mod client;
+mod clock;
+#[cfg(feature = "connector")]
+pub mod connector;
+mod context;
+mod custom;
+#[cfg(feature = "dashboard")]
+mod dashboard;
+#[cfg(feature = "diagnostics")]
+mod diagnostics;
+mod dry_run;
+mod error_type;
+mod extractor;
+mod faas;
+#[cfg(feature = "failure-logs")]
+mod failure_log;
+mod fast;
+mod grpc;
+#[cfg(any(feature = "dashboard", feature = "diagnostics"))]
+mod json;
+mod known_routes;
+mod labels;
+mod latency;
+#[cfg(feature = "limit")]
+pub mod limit;
+#[cfg(feature = "load")]
+mod load;
+#[cfg(feature = "load-shed")]
+pub mod load_shed;
+#[cfg(feature = "route-cache")]
+mod lru;
+#[cfg(feature = "axum")]
+mod middleware;
+mod naming;
+mod operation;
+#[cfg(feature = "axum")]
+mod placement;
+mod pool;
+mod preset;
+mod record;
+mod request_body;
+mod request_class;
+mod resolution;
+mod route;
+#[cfg(feature = "trace-sampling")]
+mod sampling;
+mod service;
+#[cfg(feature = "service-builder")]
+mod service_builder;
+mod slo;
+#[cfg(feature = "span-attributes")]
+mod span;
+mod tls;
+mod tunnel;
+mod user;
+#[cfg(feature = "semconv-validation")]
+mod validation;
 
 const HTTP_SERVER_DURATION_METRIC: &str = "http.server.request.duration";
 const HTTP_SERVER_DURATION_UNIT: &str = "s";
@@ -30,16 +182,17 @@ const HTTP_SERVER_DURATION_UNIT: &str = "s";
 const HTTP_SERVER_DURATION_BOUNDARIES: [f64; 14] = [
     0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0, 7.5, 10.0,
 ];
-const HTTP_SERVER_ACTIVE_REQUESTS_METRIC: &str = "http.server.active_requests";
-const HTTP_SERVER_ACTIVE_REQUESTS_UNIT: &str = "{request}";
 
-const HTTP_SERVER_REQUEST_BODY_SIZE_METRIC: &str = "http.server.request.body.size";
-const HTTP_SERVER_REQUEST_BODY_SIZE_UNIT: &str = "By";
+const TENANT_ID_LABEL: &str = "tenant.id";
 
 const HTTP_REQUEST_METHOD_LABEL: &str = "http.request.method";
 const HTTP_ROUTE_LABEL: &str = "http.route";
 const HTTP_RESPONSE_STATUS_CODE_LABEL: &str = "http.response.status_code";
+const HTTP_RESPONSE_STATUS_CLASS_LABEL: &str = "http.response.status_class";
 
+const NETWORK_IO_DIRECTION_LABEL: &str = "network.io.direction";
+const NETWORK_IO_DIRECTION_RECEIVE: &str = "receive";
+const NETWORK_IO_DIRECTION_TRANSMIT: &str = "transmit";
 const NETWORK_PROTOCOL_NAME_LABEL: &str = "network.protocol.name";
 const NETWORK_PROTOCOL_VERSION_LABEL: &str = "network.protocol.version";
 
@@ -51,26 +204,145 @@ const URL_SCHEME_LABEL: &str = "url.scheme";
 /// The OTEL SDKs do support calling for the global meter provider instead of holding a reference
 /// but it seems ideal to avoid extra access to the global meter, which sits behind a RWLock.
 struct HTTPMetricsLayerState {
-    pub server_request_duration: Histogram<f64>,
-    pub server_active_requests: UpDownCounter<i64>,
-    pub server_request_body_size: Histogram<u64>,
+    pub server_request_duration: DurationHistogram,
+    pub max_request_duration: Option<Duration>,
+    pub cancelled_requests: bool,
+    pub client_abort_error_type: bool,
+    pub duration_from_accept_time: bool,
+    pub request_context_extension: bool,
+    pub active_requests_route: bool,
+    #[cfg(feature = "span-attributes")]
+    pub span_attributes: bool,
+    pub server_request_duration_overflow: Option<Counter<u64>>,
+    pub server_request_duration_rollup: Option<DurationRollup>,
+    pub route_resolver: RouteResolver,
+    pub known_routes: Option<Arc<KnownRoutes>>,
+    pub slo_thresholds: Option<SloThresholds>,
+    pub min_recorded_durations: Option<MinRecordedDurations>,
+    pub latency_tracker: Option<LatencyTracker>,
+    pub _server_request_count: Option<ObservableCounter<u64>>,
+    #[cfg(feature = "cardinality")]
+    pub server_request_duration_cardinality: Option<Arc<CardinalityEstimator>>,
+    #[cfg(feature = "cardinality")]
+    pub _server_request_duration_attribute_sets: Option<ObservableGauge<u64>>,
+    pub server_active_requests: Option<UpDownCounter<i64>>,
+    pub server_request_body_size: Option<Histogram<u64>>,
+    pub server_request_size: Option<Histogram<u64>>,
+    pub server_concurrent_requests: Option<Histogram<u64>>,
+    pub server_response_body_size: Option<Histogram<u64>>,
+    pub response_body_size_source: ResponseBodySizeSource,
+    pub server_response_body_frame_size: Option<Histogram<u64>>,
+    pub server_response_body_frames: Option<Histogram<u64>>,
+    pub server_queue_time: Option<Histogram<f64>>,
+    pub server_request_polls: Option<Histogram<u64>>,
+    pub server_request_stage_duration: Option<Histogram<f64>>,
+    pub server_request_body_size_malformed: Option<Counter<u64>>,
+    pub max_content_length: Option<u64>,
+    pub server_informational_responses: Option<Counter<u64>>,
+    pub server_request_continue_duration: Option<Histogram<f64>>,
+    pub server_request_body_bytes: Option<Counter<u64>>,
+    pub server_response_body_bytes: Option<Counter<u64>>,
+    pub server_network_io: Option<Counter<u64>>,
+    pub connect_tunnels: bool,
+    pub server_open_tunnels: Option<UpDownCounter<i64>>,
+    pub rpc_server_requests_per_rpc: Option<Histogram<u64>>,
+    pub rpc_server_responses_per_rpc: Option<Histogram<u64>>,
+    pub grpc_service_attributes: bool,
+
+    pub default_url_scheme: StringValue,
+    pub string_status_code: bool,
+    pub status_class: bool,
+    pub kept_status_codes: Option<Vec<http::StatusCode>>,
+    pub body_metrics_filter: Option<BodyMetricsFilter>,
+
+    pub custom_histograms: HashMap<Cow<'static, str>, Histogram<f64>>,
+    pub custom_instruments: Vec<BuiltCustomInstrument>,
+    pub request_classifier: Option<Arc<dyn RequestClassifier>>,
+    pub request_attribute_extractors: Vec<Arc<RequestAttributeExtractor>>,
+    pub response_attribute_extractors: Vec<Arc<ResponseAttributeExtractor>>,
+    pub server_extractor_panics: Counter<u64>,
+    pub max_extractor_attributes: usize,
+    pub server_extractor_attributes_dropped: Counter<u64>,
+    #[cfg(feature = "async-extractor")]
+    pub async_attribute_extractors: Vec<Arc<AsyncRequestAttributeExtractor>>,
+    #[cfg(feature = "async-extractor")]
+    pub async_extractor_budget: Duration,
+    #[cfg(feature = "async-extractor")]
+    pub server_extractor_timeouts: Counter<u64>,
+    pub usage_units: Option<Counter<u64>>,
+    pub usage_tenant_header: Option<http::HeaderName>,
+
+    pub cache_status_attribute: bool,
+    pub auth_outcome_attributes: bool,
+    pub user_authenticated_attribute: bool,
+    pub operation_ids: Option<OperationIds>,
+    pub backend_attribute: bool,
+    pub backend: Option<StringValue>,
+    pub principal_buckets: Option<u32>,
+    #[cfg(feature = "tower-http")]
+    pub failure_classifier: Option<Arc<MakeFailureClassifier>>,
+    pub url_path_sanitizer: Option<Arc<UrlPathSanitizer>>,
+    pub query_param_attributes: Vec<QueryParamAttribute>,
+    pub server_address_attributes: bool,
+    pub network_peer_address_attribute: bool,
+    pub client_geo_attributes: bool,
+    pub faas_attributes: bool,
+    pub traffic_split_attribute: Option<TrafficSplitAttribute>,
+    #[cfg(feature = "user-agent")]
+    pub user_agent_device_category: bool,
+    pub route_fallback_attribute: bool,
+    #[cfg(feature = "axum")]
+    pub placement_guard: Option<PlacementGuard>,
+    #[cfg(feature = "semconv-validation")]
+    pub semconv_validator: Option<SemconvValidator>,
+    #[cfg(feature = "trace-sampling")]
+    pub trace_sampling: TraceSampling,
+    #[cfg(feature = "failure-logs")]
+    pub failure_logger: Option<Arc<dyn FailureLogger>>,
+    #[cfg(feature = "load")]
+    pub load_measure: Option<LoadMeasure>,
+
+    pub server_rate_limit_limit: Option<Gauge<u64>>,
+    pub server_request_throttled: Option<Counter<u64>>,
+    pub server_range_requests: Option<Counter<u64>>,
+    pub server_response_range_size: Option<Histogram<u64>>,
+    pub server_rate_limit_remaining: Option<Gauge<u64>>,
+
+    /// Weak handle to the meter provider of the instruments, when given to the builder,
+    /// to detect the provider being dropped.
+    pub meter_provider: Option<WeakMeterProvider>,
+    /// Whether requests are passed through without any work, the meter recording nothing.
+    pub pass_through: bool,
+    /// Whether the instruments were replaced with no-ops after the meter provider was dropped.
+    #[cfg(feature = "diagnostics")]
+    pub meter_provider_dropped: bool,
+    /// Whether the instruments were replaced with no-ops by [`HTTPMetricsLayer::unbind`].
+    #[cfg(feature = "diagnostics")]
+    pub meter_provider_unbound: bool,
+    /// Time of the latest collection, marked by the callback of an instrument reporting nothing.
+    #[cfg(feature = "diagnostics")]
+    pub last_collection: LastCollection,
+    #[cfg(feature = "diagnostics")]
+    pub _diagnostics_collection: ObservableGauge<u64>,
+
+    /// In-process count of requests currently being handled by the layer.
+    ///
+    /// The OTEL UpDownCounter cannot be read back, so we keep our own count
+    /// in order to sample the concurrency level at request completion.
+    pub active_requests: AtomicU64,
 }
 
-#[derive(Clone)]
-/// [`Service`] used by [`HTTPMetricsLayer`]
-pub struct HTTPMetricsService<S> {
-    pub(crate) state: Arc<HTTPMetricsLayerState>,
-    inner_service: S,
+/// Rollup histogram of request durations with the attribute keys it keeps.
+struct DurationRollup {
+    histogram: DurationHistogram,
+    keys: Vec<Key>,
 }
 
 #[derive(Clone)]
 /// [`Layer`] which applies the OTEL HTTP server metrics middleware
 pub struct HTTPMetricsLayer {
-    state: Arc<HTTPMetricsLayerState>,
-}
-
-pub struct HTTPMetricsLayerBuilder {
-    meter: Option<Meter>,
+    binding: Arc<LayerBinding>,
+    dry_run_summary: Option<DryRunSummary>,
 }
 
 /// Error typedef to implement `std::error::Error` for `tower_otel_http_metrics`
@@ -97,267 +369,131 @@ impl fmt::Debug for Error {
     }
 }
 
-impl HTTPMetricsLayerBuilder {
-    pub fn default() -> Self {
-        let meter = global::meter("");
-        HTTPMetricsLayerBuilder { meter: Some(meter) }
+impl fmt::Debug for HTTPMetricsLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HTTPMetricsLayer").finish_non_exhaustive()
     }
+}
 
-    pub fn new() -> Self {
-        HTTPMetricsLayerBuilder { meter: None }
+impl HTTPMetricsLayer {
+    /// Summary of the measurements recorded so far by a layer built with
+    /// [`HTTPMetricsLayerBuilder::with_dry_run`], or `None` when recording to OTEL.
+    pub fn dry_run_summary(&self) -> Option<&DryRunSummary> {
+        self.dry_run_summary.as_ref()
     }
 
-    pub fn build(self) -> Result<HTTPMetricsLayer> {
-        match self.meter {
-            Some(meter) => Ok(HTTPMetricsLayer {
-                state: Arc::from(HTTPMetricsLayerBuilder::make_state(meter)),
-            }),
-            None => Err(Error {
-                inner: ErrorKind::Config(String::from("no meter provided")),
-            }),
-        }
+    /// Create a [`DiagnosticsService`] reporting on the metrics of this layer, e.g. to mount at
+    /// `/.well-known/otel-metrics-status`.
+    ///
+    /// Every request is answered with a JSON document telling whether the meter provider is live,
+    /// which instruments are enabled, the sizes of the in-process caches, and how long ago the
+    /// metrics were last collected by an exporter, which helps when no metrics show up. Whether the
+    /// meter provider is live is only known for layers given a provider, as with
+    /// [`HTTPMetricsLayerBuilder::with_meter_provider`].
+    ///
+    /// ```
+    /// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+    ///
+    /// let layer = HTTPMetricsLayerBuilder::new()
+    ///     .with_meter(opentelemetry::global::meter("http"))
+    ///     .build()
+    ///     .unwrap();
+    /// let diagnostics = layer.diagnostics_service();
+    /// // with axum:
+    /// // Router::new().route_service("/.well-known/otel-metrics-status", diagnostics)
+    /// ```
+    #[cfg(feature = "diagnostics")]
+    pub fn diagnostics_service(&self) -> DiagnosticsService {
+        DiagnosticsService::new(self.binding.clone())
     }
 
-    pub fn with_meter(self, meter: Meter) -> Self {
-        HTTPMetricsLayerBuilder { meter: Some(meter) }
+    /// Create the [`RequestBodyMetricsLayer`] applying this layer's request body metrics.
+    ///
+    /// Request body metrics wrap the request body, which changes its type, so they are applied
+    /// by a separate layer placed around the whole router or service; apply this layer inside it
+    /// so request body metrics get the `http.route` as well.
+    pub fn request_body_layer(&self) -> RequestBodyMetricsLayer {
+        RequestBodyMetricsLayer::new(self.binding.clone())
     }
 
-    fn make_state(meter: Meter) -> HTTPMetricsLayerState {
-        HTTPMetricsLayerState {
-            server_request_duration: meter
-                .f64_histogram(Cow::from(HTTP_SERVER_DURATION_METRIC))
-                .with_description("Duration of HTTP server requests.")
-                .with_unit(Cow::from(HTTP_SERVER_DURATION_UNIT))
-                .with_boundaries(HTTP_SERVER_DURATION_BOUNDARIES.to_vec())
-                .build(),
-            server_active_requests: meter
-                .i64_up_down_counter(Cow::from(HTTP_SERVER_ACTIVE_REQUESTS_METRIC))
-                .with_description("Number of active HTTP server requests.")
-                .with_unit(Cow::from(HTTP_SERVER_ACTIVE_REQUESTS_UNIT))
-                .build(),
-            server_request_body_size: meter
-                .u64_histogram(HTTP_SERVER_REQUEST_BODY_SIZE_METRIC)
-                .with_description("Size of HTTP server request bodies.")
-                .with_unit(HTTP_SERVER_REQUEST_BODY_SIZE_UNIT)
-                .build(),
-        }
+    /// Create the [`ResponseBodyMetricsLayer`] applying this layer's response body metrics.
+    ///
+    /// Observing the response body wraps it, which changes the response type, so metrics which
+    /// need the body are applied by a separate layer placed around this one, e.g. around the
+    /// whole router or service. Responses which no body metric needs to observe are passed
+    /// through untouched.
+    pub fn response_body_layer(&self) -> ResponseBodyMetricsLayer {
+        ResponseBodyMetricsLayer::new()
     }
-}
-
-impl<S> Layer<S> for HTTPMetricsLayer {
-    type Service = HTTPMetricsService<S>;
 
-    fn layer(&self, service: S) -> Self::Service {
-        HTTPMetricsService {
-            state: self.state.clone(),
-            inner_service: service,
-        }
+    /// Create the [`ErrorTypeLayer`] classifying the errors of the inner service.
+    ///
+    /// Errors can only be inspected once converted into a [`BoxError`], which changes the error
+    /// type of the service, so they are classified by a separate layer; apply it inside this
+    /// layer, with [`HTTPMetricsLayerBuilder::with_client_abort_error_type`] enabled.
+    ///
+    /// [`BoxError`]: tower::BoxError
+    pub fn error_type_layer(&self) -> ErrorTypeLayer {
+        ErrorTypeLayer::new()
     }
-}
-
-/// ResponseFutureMetricsState holds request-scoped data for metrics and their attributes.
-///
-/// ResponseFutureMetricsState lives inside the response future, as it needs to hold data
-/// initialized or extracted from the request before it is forwarded to the inner Service.
-/// The rest of the data (e.g. status code, error) can be extracted from the response
-/// or calculated with respect to the data held here (e.g., duration = now - duration start).
-#[derive(Clone)]
-struct ResponseFutureMetricsState {
-    // fields for the metrics themselves
-    // https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#metric-httpserverrequestduration
-    http_request_duration_start: Instant,
-    // https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#metric-httpserverrequestbodysize
-    http_request_body_size: Option<u64>,
-
-    // fields for metric labels
-    http_request_method: String,
-    http_route: Option<String>,
-    network_protocol_name: String,
-    network_protocol_version: String,
-    url_scheme: String,
-}
 
-pin_project! {
-    /// Response [`Future`] for [`HTTPMetricsService`].
-    pub struct HTTPMetricsResponseFuture<F> {
-        #[pin]
-        inner_response_future: F,
-        layer_state: Arc<HTTPMetricsLayerState>,
-        metrics_state: ResponseFutureMetricsState,
+    /// Create the [`AsyncExtractorLayer`] running this layer's async attribute extractors.
+    ///
+    /// Awaiting extractors before the request is forwarded requires a cloneable inner service,
+    /// so they are run by a separate layer; place it directly around this layer.
+    ///
+    /// [`AsyncExtractorLayer`]: async_extractor::AsyncExtractorLayer
+    #[cfg(feature = "async-extractor")]
+    pub fn async_extractor_layer(&self) -> AsyncExtractorLayer {
+        AsyncExtractorLayer::new(self.binding.clone())
     }
-}
-
-impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for HTTPMetricsService<S>
-where
-    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
-{
-    type Response = S::Response;
-    type Error = S::Error;
-    type Future = HTTPMetricsResponseFuture<S::Future>;
 
-    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
-        self.inner_service.poll_ready(cx)
+    /// Re-bind the instruments of this layer, and of the services and request body layers
+    /// created from it, to `meter`.
+    ///
+    /// Services pick up the new instruments on their next request; requests already in flight
+    /// complete with the instruments they started with. In-process state such as the cardinality
+    /// estimate and the route cache starts over. Has no effect on layers built with
+    /// [`HTTPMetricsLayerBuilder::with_dry_run`].
+    pub fn rebind(&self, meter: Meter) {
+        self.binding.rebind(meter, None);
     }
 
-    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
-        let duration_start = Instant::now();
-
-        let method = req.method().as_str().to_owned();
-
-        #[allow(unused_mut)]
-        let mut matched_path = None;
-        #[cfg(feature = "axum")]
-        if let Some(mp) = req.extensions().get::<MatchedPath>() {
-            matched_path = Some(mp.as_str().to_owned());
-        };
-
-        let headers = req.headers();
-
-        let (protocol, version) = split_and_format_protocol_version(req.version());
-        let scheme = req.uri().scheme_str().unwrap_or("").to_string();
-        let content_length = headers
-            .get(http::header::CONTENT_LENGTH)
-            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
-
-        let server_active_request_labels = labels_server_active_request(&method, &scheme);
-
-        self.state
-            .server_active_requests
-            .add(1, &server_active_request_labels);
-
-        HTTPMetricsResponseFuture {
-            inner_response_future: self.inner_service.call(req),
-            layer_state: self.state.clone(),
-            metrics_state: ResponseFutureMetricsState {
-                http_request_duration_start: duration_start,
-                http_request_method: method,
-                http_route: matched_path,
-                network_protocol_name: protocol,
-                network_protocol_version: version,
-                url_scheme: scheme,
-                http_request_body_size: content_length,
-            },
-        }
+    /// Re-bind the instruments of this layer to a meter created from `provider`, holding only a
+    /// weak handle to the provider as with [`HTTPMetricsLayerBuilder::with_meter_provider`].
+    pub fn rebind_meter_provider<P>(&self, provider: &Arc<P>)
+    where
+        P: MeterProvider + Send + Sync + 'static,
+    {
+        let weak: WeakMeterProvider = Arc::downgrade(provider) as _;
+        self.binding
+            .rebind(provider.meter(env!("CARGO_PKG_NAME")), Some(weak));
     }
-}
-
-impl<F, ResBody, E> Future for HTTPMetricsResponseFuture<F>
-where
-    F: Future<Output = result::Result<http::Response<ResBody>, E>>,
-{
-    type Output = F::Output;
-
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let this = self.project();
-        let response = ready!(this.inner_response_future.poll(cx))?;
-
-        let server_request_duration_labels =
-            extract_labels_server_request_duration(this.metrics_state, &response);
-        this.layer_state.server_request_duration.record(
-            this.metrics_state
-                .http_request_duration_start
-                .elapsed()
-                .as_secs_f64(),
-            &server_request_duration_labels,
-        );
-
-        let server_active_request_labels = labels_server_active_request(
-            &this.metrics_state.http_request_method,
-            &this.metrics_state.url_scheme,
-        );
-        this.layer_state
-            .server_active_requests
-            .add(-1, &server_active_request_labels);
-
-        if let Some(content_length) = this.metrics_state.http_request_body_size {
-            let server_request_body_size_labels =
-                labels_server_request_body_size(&this.metrics_state, &response);
-
-            this.layer_state
-                .server_request_body_size
-                .record(content_length, &server_request_body_size_labels);
-        }
 
-        Ready(Ok(response))
+    /// Switch this layer, and the services and request body layers created from it, to no-op
+    /// instruments, e.g. after shutting down its meter provider with `SdkMeterProvider::shutdown`.
+    ///
+    /// Recording into a shut down provider fails on every collection, and the layer cannot tell a
+    /// shut down provider from a live one as long as it is referenced. Services pick up the no-op
+    /// instruments on their next request, until the layer is rebound. Has no effect on layers
+    /// built with [`HTTPMetricsLayerBuilder::with_dry_run`].
+    pub fn unbind(&self) {
+        self.binding.unbind();
     }
 }
 
-// fn parse_request_headers(headers: &HeaderMap) -> HashMap<String, String> {
-//     headers
-//         .iter()
-//         .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or_default().to_string()))
-//         .collect()
-// }
-
-fn extract_labels_server_request_duration<T>(
-    metrics_state: &ResponseFutureMetricsState,
-    resp: &http::Response<T>,
-) -> Vec<KeyValue> {
-    let mut labels = vec![
-        KeyValue::new(HTTP_RESPONSE_STATUS_CODE_LABEL, resp.status().to_string()),
-        KeyValue::new(
-            HTTP_REQUEST_METHOD_LABEL,
-            metrics_state.http_request_method.clone(),
-        ),
-        KeyValue::new(
-            NETWORK_PROTOCOL_NAME_LABEL,
-            metrics_state.network_protocol_name.clone(),
-        ),
-        KeyValue::new(
-            NETWORK_PROTOCOL_VERSION_LABEL,
-            metrics_state.network_protocol_version.clone(),
-        ),
-        KeyValue::new(URL_SCHEME_LABEL, metrics_state.url_scheme.clone()),
-    ];
-
-    // Conditionally required to add http.route if available
-    if let Some(route) = &metrics_state.http_route {
-        labels.push(KeyValue::new(HTTP_ROUTE_LABEL, route.clone()));
-    }
-    labels
-}
+impl<S> Layer<S> for HTTPMetricsLayer {
+    type Service = HTTPMetricsService<S>;
 
-fn labels_server_request_body_size<T>(
-    metrics_state: &ResponseFutureMetricsState,
-    resp: &http::Response<T>,
-) -> Vec<KeyValue> {
-    let mut labels = common_http_server_labels(
-        &metrics_state.http_request_method,
-        &metrics_state.url_scheme,
-    );
-
-    // Conditionally required to add response status code if sent
-    labels.push(KeyValue::new(
-        HTTP_RESPONSE_STATUS_CODE_LABEL,
-        resp.status().as_str().to_string(),
-    ));
-
-    // Conditionally required to add http route if available
-    if let Some(route) = &metrics_state.http_route {
-        labels.push(KeyValue::new(HTTP_ROUTE_LABEL, route.clone()));
+    fn layer(&self, service: S) -> Self::Service {
+        let (generation, state) = self.binding.current();
+        HTTPMetricsService {
+            #[cfg(feature = "load")]
+            load: state.load_measure.map(ServiceLoad::new),
+            state,
+            binding: self.binding.clone(),
+            generation,
+            inner_service: service,
+        }
     }
-    labels
-}
-
-fn labels_server_active_request(method: &String, scheme: &String) -> Vec<KeyValue> {
-    common_http_server_labels(method, scheme)
-}
-
-fn common_http_server_labels(method: &String, scheme: &String) -> Vec<KeyValue> {
-    vec![
-        KeyValue::new(HTTP_REQUEST_METHOD_LABEL, method.clone()),
-        KeyValue::new(URL_SCHEME_LABEL, scheme.clone()),
-    ]
-}
-
-fn split_and_format_protocol_version(http_version: http::Version) -> (String, String) {
-    let version_str = match http_version {
-        http::Version::HTTP_09 => "0.9",
-        http::Version::HTTP_10 => "1.0",
-        http::Version::HTTP_11 => "1.1",
-        http::Version::HTTP_2 => "2.0",
-        http::Version::HTTP_3 => "3.0",
-        _ => "",
-    };
-    (String::from("http"), String::from(version_str))
 }
@@ -0,0 +1,73 @@
+//! Saturation metrics for [`tower::limit::ConcurrencyLimitLayer`].
+//!
+//! [`ConcurrencyLimitMetricsLayer`] applies the same [`ConcurrencyLimit`] middleware as tower,
+//! but holds onto the shared semaphore so the fraction of the limit currently in use
+//! can be reported through an OTEL observable gauge.
+//!
+//! [`tower::limit::ConcurrencyLimitLayer`]: tower::limit::ConcurrencyLimitLayer
+
+use std::fmt;
+use std::sync::Arc;
+
+use opentelemetry::metrics::{Meter, ObservableGauge};
+use tokio::sync::Semaphore;
+use tower::limit::ConcurrencyLimit;
+use tower_layer::Layer;
+
+const HTTP_SERVER_CONCURRENCY_LIMIT_SATURATION_METRIC: &str =
+    "http.server.concurrency_limit.saturation";
+const HTTP_SERVER_CONCURRENCY_LIMIT_SATURATION_UNIT: &str = "1";
+
+#[derive(Clone)]
+/// [`Layer`] which applies a [`ConcurrencyLimit`] and reports its saturation (in-flight / limit)
+pub struct ConcurrencyLimitMetricsLayer {
+    semaphore: Arc<Semaphore>,
+    _saturation: ObservableGauge<f64>,
+}
+
+impl ConcurrencyLimitMetricsLayer {
+    /// Create a concurrency limit of `max` in-flight requests,
+    /// registering the `http.server.concurrency_limit.saturation` gauge with the given meter.
+    pub fn new(meter: &Meter, max: usize) -> Self {
+        let semaphore = Arc::new(Semaphore::new(max));
+
+        let observed_semaphore = semaphore.clone();
+        let saturation_gauge = meter
+            .f64_observable_gauge(HTTP_SERVER_CONCURRENCY_LIMIT_SATURATION_METRIC)
+            .with_description("Fraction of the concurrency limit currently in use.")
+            .with_unit(HTTP_SERVER_CONCURRENCY_LIMIT_SATURATION_UNIT)
+            .with_callback(move |observer| {
+                observer.observe(saturation(max, observed_semaphore.available_permits()), &[]);
+            })
+            .build();
+
+        ConcurrencyLimitMetricsLayer {
+            semaphore,
+            _saturation: saturation_gauge,
+        }
+    }
+}
+
+impl fmt::Debug for ConcurrencyLimitMetricsLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrencyLimitMetricsLayer")
+            .field("semaphore", &self.semaphore)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for ConcurrencyLimitMetricsLayer {
+    type Service = ConcurrencyLimit<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        ConcurrencyLimit::with_semaphore(service, self.semaphore.clone())
+    }
+}
+
+fn saturation(max: usize, available: usize) -> f64 {
+    if max == 0 {
+        // a limit of zero admits nothing and is always saturated
+        return 1.0;
+    }
+    max.saturating_sub(available) as f64 / max as f64
+}
@@ -0,0 +1,141 @@
+//! [`Load`] of metered services, for balancers such as [`tower::balance::p2c`].
+//!
+//! Balancers pick the least loaded of their services, measured by wrappers such as
+//! [`tower::load::PendingRequests`] or [`tower::load::PeakEwma`] which track the requests of each
+//! service on their own. A service wrapped by the layer already tracks its requests, so with a
+//! [`LoadMeasure`] configured it implements [`Load`] itself, without another wrapper.
+//!
+//! The load is measured per service returned by the layer, shared with its clones, so each
+//! endpoint of a balancer is wrapped by the layer separately.
+//!
+//! [`tower::balance::p2c`]: https://docs.rs/tower/latest/tower/balance/p2c/index.html
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tower::load::Load;
+
+use crate::HTTPMetricsService;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// How the [`Load`] of the layer's services is measured,
+/// set with [`HTTPMetricsLayerBuilder::with_load_measure`].
+///
+/// [`HTTPMetricsLayerBuilder::with_load_measure`]: crate::HTTPMetricsLayerBuilder::with_load_measure
+pub enum LoadMeasure {
+    /// The number of requests in flight on the service, as with
+    /// [`tower::load::PendingRequests`].
+    #[default]
+    ActiveRequests,
+    /// The moving average of the request durations of the service, in seconds, weighted by the
+    /// requests in flight on it, as with [`tower::load::PeakEwma`].
+    ///
+    /// Older durations weigh less as time passes, decaying over `decay`; the average is seeded by
+    /// the first completed request.
+    LatencyEwma {
+        /// Period over which the weight of a duration decays to about a third.
+        decay: Duration,
+    },
+}
+
+/// Load of a service and its clones.
+pub(crate) struct ServiceLoad {
+    measure: LoadMeasure,
+    pending_requests: AtomicU64,
+    // the average in seconds and the time it was last updated
+    latency_ewma: Mutex<Option<(f64, Instant)>>,
+}
+
+/// Request in flight on a service, counted towards its load until dropped.
+pub(crate) struct PendingRequest {
+    load: Arc<ServiceLoad>,
+    start: Instant,
+}
+
+impl ServiceLoad {
+    pub(crate) fn new(measure: LoadMeasure) -> Arc<Self> {
+        Arc::new(ServiceLoad {
+            measure,
+            pending_requests: AtomicU64::new(0),
+            latency_ewma: Mutex::new(None),
+        })
+    }
+
+    /// Count a request in flight, until the returned guard is dropped.
+    pub(crate) fn start(self: &Arc<Self>) -> PendingRequest {
+        self.pending_requests.fetch_add(1, Ordering::Relaxed);
+        PendingRequest {
+            load: self.clone(),
+            start: Instant::now(),
+        }
+    }
+
+    fn load(&self) -> f64 {
+        let pending_requests = self.pending_requests.load(Ordering::Relaxed) as f64;
+        match self.measure {
+            LoadMeasure::ActiveRequests => pending_requests,
+            LoadMeasure::LatencyEwma { decay } => {
+                let latency_ewma = self
+                    .latency_ewma
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner());
+                match *latency_ewma {
+                    // decayed towards zero while no requests complete, so idle services get tried
+                    Some((average, updated)) => {
+                        average * decay_weight(updated.elapsed(), decay) * (pending_requests + 1.0)
+                    }
+                    None => 0.0,
+                }
+            }
+        }
+    }
+}
+
+impl PendingRequest {
+    /// Mark the request as completed, updating the moving average with its duration.
+    pub(crate) fn complete(self) {
+        let LoadMeasure::LatencyEwma { decay } = self.load.measure else {
+            return;
+        };
+        let now = Instant::now();
+        let duration = now.saturating_duration_since(self.start).as_secs_f64();
+        let mut latency_ewma = self
+            .load
+            .latency_ewma
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        let average = match *latency_ewma {
+            Some((average, updated)) => {
+                let weight = decay_weight(now.saturating_duration_since(updated), decay);
+                average * weight + duration * (1.0 - weight)
+            }
+            None => duration,
+        };
+        *latency_ewma = Some((average, now));
+    }
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        self.load.pending_requests.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Weight kept by an average last updated `elapsed` ago.
+fn decay_weight(elapsed: Duration, decay: Duration) -> f64 {
+    if decay.is_zero() {
+        return 0.0;
+    }
+    (-elapsed.as_secs_f64() / decay.as_secs_f64()).exp()
+}
+
+impl<S> Load for HTTPMetricsService<S> {
+    type Metric = f64;
+
+    /// The load of the service per its [`LoadMeasure`], or `0.0` without one.
+    fn load(&self) -> Self::Metric {
+        self.load.as_ref().map_or(0.0, |load| load.load())
+    }
+}
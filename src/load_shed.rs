@@ -0,0 +1,222 @@
+//! Rejection metrics for [`tower::load_shed`] and similar admission-control layers.
+//!
+//! Requests rejected by load shedding never reach the handler, so the HTTP server metrics
+//! only see them as generic errors (or as whatever response an error handler maps them to).
+//! [`RejectedRequestsLayer`] sits directly outside the rejecting layer and counts each
+//! rejection under `http.server.rejected_requests` with the reason it was rejected.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::result;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures_util::ready;
+use opentelemetry::metrics::{Counter, Meter};
+use opentelemetry::{KeyValue, StringValue};
+use pin_project_lite::pin_project;
+use tower::load_shed::error::Overloaded;
+use tower::BoxError;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::labels::{method_value, scheme_value};
+use crate::record::common_http_server_labels;
+
+const HTTP_SERVER_REJECTED_REQUESTS_METRIC: &str = "http.server.rejected_requests";
+const HTTP_SERVER_REJECTED_REQUESTS_UNIT: &str = "{request}";
+
+const REJECTION_REASON_LABEL: &str = "rejection.reason";
+
+const REJECTION_REASON_OVERLOADED: &str = "overloaded";
+
+type RejectionClassifier = dyn Fn(&BoxError) -> Option<&'static str> + Send + Sync;
+
+#[derive(Clone)]
+struct RejectedRequestsLayerState {
+    rejected_requests: Counter<u64>,
+    classifier: Option<Arc<RejectionClassifier>>,
+    default_url_scheme: StringValue,
+}
+
+#[derive(Clone)]
+/// [`Layer`] which counts requests rejected by the layers it wraps
+pub struct RejectedRequestsLayer {
+    state: Arc<RejectedRequestsLayerState>,
+}
+
+#[derive(Clone)]
+/// [`Service`] used by [`RejectedRequestsLayer`]
+pub struct RejectedRequestsService<S> {
+    state: Arc<RejectedRequestsLayerState>,
+    inner_service: S,
+}
+
+impl RejectedRequestsLayer {
+    /// Create the layer, registering the `http.server.rejected_requests` counter with the given meter.
+    ///
+    /// [`Overloaded`] errors from [`tower::load_shed`] are counted with reason `overloaded`;
+    /// any other error is passed through without being counted.
+    pub fn new(meter: &Meter) -> Self {
+        RejectedRequestsLayer {
+            state: Arc::new(RejectedRequestsLayerState {
+                rejected_requests: meter
+                    .u64_counter(HTTP_SERVER_REJECTED_REQUESTS_METRIC)
+                    .with_description(
+                        "Number of HTTP server requests rejected before reaching the handler.",
+                    )
+                    .with_unit(HTTP_SERVER_REJECTED_REQUESTS_UNIT)
+                    .build(),
+                classifier: None,
+                default_url_scheme: StringValue::from(""),
+            }),
+        }
+    }
+
+    /// Classify additional errors as rejections, e.g. from custom rate limiters or timeouts.
+    ///
+    /// The classifier returns the reason to record for errors which are rejections,
+    /// and `None` for errors which are not. It is consulted only for errors which are
+    /// not already recognized as [`Overloaded`].
+    pub fn with_classifier<F>(self, classifier: F) -> Self
+    where
+        F: Fn(&BoxError) -> Option<&'static str> + Send + Sync + 'static,
+    {
+        RejectedRequestsLayer {
+            state: Arc::new(RejectedRequestsLayerState {
+                classifier: Some(Arc::new(classifier)),
+                ..(*self.state).clone()
+            }),
+        }
+    }
+
+    /// Set the `url.scheme` recorded for requests whose URI has no scheme.
+    ///
+    /// Set it to the scheme given to [`HTTPMetricsLayerBuilder::with_default_url_scheme`], so
+    /// rejections line up with the server metrics of the same listener. Defaults to an empty
+    /// scheme, as there.
+    ///
+    /// [`HTTPMetricsLayerBuilder::with_default_url_scheme`]: crate::HTTPMetricsLayerBuilder::with_default_url_scheme
+    pub fn with_default_url_scheme(self, scheme: impl Into<Cow<'static, str>>) -> Self {
+        let default_url_scheme = match scheme.into() {
+            Cow::Borrowed(scheme) => StringValue::from(scheme),
+            Cow::Owned(scheme) => StringValue::from(scheme),
+        };
+        RejectedRequestsLayer {
+            state: Arc::new(RejectedRequestsLayerState {
+                default_url_scheme,
+                ..(*self.state).clone()
+            }),
+        }
+    }
+}
+
+impl fmt::Debug for RejectedRequestsLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RejectedRequestsLayer")
+            .field("classifier", &self.state.classifier.is_some())
+            .field("default_url_scheme", &self.state.default_url_scheme)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for RejectedRequestsService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RejectedRequestsService")
+            .field("inner_service", &self.inner_service)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for RejectedRequestsLayer {
+    type Service = RejectedRequestsService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        RejectedRequestsService {
+            state: self.state.clone(),
+            inner_service: service,
+        }
+    }
+}
+
+pin_project! {
+    /// Response [`Future`] for [`RejectedRequestsService`].
+    pub struct RejectedRequestsResponseFuture<F> {
+        #[pin]
+        inner_response_future: F,
+        layer_state: Arc<RejectedRequestsLayerState>,
+        http_request_method: StringValue,
+        url_scheme: StringValue,
+    }
+}
+
+impl<F> fmt::Debug for RejectedRequestsResponseFuture<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RejectedRequestsResponseFuture")
+            .field("http_request_method", &self.http_request_method)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for RejectedRequestsService<S>
+where
+    S: Service<http::Request<ReqBody>>,
+    S::Error: Into<BoxError>,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = RejectedRequestsResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_service.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let method = method_value(req.method());
+        let scheme = scheme_value(req.uri(), &self.state.default_url_scheme);
+
+        RejectedRequestsResponseFuture {
+            inner_response_future: self.inner_service.call(req),
+            layer_state: self.state.clone(),
+            http_request_method: method,
+            url_scheme: scheme,
+        }
+    }
+}
+
+impl<F, Res, E> Future for RejectedRequestsResponseFuture<F>
+where
+    F: Future<Output = result::Result<Res, E>>,
+    E: Into<BoxError>,
+{
+    type Output = result::Result<Res, BoxError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let err: BoxError = match ready!(this.inner_response_future.poll(cx)) {
+            Ok(response) => return Poll::Ready(Ok(response)),
+            Err(err) => err.into(),
+        };
+
+        if let Some(reason) = classify_rejection(this.layer_state, &err) {
+            let mut labels =
+                common_http_server_labels(this.http_request_method, Some(this.url_scheme));
+            labels.push(KeyValue::new(REJECTION_REASON_LABEL, reason));
+            this.layer_state.rejected_requests.add(1, &labels);
+        }
+
+        Poll::Ready(Err(err))
+    }
+}
+
+fn classify_rejection(state: &RejectedRequestsLayerState, err: &BoxError) -> Option<&'static str> {
+    if err.is::<Overloaded>() {
+        return Some(REJECTION_REASON_OVERLOADED);
+    }
+    state
+        .classifier
+        .as_ref()
+        .and_then(|classifier| classifier(err))
+}
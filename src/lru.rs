@@ -0,0 +1,164 @@
+//! Memory-bounded caches of per-route state.
+//!
+//! Anything the layer keeps per route or per attribute value must be bounded, since those values
+//! ultimately come from requests. Caches evict their least recently used entry when full and
+//! count evictions so operators can size them.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use opentelemetry::metrics::Counter;
+use opentelemetry::StringValue;
+
+/// Least recently used cache of at most `capacity` entries.
+///
+/// Entries are kept in a doubly linked list threaded through a `Vec`, most recently used first,
+/// so lookups, insertions and evictions are all constant time.
+pub(crate) struct LruCache<K, V> {
+    capacity: usize,
+    indices: HashMap<K, usize>,
+    entries: Vec<LruEntry<K, V>>,
+    // most recently used entry
+    head: Option<usize>,
+    // least recently used entry
+    tail: Option<usize>,
+}
+
+struct LruEntry<K, V> {
+    key: K,
+    value: V,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    /// Create a cache of at most `capacity` entries, which must not be zero.
+    pub(crate) fn new(capacity: usize) -> Self {
+        debug_assert!(capacity > 0, "LRU cache capacity must not be zero");
+        LruCache {
+            capacity,
+            indices: HashMap::new(),
+            entries: Vec::new(),
+            head: None,
+            tail: None,
+        }
+    }
+
+    /// Get the value of `key`, marking it most recently used, or insert the entry made by `make`.
+    ///
+    /// Inserting into a full cache evicts its least recently used entry, in which case
+    /// `true` is returned along with the value.
+    pub(crate) fn get_or_insert_with<Q, F>(&mut self, key: &Q, make: F) -> (&V, bool)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce() -> (K, V),
+    {
+        if let Some(&index) = self.indices.get(key) {
+            self.unlink(index);
+            self.push_front(index);
+            return (&self.entries[index].value, false);
+        }
+
+        let (key, value) = make();
+        let mut evicted = false;
+        let index = match self.tail {
+            Some(lru) if self.entries.len() >= self.capacity => {
+                // reuse the slot of the least recently used entry
+                self.unlink(lru);
+                self.indices.remove::<K>(&self.entries[lru].key);
+                self.entries[lru].key = key.clone();
+                self.entries[lru].value = value;
+                evicted = true;
+                lru
+            }
+            _ => {
+                self.entries.push(LruEntry {
+                    key: key.clone(),
+                    value,
+                    prev: None,
+                    next: None,
+                });
+                self.entries.len() - 1
+            }
+        };
+        self.indices.insert(key, index);
+        self.push_front(index);
+        (&self.entries[index].value, evicted)
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let (prev, next) = (self.entries[index].prev, self.entries[index].next);
+        match prev {
+            Some(prev) => self.entries[prev].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => self.entries[next].prev = prev,
+            None => self.tail = prev,
+        }
+    }
+
+    fn push_front(&mut self, index: usize) {
+        self.entries[index].prev = None;
+        self.entries[index].next = self.head;
+        match self.head {
+            Some(head) => self.entries[head].prev = Some(index),
+            None => self.tail = Some(index),
+        }
+        self.head = Some(index);
+    }
+}
+
+/// Interned `http.route` values, so requests to known routes share the attribute value instead of
+/// allocating it; evictions are counted in `http.server.route_cache.evictions`.
+pub(crate) struct RouteCache {
+    routes: Mutex<LruCache<Arc<str>, StringValue>>,
+    evictions: Counter<u64>,
+}
+
+impl RouteCache {
+    pub(crate) fn new(capacity: usize, evictions: Counter<u64>) -> Self {
+        RouteCache {
+            routes: Mutex::new(LruCache::new(capacity)),
+            evictions,
+        }
+    }
+
+    /// Number of interned routes.
+    #[cfg(feature = "diagnostics")]
+    pub(crate) fn len(&self) -> usize {
+        self.routes
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .entries
+            .len()
+    }
+
+    /// Maximum number of interned routes.
+    #[cfg(feature = "diagnostics")]
+    pub(crate) fn capacity(&self) -> usize {
+        self.routes
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .capacity
+    }
+
+    /// Get the shared attribute value of `route`.
+    pub(crate) fn intern(&self, route: &str) -> StringValue {
+        let mut routes = self.routes.lock().unwrap_or_else(|err| err.into_inner());
+        let (value, evicted) = routes.get_or_insert_with(route, || {
+            let route = Arc::<str>::from(route);
+            (route.clone(), StringValue::from(route))
+        });
+        let value = value.clone();
+        drop(routes);
+
+        if evicted {
+            self.evictions.add(1, &[]);
+        }
+        value
+    }
+}
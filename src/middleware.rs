@@ -0,0 +1,49 @@
+//! Function middleware for axum's `from_fn_with_state`.
+//!
+//! Services built around [`axum::middleware::from_fn`] can record metrics with [`middleware`]
+//! instead of applying the layer itself. The function wraps axum's [`Next`] in the same
+//! [`HTTPMetricsService`] the layer produces, so instruments, route resolution and extractors
+//! are shared with every other use of the [`HTTPMetricsLayer`] it is given as state.
+//!
+//! [`HTTPMetricsService`]: crate::HTTPMetricsService
+
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::HTTPMetricsLayer;
+
+/// Record the metrics of a request, for use with [`axum::middleware::from_fn_with_state`].
+///
+/// ```
+/// use axum::routing::get;
+/// use axum::{middleware, Router};
+/// use opentelemetry::global;
+/// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+///
+/// let metrics = HTTPMetricsLayerBuilder::new()
+///     .with_meter(global::meter("my-service"))
+///     .build()
+///     .unwrap();
+///
+/// let app: Router = Router::new()
+///     .route("/", get(|| async { "hello" }))
+///     .layer(middleware::from_fn_with_state(
+///         metrics,
+///         tower_otel_http_metrics::middleware,
+///     ));
+/// ```
+pub async fn middleware(
+    State(layer): State<HTTPMetricsLayer>,
+    req: Request,
+    next: Next,
+) -> Response {
+    // Next is always ready, and takes the request and the rest of the stack only once
+    match layer.layer(next).call(req).await {
+        Ok(res) => res.map(Body::new),
+        Err(never) => match never {},
+    }
+}
@@ -0,0 +1,311 @@
+//! Naming conventions other than OTEL semconv, for backends which expect their own names.
+//!
+//! Instruments of the layer are created through a meter which renames them, and which renames the
+//! attribute keys of every measurement they record. Renaming attributes takes an allocation per
+//! measurement, which the default OTEL semconv naming avoids by not wrapping the meter at all.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use opentelemetry::metrics::{
+    AsyncInstrument, AsyncInstrumentBuilder, Counter, Gauge, Histogram, HistogramBuilder,
+    InstrumentBuilder, InstrumentProvider, Meter, ObservableCounter, ObservableGauge,
+    ObservableUpDownCounter, SyncInstrument, UpDownCounter,
+};
+use opentelemetry::{KeyValue, Value};
+
+use crate::{HTTP_REQUEST_METHOD_LABEL, HTTP_ROUTE_LABEL};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// Naming of the metrics and attributes recorded by the layer.
+///
+/// Set with [`HTTPMetricsLayerBuilder::with_naming_convention`]. Names without an equivalent in a
+/// convention keep their OTEL semconv name, as do custom instruments and extracted attributes.
+///
+/// [`HTTPMetricsLayerBuilder::with_naming_convention`]: crate::HTTPMetricsLayerBuilder::with_naming_convention
+pub enum NamingConvention {
+    /// OTEL HTTP semantic conventions, the default.
+    #[default]
+    OpenTelemetry,
+    /// Elastic Common Schema field names.
+    ///
+    /// ECS and OTEL semconv share most HTTP fields. Protocol, geolocation and tenant attributes are
+    /// renamed to their ECS fields (`http.version`, `client.geo.country_iso_code`,
+    /// `organization.id`, ...), body sizes are named in bytes (`http.server.request.body.bytes`),
+    /// and `http.route` is recorded as the Elastic APM `transaction.name`, e.g. `GET /users/:id`.
+    ElasticCommonSchema,
+    /// Datadog HTTP metric and tag names, for exporting to Datadog through OTLP.
+    ///
+    /// `http.server.request.duration` is recorded as `trace.http.request.duration` with the tags of
+    /// Datadog's web integrations (`http.method`, `http.status_code`, `http.version`, ...), and
+    /// `http.route` as the `resource_name` tag, e.g. `GET /users/:id`.
+    Datadog,
+    /// Metric and label names of the `axum-prometheus` crate, for migrating off it while keeping
+    /// its dashboards and alerts working.
+    ///
+    /// Requests are counted in `axum_http_requests_total` alongside their duration in
+    /// `axum_http_requests_duration_seconds`, and `http.server.active_requests` is recorded as
+    /// `axum_http_requests_pending`. These carry the `method`, `endpoint` and `status` labels only;
+    /// other attributes, including extracted ones, are not recorded. `axum-prometheus` labels
+    /// pending requests by endpoint as well, which the layer does not know before routing.
+    AxumPrometheus,
+}
+
+impl NamingConvention {
+    /// The name of the instrument `name` in this convention, when it differs from semconv.
+    fn metric_name(self, name: &str) -> Option<&'static str> {
+        match (self, name) {
+            (NamingConvention::ElasticCommonSchema, "http.server.request.body.size") => {
+                Some("http.server.request.body.bytes")
+            }
+            (NamingConvention::ElasticCommonSchema, "http.server.response.body.size") => {
+                Some("http.server.response.body.bytes")
+            }
+            (NamingConvention::Datadog, "http.server.request.duration") => {
+                Some("trace.http.request.duration")
+            }
+            (NamingConvention::AxumPrometheus, "http.server.request.duration") => {
+                Some("axum_http_requests_duration_seconds")
+            }
+            (NamingConvention::AxumPrometheus, "http.server.active_requests") => {
+                Some("axum_http_requests_pending")
+            }
+            _ => None,
+        }
+    }
+
+    /// The key of the attribute `key` in this convention, when it differs from semconv.
+    fn attribute_key(self, key: &str) -> Option<&'static str> {
+        match (self, key) {
+            (NamingConvention::ElasticCommonSchema, "network.protocol.name") => {
+                Some("network.protocol")
+            }
+            (NamingConvention::ElasticCommonSchema, "network.protocol.version") => {
+                Some("http.version")
+            }
+            (NamingConvention::ElasticCommonSchema, "geo.country.iso_code") => {
+                Some("client.geo.country_iso_code")
+            }
+            (NamingConvention::ElasticCommonSchema, "geo.region.iso_code") => {
+                Some("client.geo.region_iso_code")
+            }
+            (NamingConvention::ElasticCommonSchema, "tenant.id") => Some("organization.id"),
+            (NamingConvention::Datadog, "http.request.method") => Some("http.method"),
+            (NamingConvention::Datadog, "http.response.status_code") => Some("http.status_code"),
+            (NamingConvention::Datadog, "network.protocol.version") => Some("http.version"),
+            (NamingConvention::Datadog, "url.scheme") => Some("http.url_details.scheme"),
+            (NamingConvention::Datadog, "geo.country.iso_code") => {
+                Some("network.client.geoip.country.iso_code")
+            }
+            (NamingConvention::Datadog, "geo.region.iso_code") => {
+                Some("network.client.geoip.subdivision.iso_code")
+            }
+            (NamingConvention::AxumPrometheus, "http.request.method") => Some("method"),
+            (NamingConvention::AxumPrometheus, "http.route") => Some("endpoint"),
+            (NamingConvention::AxumPrometheus, "http.response.status_code") => Some("status"),
+            _ => None,
+        }
+    }
+
+    /// Whether attributes without an equivalent in this convention are recorded as they are.
+    fn keeps_unknown_attributes(self) -> bool {
+        self != NamingConvention::AxumPrometheus
+    }
+
+    /// Name of a counter of the measurements of the instrument `name`, if the convention has one.
+    fn measurement_counter(self, name: &str) -> Option<&'static str> {
+        match (self, name) {
+            (NamingConvention::AxumPrometheus, "http.server.request.duration") => {
+                Some("axum_http_requests_total")
+            }
+            _ => None,
+        }
+    }
+
+    /// The key recording `http.route` prefixed with the request method, if any.
+    fn method_route_key(self) -> Option<&'static str> {
+        match self {
+            NamingConvention::ElasticCommonSchema => Some("transaction.name"),
+            NamingConvention::Datadog => Some("resource_name"),
+            NamingConvention::OpenTelemetry | NamingConvention::AxumPrometheus => None,
+        }
+    }
+
+    /// The attributes of a measurement, renamed to this convention.
+    fn rename(self, attributes: &[KeyValue]) -> Vec<KeyValue> {
+        let method_route_key = self.method_route_key();
+        let method = attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == HTTP_REQUEST_METHOD_LABEL)
+            .map(|kv| &kv.value);
+        attributes
+            .iter()
+            .filter_map(|kv| match (kv.key.as_str(), method_route_key) {
+                (HTTP_ROUTE_LABEL, Some(key)) => {
+                    let value = match method {
+                        Some(method) => {
+                            Value::from(format!("{} {}", method.as_str(), kv.value.as_str()))
+                        }
+                        None => kv.value.clone(),
+                    };
+                    Some(KeyValue::new(key, value))
+                }
+                (key, _) => match self.attribute_key(key) {
+                    Some(key) => Some(KeyValue::new(key, kv.value.clone())),
+                    None => self.keeps_unknown_attributes().then(|| kv.clone()),
+                },
+            })
+            .collect()
+    }
+
+    /// The key an attribute of the layer is recorded under in this convention.
+    #[cfg_attr(not(feature = "dashboard"), allow(dead_code))]
+    pub(crate) fn key(self, key: &'static str) -> &'static str {
+        match self.method_route_key() {
+            Some(method_route_key) if key == HTTP_ROUTE_LABEL => method_route_key,
+            _ => self.attribute_key(key).unwrap_or(key),
+        }
+    }
+
+    fn rename_metric(self, name: Cow<'static, str>) -> Cow<'static, str> {
+        self.metric_name(&name).map_or(name, Cow::Borrowed)
+    }
+}
+
+/// Create a meter which records the instruments of `meter` under the names of `convention`.
+pub(crate) fn named_meter(meter: Meter, convention: NamingConvention) -> Meter {
+    match convention {
+        NamingConvention::OpenTelemetry => meter,
+        convention => Meter::new(Arc::new(NamingInstrumentProvider { meter, convention })),
+    }
+}
+
+struct NamingInstrumentProvider {
+    meter: Meter,
+    convention: NamingConvention,
+}
+
+/// Sync instrument recording measurements with renamed attributes.
+struct Renamed<I> {
+    instrument: I,
+    /// Counter of the measurements, for conventions which count them separately
+    count: Option<Counter<u64>>,
+    convention: NamingConvention,
+}
+
+/// Observer of an async instrument, observing with renamed attributes.
+struct RenamedObserver<'a, T> {
+    observer: &'a dyn AsyncInstrument<T>,
+    convention: NamingConvention,
+}
+
+impl<T> AsyncInstrument<T> for RenamedObserver<'_, T> {
+    fn observe(&self, measurement: T, attributes: &[KeyValue]) {
+        self.observer
+            .observe(measurement, &self.convention.rename(attributes));
+    }
+}
+
+macro_rules! sync_instruments {
+    ($($method:ident: $builder:ident<$inst:ident<$value:ty>>, $record:ident;)*) => {
+        $(
+            impl SyncInstrument<$value> for Renamed<$inst<$value>> {
+                fn measure(&self, measurement: $value, attributes: &[KeyValue]) {
+                    let attributes = self.convention.rename(attributes);
+                    if let Some(count) = &self.count {
+                        count.add(1, &attributes);
+                    }
+                    self.instrument.$record(measurement, &attributes);
+                }
+            }
+        )*
+
+        impl InstrumentProvider for NamingInstrumentProvider {
+            $(
+                fn $method(&self, builder: $builder<'_, $inst<$value>>) -> $inst<$value> {
+                    let mut renamed = self
+                        .meter
+                        .$method(self.convention.rename_metric(builder.name.clone()));
+                    if let Some(description) = builder.description.clone() {
+                        renamed = renamed.with_description(description);
+                    }
+                    if let Some(unit) = builder.unit.clone() {
+                        renamed = renamed.with_unit(unit);
+                    }
+                    let instrument = sync_instruments!(@boundaries renamed, builder, $builder).build();
+                    let count = self
+                        .convention
+                        .measurement_counter(&builder.name)
+                        .map(|name| self.meter.u64_counter(name).build());
+                    $inst::new(Arc::new(Renamed {
+                        instrument,
+                        count,
+                        convention: self.convention,
+                    }))
+                }
+            )*
+
+            async_instruments! {
+                u64_observable_counter: ObservableCounter<u64>;
+                f64_observable_counter: ObservableCounter<f64>;
+                i64_observable_up_down_counter: ObservableUpDownCounter<i64>;
+                f64_observable_up_down_counter: ObservableUpDownCounter<f64>;
+                u64_observable_gauge: ObservableGauge<u64>;
+                i64_observable_gauge: ObservableGauge<i64>;
+                f64_observable_gauge: ObservableGauge<f64>;
+            }
+        }
+    };
+    (@boundaries $renamed:ident, $builder:ident, HistogramBuilder) => {
+        match $builder.boundaries.clone() {
+            Some(boundaries) => $renamed.with_boundaries(boundaries),
+            None => $renamed,
+        }
+    };
+    (@boundaries $renamed:ident, $builder:ident, InstrumentBuilder) => {
+        $renamed
+    };
+}
+
+macro_rules! async_instruments {
+    ($($method:ident: $inst:ident<$value:ty>;)*) => {
+        $(
+            fn $method(
+                &self,
+                builder: AsyncInstrumentBuilder<'_, $inst<$value>, $value>,
+            ) -> $inst<$value> {
+                let callbacks = builder.callbacks;
+                let convention = self.convention;
+                let mut renamed = self
+                    .meter
+                    .$method(convention.rename_metric(builder.name))
+                    .with_callback(move |observer: &dyn AsyncInstrument<$value>| {
+                        let observer = RenamedObserver { observer, convention };
+                        for callback in &callbacks {
+                            callback(&observer);
+                        }
+                    });
+                if let Some(description) = builder.description {
+                    renamed = renamed.with_description(description);
+                }
+                if let Some(unit) = builder.unit {
+                    renamed = renamed.with_unit(unit);
+                }
+                renamed.build()
+            }
+        )*
+    };
+}
+
+sync_instruments! {
+    u64_counter: InstrumentBuilder<Counter<u64>>, add;
+    f64_counter: InstrumentBuilder<Counter<f64>>, add;
+    i64_up_down_counter: InstrumentBuilder<UpDownCounter<i64>>, add;
+    f64_up_down_counter: InstrumentBuilder<UpDownCounter<f64>>, add;
+    u64_gauge: InstrumentBuilder<Gauge<u64>>, record;
+    f64_gauge: InstrumentBuilder<Gauge<f64>>, record;
+    i64_gauge: InstrumentBuilder<Gauge<i64>>, record;
+    f64_histogram: HistogramBuilder<Histogram<f64>>, record;
+    u64_histogram: HistogramBuilder<Histogram<u64>>, record;
+}
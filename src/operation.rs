@@ -0,0 +1,65 @@
+//! OpenAPI operation ids of requests, recorded as the `openapi.operation_id` attribute.
+//!
+//! Services generating their OpenAPI document, e.g. with aide or utoipa, name each operation; the
+//! operation id lines metrics up with tooling driven by the API spec. Operation ids are given up
+//! front for each method and route, or by handlers through the [`OperationId`] response extension.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use opentelemetry::StringValue;
+
+use crate::route::static_route_value;
+
+pub(crate) const OPENAPI_OPERATION_ID_LABEL: &str = "openapi.operation_id";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Response extension naming the OpenAPI operation which handled a request.
+///
+/// Recorded as the `openapi.operation_id` attribute of `http.server.request.duration`, taking
+/// precedence over the operation ids registered with [`with_operation_id`].
+///
+/// ```
+/// use tower_otel_http_metrics::OperationId;
+///
+/// let mut response = http::Response::new(());
+/// response.extensions_mut().insert(OperationId::new("getUser"));
+/// ```
+///
+/// [`with_operation_id`]: crate::HTTPMetricsLayerBuilder::with_operation_id
+pub struct OperationId(pub Cow<'static, str>);
+
+impl OperationId {
+    /// Name the operation which handled the request.
+    pub fn new(operation_id: impl Into<Cow<'static, str>>) -> Self {
+        OperationId(operation_id.into())
+    }
+}
+
+/// Operation ids of the methods of each route.
+pub(crate) struct OperationIds {
+    routes: HashMap<Cow<'static, str>, Vec<(http::Method, StringValue)>>,
+}
+
+impl OperationIds {
+    pub(crate) fn new(operations: &[(http::Method, Cow<'static, str>, Cow<'static, str>)]) -> Self {
+        let mut routes: HashMap<Cow<'static, str>, Vec<(http::Method, StringValue)>> =
+            HashMap::new();
+        for (method, route, operation_id) in operations {
+            routes
+                .entry(route.clone())
+                .or_default()
+                .push((method.clone(), static_route_value(operation_id.clone())));
+        }
+        OperationIds { routes }
+    }
+
+    /// The operation id of a method and route, if registered.
+    pub(crate) fn get(&self, method: &str, route: &str) -> Option<&StringValue> {
+        self.routes
+            .get(route)?
+            .iter()
+            .find(|(known, _)| known.as_str() == method)
+            .map(|(_, operation_id)| operation_id)
+    }
+}
@@ -0,0 +1,61 @@
+//! Detection of a layer placed where axum never reports the matched route.
+//!
+//! axum inserts the `MatchedPath` of a request once its router has matched a route, so the layer
+//! only sees it when applied with `Router::layer` or `Router::route_layer`. Wrapped around the
+//! router as a whole, e.g. with `ServiceBuilder` before serving, the layer runs before routing and
+//! records every request without an `http.route`, which nothing points out.
+//!
+//! The guard assumes a misplaced layer when the first requests all lack a matched route, and
+//! disarms as soon as one has it, so requests served by the router's fallback later on do not
+//! trip it.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::route::matched_no_route;
+
+pub(crate) type PlacementWarning = dyn Fn(&str) + Send + Sync;
+
+const PLACEMENT_MESSAGE: &str = "HTTPMetricsLayer sees requests without an axum MatchedPath, so \
+     http.route is not recorded; apply it with Router::layer rather than around the router";
+
+pub(crate) struct PlacementGuard {
+    warning: Option<(usize, Arc<PlacementWarning>)>,
+    strict: bool,
+    unrouted: AtomicUsize,
+    disarmed: AtomicBool,
+}
+
+impl PlacementGuard {
+    pub(crate) fn new(warning: Option<(usize, Arc<PlacementWarning>)>, strict: bool) -> Self {
+        PlacementGuard {
+            warning,
+            strict,
+            unrouted: AtomicUsize::new(0),
+            disarmed: AtomicBool::new(false),
+        }
+    }
+
+    /// Check the placement of the layer against a request's extensions.
+    ///
+    /// # Panics
+    ///
+    /// In strict mode, when no request had a matched route yet.
+    pub(crate) fn check(&self, extensions: &http::Extensions) {
+        if self.disarmed.load(Ordering::Relaxed) {
+            return;
+        }
+        if !matched_no_route(extensions) {
+            self.disarmed.store(true, Ordering::Relaxed);
+            return;
+        }
+        assert!(!self.strict, "{PLACEMENT_MESSAGE}");
+        if let Some((requests, warn)) = &self.warning {
+            let unrouted = self.unrouted.fetch_add(1, Ordering::Relaxed) + 1;
+            if unrouted == *requests {
+                self.disarmed.store(true, Ordering::Relaxed);
+                warn(PLACEMENT_MESSAGE);
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Pool of attribute buffers reused across requests.
+//!
+//! Opt-in attributes and extractors collect the attributes of a request into a buffer which lives
+//! as long as the request. Rather than allocating one per request, buffers are taken from a
+//! per-thread pool and returned to the pool of whichever thread finishes the request. Requests
+//! without opt-in attributes never grow their buffer, and empty buffers are not pooled, so the
+//! default request path neither allocates nor touches the pool beyond an empty lookup.
+
+use std::cell::RefCell;
+
+use opentelemetry::KeyValue;
+
+/// Buffers kept per thread; enough to cover the requests in flight on a busy worker thread.
+const MAX_POOLED_BUFFERS: usize = 64;
+
+/// Buffers grown past this capacity, e.g. by a misbehaving extractor, are freed instead of pooled.
+const MAX_POOLED_CAPACITY: usize = 64;
+
+thread_local! {
+    static BUFFERS: RefCell<Vec<Vec<KeyValue>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Take an empty attribute buffer, reusing a pooled one when available.
+pub(crate) fn take_labels() -> Vec<KeyValue> {
+    BUFFERS
+        .try_with(|buffers| buffers.borrow_mut().pop())
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+/// Return an attribute buffer to the pool of the current thread.
+pub(crate) fn recycle_labels(mut labels: Vec<KeyValue>) {
+    if labels.capacity() == 0 || labels.capacity() > MAX_POOLED_CAPACITY {
+        return;
+    }
+    labels.clear();
+    let _ = BUFFERS.try_with(|buffers| {
+        let mut buffers = buffers.borrow_mut();
+        if buffers.len() < MAX_POOLED_BUFFERS {
+            buffers.push(labels);
+        }
+    });
+}
@@ -0,0 +1,39 @@
+//! Named combinations of builder options, for a sensible configuration in one call.
+//!
+//! A preset sets every option it covers, enabling some and disabling the others, so the builders
+//! of two presets differ exactly in those options; their `Debug` output can be diffed against a
+//! custom configuration to see what it adds or leaves out.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// Combination of options applied with [`HTTPMetricsLayerBuilder::with_preset`].
+///
+/// [`HTTPMetricsLayerBuilder::with_preset`]: crate::HTTPMetricsLayerBuilder::with_preset
+pub enum Preset {
+    /// Only `http.server.request.duration`, recording the `http.response.status_class` (`2xx`,
+    /// `5xx`, ...) instead of the status code, for the fewest series possible.
+    ///
+    /// Disables `http.server.active_requests`, `http.server.request.body.size`,
+    /// `http.server.request.size` and `http.server.response.body.size`, and enables the status
+    /// class.
+    Minimal,
+    /// The OTEL semconv metrics and attributes, as recorded by a builder without any options.
+    ///
+    /// Enables `http.server.active_requests` and `http.server.request.body.size`, and disables
+    /// the status class, `http.server.request.size` and `http.server.response.body.size`.
+    #[default]
+    Standard,
+    /// The standard metrics along with the size metrics, the opt-in semconv
+    /// `http.server.response.body.size` and `http.server.request.size`, the size of the whole
+    /// request, and every opt-in attribute which needs no further configuration.
+    ///
+    /// Enables `http.server.active_requests`, `http.server.request.body.size`,
+    /// `http.server.request.size` and `http.server.response.body.size`, disables the status class,
+    /// and enables the `server.address`, `server.port`, `network.peer.address`, `geo.*`, `faas.*`,
+    /// `rpc.*`, `http.route.fallback`, `http.cache.status`, `http.auth.*`, `user.authenticated`,
+    /// `openapi.operation_id`, `http.server.backend` and `user_agent.device.category` attributes,
+    /// as well as `http.route` on `http.server.active_requests`. Minimal and Standard disable
+    /// them. Attributes needing further configuration, such as query parameters or extractors,
+    /// and metrics needing further layers, are left to their own options.
+    Full,
+}
@@ -0,0 +1,336 @@
+//! Recording of the server instruments, and the attributes they share, for requests which
+//! completed, failed or were abandoned.
+
+use opentelemetry::{KeyValue, StringValue};
+use smallvec::smallvec;
+
+use crate::attributes::{content_length, ContentLength, ERROR_TYPE_LABEL};
+use crate::backend::HTTP_SERVER_BACKEND_LABEL;
+#[cfg(feature = "failure-logs")]
+use crate::failure_log::is_failure;
+use crate::labels::{status_code_attribute, status_code_reason_value, status_code_value, Labels};
+use crate::service::ResponseFutureMetricsState;
+use crate::slo::SLO_VIOLATED_LABEL;
+#[cfg(feature = "span-attributes")]
+use crate::span::record_span_attributes;
+use crate::{
+    HTTPMetricsLayerState, HTTP_REQUEST_METHOD_LABEL, HTTP_RESPONSE_STATUS_CLASS_LABEL,
+    HTTP_RESPONSE_STATUS_CODE_LABEL, HTTP_ROUTE_LABEL, NETWORK_PROTOCOL_NAME_LABEL,
+    NETWORK_PROTOCOL_VERSION_LABEL, URL_SCHEME_LABEL,
+};
+
+/// Record `http.server.request.duration`, clamping durations beyond the configured maximum and
+/// counting requests below the minimum of their route instead,
+/// along with its rollup and the log event of failed requests when enabled.
+pub(crate) fn record_server_request_duration(
+    layer_state: &HTTPMetricsLayerState,
+    metrics_state: &ResponseFutureMetricsState,
+    status: Option<http::StatusCode>,
+    labels: &[KeyValue],
+) {
+    let mut duration = metrics_state.http_request_duration_start.elapsed();
+    if let Some(max_duration) = layer_state.max_request_duration {
+        if duration > max_duration {
+            duration = max_duration;
+            if let Some(overflow) = &layer_state.server_request_duration_overflow {
+                overflow.add(1, labels);
+            }
+        }
+    }
+    let slo_labels: Vec<KeyValue>;
+    let labels = match layer_state
+        .slo_thresholds
+        .as_ref()
+        .and_then(|slo| Some((slo, slo.threshold(metrics_state.http_route.as_ref())?)))
+    {
+        Some((slo, threshold)) => {
+            let violated = duration > threshold;
+            if violated {
+                slo.slow_requests.add(1, labels);
+            }
+            slo_labels = labels
+                .iter()
+                .cloned()
+                .chain([KeyValue::new(SLO_VIOLATED_LABEL, violated)])
+                .collect();
+            &slo_labels
+        }
+        None => labels,
+    };
+    let fast = layer_state
+        .min_recorded_durations
+        .as_ref()
+        .filter(|min| min.is_fast(metrics_state.http_route.as_ref(), duration));
+    match fast {
+        Some(min) => min.fast_requests.add(1, labels),
+        None => layer_state.server_request_duration.record(duration, labels),
+    }
+    #[cfg(feature = "span-attributes")]
+    if let Some(context) = &metrics_state.span_context {
+        record_span_attributes(context, duration, status, labels);
+    }
+    #[cfg(feature = "semconv-validation")]
+    if let Some(validator) = &layer_state.semconv_validator {
+        validator.validate_request_duration(labels);
+    }
+    #[cfg(feature = "cardinality")]
+    if let Some(cardinality) = &layer_state.server_request_duration_cardinality {
+        cardinality.observe(labels);
+    }
+    if let Some(latency_tracker) = &layer_state.latency_tracker {
+        latency_tracker.record(metrics_state.http_route.as_ref(), duration);
+    }
+    if let Some(known_routes) = &layer_state.known_routes {
+        known_routes.increment(
+            &metrics_state.http_request_method,
+            metrics_state.http_route.as_ref(),
+        );
+    }
+
+    if let Some(rollup) = layer_state
+        .server_request_duration_rollup
+        .as_ref()
+        .filter(|_| fast.is_none())
+    {
+        let mut rollup_labels: Vec<KeyValue> = labels
+            .iter()
+            .filter(|label| rollup.keys.contains(&label.key))
+            .cloned()
+            .collect();
+        if let Some(status) = status {
+            rollup_labels.push(KeyValue::new(
+                HTTP_RESPONSE_STATUS_CLASS_LABEL,
+                status_class(status),
+            ));
+        }
+        rollup.histogram.record(duration, &rollup_labels);
+    }
+
+    #[cfg(feature = "failure-logs")]
+    if let Some(failure_logger) = &layer_state.failure_logger {
+        if is_failure(status, labels) {
+            failure_logger.emit_failure(duration, status, labels);
+        }
+    }
+}
+
+/// Content-Length of a response which has a body.
+pub(crate) fn response_content_length(
+    metrics_state: &ResponseFutureMetricsState,
+    parts: &http::response::Parts,
+) -> Option<u64> {
+    let bodiless = metrics_state.http_request_method.as_str() == "HEAD"
+        || parts.status.is_informational()
+        || parts.status == http::StatusCode::NO_CONTENT
+        || parts.status == http::StatusCode::NOT_MODIFIED;
+    match content_length(&parts.headers, None) {
+        ContentLength::Valid(length) if !bodiless => Some(length),
+        _ => None,
+    }
+}
+
+/// Bucket a status code into its class, e.g. `2xx`.
+fn status_class(status: http::StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+/// Record a request which failed or was abandoned before a response was produced,
+/// per semconv recording `error.type` in place of the response status code.
+pub(crate) fn record_server_request_error(
+    layer_state: &HTTPMetricsLayerState,
+    metrics_state: &ResponseFutureMetricsState,
+    error_type: &'static str,
+) {
+    let mut server_request_duration_labels = labels_server_request(metrics_state);
+    server_request_duration_labels.extend(metrics_state.request_labels.iter().cloned());
+    if let Some(backend) = &layer_state.backend {
+        server_request_duration_labels
+            .push(KeyValue::new(HTTP_SERVER_BACKEND_LABEL, backend.clone()));
+    }
+    server_request_duration_labels.push(KeyValue::new(ERROR_TYPE_LABEL, error_type));
+    record_server_request_duration(
+        layer_state,
+        metrics_state,
+        None,
+        &server_request_duration_labels,
+    );
+    release_server_active_request(layer_state, metrics_state);
+}
+
+/// Decrement `http.server.active_requests` for a request which did not produce a response.
+pub(crate) fn release_server_active_request(
+    layer_state: &HTTPMetricsLayerState,
+    metrics_state: &ResponseFutureMetricsState,
+) {
+    if let Some(server_active_requests) = &layer_state.server_active_requests {
+        server_active_requests.add(
+            -1,
+            &labels_server_active_request(
+                layer_state,
+                &metrics_state.http_request_method,
+                metrics_state.url_scheme.as_ref(),
+                metrics_state.http_route.as_ref(),
+            ),
+        );
+    }
+}
+
+pub(crate) fn extract_labels_server_request_duration(
+    layer_state: &HTTPMetricsLayerState,
+    metrics_state: &ResponseFutureMetricsState,
+    status: http::StatusCode,
+) -> Labels {
+    let mut labels: Labels = if layer_state.status_class {
+        smallvec![KeyValue::new(
+            HTTP_RESPONSE_STATUS_CLASS_LABEL,
+            status_class(status)
+        )]
+    } else {
+        smallvec![KeyValue::new(
+            HTTP_RESPONSE_STATUS_CODE_LABEL,
+            status_code_attribute(
+                status,
+                layer_state.string_status_code,
+                layer_state.kept_status_codes.as_deref(),
+                status_code_reason_value
+            ),
+        )]
+    };
+    labels.extend(labels_server_request(metrics_state));
+    labels
+}
+
+/// Labels of `http.server.request.duration` which are known before a response is produced.
+fn labels_server_request(metrics_state: &ResponseFutureMetricsState) -> Labels {
+    let mut labels: Labels = smallvec![
+        KeyValue::new(
+            HTTP_REQUEST_METHOD_LABEL,
+            metrics_state.http_request_method.clone(),
+        ),
+        KeyValue::new(
+            NETWORK_PROTOCOL_NAME_LABEL,
+            metrics_state.network_protocol_name,
+        ),
+        KeyValue::new(
+            NETWORK_PROTOCOL_VERSION_LABEL,
+            metrics_state.network_protocol_version,
+        ),
+    ];
+    if let Some(scheme) = &metrics_state.url_scheme {
+        labels.push(KeyValue::new(URL_SCHEME_LABEL, scheme.clone()));
+    }
+
+    // Conditionally required to add http.route if available
+    if let Some(route) = &metrics_state.http_route {
+        labels.push(KeyValue::new(HTTP_ROUTE_LABEL, route.clone()));
+    }
+    labels
+}
+
+pub(crate) fn labels_server_body_size(
+    layer_state: &HTTPMetricsLayerState,
+    metrics_state: &ResponseFutureMetricsState,
+    status: http::StatusCode,
+) -> Labels {
+    let mut labels = common_http_server_labels(
+        &metrics_state.http_request_method,
+        metrics_state.url_scheme.as_ref(),
+    );
+
+    // Conditionally required to add response status code if sent
+    labels.push(KeyValue::new(
+        HTTP_RESPONSE_STATUS_CODE_LABEL,
+        status_code_attribute(
+            status,
+            layer_state.string_status_code,
+            layer_state.kept_status_codes.as_deref(),
+            status_code_value,
+        ),
+    ));
+
+    // Conditionally required to add http route if available
+    if let Some(route) = &metrics_state.http_route {
+        labels.push(KeyValue::new(HTTP_ROUTE_LABEL, route.clone()));
+    }
+    labels
+}
+
+pub(crate) fn labels_server_route(metrics_state: &ResponseFutureMetricsState) -> Labels {
+    let mut labels: Labels = smallvec![KeyValue::new(
+        HTTP_REQUEST_METHOD_LABEL,
+        metrics_state.http_request_method.clone(),
+    )];
+    if let Some(route) = &metrics_state.http_route {
+        labels.push(KeyValue::new(HTTP_ROUTE_LABEL, route.clone()));
+    }
+    labels
+}
+
+pub(crate) fn labels_server_active_request(
+    layer_state: &HTTPMetricsLayerState,
+    method: &StringValue,
+    scheme: Option<&StringValue>,
+    route: Option<&StringValue>,
+) -> Labels {
+    let mut labels = common_http_server_labels(method, scheme);
+    if let Some(route) = route.filter(|_| layer_state.active_requests_route) {
+        labels.push(KeyValue::new(HTTP_ROUTE_LABEL, route.clone()));
+    }
+    labels
+}
+
+pub(crate) fn common_http_server_labels(
+    method: &StringValue,
+    scheme: Option<&StringValue>,
+) -> Labels {
+    let mut labels: Labels = smallvec![KeyValue::new(HTTP_REQUEST_METHOD_LABEL, method.clone())];
+    // CONNECT tunnels have no scheme
+    if let Some(scheme) = scheme {
+        labels.push(KeyValue::new(URL_SCHEME_LABEL, scheme.clone()));
+    }
+    labels
+}
+
+/// Size of the HTTP/1.1 header section of a request.
+pub(crate) fn request_header_section_size<B>(req: &http::Request<B>) -> u64 {
+    // method SP request-target SP HTTP-version
+    let start_line_size = req.method().as_str().len()
+        + " ".len()
+        + req
+            .uri()
+            .path_and_query()
+            .map_or(1, |path| path.as_str().len())
+        + " HTTP/1.1".len();
+    header_section_size(start_line_size, req.headers())
+}
+
+/// Size of an HTTP/1.1 header section whose start line, without its line ending, has the given
+/// size, including the line endings of the start line and fields and the empty line ending it.
+pub(crate) fn header_section_size(start_line_size: usize, headers: &http::HeaderMap) -> u64 {
+    let fields_size: usize = headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + ": ".len() + value.len() + "\r\n".len())
+        .sum();
+    // the start line and the empty line ending the section each end with CRLF
+    (start_line_size + "\r\n".len() + fields_size + "\r\n".len()) as u64
+}
+
+pub(crate) fn split_and_format_protocol_version(
+    http_version: http::Version,
+) -> (&'static str, &'static str) {
+    let version_str = match http_version {
+        http::Version::HTTP_09 => "0.9",
+        http::Version::HTTP_10 => "1.0",
+        http::Version::HTTP_11 => "1.1",
+        http::Version::HTTP_2 => "2.0",
+        http::Version::HTTP_3 => "3.0",
+        _ => "",
+    };
+    ("http", version_str)
+}
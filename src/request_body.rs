@@ -0,0 +1,449 @@
+//! Request body wrapper used by [`RequestBodyMetricsLayer`] to observe requests as they are read.
+//!
+//! [`HTTPMetricsLayer`] leaves the request body type untouched so that it can be applied with
+//! `axum::Router::layer`, where inner routes require axum's own body type. Metrics which need
+//! to observe the request body are instead applied by [`RequestBodyMetricsLayer`], which wraps
+//! request bodies in [`HTTPMetricsRequestBody`] and belongs around the whole router or service,
+//! e.g. with a `ServiceBuilder` before handing the service to the server.
+//!
+//! When an [`HTTPMetricsLayer`] built from the same builder runs inside it, the `http.route`
+//! it sees and the response status are shared with the request body through a request extension.
+//!
+//! Request bodies without a `Content-Length`, such as chunked uploads, have their size counted
+//! as they are read. Handlers generally consume the body before responding, so the size is held
+//! until the response status is known and then recorded into `http.server.request.body.size` with
+//! its `http.response.status_code`, like sizes taken from the `Content-Length`. Requests which
+//! fail without a response, or are served without an [`HTTPMetricsLayer`] inside, have the size
+//! recorded without a status once the body is dropped.
+//!
+//! [`HTTPMetricsLayer`]: crate::HTTPMetricsLayer
+
+use std::fmt;
+use std::pin::Pin;
+use std::result;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use bytes::Buf;
+use futures_util::ready;
+use http_body::{Body, Frame, SizeHint};
+use opentelemetry::metrics::Histogram;
+use opentelemetry::{KeyValue, StringValue};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::attributes::{content_length, ContentLength};
+use crate::binding::LayerBinding;
+use crate::clock::Instant;
+use crate::grpc::{grpc_labels, GrpcMessageCounter};
+use crate::labels::{method_value, scheme_value, status_code_attribute, status_code_value, Labels};
+use crate::record::common_http_server_labels;
+use crate::{
+    HTTPMetricsLayerState, HTTP_RESPONSE_STATUS_CODE_LABEL, HTTP_ROUTE_LABEL,
+    NETWORK_IO_DIRECTION_LABEL, NETWORK_IO_DIRECTION_RECEIVE,
+};
+
+#[derive(Clone)]
+/// [`Layer`] which applies request body metrics, created with
+/// [`HTTPMetricsLayer::request_body_layer`].
+///
+/// [`HTTPMetricsLayer::request_body_layer`]: crate::HTTPMetricsLayer::request_body_layer
+pub struct RequestBodyMetricsLayer {
+    binding: Arc<LayerBinding>,
+}
+
+#[derive(Clone)]
+/// [`Service`] used by [`RequestBodyMetricsLayer`]
+pub struct RequestBodyMetricsService<S> {
+    state: Arc<HTTPMetricsLayerState>,
+    binding: Arc<LayerBinding>,
+    generation: u64,
+    inner_service: S,
+}
+
+pin_project! {
+    /// Request body for [`RequestBodyMetricsService`].
+    ///
+    /// Frames are passed through unchanged; when request body metrics are enabled
+    /// on the layer, they are observed as they are polled.
+    pub struct HTTPMetricsRequestBody<B> {
+        #[pin]
+        inner_body: B,
+        metrics_state: Option<RequestBodyMetricsState>,
+    }
+}
+
+/// Request extension through which [`HTTPMetricsService`] shares the `http.route` and the
+/// response status of a request with its [`HTTPMetricsRequestBody`].
+///
+/// [`HTTPMetricsService`]: crate::HTTPMetricsService
+#[derive(Clone, Default)]
+pub(crate) struct RequestBodyLink(Arc<Mutex<RequestBodyLinkState>>);
+
+#[derive(Default)]
+struct RequestBodyLinkState {
+    http_route: Option<StringValue>,
+    // exact size reported by a body without a `Content-Length`, for `http.server.request.size`
+    body_size_hint: Option<u64>,
+    // `http.response.status_code` attribute, once the response is returned
+    status_code: Option<KeyValue>,
+    // body size read before the response was returned, recorded along with its status
+    pending_body_size: Option<PendingBodySize>,
+}
+
+struct PendingBodySize {
+    server_request_body_size: Histogram<u64>,
+    body_size: u64,
+    labels: Labels,
+}
+
+impl PendingBodySize {
+    fn record(self, status_code: Option<KeyValue>) {
+        let mut labels = self.labels;
+        labels.extend(status_code);
+        self.server_request_body_size
+            .record(self.body_size, &labels);
+    }
+}
+
+impl RequestBodyLink {
+    pub(crate) fn set_route(&self, route: StringValue) {
+        self.lock().http_route = Some(route);
+    }
+
+    /// Set the status of the response, recording the body size if it was read before.
+    pub(crate) fn set_status_code(&self, status_code: KeyValue) {
+        let mut state = self.lock();
+        if let Some(pending_body_size) = state.pending_body_size.take() {
+            pending_body_size.record(Some(status_code.clone()));
+        }
+        state.status_code = Some(status_code);
+    }
+
+    pub(crate) fn body_size_hint(&self) -> Option<u64> {
+        self.lock().body_size_hint
+    }
+
+    fn route(&self) -> Option<StringValue> {
+        self.lock().http_route.clone()
+    }
+
+    /// Record the body size along with the response status, or once it is known.
+    fn record_body_size(&self, pending_body_size: PendingBodySize) {
+        let mut state = self.lock();
+        match &state.status_code {
+            Some(status_code) => pending_body_size.record(Some(status_code.clone())),
+            None => state.pending_body_size = Some(pending_body_size),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RequestBodyLinkState> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Drop for RequestBodyLinkState {
+    fn drop(&mut self) {
+        // no response was returned for the request
+        if let Some(pending_body_size) = self.pending_body_size.take() {
+            pending_body_size.record(None);
+        }
+    }
+}
+
+/// RequestBodyMetricsState holds the data needed to record request body metrics
+/// while the body is read by the inner service.
+pub(crate) struct RequestBodyMetricsState {
+    layer_state: Arc<HTTPMetricsLayerState>,
+    http_request_method: StringValue,
+    url_scheme: StringValue,
+    link: RequestBodyLink,
+    // set for Expect: 100-continue requests until the body is first polled
+    awaiting_continue: bool,
+    // set once 100 Continue has been sent, until the body is complete
+    continue_sent_at: Option<Instant>,
+    // bytes read so far, for bodies whose size is not known up front
+    body_size: Option<u64>,
+    count_body_bytes: bool,
+    count_network_io: bool,
+    // message counter and labels of gRPC calls, until the body is complete
+    grpc: Option<(GrpcMessageCounter, Labels)>,
+}
+
+impl RequestBodyMetricsLayer {
+    pub(crate) fn new(binding: Arc<LayerBinding>) -> Self {
+        RequestBodyMetricsLayer { binding }
+    }
+}
+
+impl fmt::Debug for RequestBodyMetricsLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBodyMetricsLayer")
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for RequestBodyMetricsService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestBodyMetricsService")
+            .field("inner_service", &self.inner_service)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B: fmt::Debug> fmt::Debug for HTTPMetricsRequestBody<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HTTPMetricsRequestBody")
+            .field("inner_body", &self.inner_body)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for RequestBodyMetricsLayer {
+    type Service = RequestBodyMetricsService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        let (generation, state) = self.binding.current();
+        RequestBodyMetricsService {
+            state,
+            binding: self.binding.clone(),
+            generation,
+            inner_service: service,
+        }
+    }
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for RequestBodyMetricsService<S>
+where
+    S: Service<http::Request<HTTPMetricsRequestBody<ReqBody>>>,
+    ReqBody: Body,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        self.binding.refresh(&mut self.generation, &mut self.state);
+
+        let (mut parts, body) = req.into_parts();
+
+        let expect_continue = parts
+            .headers
+            .get(http::header::EXPECT)
+            .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"100-continue"));
+        let awaiting_continue =
+            expect_continue && self.state.server_request_continue_duration.is_some();
+
+        let body_metrics_enabled = !body.is_end_stream()
+            && self
+                .state
+                .body_metrics_filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches(&parts.method, None, parts.uri.path()));
+        // bodies with a valid Content-Length have their size recorded by HTTPMetricsService
+        let content_length_valid = matches!(
+            content_length(&parts.headers, None),
+            ContentLength::Valid(_)
+        );
+        let count_body_size = body_metrics_enabled && !content_length_valid;
+        // HTTPMetricsService takes the request size of bodies without a Content-Length from here
+        let body_size_hint = (!content_length_valid && self.state.server_request_size.is_some())
+            .then(|| body.size_hint().exact())
+            .flatten();
+        let count_body_bytes =
+            body_metrics_enabled && self.state.server_request_body_bytes.is_some();
+        // HTTPMetricsService counts bodies with a Content-Length up front
+        let count_network_io = !body.is_end_stream()
+            && !content_length_valid
+            && self.state.server_network_io.is_some();
+        let grpc = self
+            .state
+            .rpc_server_requests_per_rpc
+            .as_ref()
+            .and_then(|_| grpc_labels(&parts.headers, &parts.uri))
+            .map(|labels| (GrpcMessageCounter::default(), labels));
+
+        let observe_body = awaiting_continue
+            || count_body_size
+            || count_body_bytes
+            || count_network_io
+            || grpc.is_some();
+        let link = (observe_body || body_size_hint.is_some()).then(|| {
+            let link = RequestBodyLink::default();
+            link.lock().body_size_hint = body_size_hint;
+            parts.extensions.insert(link.clone());
+            link
+        });
+        let metrics_state = link
+            .filter(|_| observe_body)
+            .map(|link| RequestBodyMetricsState {
+                layer_state: self.state.clone(),
+                http_request_method: method_value(&parts.method),
+                url_scheme: scheme_value(&parts.uri, &self.state.default_url_scheme),
+                link,
+                awaiting_continue,
+                continue_sent_at: None,
+                body_size: count_body_size.then_some(0),
+                count_body_bytes,
+                count_network_io,
+                grpc,
+            });
+
+        let body = HTTPMetricsRequestBody {
+            inner_body: body,
+            metrics_state,
+        };
+        self.inner_service
+            .call(http::Request::from_parts(parts, body))
+    }
+}
+
+impl RequestBodyMetricsState {
+    fn labels(&self) -> Labels {
+        let mut labels =
+            common_http_server_labels(&self.http_request_method, Some(&self.url_scheme));
+        if let Some(route) = self.link.route() {
+            labels.push(KeyValue::new(HTTP_ROUTE_LABEL, route));
+        }
+        labels
+    }
+
+    /// Called before each poll of the body; the server sends 100 Continue
+    /// when the body is first polled.
+    fn observe_poll(&mut self) {
+        if !self.awaiting_continue {
+            return;
+        }
+        self.awaiting_continue = false;
+        self.continue_sent_at = Some(Instant::now());
+
+        if let Some(server_informational_responses) =
+            &self.layer_state.server_informational_responses
+        {
+            let mut labels = self.labels();
+            labels.push(KeyValue::new(
+                HTTP_RESPONSE_STATUS_CODE_LABEL,
+                status_code_attribute(
+                    http::StatusCode::CONTINUE,
+                    self.layer_state.string_status_code,
+                    self.layer_state.kept_status_codes.as_deref(),
+                    status_code_value,
+                ),
+            ));
+            server_informational_responses.add(1, &labels);
+        }
+    }
+
+    fn observe_frame<D: Buf>(&mut self, frame: &Frame<D>) {
+        let Some(data) = frame.data_ref() else {
+            return;
+        };
+        let bytes = data.remaining() as u64;
+        if let Some(body_size) = self.body_size.as_mut() {
+            *body_size += bytes;
+        }
+        if let Some((grpc_messages, _)) = self.grpc.as_mut() {
+            grpc_messages.observe(data);
+        }
+        if let (true, Some(server_request_body_bytes)) = (
+            self.count_body_bytes,
+            &self.layer_state.server_request_body_bytes,
+        ) {
+            server_request_body_bytes.add(bytes, &self.labels());
+        }
+        if let (true, Some(server_network_io)) =
+            (self.count_network_io, &self.layer_state.server_network_io)
+        {
+            server_network_io.add(
+                bytes,
+                &[KeyValue::new(
+                    NETWORK_IO_DIRECTION_LABEL,
+                    NETWORK_IO_DIRECTION_RECEIVE,
+                )],
+            );
+        }
+    }
+
+    fn observe_end_of_stream(&mut self) {
+        self.record_grpc_messages();
+
+        if let (Some(body_size), Some(server_request_body_size)) = (
+            self.body_size.take(),
+            &self.layer_state.server_request_body_size,
+        ) {
+            self.link.record_body_size(PendingBodySize {
+                server_request_body_size: server_request_body_size.clone(),
+                body_size,
+                labels: self.labels(),
+            });
+        }
+
+        if let (Some(continue_sent_at), Some(server_request_continue_duration)) = (
+            self.continue_sent_at.take(),
+            &self.layer_state.server_request_continue_duration,
+        ) {
+            server_request_continue_duration
+                .record(continue_sent_at.elapsed().as_secs_f64(), &self.labels());
+        }
+    }
+
+    fn record_grpc_messages(&mut self) {
+        if let (Some((grpc_messages, labels)), Some(rpc_server_requests_per_rpc)) = (
+            self.grpc.take(),
+            &self.layer_state.rpc_server_requests_per_rpc,
+        ) {
+            rpc_server_requests_per_rpc.record(grpc_messages.messages(), &labels);
+        }
+    }
+}
+
+impl Drop for RequestBodyMetricsState {
+    fn drop(&mut self) {
+        // streaming calls may be cancelled before the request stream ends
+        self.record_grpc_messages();
+    }
+}
+
+impl<B> Body for HTTPMetricsRequestBody<B>
+where
+    B: Body,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<result::Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        if let Some(metrics_state) = this.metrics_state.as_mut() {
+            metrics_state.observe_poll();
+        }
+
+        let frame = ready!(this.inner_body.as_mut().poll_frame(cx));
+
+        if let Some(metrics_state) = this.metrics_state {
+            if let Some(Ok(frame)) = &frame {
+                metrics_state.observe_frame(frame);
+            }
+            if frame.is_none() || this.inner_body.is_end_stream() {
+                metrics_state.observe_end_of_stream();
+            }
+        }
+
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner_body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner_body.size_hint()
+    }
+}
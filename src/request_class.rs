@@ -0,0 +1,71 @@
+//! Coarse classification of requests, recorded as the `http.request.class` attribute.
+//!
+//! Traffic mix dashboards rarely need route-level detail; a handful of classes such as API,
+//! static assets, admin and webhooks are enough, and keep the attribute bounded no matter how many
+//! routes the application has.
+
+use http::request::Parts;
+
+pub(crate) const HTTP_REQUEST_CLASS_LABEL: &str = "http.request.class";
+
+/// Classifier mapping requests to one of a fixed set of classes, recorded as `http.request.class`.
+///
+/// Classes are `&'static str` so the attribute stays bounded; typically they are the names of the
+/// variants of an application enum. Closures taking the request [`Parts`] implement the trait as
+/// well. A panicking classifier is counted like a panicking request extractor.
+///
+/// ```
+/// use http::request::Parts;
+/// use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, RequestClassifier};
+///
+/// enum RequestClass {
+///     Api,
+///     Static,
+///     Admin,
+///     Webhook,
+/// }
+///
+/// impl RequestClass {
+///     fn as_str(&self) -> &'static str {
+///         match self {
+///             RequestClass::Api => "api",
+///             RequestClass::Static => "static",
+///             RequestClass::Admin => "admin",
+///             RequestClass::Webhook => "webhook",
+///         }
+///     }
+/// }
+///
+/// struct PathClassifier;
+///
+/// impl RequestClassifier for PathClassifier {
+///     fn classify(&self, parts: &Parts) -> Option<&'static str> {
+///         let path = parts.uri.path();
+///         let class = if path.starts_with("/api/") {
+///             RequestClass::Api
+///         } else if path.starts_with("/admin/") {
+///             RequestClass::Admin
+///         } else if path.starts_with("/hooks/") {
+///             RequestClass::Webhook
+///         } else {
+///             RequestClass::Static
+///         };
+///         Some(class.as_str())
+///     }
+/// }
+///
+/// let builder = HTTPMetricsLayerBuilder::default().with_request_classifier(PathClassifier);
+/// ```
+pub trait RequestClassifier: Send + Sync + 'static {
+    /// The class of the request, or `None` to leave it unclassified.
+    fn classify(&self, parts: &Parts) -> Option<&'static str>;
+}
+
+impl<F> RequestClassifier for F
+where
+    F: Fn(&Parts) -> Option<&'static str> + Send + Sync + 'static,
+{
+    fn classify(&self, parts: &Parts) -> Option<&'static str> {
+        self(parts)
+    }
+}
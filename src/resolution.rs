@@ -0,0 +1,97 @@
+//! Resolution of `http.server.request.duration`, for services answering in well under a millisecond.
+//!
+//! The semconv histogram records seconds into buckets starting at 5ms, so every request of an
+//! internal service answering in microseconds lands in the first bucket and its latency
+//! distribution is lost. Finer bucket boundaries keep the semconv unit; integer nanoseconds
+//! additionally avoid the float conversion, for backends expecting them.
+
+use std::borrow::Cow;
+use std::time::Duration;
+
+use opentelemetry::metrics::{Histogram, Meter};
+use opentelemetry::KeyValue;
+
+use crate::{HTTP_SERVER_DURATION_BOUNDARIES, HTTP_SERVER_DURATION_UNIT};
+
+const HTTP_SERVER_DURATION_NANOSECONDS_UNIT: &str = "ns";
+
+/// Boundaries in seconds from 1µs up to 10s, used by the finer resolutions.
+const HTTP_SERVER_DURATION_FINE_BOUNDARIES: [f64; 21] = [
+    0.000_001, 0.000_005, 0.000_01, 0.000_025, 0.000_05, 0.000_1, 0.000_25, 0.000_5, 0.001,
+    0.002_5, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// Resolution of the request duration histograms,
+/// set with [`HTTPMetricsLayerBuilder::with_duration_resolution`].
+///
+/// [`HTTPMetricsLayerBuilder::with_duration_resolution`]: crate::HTTPMetricsLayerBuilder::with_duration_resolution
+pub enum DurationResolution {
+    /// Seconds as `f64`, into the semconv bucket boundaries from 5ms up to 10s.
+    #[default]
+    Seconds,
+    /// Seconds as `f64`, into bucket boundaries from 1µs up to 10s.
+    FineSeconds,
+    /// Nanoseconds as `u64` with unit `ns`, into bucket boundaries from 1µs up to 10s.
+    Nanoseconds,
+}
+
+/// Request duration histogram of either resolution.
+pub(crate) enum DurationHistogram {
+    Seconds(Histogram<f64>),
+    Nanoseconds(Histogram<u64>),
+}
+
+impl DurationHistogram {
+    pub(crate) fn new(
+        meter: &Meter,
+        resolution: DurationResolution,
+        name: impl Into<Cow<'static, str>>,
+        description: &'static str,
+    ) -> Self {
+        match resolution {
+            DurationResolution::Seconds | DurationResolution::FineSeconds => {
+                let boundaries = match resolution {
+                    DurationResolution::FineSeconds => {
+                        HTTP_SERVER_DURATION_FINE_BOUNDARIES.to_vec()
+                    }
+                    _ => HTTP_SERVER_DURATION_BOUNDARIES.to_vec(),
+                };
+                DurationHistogram::Seconds(
+                    meter
+                        .f64_histogram(name)
+                        .with_description(description)
+                        .with_unit(HTTP_SERVER_DURATION_UNIT)
+                        .with_boundaries(boundaries)
+                        .build(),
+                )
+            }
+            DurationResolution::Nanoseconds => DurationHistogram::Nanoseconds(
+                meter
+                    .u64_histogram(name)
+                    .with_description(description)
+                    .with_unit(HTTP_SERVER_DURATION_NANOSECONDS_UNIT)
+                    .with_boundaries(
+                        HTTP_SERVER_DURATION_FINE_BOUNDARIES
+                            .iter()
+                            .map(|seconds| (seconds * 1e9).round())
+                            .collect(),
+                    )
+                    .build(),
+            ),
+        }
+    }
+
+    pub(crate) fn record(&self, duration: Duration, labels: &[KeyValue]) {
+        match self {
+            DurationHistogram::Seconds(histogram) => {
+                histogram.record(duration.as_secs_f64(), labels)
+            }
+            DurationHistogram::Nanoseconds(histogram) => histogram.record(
+                u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX),
+                labels,
+            ),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Matching of request paths and routes against route patterns.
+//!
+//! Patterns are written in the style of router path templates, with either axum 0.7
+//! (`/users/:id`, `/files/*path`) or OpenAPI / axum 0.8 (`/users/{id}`, `/files/{*path}`) syntax.
+//! A parameter segment matches any single path segment and a wildcard segment matches
+//! the remainder of the path.
+//!
+//! Since parameter segments match any segment, a pattern matches both concrete request paths
+//! (`/users/123`) and the route templates produced by routers (`/users/:id`).
+//!
+//! The `http.route` of a request is determined by [`RouteResolver`], an ordered pipeline whose
+//! stages are each optional: the axum `MatchedPath`, a user route extractor, the configured route
+//! patterns with the `route-patterns` feature, and finally a fallback route for requests no stage
+//! resolved.
+//!
+//! Services mounted with axum's `Router::nest_service` see requests with the mount path stripped
+//! and without a `MatchedPath`. Route patterns are therefore matched against the full path from
+//! axum's `OriginalUri` when present, and requests no other stage resolved get the mount path from
+//! `NestedPath` followed by a wildcard, e.g. `/assets/*path`, before falling back.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+#[cfg(all(feature = "route-patterns", feature = "axum"))]
+use axum::extract::OriginalUri;
+#[cfg(feature = "axum")]
+use axum::extract::{MatchedPath, NestedPath};
+use opentelemetry::metrics::Counter;
+use opentelemetry::StringValue;
+
+use crate::extractor::{catch_extractor_panic, EXTRACTOR_KIND_ROUTE};
+#[cfg(feature = "route-cache")]
+use crate::lru::RouteCache;
+
+pub(crate) const HTTP_ROUTE_FALLBACK_LABEL: &str = "http.route.fallback";
+
+pub(crate) type RouteExtractor =
+    dyn Fn(&http::request::Parts) -> Option<Cow<'static, str>> + Send + Sync;
+
+#[derive(Clone, Debug, PartialEq)]
+enum RouteSegment {
+    Literal(String),
+    Param,
+    Wildcard,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RoutePattern {
+    segments: Vec<RouteSegment>,
+}
+
+impl RoutePattern {
+    pub(crate) fn new(pattern: &str) -> Self {
+        let segments = split_path(pattern)
+            .map(|segment| {
+                if segment.starts_with('*') || segment.starts_with("{*") {
+                    RouteSegment::Wildcard
+                } else if segment.starts_with(':')
+                    || (segment.starts_with('{') && segment.ends_with('}'))
+                {
+                    RouteSegment::Param
+                } else {
+                    RouteSegment::Literal(segment.to_owned())
+                }
+            })
+            .collect();
+        RoutePattern { segments }
+    }
+
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        let mut path_segments = split_path(path);
+        for segment in &self.segments {
+            match segment {
+                RouteSegment::Wildcard => return true,
+                RouteSegment::Param => {
+                    if path_segments.next().is_none() {
+                        return false;
+                    }
+                }
+                RouteSegment::Literal(literal) => {
+                    if path_segments.next() != Some(literal.as_str()) {
+                        return false;
+                    }
+                }
+            }
+        }
+        path_segments.next().is_none()
+    }
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+/// Ordered stages determining the `http.route` of a request; the first stage to resolve wins.
+pub(crate) struct RouteResolver {
+    #[cfg_attr(not(feature = "axum"), allow(dead_code))]
+    pub(crate) matched_path: bool,
+    pub(crate) extractor: Option<Arc<RouteExtractor>>,
+    #[cfg(feature = "route-patterns")]
+    pub(crate) patterns: Vec<(RoutePattern, StringValue)>,
+    pub(crate) fallback: Option<StringValue>,
+    // interns routes which are not static, bounding the memory they take
+    #[cfg(feature = "route-cache")]
+    pub(crate) cache: Option<RouteCache>,
+    // counts panics of the route extractor
+    pub(crate) panics: Counter<u64>,
+}
+
+impl RouteResolver {
+    /// Resolve the route of `req`, interning routes which are not static through the route cache.
+    ///
+    /// The request is handed back since the route extractor is given the request parts.
+    pub(crate) fn resolve<B>(
+        &self,
+        req: http::Request<B>,
+    ) -> (http::Request<B>, Option<StringValue>) {
+        #[cfg(feature = "axum")]
+        if self.matched_path {
+            if let Some(mp) = req.extensions().get::<MatchedPath>() {
+                let route = self.route_value(Cow::Borrowed(mp.as_str()));
+                return (req, Some(route));
+            }
+        }
+
+        let req = match &self.extractor {
+            Some(extractor) => {
+                let (parts, body) = req.into_parts();
+                let route =
+                    catch_extractor_panic(&self.panics, EXTRACTOR_KIND_ROUTE, || extractor(&parts));
+                if let Some(route) = route.flatten() {
+                    let route = match route {
+                        Cow::Borrowed(route) => StringValue::from(route),
+                        Cow::Owned(route) => self.route_value(Cow::Owned(route)),
+                    };
+                    return (http::Request::from_parts(parts, body), Some(route));
+                }
+                http::Request::from_parts(parts, body)
+            }
+            None => req,
+        };
+
+        #[cfg(all(feature = "route-patterns", feature = "axum"))]
+        let path = match req.extensions().get::<OriginalUri>() {
+            Some(OriginalUri(uri)) => uri.path(),
+            None => req.uri().path(),
+        };
+        #[cfg(all(feature = "route-patterns", not(feature = "axum")))]
+        let path = req.uri().path();
+        #[cfg(feature = "route-patterns")]
+        if let Some((_, route)) = self
+            .patterns
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path))
+        {
+            let route = route.clone();
+            return (req, Some(route));
+        }
+
+        #[cfg(feature = "axum")]
+        if self.matched_path {
+            if let Some(nested_path) = req.extensions().get::<NestedPath>() {
+                let mount = nested_path.as_str().trim_end_matches('/');
+                let route = self.route_value(Cow::Owned(format!("{mount}/*path")));
+                return (req, Some(route));
+            }
+        }
+
+        (req, self.fallback.clone())
+    }
+
+    /// Attribute value of a route taken from the request, shared through the route cache when
+    /// enabled.
+    fn route_value(&self, route: Cow<'_, str>) -> StringValue {
+        #[cfg(feature = "route-cache")]
+        if let Some(cache) = &self.cache {
+            return cache.intern(&route);
+        }
+        StringValue::from(route.into_owned())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Response extension marking responses of the router's fallback handler.
+///
+/// Recorded as `http.route.fallback` with
+/// [`with_route_fallback_attribute`](crate::HTTPMetricsLayerBuilder::with_route_fallback_attribute).
+/// With axum, the fallback handler returns `(StatusCode::NOT_FOUND, Extension(RouteFallback))`.
+///
+/// ```
+/// use tower_otel_http_metrics::RouteFallback;
+///
+/// let mut response = http::Response::new(());
+/// response.extensions_mut().insert(RouteFallback);
+/// ```
+pub struct RouteFallback;
+
+/// Whether the router matched no route for a request.
+///
+/// Only meaningful for layers applied to the router, which run after routing; layers wrapped
+/// around the router see no request with a matched route.
+#[cfg(feature = "axum")]
+pub(crate) fn matched_no_route(extensions: &http::Extensions) -> bool {
+    extensions.get::<MatchedPath>().is_none() && extensions.get::<NestedPath>().is_none()
+}
+
+/// Attribute value of a configured route.
+pub(crate) fn static_route_value(route: Cow<'static, str>) -> StringValue {
+    match route {
+        Cow::Borrowed(route) => StringValue::from(route),
+        Cow::Owned(route) => StringValue::from(Arc::<str>::from(route)),
+    }
+}
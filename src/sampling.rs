@@ -0,0 +1,40 @@
+//! Recording metrics in proportion to trace sampling.
+//!
+//! Very hot services can bound the cost of their metrics by the trace sampling rate: either the
+//! attributes of custom extractors, or the measurements of the request altogether, are only
+//! recorded when the request's trace is sampled.
+//!
+//! The sampling decision is read from the [`Context`] inserted into the request extensions, as
+//! done by propagating middleware, falling back to the current context. The layer therefore has
+//! to sit inside the tracing middleware which starts the request's span.
+
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::Context;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+/// What to record for requests whose trace is not sampled,
+/// set with [`HTTPMetricsLayerBuilder::with_trace_sampling`].
+///
+/// [`HTTPMetricsLayerBuilder::with_trace_sampling`]: crate::HTTPMetricsLayerBuilder::with_trace_sampling
+pub enum TraceSampling {
+    /// Record every request regardless of trace sampling.
+    #[default]
+    Ignore,
+    /// Record every request, adding the attributes of custom request and response extractors,
+    /// async attributes and the request classifier only for sampled requests.
+    ExtractorAttributes,
+    /// Record the metrics of sampled requests only.
+    ///
+    /// The counts and rates of the recorded metrics are then a sample of the actual traffic,
+    /// to be scaled up by the sampling rate.
+    Measurements,
+}
+
+/// Whether the trace of a request is sampled.
+pub(crate) fn trace_sampled(extensions: &http::Extensions) -> bool {
+    match extensions.get::<Context>() {
+        Some(context) => context.span().span_context().is_sampled(),
+        None => Context::current().span().span_context().is_sampled(),
+    }
+}
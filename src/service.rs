@@ -0,0 +1,1111 @@
+//! The request side of the recording pipeline: [`HTTPMetricsService`] measures each request
+//! as it is forwarded, and [`HTTPMetricsResponseFuture`] records the measurements once the
+//! response is produced, or the request fails or is abandoned.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::string::String;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::Poll::Ready;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+use std::{fmt, mem, result};
+
+use futures_util::ready;
+use opentelemetry::{KeyValue, StringValue};
+use pin_project_lite::pin_project;
+use tower_service::Service;
+
+#[cfg(feature = "async-extractor")]
+use crate::async_extractor::AsyncAttributes;
+#[cfg(feature = "tower-http")]
+use crate::attributes::ERROR_TYPE_LABEL;
+use crate::attributes::{
+    auth_outcome, cache_status, content_length, content_range_size, push_client_geo_labels,
+    push_network_peer_label, push_query_param_labels, push_server_address_labels,
+    push_traffic_split_label, queue_time, rate_limit, throttled, ContentLength,
+    ERROR_TYPE_CANCELLED, HTTP_AUTH_OUTCOME_LABEL, HTTP_AUTH_SCHEME_LABEL, HTTP_CACHE_STATUS_LABEL,
+    HTTP_THROTTLE_POLICY_LABEL, OTHER_LABEL_VALUE, URL_PATH_LABEL,
+};
+#[cfg(feature = "user-agent")]
+use crate::attributes::{device_category, USER_AGENT_DEVICE_CATEGORY_LABEL};
+use crate::backend::HTTP_SERVER_BACKEND_LABEL;
+use crate::binding::LayerBinding;
+use crate::body::{ResponseBodyLink, ResponseBodyMetricsState, ResponseBodyObservers};
+#[cfg(feature = "tower-http")]
+use crate::classify::ClassifyFailure;
+use crate::clock::{self, Instant};
+use crate::custom::{record_custom_histograms, record_custom_instruments};
+use crate::error_type::ErrorTypeLink;
+use crate::extractor::{
+    cap_extracted_attributes, catch_extractor_panic, run_attribute_extractors,
+    EXTRACTOR_KIND_REQUEST, EXTRACTOR_KIND_RESPONSE,
+};
+use crate::faas::{faas_scheme_value, push_faas_labels};
+use crate::grpc::{grpc_labels, push_grpc_service_labels};
+use crate::labels::{method_value, scheme_value, status_code_attribute, status_code_value, Labels};
+#[cfg(feature = "load")]
+use crate::load::{PendingRequest, ServiceLoad};
+use crate::operation::OPENAPI_OPERATION_ID_LABEL;
+use crate::pool::{recycle_labels, take_labels};
+use crate::record::{
+    common_http_server_labels, extract_labels_server_request_duration, header_section_size,
+    labels_server_active_request, labels_server_body_size, labels_server_route,
+    record_server_request_duration, record_server_request_error, release_server_active_request,
+    request_header_section_size, response_content_length, split_and_format_protocol_version,
+};
+use crate::request_body::RequestBodyLink;
+use crate::request_class::HTTP_REQUEST_CLASS_LABEL;
+use crate::route::HTTP_ROUTE_FALLBACK_LABEL;
+#[cfg(feature = "trace-sampling")]
+use crate::sampling::trace_sampled;
+#[cfg(feature = "span-attributes")]
+use crate::span::request_context;
+use crate::user::push_user_labels;
+#[cfg(feature = "trace-sampling")]
+use crate::TraceSampling;
+use crate::{
+    AcceptTime, Authenticated, Backend, HTTPMetricsLayerState, OpenTunnel, OperationId,
+    RecordValues, RequestMetricsContext, ResponseBodySizeSource, RouteFallback, ThrottlePolicy,
+    TimingCheckpoints, UsageUnits, HTTP_RESPONSE_STATUS_CODE_LABEL, HTTP_ROUTE_LABEL,
+    NETWORK_IO_DIRECTION_LABEL, NETWORK_IO_DIRECTION_RECEIVE, NETWORK_IO_DIRECTION_TRANSMIT,
+    TENANT_ID_LABEL,
+};
+
+#[derive(Clone)]
+/// [`Service`] used by [`HTTPMetricsLayer`]
+///
+/// The service, its response future and response body are `Send` exactly when the inner service,
+/// its future and body are, so `!Send` services of thread-per-core runtimes (monoio, glommio)
+/// or of a tokio `LocalSet` can be wrapped as well:
+///
+/// ```
+/// use std::cell::Cell;
+/// use std::convert::Infallible;
+/// use std::future::{ready, Ready};
+/// use std::rc::Rc;
+/// use std::task::{Context, Poll};
+///
+/// use tower_layer::Layer;
+/// use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+/// use tower_service::Service;
+///
+/// // a handler keeping per-thread state
+/// #[derive(Clone)]
+/// struct LocalHandler {
+///     served: Rc<Cell<u64>>,
+/// }
+///
+/// impl Service<http::Request<String>> for LocalHandler {
+///     type Response = http::Response<String>;
+///     type Error = Infallible;
+///     type Future = Ready<Result<Self::Response, Self::Error>>;
+///
+///     fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+///         Poll::Ready(Ok(()))
+///     }
+///
+///     fn call(&mut self, _req: http::Request<String>) -> Self::Future {
+///         self.served.set(self.served.get() + 1);
+///         ready(Ok(http::Response::new(String::new())))
+///     }
+/// }
+///
+/// let layer = HTTPMetricsLayerBuilder::default().build().unwrap();
+/// let mut service = layer.layer(LocalHandler {
+///     served: Rc::new(Cell::new(0)),
+/// });
+/// let _response_future = service.call(http::Request::new(String::new()));
+/// ```
+///
+/// [`HTTPMetricsLayer`]: crate::HTTPMetricsLayer
+pub struct HTTPMetricsService<S> {
+    pub(crate) state: Arc<HTTPMetricsLayerState>,
+    pub(crate) binding: Arc<LayerBinding>,
+    pub(crate) generation: u64,
+    #[cfg(feature = "load")]
+    pub(crate) load: Option<Arc<ServiceLoad>>,
+    pub(crate) inner_service: S,
+}
+
+impl<S> HTTPMetricsService<S> {
+    /// Gets a reference to the underlying service.
+    pub fn get_ref(&self) -> &S {
+        &self.inner_service
+    }
+
+    /// Gets a mutable reference to the underlying service.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner_service
+    }
+
+    /// Consumes `self`, returning the underlying service.
+    pub fn into_inner(self) -> S {
+        self.inner_service
+    }
+
+    /// Forward a request without recording its metrics.
+    fn call_unrecorded<ReqBody>(
+        &mut self,
+        req: http::Request<ReqBody>,
+    ) -> HTTPMetricsResponseFuture<S::Future>
+    where
+        S: Service<http::Request<ReqBody>>,
+    {
+        let metrics_state = ResponseFutureMetricsState::unrecorded(
+            method_value(req.method()),
+            scheme_value(req.uri(), &self.state.default_url_scheme),
+        );
+        #[cfg(feature = "load")]
+        let metrics_state = metrics_state.with_pending_request(self.load.as_ref());
+        HTTPMetricsResponseFuture {
+            inner_response_future: self.inner_service.call(req),
+            layer_state: self.state.clone(),
+            metrics_state,
+            recorded: false,
+            completed: false,
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for HTTPMetricsService<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HTTPMetricsService")
+            .field("inner_service", &self.inner_service)
+            .finish_non_exhaustive()
+    }
+}
+
+/// ResponseFutureMetricsState holds request-scoped data for metrics and their attributes.
+///
+/// ResponseFutureMetricsState lives inside the response future, as it needs to hold data
+/// initialized or extracted from the request before it is forwarded to the inner Service.
+/// The rest of the data (e.g. status code, error) can be extracted from the response
+/// or calculated with respect to the data held here (e.g., duration = now - duration start).
+pub(crate) struct ResponseFutureMetricsState {
+    // fields for the metrics themselves
+    // https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#metric-httpserverrequestduration
+    pub(crate) http_request_duration_start: Instant,
+    // https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#metric-httpserverrequestbodysize
+    pub(crate) http_request_body_size: Option<u64>,
+    // size of the request head and body, when http.server.request.size is enabled
+    pub(crate) http_request_size: Option<u64>,
+    pub(crate) body_metrics_enabled: bool,
+    // tunnel of a CONNECT request, counted as open once the request succeeds
+    pub(crate) open_tunnel: Option<OpenTunnel>,
+    // whether the request has a Range header, when range request metrics are enabled
+    pub(crate) range_request: bool,
+    // number of times the response future was polled
+    pub(crate) polls: u64,
+    // checkpoints marked inside the layer, when timing checkpoints are enabled
+    pub(crate) timing_checkpoints: Option<TimingCheckpoints>,
+    // request counted towards the load of the service until completed
+    #[cfg(feature = "load")]
+    pub(crate) pending_request: Option<PendingRequest>,
+
+    // fields for metric labels
+    pub(crate) http_request_method: StringValue,
+    pub(crate) http_route: Option<StringValue>,
+    pub(crate) network_protocol_name: &'static str,
+    pub(crate) network_protocol_version: &'static str,
+    // absent for CONNECT tunnels
+    pub(crate) url_scheme: Option<StringValue>,
+    pub(crate) tenant_id: Option<String>,
+    // opt-in labels extracted from the request for http.server.request.duration
+    pub(crate) request_labels: Vec<KeyValue>,
+    // number of request_labels added by extractors, counting towards their maximum
+    pub(crate) extractor_attributes: usize,
+    // known route matching the path of a request without a route, attributed to its 405 response
+    pub(crate) method_mismatch_route: Option<StringValue>,
+    // whether user.authenticated is left to the response, not being marked on the request
+    pub(crate) user_authenticated_pending: bool,
+    // whether response extractors run, which trace sampling may rule out
+    pub(crate) extract_attributes: bool,
+    // context of the span the measurements are recorded on
+    #[cfg(feature = "span-attributes")]
+    pub(crate) span_context: Option<opentelemetry::Context>,
+    // labels of gRPC calls when gRPC message counts are enabled
+    pub(crate) grpc_labels: Option<Labels>,
+    // link to the request body, whose size may wait for the response status
+    pub(crate) request_body_link: Option<RequestBodyLink>,
+    // link to the response body layer outside, which observes the response body
+    pub(crate) response_body_link: Option<ResponseBodyLink>,
+    // link to the ErrorTypeService inside, when client aborts are classified
+    pub(crate) error_type_link: Option<ErrorTypeLink>,
+    // classifier of the response, taken once it is classified
+    #[cfg(feature = "tower-http")]
+    pub(crate) failure_classifier: Option<Box<dyn ClassifyFailure>>,
+}
+
+impl ResponseFutureMetricsState {
+    /// State of a request whose metrics are not recorded.
+    fn unrecorded(method: StringValue, scheme: StringValue) -> Self {
+        ResponseFutureMetricsState {
+            http_request_duration_start: Instant::now(),
+            http_request_body_size: None,
+            http_request_size: None,
+            body_metrics_enabled: false,
+            open_tunnel: None,
+            range_request: false,
+            polls: 0,
+            timing_checkpoints: None,
+            #[cfg(feature = "load")]
+            pending_request: None,
+            http_request_method: method,
+            http_route: None,
+            network_protocol_name: "",
+            network_protocol_version: "",
+            url_scheme: Some(scheme),
+            tenant_id: None,
+            request_labels: Vec::new(),
+            extractor_attributes: 0,
+            method_mismatch_route: None,
+            user_authenticated_pending: false,
+            extract_attributes: false,
+            #[cfg(feature = "span-attributes")]
+            span_context: None,
+            grpc_labels: None,
+            request_body_link: None,
+            response_body_link: None,
+            error_type_link: None,
+            #[cfg(feature = "tower-http")]
+            failure_classifier: None,
+        }
+    }
+
+    /// Count the request towards the load of its service.
+    #[cfg(feature = "load")]
+    fn with_pending_request(mut self, load: Option<&Arc<ServiceLoad>>) -> Self {
+        self.pending_request = load.map(ServiceLoad::start);
+        self
+    }
+}
+
+impl Drop for ResponseFutureMetricsState {
+    fn drop(&mut self) {
+        recycle_labels(mem::take(&mut self.request_labels));
+    }
+}
+
+pin_project! {
+    /// Response [`Future`] for [`HTTPMetricsService`].
+    pub struct HTTPMetricsResponseFuture<F> {
+        #[pin]
+        inner_response_future: F,
+        layer_state: Arc<HTTPMetricsLayerState>,
+        metrics_state: ResponseFutureMetricsState,
+        // requests left unrecorded, by trace sampling or a no-op meter, are passed through
+        recorded: bool,
+        completed: bool,
+    }
+
+    impl<F> PinnedDrop for HTTPMetricsResponseFuture<F> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if *this.completed || !*this.recorded {
+                return;
+            }
+            // Servers drop the response future when the client disconnects before the response
+            // is ready; outer timeouts, graceful shutdowns and `select!` drop it as well, and
+            // are indistinguishable here.
+            this.layer_state
+                .active_requests
+                .fetch_sub(1, Ordering::Relaxed);
+            if this.layer_state.cancelled_requests {
+                record_server_request_error(
+                    this.layer_state,
+                    this.metrics_state,
+                    ERROR_TYPE_CANCELLED,
+                );
+            } else {
+                release_server_active_request(this.layer_state, this.metrics_state);
+            }
+        }
+    }
+}
+
+impl<F> fmt::Debug for HTTPMetricsResponseFuture<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // only the method and route are shown, as other labels (e.g. the tenant) may identify the caller
+        f.debug_struct("HTTPMetricsResponseFuture")
+            .field(
+                "http_request_method",
+                &self.metrics_state.http_request_method,
+            )
+            .field("http_route", &self.metrics_state.http_route)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for HTTPMetricsService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = HTTPMetricsResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<result::Result<(), Self::Error>> {
+        self.inner_service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        self.binding.refresh(&mut self.generation, &mut self.state);
+        if self.state.pass_through {
+            return self.call_unrecorded(req);
+        }
+
+        #[cfg(feature = "trace-sampling")]
+        let extract_attributes = match self.state.trace_sampling {
+            TraceSampling::Ignore => true,
+            TraceSampling::ExtractorAttributes => trace_sampled(req.extensions()),
+            TraceSampling::Measurements => {
+                if !trace_sampled(req.extensions()) {
+                    return self.call_unrecorded(req);
+                }
+                true
+            }
+        };
+        #[cfg(not(feature = "trace-sampling"))]
+        let extract_attributes = true;
+
+        let accept_time = req
+            .extensions()
+            .get::<AcceptTime>()
+            .filter(|_| self.state.duration_from_accept_time);
+        let duration_start = accept_time.map_or_else(Instant::now, |accept_time| {
+            clock::from_std(accept_time.instant())
+        });
+
+        let method = method_value(req.method());
+
+        #[cfg(feature = "axum")]
+        if let Some(placement_guard) = &self.state.placement_guard {
+            placement_guard.check(req.extensions());
+        }
+
+        let (req, matched_path) = self.state.route_resolver.resolve(req);
+
+        let tunnel = self.state.connect_tunnels && req.method() == http::Method::CONNECT;
+        // CONNECT requests target an authority rather than a path, so not even a fallback route
+        // applies to them
+        let matched_path = matched_path.filter(|_| !tunnel);
+        let open_tunnel = self
+            .state
+            .server_open_tunnels
+            .as_ref()
+            .filter(|_| tunnel)
+            .map(|open_tunnels| OpenTunnel::new(open_tunnels.clone()));
+        let req = match &open_tunnel {
+            Some(open_tunnel) => {
+                let mut req = req;
+                req.extensions_mut().insert(open_tunnel.clone());
+                req
+            }
+            None => req,
+        };
+
+        let request_body_link = req.extensions().get::<RequestBodyLink>().cloned();
+        if let (Some(request_body_link), Some(route)) = (&request_body_link, &matched_path) {
+            request_body_link.set_route(route.clone());
+        }
+        let response_body_link = req.extensions().get::<ResponseBodyLink>().cloned();
+
+        let (req, error_type_link) = if self.state.client_abort_error_type {
+            let mut req = req;
+            let error_type_link = ErrorTypeLink::default();
+            req.extensions_mut().insert(error_type_link.clone());
+            (req, Some(error_type_link))
+        } else {
+            (req, None)
+        };
+
+        let headers = req.headers();
+
+        let (protocol, version) = split_and_format_protocol_version(req.version());
+        let scheme = if tunnel {
+            None
+        } else if self.state.faas_attributes {
+            Some(faas_scheme_value(
+                req.uri(),
+                headers,
+                &self.state.default_url_scheme,
+            ))
+        } else {
+            Some(scheme_value(req.uri(), &self.state.default_url_scheme))
+        };
+        let mut request_labels = take_labels();
+        if let Some(sanitize) = &self.state.url_path_sanitizer {
+            request_labels.push(KeyValue::new(URL_PATH_LABEL, sanitize(req.uri().path())));
+        }
+        push_query_param_labels(
+            &self.state.query_param_attributes,
+            req.uri().query(),
+            &mut request_labels,
+        );
+        if self.state.server_address_attributes {
+            push_server_address_labels(req.uri(), headers, &mut request_labels);
+        }
+        if self.state.network_peer_address_attribute {
+            push_network_peer_label(req.extensions(), &mut request_labels);
+        }
+        if self.state.client_geo_attributes {
+            push_client_geo_labels(headers, &mut request_labels);
+        }
+        if self.state.faas_attributes {
+            push_faas_labels(&mut request_labels);
+        }
+        if self.state.grpc_service_attributes {
+            push_grpc_service_labels(headers, req.uri(), &mut request_labels);
+        }
+        if let Some(traffic_split) = &self.state.traffic_split_attribute {
+            push_traffic_split_label(traffic_split, headers, &mut request_labels);
+        }
+        let mut user_authenticated_pending = false;
+        if self.state.user_authenticated_attribute {
+            match req.extensions().get::<Authenticated>() {
+                Some(authenticated) => push_user_labels(
+                    Some(authenticated),
+                    self.state.principal_buckets,
+                    &mut request_labels,
+                ),
+                None => user_authenticated_pending = true,
+            }
+        }
+        #[cfg(feature = "user-agent")]
+        if self.state.user_agent_device_category {
+            request_labels.push(KeyValue::new(
+                USER_AGENT_DEVICE_CATEGORY_LABEL,
+                device_category(headers),
+            ));
+        }
+        let body_metrics_enabled = self
+            .state
+            .body_metrics_filter
+            .as_ref()
+            .is_none_or(|filter| {
+                filter.matches(
+                    req.method(),
+                    matched_path.as_ref().map(StringValue::as_str),
+                    req.uri().path(),
+                )
+            });
+        let request_content_length = content_length(headers, self.state.max_content_length);
+        if request_content_length == ContentLength::Malformed {
+            if let Some(malformed) = &self.state.server_request_body_size_malformed {
+                let mut labels = common_http_server_labels(&method, scheme.as_ref());
+                if let Some(route) = &matched_path {
+                    labels.push(KeyValue::new(HTTP_ROUTE_LABEL, route.clone()));
+                }
+                malformed.add(1, &labels);
+            }
+        }
+        let content_length = match request_content_length {
+            ContentLength::Valid(length) if body_metrics_enabled => Some(length),
+            _ => None,
+        };
+        let range_request =
+            self.state.server_range_requests.is_some() && headers.contains_key(http::header::RANGE);
+        let header_section_size =
+            if self.state.server_request_size.is_some() || self.state.server_network_io.is_some() {
+                request_header_section_size(&req)
+            } else {
+                0
+            };
+        let request_size = self
+            .state
+            .server_request_size
+            .as_ref()
+            .filter(|_| body_metrics_enabled)
+            .and_then(|_| {
+                let body_size = match request_content_length {
+                    ContentLength::Valid(length) => length,
+                    ContentLength::Absent => request_body_link.as_ref()?.body_size_hint()?,
+                    ContentLength::Malformed => return None,
+                };
+                Some(header_section_size.saturating_add(body_size))
+            });
+
+        let tenant_id = self
+            .state
+            .usage_tenant_header
+            .as_ref()
+            .filter(|_| self.state.usage_units.is_some())
+            .and_then(|header| headers.get(header)?.to_str().ok())
+            .map(str::to_owned);
+
+        let grpc_labels = self
+            .state
+            .rpc_server_responses_per_rpc
+            .as_ref()
+            .and_then(|_| grpc_labels(headers, req.uri()));
+
+        #[cfg(feature = "span-attributes")]
+        let span_context = self
+            .state
+            .span_attributes
+            .then(|| request_context(req.extensions()));
+
+        let method_mismatch_route = match (&self.state.known_routes, &matched_path) {
+            (Some(known_routes), None) if !tunnel => {
+                known_routes.matching_route(req.uri().path()).cloned()
+            }
+            _ => None,
+        };
+
+        let server_active_request_labels = labels_server_active_request(
+            &self.state,
+            &method,
+            scheme.as_ref(),
+            matched_path.as_ref(),
+        );
+
+        if let Some(server_active_requests) = &self.state.server_active_requests {
+            server_active_requests.add(1, &server_active_request_labels);
+        }
+        self.state.active_requests.fetch_add(1, Ordering::Relaxed);
+
+        if let Some(server_network_io) = &self.state.server_network_io {
+            // bodies without a Content-Length are counted by the request body layer
+            let body_size = match request_content_length {
+                ContentLength::Valid(length) => length,
+                ContentLength::Absent | ContentLength::Malformed => 0,
+            };
+            server_network_io.add(
+                header_section_size + body_size,
+                &[KeyValue::new(
+                    NETWORK_IO_DIRECTION_LABEL,
+                    NETWORK_IO_DIRECTION_RECEIVE,
+                )],
+            );
+        }
+
+        if let Some(server_queue_time) = &self.state.server_queue_time {
+            if let Some(queue_time) = queue_time(headers, SystemTime::now()) {
+                let mut labels = common_http_server_labels(&method, scheme.as_ref());
+                if let Some(route) = &matched_path {
+                    labels.push(KeyValue::new(HTTP_ROUTE_LABEL, route.clone()));
+                }
+                server_queue_time.record(queue_time.as_secs_f64(), &labels);
+            }
+        }
+
+        let req = if self.state.request_context_extension {
+            let mut req = req;
+            req.extensions_mut().insert(RequestMetricsContext::new(
+                duration_start,
+                method.clone(),
+                matched_path.clone(),
+            ));
+            req
+        } else {
+            req
+        };
+
+        let timing_checkpoints = self
+            .state
+            .server_request_stage_duration
+            .is_some()
+            .then(|| TimingCheckpoints::new(duration_start));
+        let req = match &timing_checkpoints {
+            Some(timing_checkpoints) => {
+                let mut req = req;
+                req.extensions_mut().insert(timing_checkpoints.clone());
+                req
+            }
+            None => req,
+        };
+
+        #[cfg(feature = "tower-http")]
+        let (req, failure_classifier) = match &self.state.failure_classifier {
+            Some(make_classifier) => {
+                // classifiers take a request, which is rebuilt around the body afterwards
+                let (parts, body) = req.into_parts();
+                let req = http::Request::from_parts(parts, ());
+                let classifier = make_classifier(&req);
+                let (parts, ()) = req.into_parts();
+                (http::Request::from_parts(parts, body), Some(classifier))
+            }
+            None => (req, None),
+        };
+
+        let req = match self
+            .state
+            .request_classifier
+            .as_ref()
+            .filter(|_| extract_attributes)
+        {
+            Some(classifier) => {
+                let (parts, body) = req.into_parts();
+                if let Some(Some(class)) = catch_extractor_panic(
+                    &self.state.server_extractor_panics,
+                    EXTRACTOR_KIND_REQUEST,
+                    || classifier.classify(&parts),
+                ) {
+                    request_labels.push(KeyValue::new(HTTP_REQUEST_CLASS_LABEL, class));
+                }
+                http::Request::from_parts(parts, body)
+            }
+            None => req,
+        };
+
+        let extracted_start = request_labels.len();
+        let req = if self.state.request_attribute_extractors.is_empty() || !extract_attributes {
+            req
+        } else {
+            let (parts, body) = req.into_parts();
+            run_attribute_extractors(
+                &self.state.request_attribute_extractors,
+                &parts,
+                &mut request_labels,
+                &self.state.server_extractor_panics,
+                EXTRACTOR_KIND_REQUEST,
+            );
+            http::Request::from_parts(parts, body)
+        };
+        #[cfg(feature = "async-extractor")]
+        let req = {
+            let mut req = req;
+            if let Some(AsyncAttributes(attributes)) = req.extensions_mut().remove() {
+                if extract_attributes {
+                    request_labels.extend(attributes);
+                }
+            }
+            req
+        };
+        let extractor_attributes = cap_extracted_attributes(
+            &mut request_labels,
+            extracted_start,
+            self.state.max_extractor_attributes,
+            &self.state.server_extractor_attributes_dropped,
+        );
+
+        HTTPMetricsResponseFuture {
+            inner_response_future: self.inner_service.call(req),
+            layer_state: self.state.clone(),
+            metrics_state: ResponseFutureMetricsState {
+                http_request_duration_start: duration_start,
+                http_request_method: method,
+                http_route: matched_path,
+                network_protocol_name: protocol,
+                network_protocol_version: version,
+                url_scheme: scheme,
+                tenant_id,
+                request_labels,
+                extractor_attributes,
+                method_mismatch_route,
+                user_authenticated_pending,
+                extract_attributes,
+                #[cfg(feature = "span-attributes")]
+                span_context,
+                grpc_labels,
+                request_body_link,
+                response_body_link,
+                error_type_link,
+                #[cfg(feature = "tower-http")]
+                failure_classifier,
+                http_request_body_size: content_length,
+                http_request_size: request_size,
+                body_metrics_enabled,
+                open_tunnel,
+                range_request,
+                polls: 0,
+                timing_checkpoints,
+                #[cfg(feature = "load")]
+                pending_request: self.load.as_ref().map(ServiceLoad::start),
+            },
+            recorded: true,
+            completed: false,
+        }
+    }
+}
+
+impl<F, ResBody, E> Future for HTTPMetricsResponseFuture<F>
+where
+    F: Future<Output = result::Result<http::Response<ResBody>, E>>,
+{
+    type Output = result::Result<http::Response<ResBody>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        this.metrics_state.polls += 1;
+        let result = ready!(this.inner_response_future.poll(cx));
+        *this.completed = true;
+        #[cfg(feature = "load")]
+        if let Some(pending_request) = this.metrics_state.pending_request.take() {
+            pending_request.complete();
+        }
+        if !*this.recorded {
+            return Ready(result);
+        }
+
+        if let Some(server_request_polls) = &this.layer_state.server_request_polls {
+            server_request_polls.record(
+                this.metrics_state.polls,
+                &labels_server_route(this.metrics_state),
+            );
+        }
+        if let (Some(server_request_stage_duration), Some(timing_checkpoints)) = (
+            &this.layer_state.server_request_stage_duration,
+            &this.metrics_state.timing_checkpoints,
+        ) {
+            timing_checkpoints.record(
+                server_request_stage_duration,
+                &labels_server_route(this.metrics_state),
+            );
+        }
+
+        // fetch_sub returns the previous value, which includes the request completing here
+        let concurrent_requests = this
+            .layer_state
+            .active_requests
+            .fetch_sub(1, Ordering::Relaxed);
+
+        let (parts, body) = match result {
+            Ok(response) => response.into_parts(),
+            Err(err) => {
+                // errors are opaque unless classified by an ErrorTypeService inside
+                let error_type = this
+                    .metrics_state
+                    .error_type_link
+                    .as_ref()
+                    .and_then(ErrorTypeLink::get)
+                    .unwrap_or(OTHER_LABEL_VALUE);
+                record_server_request_error(this.layer_state, this.metrics_state, error_type);
+                return Ready(Err(err));
+            }
+        };
+
+        if let Some(request_body_link) = this.metrics_state.request_body_link.take() {
+            request_body_link.set_status_code(KeyValue::new(
+                HTTP_RESPONSE_STATUS_CODE_LABEL,
+                status_code_attribute(
+                    parts.status,
+                    this.layer_state.string_status_code,
+                    this.layer_state.kept_status_codes.as_deref(),
+                    status_code_value,
+                ),
+            ));
+        }
+
+        if let Some(open_tunnel) = this.metrics_state.open_tunnel.take() {
+            if parts.status.is_success() {
+                open_tunnel.established();
+            }
+        }
+
+        // active requests were counted without the route attributed to the response here
+        let active_route = this.metrics_state.http_route.clone();
+        if parts.status == http::StatusCode::METHOD_NOT_ALLOWED && active_route.is_none() {
+            this.metrics_state.http_route = this.metrics_state.method_mismatch_route.take();
+        }
+
+        let mut server_request_duration_labels = extract_labels_server_request_duration(
+            this.layer_state,
+            this.metrics_state,
+            parts.status,
+        );
+        if this.layer_state.cache_status_attribute {
+            if let Some(status) = cache_status(&parts.headers) {
+                server_request_duration_labels.push(KeyValue::new(HTTP_CACHE_STATUS_LABEL, status));
+            }
+        }
+        server_request_duration_labels.extend(this.metrics_state.request_labels.iter().cloned());
+        #[cfg(feature = "tower-http")]
+        let parts = match this.metrics_state.failure_classifier.take() {
+            Some(classifier) => {
+                let res = http::Response::from_parts(parts, ());
+                if let Some(class) = classifier.classify(&res) {
+                    server_request_duration_labels.push(KeyValue::new(ERROR_TYPE_LABEL, class));
+                }
+                res.into_parts().0
+            }
+            None => parts,
+        };
+        if this.layer_state.auth_outcome_attributes {
+            if let Some((outcome, scheme)) = auth_outcome(parts.status, &parts.headers) {
+                server_request_duration_labels
+                    .push(KeyValue::new(HTTP_AUTH_OUTCOME_LABEL, outcome));
+                if let Some(scheme) = scheme {
+                    server_request_duration_labels
+                        .push(KeyValue::new(HTTP_AUTH_SCHEME_LABEL, scheme));
+                }
+            }
+        }
+        if let Some(operation_ids) = &this.layer_state.operation_ids {
+            let operation_id = match parts.extensions.get::<OperationId>() {
+                Some(OperationId(operation_id)) => Some(StringValue::from(operation_id.clone())),
+                None => this.metrics_state.http_route.as_ref().and_then(|route| {
+                    operation_ids
+                        .get(
+                            this.metrics_state.http_request_method.as_str(),
+                            route.as_str(),
+                        )
+                        .cloned()
+                }),
+            };
+            if let Some(operation_id) = operation_id {
+                server_request_duration_labels
+                    .push(KeyValue::new(OPENAPI_OPERATION_ID_LABEL, operation_id));
+            }
+        }
+        if this.layer_state.route_fallback_attribute {
+            server_request_duration_labels.push(KeyValue::new(
+                HTTP_ROUTE_FALLBACK_LABEL,
+                parts.extensions.get::<RouteFallback>().is_some(),
+            ));
+        }
+        if this.layer_state.backend_attribute {
+            let backend = match parts.extensions.get::<Backend>() {
+                Some(Backend(backend)) => Some(StringValue::from(backend.clone())),
+                None => this.layer_state.backend.clone(),
+            };
+            if let Some(backend) = backend {
+                server_request_duration_labels
+                    .push(KeyValue::new(HTTP_SERVER_BACKEND_LABEL, backend));
+            }
+        }
+        if this.metrics_state.user_authenticated_pending {
+            push_user_labels(
+                parts.extensions.get::<Authenticated>(),
+                this.layer_state.principal_buckets,
+                &mut server_request_duration_labels,
+            );
+        }
+        if this.metrics_state.extract_attributes
+            && !this.layer_state.response_attribute_extractors.is_empty()
+        {
+            let mut extracted_labels = take_labels();
+            run_attribute_extractors(
+                &this.layer_state.response_attribute_extractors,
+                &parts,
+                &mut extracted_labels,
+                &this.layer_state.server_extractor_panics,
+                EXTRACTOR_KIND_RESPONSE,
+            );
+            cap_extracted_attributes(
+                &mut extracted_labels,
+                0,
+                this.layer_state
+                    .max_extractor_attributes
+                    .saturating_sub(this.metrics_state.extractor_attributes),
+                &this.layer_state.server_extractor_attributes_dropped,
+            );
+            server_request_duration_labels.extend(extracted_labels.drain(..));
+            recycle_labels(extracted_labels);
+        }
+        record_server_request_duration(
+            this.layer_state,
+            this.metrics_state,
+            Some(parts.status),
+            &server_request_duration_labels,
+        );
+
+        if let Some(server_informational_responses) =
+            &this.layer_state.server_informational_responses
+        {
+            if parts.status.is_informational() {
+                server_informational_responses.add(
+                    1,
+                    &labels_server_body_size(this.layer_state, this.metrics_state, parts.status),
+                );
+            }
+        }
+
+        if let Some(record_values) = parts.extensions.get::<RecordValues>() {
+            record_custom_histograms(
+                &this.layer_state.custom_histograms,
+                record_values,
+                &server_request_duration_labels,
+            );
+        }
+        record_custom_instruments(
+            &this.layer_state.custom_instruments,
+            &parts,
+            &server_request_duration_labels,
+        );
+
+        if let Some(usage_units) = &this.layer_state.usage_units {
+            if let Some(UsageUnits(units)) = parts.extensions.get::<UsageUnits>() {
+                let mut labels = labels_server_route(this.metrics_state);
+                if let Some(tenant_id) = &this.metrics_state.tenant_id {
+                    labels.push(KeyValue::new(TENANT_ID_LABEL, tenant_id.clone()));
+                }
+                usage_units.add(*units, &labels);
+            }
+        }
+
+        if let (Some(limit_gauge), Some(remaining_gauge)) = (
+            &this.layer_state.server_rate_limit_limit,
+            &this.layer_state.server_rate_limit_remaining,
+        ) {
+            let (limit, remaining) = rate_limit(&parts.headers);
+            if limit.is_some() || remaining.is_some() {
+                let labels = labels_server_route(this.metrics_state);
+                if let Some(limit) = limit {
+                    limit_gauge.record(limit, &labels);
+                }
+                if let Some(remaining) = remaining {
+                    remaining_gauge.record(remaining, &labels);
+                }
+            }
+        }
+
+        if let Some(server_request_throttled) = &this.layer_state.server_request_throttled {
+            if throttled(parts.status, &parts.headers) {
+                let mut labels = labels_server_route(this.metrics_state);
+                labels.push(KeyValue::new(
+                    HTTP_RESPONSE_STATUS_CODE_LABEL,
+                    status_code_attribute(
+                        parts.status,
+                        this.layer_state.string_status_code,
+                        this.layer_state.kept_status_codes.as_deref(),
+                        status_code_value,
+                    ),
+                ));
+                if let Some(ThrottlePolicy(policy)) = parts.extensions.get::<ThrottlePolicy>() {
+                    labels.push(KeyValue::new(HTTP_THROTTLE_POLICY_LABEL, policy.clone()));
+                }
+                server_request_throttled.add(1, &labels);
+            }
+        }
+
+        if let Some(server_range_requests) = &this.layer_state.server_range_requests {
+            let partial_content = parts.status == http::StatusCode::PARTIAL_CONTENT;
+            if this.metrics_state.range_request || partial_content {
+                let mut labels = labels_server_route(this.metrics_state);
+                labels.push(KeyValue::new(
+                    HTTP_RESPONSE_STATUS_CODE_LABEL,
+                    status_code_attribute(
+                        parts.status,
+                        this.layer_state.string_status_code,
+                        this.layer_state.kept_status_codes.as_deref(),
+                        status_code_value,
+                    ),
+                ));
+                server_range_requests.add(1, &labels);
+            }
+            let range_size = content_range_size(&parts.headers).filter(|_| partial_content);
+            if let (Some(server_response_range_size), Some(range_size)) =
+                (&this.layer_state.server_response_range_size, range_size)
+            {
+                server_response_range_size
+                    .record(range_size, &labels_server_route(this.metrics_state));
+            }
+        }
+
+        let server_active_request_labels = labels_server_active_request(
+            this.layer_state,
+            &this.metrics_state.http_request_method,
+            this.metrics_state.url_scheme.as_ref(),
+            active_route.as_ref(),
+        );
+        if let Some(server_active_requests) = &this.layer_state.server_active_requests {
+            server_active_requests.add(-1, &server_active_request_labels);
+        }
+
+        if let Some(server_concurrent_requests) = &this.layer_state.server_concurrent_requests {
+            server_concurrent_requests.record(concurrent_requests, &server_active_request_labels);
+        }
+
+        if let (Some(server_request_body_size), Some(content_length)) = (
+            &this.layer_state.server_request_body_size,
+            this.metrics_state.http_request_body_size,
+        ) {
+            let server_request_body_size_labels =
+                labels_server_body_size(this.layer_state, this.metrics_state, parts.status);
+
+            server_request_body_size.record(content_length, &server_request_body_size_labels);
+        }
+        if let (Some(server_request_size), Some(request_size)) = (
+            &this.layer_state.server_request_size,
+            this.metrics_state.http_request_size,
+        ) {
+            server_request_size.record(
+                request_size,
+                &labels_server_body_size(this.layer_state, this.metrics_state, parts.status),
+            );
+        }
+
+        // The response body size is recorded up front from the Content-Length, and otherwise
+        // left to the response body layer, which takes the exact size reported by the body or
+        // counts its bytes as the body is streamed.
+        let response_body_link = this.metrics_state.response_body_link.take();
+        let observe_body = response_body_link.is_some();
+        let mut size_hint = false;
+        let mut count_response_body_size = false;
+        let server_response_body_size = this
+            .layer_state
+            .server_response_body_size
+            .as_ref()
+            .filter(|_| this.metrics_state.body_metrics_enabled);
+        if let Some(server_response_body_size) = server_response_body_size {
+            let source = this.layer_state.response_body_size_source;
+            let header_size = match source {
+                ResponseBodySizeSource::Body => None,
+                ResponseBodySizeSource::ContentLength
+                | ResponseBodySizeSource::ContentLengthOrBody => {
+                    response_content_length(this.metrics_state, &parts)
+                }
+            };
+            match header_size {
+                Some(size) => server_response_body_size.record(
+                    size,
+                    &labels_server_body_size(this.layer_state, this.metrics_state, parts.status),
+                ),
+                None => {
+                    size_hint = observe_body;
+                    count_response_body_size =
+                        observe_body && source != ResponseBodySizeSource::ContentLength;
+                }
+            }
+        }
+
+        let mut count_network_io = false;
+        if let Some(server_network_io) = &this.layer_state.server_network_io {
+            let start_line_size = "HTTP/1.1 ".len()
+                + parts.status.as_str().len()
+                + parts
+                    .status
+                    .canonical_reason()
+                    .map_or(0, |reason| reason.len() + 1);
+            let mut transmitted = header_section_size(start_line_size, &parts.headers);
+            match response_content_length(this.metrics_state, &parts) {
+                Some(size) => transmitted += size,
+                None => count_network_io = observe_body,
+            }
+            server_network_io.add(
+                transmitted,
+                &[KeyValue::new(
+                    NETWORK_IO_DIRECTION_LABEL,
+                    NETWORK_IO_DIRECTION_TRANSMIT,
+                )],
+            );
+        }
+
+        if let Some(response_body_link) = response_body_link {
+            let body_metrics_enabled = this.metrics_state.body_metrics_enabled;
+            let observers = ResponseBodyObservers {
+                size_hint,
+                count_size: count_response_body_size,
+                frame_metrics: body_metrics_enabled
+                    && this.layer_state.server_response_body_frame_size.is_some(),
+                bytes_labels: (body_metrics_enabled
+                    && this.layer_state.server_response_body_bytes.is_some())
+                .then(|| labels_server_route(this.metrics_state)),
+                count_network_io,
+                grpc_labels: this.metrics_state.grpc_labels.take(),
+            };
+            if observers.any() {
+                response_body_link.set(ResponseBodyMetricsState::new(
+                    this.layer_state.clone(),
+                    labels_server_body_size(this.layer_state, this.metrics_state, parts.status),
+                    observers,
+                ));
+            }
+        }
+
+        Ready(Ok(http::Response::from_parts(parts, body)))
+    }
+}
@@ -0,0 +1,42 @@
+//! Extension of tower's [`ServiceBuilder`] adding the layer in the usual layering style.
+
+use opentelemetry::metrics::Meter;
+use tower::ServiceBuilder;
+use tower_layer::Stack;
+
+use crate::{HTTPMetricsLayer, HTTPMetricsLayerBuilder};
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl<L> Sealed for tower::ServiceBuilder<L> {}
+}
+
+/// Extension trait adding [`HTTPMetricsLayer`] to a [`ServiceBuilder`].
+///
+/// ```
+/// use opentelemetry::global;
+/// use tower::ServiceBuilder;
+/// use tower_otel_http_metrics::ServiceBuilderExt;
+///
+/// let meter = global::meter("my-service");
+/// let builder = ServiceBuilder::new().otel_http_metrics(meter);
+/// # let _ = builder;
+/// ```
+///
+/// For a layer configured beyond its meter, build it with [`HTTPMetricsLayerBuilder`] and add it
+/// with [`ServiceBuilder::layer`] instead.
+pub trait ServiceBuilderExt<L>: sealed::Sealed {
+    /// Add an [`HTTPMetricsLayer`] recording with `meter` and the default configuration.
+    fn otel_http_metrics(self, meter: Meter) -> ServiceBuilder<Stack<HTTPMetricsLayer, L>>;
+}
+
+impl<L> ServiceBuilderExt<L> for ServiceBuilder<L> {
+    fn otel_http_metrics(self, meter: Meter) -> ServiceBuilder<Stack<HTTPMetricsLayer, L>> {
+        let layer = HTTPMetricsLayerBuilder::new()
+            .with_meter(meter)
+            .build()
+            .expect("building a layer with a meter does not fail");
+        self.layer(layer)
+    }
+}
@@ -0,0 +1,42 @@
+//! Latency objectives per route, counting the requests which miss them.
+//!
+//! Endpoints served by one layer rarely share a latency objective: a health check, a search and a
+//! report export each have their own. Routes are given a threshold; their requests taking longer
+//! are counted in `http.server.request.slow`, and every request to them is recorded into
+//! `http.server.request.duration` with an `slo.violated` attribute, so good and bad events of the
+//! objective can be read off the same histogram.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use opentelemetry::metrics::Counter;
+use opentelemetry::StringValue;
+
+pub(crate) const HTTP_SERVER_REQUEST_SLOW_METRIC: &str = "http.server.request.slow";
+pub(crate) const HTTP_SERVER_REQUEST_SLOW_UNIT: &str = "{request}";
+
+pub(crate) const SLO_VIOLATED_LABEL: &str = "slo.violated";
+
+/// Latency thresholds of routes, with the counter of requests exceeding them.
+pub(crate) struct SloThresholds {
+    thresholds: HashMap<Cow<'static, str>, Duration>,
+    pub(crate) slow_requests: Counter<u64>,
+}
+
+impl SloThresholds {
+    pub(crate) fn new(
+        thresholds: HashMap<Cow<'static, str>, Duration>,
+        slow_requests: Counter<u64>,
+    ) -> Self {
+        SloThresholds {
+            thresholds,
+            slow_requests,
+        }
+    }
+
+    /// The threshold of `route`, if one is configured.
+    pub(crate) fn threshold(&self, route: Option<&StringValue>) -> Option<Duration> {
+        self.thresholds.get(route?.as_str()).copied()
+    }
+}
@@ -0,0 +1,52 @@
+//! Recording the measured duration and outcome of a request on its trace span.
+//!
+//! Spans started by tracing middleware time the request on their own, from a slightly different
+//! start and end than the layer, so durations seen in traces and logs differ from the metric's.
+//! Recording the metric's values as span attributes lets trace and log based analysis work with
+//! exactly the same numbers.
+//!
+//! The span is taken from the [`Context`] inserted into the request extensions, as done by
+//! propagating middleware, falling back to the current context when the request is called. The
+//! layer therefore has to sit inside the tracing middleware which starts the request's span.
+
+use std::time::Duration;
+
+use opentelemetry::trace::TraceContextExt;
+use opentelemetry::{Context, KeyValue};
+
+use crate::attributes::ERROR_TYPE_LABEL;
+use crate::{HTTP_RESPONSE_STATUS_CODE_LABEL, HTTP_SERVER_DURATION_METRIC};
+
+/// Context holding the span of a request.
+pub(crate) fn request_context(extensions: &http::Extensions) -> Context {
+    extensions
+        .get::<Context>()
+        .cloned()
+        .unwrap_or_else(Context::current)
+}
+
+/// Record the duration, in seconds as recorded by the metric, and the outcome on the span.
+pub(crate) fn record_span_attributes(
+    context: &Context,
+    duration: Duration,
+    status: Option<http::StatusCode>,
+    labels: &[KeyValue],
+) {
+    let span = context.span();
+    if !span.is_recording() {
+        return;
+    }
+    span.set_attribute(KeyValue::new(
+        HTTP_SERVER_DURATION_METRIC,
+        duration.as_secs_f64(),
+    ));
+    if let Some(status) = status {
+        span.set_attribute(KeyValue::new(
+            HTTP_RESPONSE_STATUS_CODE_LABEL,
+            i64::from(status.as_u16()),
+        ));
+    }
+    if let Some(error_type) = labels.iter().find(|kv| kv.key.as_str() == ERROR_TYPE_LABEL) {
+        span.set_attribute(error_type.clone());
+    }
+}
@@ -0,0 +1,173 @@
+//! TLS handshake duration of accepted connections.
+//!
+//! The handshake happens before the first request of a connection reaches the middleware, so
+//! neither `http.server.request.duration` nor its accept time start see it, yet a burst of new
+//! connections, e.g. after a deploy or a load balancer reshuffle, can keep workers busy with
+//! handshakes for long stretches. [`TlsHandshakeMetrics`] wraps the handshake future of any TLS
+//! acceptor, such as the one returned by `tokio_rustls::TlsAcceptor::accept`, and records
+//! `tls.server.handshake.duration` once it completes.
+
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use futures_util::ready;
+use opentelemetry::metrics::{Histogram, Meter};
+use opentelemetry::KeyValue;
+use pin_project_lite::pin_project;
+
+use crate::attributes::ERROR_TYPE_LABEL;
+
+const TLS_SERVER_HANDSHAKE_DURATION_METRIC: &str = "tls.server.handshake.duration";
+const TLS_SERVER_HANDSHAKE_DURATION_UNIT: &str = "s";
+
+const TLS_SERVER_HANDSHAKE_DURATION_BOUNDARIES: [f64; 14] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0,
+];
+
+const ERROR_TYPE_CONNECTION_RESET: &str = "connection_reset";
+const ERROR_TYPE_TLS: &str = "tls_error";
+const ERROR_TYPE_TIMEOUT: &str = "timeout";
+const ERROR_TYPE_OTHER: &str = "_OTHER";
+
+#[derive(Clone)]
+/// Records `tls.server.handshake.duration` for the TLS handshakes of accepted connections.
+///
+/// Failed handshakes are recorded with an `error.type` of `connection_reset` when the client went
+/// away, `tls_error` for protocol errors such as an unsupported version or a bad certificate,
+/// or `timeout`; handshakes abandoned before completing, typically by a timeout around them,
+/// are recorded as `timeout` as well.
+///
+/// ```
+/// use std::future::Future;
+/// use std::io;
+///
+/// use tower_otel_http_metrics::TlsHandshakeMetrics;
+///
+/// // called with e.g. `tokio_rustls::TlsAcceptor::accept(stream)` for each accepted connection
+/// async fn handshake<S>(
+///     metrics: &TlsHandshakeMetrics,
+///     handshake: impl Future<Output = io::Result<S>>,
+/// ) -> io::Result<S> {
+///     metrics.handshake(handshake).await
+/// }
+///
+/// let metrics = TlsHandshakeMetrics::new(&opentelemetry::global::meter("my-server"));
+/// ```
+pub struct TlsHandshakeMetrics {
+    server_handshake_duration: Histogram<f64>,
+}
+
+impl TlsHandshakeMetrics {
+    /// Register the handshake duration histogram with the given meter.
+    pub fn new(meter: &Meter) -> Self {
+        TlsHandshakeMetrics {
+            server_handshake_duration: meter
+                .f64_histogram(TLS_SERVER_HANDSHAKE_DURATION_METRIC)
+                .with_description("Duration of TLS handshakes of inbound connections.")
+                .with_unit(TLS_SERVER_HANDSHAKE_DURATION_UNIT)
+                .with_boundaries(TLS_SERVER_HANDSHAKE_DURATION_BOUNDARIES.to_vec())
+                .build(),
+        }
+    }
+
+    /// Time the handshake future of a TLS acceptor, from now until it completes.
+    pub fn handshake<F>(&self, handshake: F) -> TlsHandshake<F> {
+        TlsHandshake {
+            inner_handshake: handshake,
+            server_handshake_duration: self.server_handshake_duration.clone(),
+            start: Instant::now(),
+            completed: false,
+        }
+    }
+}
+
+impl fmt::Debug for TlsHandshakeMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsHandshakeMetrics")
+            .finish_non_exhaustive()
+    }
+}
+
+pin_project! {
+    /// Handshake future returned by [`TlsHandshakeMetrics::handshake`]
+    pub struct TlsHandshake<F> {
+        #[pin]
+        inner_handshake: F,
+        server_handshake_duration: Histogram<f64>,
+        start: Instant,
+        completed: bool,
+    }
+
+    impl<F> PinnedDrop for TlsHandshake<F> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if *this.completed {
+                return;
+            }
+            this.server_handshake_duration.record(
+                this.start.elapsed().as_secs_f64(),
+                &[KeyValue::new(ERROR_TYPE_LABEL, ERROR_TYPE_TIMEOUT)],
+            );
+        }
+    }
+}
+
+impl<F, S, E> Future for TlsHandshake<F>
+where
+    F: Future<Output = Result<S, E>>,
+    E: StdError + 'static,
+{
+    type Output = Result<S, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.inner_handshake.poll(cx));
+        *this.completed = true;
+        let duration = this.start.elapsed().as_secs_f64();
+        match &result {
+            Ok(_) => this.server_handshake_duration.record(duration, &[]),
+            Err(err) => this.server_handshake_duration.record(
+                duration,
+                &[KeyValue::new(ERROR_TYPE_LABEL, handshake_error_type(err))],
+            ),
+        }
+        Poll::Ready(result)
+    }
+}
+
+impl<F> fmt::Debug for TlsHandshake<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsHandshake")
+            .field("start", &self.start)
+            .field("completed", &self.completed)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Classify a handshake failure by the first I/O error in its source chain.
+///
+/// TLS acceptors report protocol failures, e.g. rustls' alerts and invalid messages, as I/O errors
+/// of kind `InvalidData`, and the client going away as the stream ending early.
+fn handshake_error_type(err: &(dyn StdError + 'static)) -> &'static str {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            match err.kind() {
+                io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof => return ERROR_TYPE_CONNECTION_RESET,
+                io::ErrorKind::TimedOut => return ERROR_TYPE_TIMEOUT,
+                io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput => return ERROR_TYPE_TLS,
+                _ => {}
+            }
+        }
+        source = err.source();
+    }
+    ERROR_TYPE_OTHER
+}
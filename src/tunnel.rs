@@ -0,0 +1,78 @@
+//! `CONNECT` requests of forward proxies, which establish tunnels rather than request a resource.
+//!
+//! A `CONNECT` request targets a host and port in authority form, so it has neither a route nor a
+//! scheme, and its response ends the HTTP exchange while the tunnel carries on over the upgraded
+//! connection. Its `http.server.request.duration` measures how long the tunnel took to establish;
+//! how long the tunnel stays open is up to the handler, which holds an [`OpenTunnel`] for it.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use opentelemetry::metrics::UpDownCounter;
+
+pub(crate) const HTTP_SERVER_OPEN_TUNNELS_METRIC: &str = "http.server.open_tunnels";
+pub(crate) const HTTP_SERVER_OPEN_TUNNELS_UNIT: &str = "{tunnel}";
+
+#[derive(Clone)]
+/// Request extension of `CONNECT` requests keeping their tunnel counted in
+/// `http.server.open_tunnels`.
+///
+/// Inserted when enabled with [`HTTPMetricsLayerBuilder::with_open_tunnels_counter`]. The tunnel
+/// is counted once the layer sees a `2xx` response, until the extension and all of its clones are
+/// dropped, so the handler takes it out of the request and moves it into the task serving the
+/// tunnel:
+///
+/// ```
+/// use tower_otel_http_metrics::OpenTunnel;
+///
+/// async fn connect(mut req: http::Request<()>) -> http::Response<()> {
+///     let open_tunnel = req.extensions_mut().remove::<OpenTunnel>();
+///     // spawned with the upgraded connection, e.g. from `hyper::upgrade::on(&mut req)`
+///     let _tunnel = async move {
+///         let _open_tunnel = open_tunnel;
+///         // copy bytes between the client and the target until either side closes
+///     };
+///     http::Response::new(())
+/// }
+/// ```
+///
+/// [`HTTPMetricsLayerBuilder::with_open_tunnels_counter`]: crate::HTTPMetricsLayerBuilder::with_open_tunnels_counter
+pub struct OpenTunnel(Arc<TunnelState>);
+
+struct TunnelState {
+    open_tunnels: UpDownCounter<i64>,
+    established: AtomicBool,
+}
+
+impl OpenTunnel {
+    pub(crate) fn new(open_tunnels: UpDownCounter<i64>) -> Self {
+        OpenTunnel(Arc::new(TunnelState {
+            open_tunnels,
+            established: AtomicBool::new(false),
+        }))
+    }
+
+    /// Count the tunnel as open, once its `CONNECT` request was answered successfully.
+    pub(crate) fn established(&self) {
+        if !self.0.established.swap(true, Ordering::Relaxed) {
+            self.0.open_tunnels.add(1, &[]);
+        }
+    }
+}
+
+impl fmt::Debug for OpenTunnel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpenTunnel")
+            .field("established", &self.0.established.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl Drop for TunnelState {
+    fn drop(&mut self) {
+        if *self.established.get_mut() {
+            self.open_tunnels.add(-1, &[]);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Authentication state of requests, reported by auth middleware through an extension.
+//!
+//! Auth middleware marks authenticated requests with the [`Authenticated`] extension, which the
+//! layer records as the `user.authenticated` attribute, so traffic can be split by authentication
+//! state without a custom extractor in every application. The principal is never recorded; it can
+//! only be hashed into one of a fixed number of buckets, recorded as `user.principal.bucket`, to
+//! tell whether traffic is spread across users or concentrated on a few.
+
+use std::borrow::Cow;
+
+use opentelemetry::KeyValue;
+
+pub(crate) const USER_AUTHENTICATED_LABEL: &str = "user.authenticated";
+pub(crate) const USER_PRINCIPAL_BUCKET_LABEL: &str = "user.principal.bucket";
+
+#[derive(Clone, Debug, Default)]
+/// Extension marking a request as authenticated, recorded as `user.authenticated`.
+///
+/// Auth middleware applied around the layer inserts it into the request extensions; middleware
+/// and handlers inside the layer insert it into the response extensions instead, since the request
+/// has already passed the layer. Requests marked in neither are recorded as not authenticated.
+///
+/// ```
+/// use tower_otel_http_metrics::Authenticated;
+///
+/// let mut req = http::Request::new(());
+/// // in auth middleware, once the credentials are verified
+/// req.extensions_mut()
+///     .insert(Authenticated::new().with_principal("user-1234"));
+/// ```
+pub struct Authenticated {
+    principal: Option<Cow<'static, str>>,
+}
+
+impl Authenticated {
+    pub fn new() -> Self {
+        Authenticated::default()
+    }
+
+    /// Set the authenticated principal, hashed into `user.principal.bucket` when enabled with
+    /// [`HTTPMetricsLayerBuilder::with_principal_buckets`].
+    ///
+    /// [`HTTPMetricsLayerBuilder::with_principal_buckets`]: crate::HTTPMetricsLayerBuilder::with_principal_buckets
+    pub fn with_principal(self, principal: impl Into<Cow<'static, str>>) -> Self {
+        Authenticated {
+            principal: Some(principal.into()),
+        }
+    }
+}
+
+/// Push `user.authenticated`, and the principal bucket when there are `principal_buckets`.
+pub(crate) fn push_user_labels(
+    authenticated: Option<&Authenticated>,
+    principal_buckets: Option<u32>,
+    labels: &mut impl Extend<KeyValue>,
+) {
+    labels.extend([KeyValue::new(
+        USER_AUTHENTICATED_LABEL,
+        authenticated.is_some(),
+    )]);
+    let principal = authenticated.and_then(|authenticated| authenticated.principal.as_deref());
+    if let (Some(principal), Some(buckets)) = (principal, principal_buckets) {
+        labels.extend([KeyValue::new(
+            USER_PRINCIPAL_BUCKET_LABEL,
+            i64::from(principal_bucket(principal, buckets)),
+        )]);
+    }
+}
+
+/// Bucket of a principal, hashed with FNV-1a so buckets are stable across processes and releases.
+fn principal_bucket(principal: &str, buckets: u32) -> u32 {
+    let hash = principal
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    (hash % u64::from(buckets.max(1))) as u32
+}
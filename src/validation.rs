@@ -0,0 +1,163 @@
+//! Validation of recorded attribute sets against the HTTP semantic conventions, for development.
+//!
+//! Every attribute set recorded into `http.server.request.duration` is checked against the
+//! requirement levels of the metric: required attributes must be present and well formed, one of
+//! the conditionally required `http.response.status_code` and `error.type` must be present, and
+//! opt-in attributes, including those of custom extractors, must follow the attribute naming
+//! rules. Violations are reported to a callback rather than failing the request.
+//!
+//! Validation inspects each attribute set on the request path and is meant for development and
+//! tests, which is why it sits behind the `semconv-validation` feature.
+
+use std::fmt;
+use std::sync::Arc;
+
+use opentelemetry::{KeyValue, Value};
+
+use crate::labels::OTHER_STATUS_CODE;
+use crate::HTTP_SERVER_DURATION_METRIC;
+
+pub(crate) type SemconvViolationReport = dyn Fn(&SemconvViolation) + Send + Sync;
+
+/// Methods known to the HTTP semantic conventions; others must be recorded as `_OTHER`
+const KNOWN_METHODS: [&str; 10] = [
+    "CONNECT", "DELETE", "GET", "HEAD", "OPTIONS", "PATCH", "POST", "PUT", "TRACE", "_OTHER",
+];
+
+#[derive(Clone, Debug)]
+/// An attribute set recorded by the layer which does not comply with the HTTP semantic conventions.
+///
+/// Reported to the callback given to [`HTTPMetricsLayerBuilder::with_semconv_validation`].
+///
+/// [`HTTPMetricsLayerBuilder::with_semconv_validation`]: crate::HTTPMetricsLayerBuilder::with_semconv_validation
+pub struct SemconvViolation {
+    metric: &'static str,
+    attribute: String,
+    problem: &'static str,
+}
+
+impl SemconvViolation {
+    /// The name of the metric the attribute set was recorded into.
+    pub fn metric(&self) -> &str {
+        self.metric
+    }
+
+    /// The key of the offending or missing attribute.
+    pub fn attribute(&self) -> &str {
+        &self.attribute
+    }
+
+    /// What is wrong with the attribute.
+    pub fn problem(&self) -> &str {
+        self.problem
+    }
+}
+
+impl fmt::Display for SemconvViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.metric, self.attribute, self.problem)
+    }
+}
+
+pub(crate) struct SemconvValidator {
+    report: Arc<SemconvViolationReport>,
+    string_status_code: bool,
+}
+
+impl SemconvValidator {
+    pub(crate) fn new(report: Arc<SemconvViolationReport>, string_status_code: bool) -> Self {
+        SemconvValidator {
+            report,
+            string_status_code,
+        }
+    }
+
+    fn violation(&self, attribute: &str, problem: &'static str) {
+        (self.report)(&SemconvViolation {
+            metric: HTTP_SERVER_DURATION_METRIC,
+            attribute: attribute.to_owned(),
+            problem,
+        });
+    }
+
+    /// Check an attribute set of `http.server.request.duration`.
+    pub(crate) fn validate_request_duration(&self, labels: &[KeyValue]) {
+        let get = |key: &str| labels.iter().find(|kv| kv.key.as_str() == key);
+
+        match get("http.request.method").map(|kv| &kv.value) {
+            None => self.violation("http.request.method", "required attribute is missing"),
+            Some(method) if !KNOWN_METHODS.contains(&&*method.as_str()) => self.violation(
+                "http.request.method",
+                "unknown methods must be recorded as _OTHER",
+            ),
+            Some(_) => {}
+        }
+
+        if get("url.scheme").is_none() {
+            self.violation("url.scheme", "required attribute is missing");
+        }
+
+        let status_code = get("http.response.status_code");
+        match status_code.map(|kv| &kv.value) {
+            // the status class stands in for the status code when recorded instead
+            None if get("error.type").is_none() && get("http.response.status_class").is_none() => {
+                self.violation(
+                    "http.response.status_code",
+                    "either the status code or error.type is conditionally required",
+                )
+            }
+            Some(Value::String(value))
+                if !self.string_status_code && value.as_str() != OTHER_STATUS_CODE =>
+            {
+                self.violation(
+                    "http.response.status_code",
+                    "status code must be recorded as an integer",
+                )
+            }
+            _ => {}
+        }
+
+        if get("network.protocol.name").is_some() && get("network.protocol.version").is_none() {
+            self.violation(
+                "network.protocol.version",
+                "recommended attribute is missing while network.protocol.name is set",
+            );
+        }
+
+        for (i, kv) in labels.iter().enumerate() {
+            let key = kv.key.as_str();
+            if labels[..i]
+                .iter()
+                .any(|earlier| earlier.key.as_str() == key)
+            {
+                self.violation(key, "attribute is recorded more than once");
+            }
+            if let Some(problem) = key_problem(key) {
+                self.violation(key, problem);
+            }
+            if matches!(&kv.value, Value::String(value) if value.as_str().is_empty()) {
+                self.violation(key, "attribute value is empty");
+            }
+        }
+    }
+}
+
+/// The problem with an attribute key under the semconv naming rules, if any.
+fn key_problem(key: &str) -> Option<&'static str> {
+    if key.is_empty() {
+        return Some("attribute key is empty");
+    }
+    if key.starts_with("otel.") {
+        return Some("the otel namespace is reserved");
+    }
+    if key.split('.').any(str::is_empty) {
+        return Some("attribute key has an empty namespace or name");
+    }
+    if !key
+        .bytes()
+        .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_' || b == b'.')
+    {
+        return Some("attribute keys must be lowercase letters, digits, '_' and '.'");
+    }
+    None
+}
@@ -0,0 +1,51 @@
+//! Durations are measured from the accept time of the connection when enabled.
+
+mod common;
+
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{AcceptTime, AcceptTimeService, HTTPMetricsLayerBuilder};
+
+use common::{block_on, TestMetrics};
+
+const BACKLOG_TIME: Duration = Duration::from_millis(30);
+
+/// The recorded duration of a request accepted `BACKLOG_TIME` before it was handled.
+fn duration_after_backlog(from_accept_time: bool) -> f64 {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_duration_from_accept_time(from_accept_time)
+        .build()
+        .unwrap();
+    let accepted_at = Instant::now();
+    std::thread::sleep(BACKLOG_TIME);
+    let service = AcceptTimeService::new(
+        layer.layer(tower::service_fn(|req: http::Request<String>| async move {
+            let accept_time = req.extensions().get::<AcceptTime>().unwrap();
+            assert!(accept_time.instant().elapsed() >= BACKLOG_TIME);
+            Ok::<_, Infallible>(http::Response::new(String::new()))
+        })),
+        accepted_at,
+    );
+    block_on(service.oneshot(http::Request::new(String::new()))).unwrap();
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("http.request.method").unwrap(), "GET");
+    duration[0].value
+}
+
+#[test]
+fn duration_includes_the_backlog() {
+    assert!(duration_after_backlog(true) >= BACKLOG_TIME.as_secs_f64());
+}
+
+#[test]
+fn duration_starts_at_call_by_default() {
+    assert!(duration_after_backlog(false) < BACKLOG_TIME.as_secs_f64());
+}
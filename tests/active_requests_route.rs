@@ -0,0 +1,64 @@
+//! In-flight requests can be counted per route.
+
+mod common;
+
+use tower_otel_http_metrics::{HTTPMetricsLayer, HTTPMetricsLayerBuilder};
+
+use common::{send, TestMetrics};
+
+const ACTIVE_REQUESTS: &str = "http.server.active_requests";
+
+/// Layer routing `/users` requests, adding the route to active requests if `active_requests_route`.
+fn layer(metrics: &TestMetrics, active_requests_route: bool) -> HTTPMetricsLayer {
+    HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|parts| (parts.uri.path() == "/users").then_some("/users"))
+        .with_active_requests_route(active_requests_route)
+        .build()
+        .unwrap()
+}
+
+/// The routes of active requests with their in-flight count.
+fn active_requests(metrics: &TestMetrics) -> Vec<(Option<String>, i64)> {
+    metrics
+        .points::<i64>(ACTIVE_REQUESTS)
+        .iter()
+        .map(|point| (point.attribute("http.route"), point.value))
+        .collect()
+}
+
+#[test]
+fn active_requests_are_counted_per_route_when_enabled() {
+    let metrics = TestMetrics::new();
+    let layer = layer(&metrics, true);
+    let request = http::Request::get("/users").body(String::new()).unwrap();
+    send(&layer, request, |_| {
+        assert_eq!(
+            active_requests(&metrics),
+            [(Some(String::from("/users")), 1)]
+        );
+        http::Response::new(String::new())
+    });
+
+    assert_eq!(
+        active_requests(&metrics),
+        [(Some(String::from("/users")), 0)]
+    );
+    let active = metrics.points::<i64>(ACTIVE_REQUESTS);
+    assert_eq!(active[0].attribute("http.request.method").unwrap(), "GET");
+}
+
+#[test]
+fn active_requests_have_no_route_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = layer(&metrics, false);
+    let request = http::Request::get("/users").body(String::new()).unwrap();
+    send(&layer, request, |_| {
+        assert_eq!(active_requests(&metrics), [(None, 1)]);
+        http::Response::new(String::new())
+    });
+
+    assert_eq!(active_requests(&metrics), [(None, 0)]);
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration[0].attribute("http.route").unwrap(), "/users");
+}
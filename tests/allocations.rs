@@ -0,0 +1,174 @@
+//! The default request path of the layer must not allocate.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::convert::Infallible;
+use std::future::{ready, Future, Ready};
+use std::pin::pin;
+#[cfg(all(feature = "axum", feature = "route-cache"))]
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use tower_layer::Layer;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+use tower_service::Service;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // only count allocations on the thread measuring them
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get().map(|count| count + 1)));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations(f: impl FnOnce()) -> usize {
+    ALLOCATIONS.with(|count| count.set(Some(0)));
+    f();
+    ALLOCATIONS.with(|count| count.replace(None)).unwrap()
+}
+
+#[derive(Clone)]
+struct Handler;
+
+impl Service<http::Request<String>> for Handler {
+    type Response = http::Response<String>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: http::Request<String>) -> Self::Future {
+        ready(Ok(http::Response::new(String::new())))
+    }
+}
+
+fn serve<S>(service: &mut S, req: http::Request<String>)
+where
+    S: Service<http::Request<String>>,
+    S::Error: std::fmt::Debug,
+{
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(service.poll_ready(&mut cx).is_ready());
+    let future = pin!(service.call(req));
+    match future.poll(&mut cx) {
+        Poll::Ready(response) => drop(response.unwrap()),
+        Poll::Pending => panic!("handler is always ready"),
+    }
+}
+
+#[test]
+fn default_request_path_does_not_allocate() {
+    let layer = HTTPMetricsLayerBuilder::default()
+        .with_default_url_scheme("https")
+        .build()
+        .unwrap();
+    let mut service = layer.layer(Handler);
+
+    let request = || {
+        http::Request::builder()
+            .method(http::Method::POST)
+            .header(http::header::CONTENT_LENGTH, "0")
+            .body(String::new())
+            .unwrap()
+    };
+
+    // the first request formats the shared attribute values
+    serve(&mut service, request());
+
+    let requests: Vec<_> = (0..100).map(|_| request()).collect();
+    let allocations = count_allocations(|| {
+        for req in requests {
+            serve(&mut service, req);
+        }
+    });
+    assert_eq!(allocations, 0);
+}
+
+/// `MatchedPath` axum inserts when routing `uri` through a router serving `route`.
+#[cfg(all(feature = "axum", feature = "route-cache"))]
+fn matched_path(route: &str, uri: &str) -> axum::extract::MatchedPath {
+    let matched = Arc::new(Mutex::new(None));
+    let handler_matched = matched.clone();
+    let mut router = axum::Router::new().route(
+        route,
+        axum::routing::get(move |path: axum::extract::MatchedPath| {
+            *handler_matched.lock().unwrap() = Some(path);
+            ready(())
+        }),
+    );
+    let mut cx = Context::from_waker(Waker::noop());
+    let future = pin!(router.call(
+        http::Request::get(uri)
+            .body(axum::body::Body::empty())
+            .unwrap()
+    ));
+    assert!(future.poll(&mut cx).is_ready());
+    let matched = matched.lock().unwrap().take();
+    matched.expect("router matched the route")
+}
+
+#[cfg(all(feature = "axum", feature = "route-cache"))]
+#[test]
+fn matched_path_route_does_not_allocate() {
+    let layer = HTTPMetricsLayerBuilder::default()
+        .with_default_url_scheme("https")
+        .build()
+        .unwrap();
+    let mut service = layer.layer(Handler);
+
+    let matched_path = matched_path("/users/:id", "/users/1");
+    let request = || {
+        let mut req = http::Request::new(String::new());
+        req.extensions_mut().insert(matched_path.clone());
+        req
+    };
+
+    // the first request interns the route in the route cache
+    serve(&mut service, request());
+
+    let requests: Vec<_> = (0..100).map(|_| request()).collect();
+    let allocations = count_allocations(|| {
+        for req in requests {
+            serve(&mut service, req);
+        }
+    });
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn opt_in_attribute_buffers_are_reused() {
+    let layer = HTTPMetricsLayerBuilder::default()
+        .with_default_url_scheme("https")
+        .with_faas_attributes(true)
+        .build()
+        .unwrap();
+    let mut service = layer.layer(Handler);
+
+    let request = || http::Request::new(String::new());
+
+    // the first request fills the attribute buffer pool of the thread
+    serve(&mut service, request());
+
+    let requests: Vec<_> = (0..100).map(|_| request()).collect();
+    let allocations = count_allocations(|| {
+        for req in requests {
+            serve(&mut service, req);
+        }
+    });
+    assert_eq!(allocations, 0);
+}
@@ -0,0 +1,60 @@
+//! Async extractors are awaited within their budget before the request is forwarded.
+#![cfg(feature = "async-extractor")]
+
+mod common;
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use opentelemetry::KeyValue;
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::TestMetrics;
+
+#[tokio::test]
+async fn attributes_of_extractors_within_the_budget_are_recorded() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_async_attribute_extractor(|parts| {
+            let plan = parts.headers.contains_key("authorization");
+            async move {
+                tokio::task::yield_now().await;
+                Some(KeyValue::new(
+                    "app.plan",
+                    if plan { "paid" } else { "anonymous" },
+                ))
+            }
+        })
+        .with_async_attribute_extractor(|_| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Some(KeyValue::new("app.slow", true))
+        })
+        .with_async_extractor_budget(Duration::from_millis(10))
+        .build()
+        .unwrap();
+    let service = layer
+        .async_extractor_layer()
+        .layer(
+            layer.layer(tower::service_fn(|_: http::Request<String>| async {
+                Ok::<_, Infallible>(http::Response::new(String::new()))
+            })),
+        );
+    let request = http::Request::get("/")
+        .header(http::header::AUTHORIZATION, "Bearer token")
+        .body(String::new())
+        .unwrap();
+    service.oneshot(request).await.unwrap();
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("app.plan").unwrap(), "paid");
+    assert_eq!(duration[0].attribute("app.slow"), None);
+
+    let timeouts = metrics.points::<u64>("http.server.extractor.timeouts");
+    assert_eq!(timeouts.len(), 1);
+    assert_eq!(timeouts[0].value, 1);
+}
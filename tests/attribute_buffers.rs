@@ -0,0 +1,49 @@
+//! Reused attribute buffers carry no attributes over from earlier requests.
+
+mod common;
+
+use opentelemetry::KeyValue;
+use tower_otel_http_metrics::{AttributeExtractor, HTTPMetricsLayerBuilder};
+
+use common::{send, TestMetrics};
+
+#[test]
+fn each_request_records_only_its_own_attributes() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_attribute_extractor(AttributeExtractor::request(|parts| {
+            parts
+                .headers
+                .get("x-tenant-id")
+                .and_then(|value| value.to_str().ok())
+                .map(|tenant| KeyValue::new("app.tenant", tenant.to_owned()))
+        }))
+        .build()
+        .unwrap();
+    // alternate between requests filling and leaving empty the pooled buffers
+    for tenant in [Some("acme"), None, Some("globex"), None, Some("acme")] {
+        let mut request = http::Request::builder();
+        if let Some(tenant) = tenant {
+            request = request.header("x-tenant-id", tenant);
+        }
+        send(&layer, request.body(String::new()).unwrap(), |_| {
+            http::Response::new(String::new())
+        });
+    }
+
+    let mut tenants: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("app.tenant"), point.count))
+        .collect();
+    tenants.sort();
+    assert_eq!(
+        tenants,
+        [
+            (None, 2),
+            (Some(String::from("acme")), 2),
+            (Some(String::from("globex")), 1),
+        ]
+    );
+}
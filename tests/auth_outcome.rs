@@ -0,0 +1,82 @@
+//! Authentication failures are classified with their challenge scheme.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+/// The `http.auth.outcome` and `http.auth.scheme` of a response.
+fn auth_attributes(
+    enabled: bool,
+    status: http::StatusCode,
+    challenge: Option<&'static str>,
+) -> (Option<String>, Option<String>) {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_auth_outcome_attributes(enabled)
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        let mut response = http::Response::builder().status(status);
+        if let Some(challenge) = challenge {
+            response = response.header(http::header::WWW_AUTHENTICATE, challenge);
+        }
+        response.body(String::new()).unwrap()
+    });
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    (
+        duration[0].attribute("http.auth.outcome"),
+        duration[0].attribute("http.auth.scheme"),
+    )
+}
+
+#[test]
+fn unauthorized_responses_record_the_challenge_scheme() {
+    assert_eq!(
+        auth_attributes(
+            true,
+            http::StatusCode::UNAUTHORIZED,
+            Some("Bearer realm=\"api\", error=\"invalid_token\""),
+        ),
+        (
+            Some(String::from("unauthenticated")),
+            Some(String::from("bearer"))
+        )
+    );
+    assert_eq!(
+        auth_attributes(true, http::StatusCode::UNAUTHORIZED, Some("Custom")),
+        (
+            Some(String::from("unauthenticated")),
+            Some(String::from("_OTHER"))
+        )
+    );
+}
+
+#[test]
+fn forbidden_responses_are_classified() {
+    assert_eq!(
+        auth_attributes(true, http::StatusCode::FORBIDDEN, None),
+        (Some(String::from("forbidden")), None)
+    );
+}
+
+#[test]
+fn other_responses_are_not_classified() {
+    assert_eq!(
+        auth_attributes(true, http::StatusCode::OK, Some("Basic")),
+        (None, None)
+    );
+}
+
+#[test]
+fn attributes_are_disabled_by_default() {
+    assert_eq!(
+        auth_attributes(false, http::StatusCode::UNAUTHORIZED, Some("Basic")),
+        (None, None)
+    );
+}
@@ -0,0 +1,118 @@
+//! Requests fanned out by a router are recorded with the backend which served them.
+
+mod common;
+
+use std::convert::Infallible;
+
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{Backend, BackendLayer, HTTPMetricsLayer, HTTPMetricsLayerBuilder};
+use tower_service::Service;
+
+use common::{block_on, send, TestMetrics};
+
+/// Branch of a router answering every request.
+fn branch(
+) -> impl Service<http::Request<String>, Response = http::Response<String>, Error = Infallible> + Clone
+{
+    tower::service_fn(|_: http::Request<String>| async {
+        Ok::<_, Infallible>(http::Response::new(String::new()))
+    })
+}
+
+/// The `http.server.backend` values recorded, with their request count.
+fn backends(metrics: &TestMetrics) -> Vec<(Option<String>, u64)> {
+    let mut backends: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("http.server.backend"), point.count))
+        .collect();
+    backends.sort();
+    backends
+}
+
+#[test]
+fn branches_name_their_backend_in_the_response() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_backend_attribute(true)
+        .build()
+        .unwrap();
+    let primary = BackendLayer::new("primary").layer(branch());
+    let replica = BackendLayer::new("replica").layer(branch());
+    // routes reads to the replica, as `tower::steer::Steer` would
+    let router = layer.layer(tower::service_fn(move |req: http::Request<String>| {
+        let branch = if req.method() == http::Method::GET {
+            replica.clone()
+        } else {
+            primary.clone()
+        };
+        branch.oneshot(req)
+    }));
+    for method in [http::Method::GET, http::Method::GET, http::Method::POST] {
+        let request = http::Request::builder()
+            .method(method)
+            .body(String::new())
+            .unwrap();
+        block_on(router.clone().oneshot(request)).unwrap();
+    }
+
+    assert_eq!(
+        backends(&metrics),
+        [
+            (Some(String::from("primary")), 1),
+            (Some(String::from("replica")), 2),
+        ]
+    );
+}
+
+/// Layer for the branch of `backend`.
+fn branch_layer(metrics: &TestMetrics, backend: &'static str) -> HTTPMetricsLayer {
+    HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_backend(backend)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn layers_per_branch_name_their_backend() {
+    let metrics = TestMetrics::new();
+    let primary = branch_layer(&metrics, "primary");
+    let replica = branch_layer(&metrics, "replica");
+    send(&primary, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+    send(&replica, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+    // the response extension takes precedence
+    send(&replica, http::Request::new(String::new()), |_| {
+        let mut response = http::Response::new(String::new());
+        response.extensions_mut().insert(Backend::new("cache"));
+        response
+    });
+
+    assert_eq!(
+        backends(&metrics),
+        [
+            (Some(String::from("cache")), 1),
+            (Some(String::from("primary")), 1),
+            (Some(String::from("replica")), 1),
+        ]
+    );
+}
+
+#[test]
+fn backends_are_not_recorded_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    let service = layer.layer(BackendLayer::new("primary").layer(branch()));
+    block_on(service.oneshot(http::Request::new(String::new()))).unwrap();
+
+    assert_eq!(backends(&metrics), [(None, 1)]);
+}
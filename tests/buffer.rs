@@ -0,0 +1,99 @@
+//! The buffer queue depth counts requests until they reach the inner service or are abandoned.
+#![cfg(feature = "buffer")]
+
+mod common;
+
+use std::future::{ready, Ready};
+use std::task::{Context, Poll};
+
+use tower::BoxError;
+use tower_layer::Layer;
+use tower_otel_http_metrics::buffer::BufferMetricsLayer;
+use tower_service::Service;
+
+use common::TestMetrics;
+
+const QUEUE_DEPTH: &str = "http.server.buffer.queue_depth";
+
+/// Inner service which is ready, never ready, or failed.
+#[derive(Clone, Copy)]
+enum Inner {
+    Ready,
+    Pending,
+    Failed,
+}
+
+impl Service<()> for Inner {
+    type Response = ();
+    type Error = BoxError;
+    type Future = Ready<Result<(), BoxError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self {
+            Inner::Ready => Poll::Ready(Ok(())),
+            Inner::Pending => Poll::Pending,
+            Inner::Failed => Poll::Ready(Err("failed".into())),
+        }
+    }
+
+    fn call(&mut self, _req: ()) -> Self::Future {
+        ready(Ok(()))
+    }
+}
+
+fn queue_depth(metrics: &TestMetrics) -> u64 {
+    metrics.points::<u64>(QUEUE_DEPTH)[0].value
+}
+
+async fn poll_ready<S: Service<()>>(service: &mut S) {
+    std::future::poll_fn(|cx| service.poll_ready(cx))
+        .await
+        .unwrap_or_else(|_| panic!("buffer not ready"));
+}
+
+#[tokio::test]
+async fn served_requests_leave_the_queue() {
+    let metrics = TestMetrics::new();
+    let mut service = BufferMetricsLayer::new(&metrics.meter(), 4).layer(Inner::Ready);
+
+    for _ in 0..3 {
+        poll_ready(&mut service).await;
+        service.call(()).await.unwrap();
+    }
+    assert_eq!(queue_depth(&metrics), 0);
+}
+
+#[tokio::test]
+async fn dropped_requests_leave_the_queue() {
+    let metrics = TestMetrics::new();
+    let mut service = BufferMetricsLayer::new(&metrics.meter(), 4).layer(Inner::Pending);
+
+    poll_ready(&mut service).await;
+    let first = service.call(());
+    poll_ready(&mut service).await;
+    let second = service.call(());
+    tokio::task::yield_now().await;
+    assert_eq!(queue_depth(&metrics), 2);
+
+    drop(first);
+    assert_eq!(queue_depth(&metrics), 1);
+    drop(second);
+    assert_eq!(queue_depth(&metrics), 0);
+}
+
+#[tokio::test]
+async fn failed_requests_leave_the_queue() {
+    let metrics = TestMetrics::new();
+    let mut service = BufferMetricsLayer::new(&metrics.meter(), 4).layer(Inner::Failed);
+    let mut closed_service = service.clone();
+    poll_ready(&mut closed_service).await;
+
+    poll_ready(&mut service).await;
+    assert!(service.call(()).await.is_err());
+    assert_eq!(queue_depth(&metrics), 0);
+
+    // the worker has shut down, so the request fails without ever being queued
+    let response = closed_service.call(());
+    assert!(response.await.is_err());
+    assert_eq!(queue_depth(&metrics), 0);
+}
@@ -0,0 +1,64 @@
+//! `http.cache.status` is parsed from the cache headers of the response.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+/// The `http.cache.status` of a response carrying `headers`.
+fn cache_status(enabled: bool, headers: &[(&'static str, &'static str)]) -> Option<String> {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_cache_status_attribute(enabled)
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        let mut response = http::Response::builder();
+        for (name, value) in headers {
+            response = response.header(*name, *value);
+        }
+        response.body(String::new()).unwrap()
+    });
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    duration[0].attribute("http.cache.status")
+}
+
+#[test]
+fn cloudflare_status_is_recorded() {
+    assert_eq!(
+        cache_status(true, &[("cf-cache-status", "HIT")]).as_deref(),
+        Some("hit")
+    );
+    assert_eq!(
+        cache_status(true, &[("cf-cache-status", "updating")]).as_deref(),
+        Some("stale")
+    );
+    assert_eq!(
+        cache_status(true, &[("cf-cache-status", "BYPASS"), ("age", "10")]).as_deref(),
+        Some("miss")
+    );
+}
+
+#[test]
+fn edge_tier_of_x_cache_is_recorded() {
+    assert_eq!(
+        cache_status(true, &[("x-cache", "HIT, MISS")]).as_deref(),
+        Some("miss")
+    );
+}
+
+#[test]
+fn non_zero_age_is_a_hit() {
+    assert_eq!(cache_status(true, &[("age", "30")]).as_deref(), Some("hit"));
+    assert_eq!(cache_status(true, &[("age", "0")]), None);
+}
+
+#[test]
+fn attribute_is_disabled_by_default() {
+    assert_eq!(cache_status(false, &[("cf-cache-status", "HIT")]), None);
+}
@@ -0,0 +1,37 @@
+//! The distinct attribute sets of `http.server.request.duration` are estimated.
+#![cfg(feature = "cardinality")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+#[test]
+fn attribute_sets_are_estimated_and_warned_about() {
+    let metrics = TestMetrics::new();
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|parts| Some(parts.uri.path().to_owned()))
+        .with_cardinality_warning(3, Duration::ZERO, {
+            let warnings = warnings.clone();
+            move |estimate| warnings.lock().unwrap().push(estimate)
+        })
+        .build()
+        .unwrap();
+    for uri in ["/a", "/b", "/a", "/c"] {
+        let request = http::Request::get(uri).body(String::new()).unwrap();
+        send(&layer, request, |_| http::Response::new(String::new()));
+    }
+
+    let attribute_sets = metrics.points::<u64>("http.server.request.duration.attribute_sets");
+    assert_eq!(attribute_sets.len(), 1);
+    assert_eq!(attribute_sets[0].value, 3);
+    assert!(attribute_sets[0].attributes.is_empty());
+    // only the request adding the third attribute set reaches the threshold
+    assert_eq!(*warnings.lock().unwrap(), [3]);
+}
@@ -0,0 +1,225 @@
+//! The client layer records the duration and body sizes of outgoing requests.
+#![cfg(feature = "client")]
+
+mod common;
+
+use std::convert::Infallible;
+
+use bytes::Bytes;
+use futures_util::stream;
+use http_body::Frame;
+use http_body_util::{BodyExt, StreamBody};
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::client::{ClientMetricsLayer, ClientRedirects};
+
+use common::{block_on, TestMetrics};
+
+const DURATION: &str = "http.client.request.duration";
+const REQUEST_BODY_SIZE: &str = "http.client.request.body.size";
+const RESPONSE_BODY_SIZE: &str = "http.client.response.body.size";
+
+/// Body streaming `chunks`, whose size is not known up front.
+fn streamed(
+    chunks: &[&'static str],
+) -> StreamBody<impl futures_util::Stream<Item = Result<Frame<Bytes>, Infallible>>> {
+    StreamBody::new(stream::iter(
+        chunks
+            .iter()
+            .map(|chunk| Ok(Frame::data(Bytes::from_static(chunk.as_bytes()))))
+            .collect::<Vec<_>>(),
+    ))
+}
+
+#[test]
+fn records_the_duration_and_known_body_sizes() {
+    let metrics = TestMetrics::new();
+    let client = ClientMetricsLayer::new(&metrics.meter())
+        .with_response_body_size(true)
+        .layer(tower::service_fn(|_: http::Request<_>| async {
+            let response = http::Response::builder()
+                .status(http::StatusCode::CREATED)
+                .body(String::from("created"))
+                .unwrap();
+            Ok::<_, Infallible>(response)
+        }));
+    let request = http::Request::post("https://API.example.com/users")
+        .body(String::from("hello"))
+        .unwrap();
+    block_on(client.oneshot(request)).unwrap();
+
+    let duration = metrics.histogram::<f64>(DURATION);
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(
+        duration[0].attribute("http.request.method").unwrap(),
+        "POST"
+    );
+    assert_eq!(
+        duration[0].attribute("server.address").unwrap(),
+        "api.example.com"
+    );
+    assert_eq!(duration[0].attribute("server.port").unwrap(), "443");
+    assert_eq!(
+        duration[0].attribute("http.response.status_code").unwrap(),
+        "201"
+    );
+    assert_eq!(duration[0].attribute("error.type"), None);
+
+    let request_body_size = metrics.histogram::<u64>(REQUEST_BODY_SIZE);
+    assert_eq!(request_body_size.len(), 1);
+    assert_eq!(request_body_size[0].value, 5);
+    assert_eq!(
+        request_body_size[0]
+            .attribute("http.response.status_code")
+            .unwrap(),
+        "201"
+    );
+    let response_body_size = metrics.histogram::<u64>(RESPONSE_BODY_SIZE);
+    assert_eq!(response_body_size[0].value, 7);
+}
+
+#[test]
+fn records_streamed_body_sizes_once_they_end() {
+    let metrics = TestMetrics::new();
+    // echoes the request body, which is still streaming when the response arrives
+    let client = ClientMetricsLayer::new(&metrics.meter())
+        .with_response_body_size(true)
+        .layer(tower::service_fn(|req: http::Request<_>| async {
+            Ok::<_, Infallible>(http::Response::new(req.into_body()))
+        }));
+    let request = http::Request::put("http://localhost:8080/upload")
+        .body(streamed(&["abc", "defg"]))
+        .unwrap();
+    let response = block_on(client.oneshot(request)).unwrap();
+
+    assert_eq!(metrics.histogram::<f64>(DURATION)[0].count, 1);
+    assert!(metrics.histogram::<u64>(REQUEST_BODY_SIZE).is_empty());
+    assert!(metrics.histogram::<u64>(RESPONSE_BODY_SIZE).is_empty());
+
+    block_on(response.into_body().collect()).unwrap();
+
+    let request_body_size = metrics.histogram::<u64>(REQUEST_BODY_SIZE);
+    assert_eq!(request_body_size.len(), 1);
+    assert_eq!(request_body_size[0].value, 7);
+    assert_eq!(
+        request_body_size[0].attribute("server.port").unwrap(),
+        "8080"
+    );
+    let response_body_size = metrics.histogram::<u64>(RESPONSE_BODY_SIZE);
+    assert_eq!(response_body_size.len(), 1);
+    assert_eq!(response_body_size[0].value, 7);
+    assert_eq!(
+        response_body_size[0]
+            .attribute("http.request.method")
+            .unwrap(),
+        "PUT"
+    );
+}
+
+#[test]
+fn records_error_responses_and_failures_with_their_error_type() {
+    let metrics = TestMetrics::new();
+    let layer = ClientMetricsLayer::new(&metrics.meter());
+    let not_found = layer.layer(tower::service_fn(|_: http::Request<_>| async {
+        let response = http::Response::builder()
+            .status(http::StatusCode::NOT_FOUND)
+            .body(String::new())
+            .unwrap();
+        Ok::<_, Infallible>(response)
+    }));
+    block_on(not_found.oneshot(http::Request::new(String::new()))).unwrap();
+    let failing = layer.layer(tower::service_fn(|_: http::Request<_>| async {
+        Err::<http::Response<String>, _>(std::io::Error::other("connection refused"))
+    }));
+    block_on(failing.oneshot(http::Request::new(String::new()))).unwrap_err();
+
+    let mut error_types: Vec<_> = metrics
+        .histogram::<f64>(DURATION)
+        .iter()
+        .map(|point| point.attribute("error.type").unwrap())
+        .collect();
+    error_types.sort();
+    assert_eq!(error_types, ["404", "_OTHER"]);
+}
+
+#[test]
+fn records_the_url_template_of_matching_paths() {
+    let metrics = TestMetrics::new();
+    let layer = ClientMetricsLayer::new(&metrics.meter())
+        .with_url_template("/users/me")
+        .with_url_template("/users/{id}");
+    for uri in [
+        "http://api/users/me",
+        "http://api/users/42",
+        "http://api/users/7?full=1",
+        "http://api/health",
+    ] {
+        let client = layer.layer(tower::service_fn(|_: http::Request<_>| async {
+            Ok::<_, Infallible>(http::Response::new(String::new()))
+        }));
+        let request = http::Request::get(uri).body(String::new()).unwrap();
+        block_on(client.oneshot(request)).unwrap();
+    }
+
+    let mut templates: Vec<_> = metrics
+        .histogram::<f64>(DURATION)
+        .iter()
+        .map(|point| (point.attribute("url.template"), point.count))
+        .collect();
+    templates.sort();
+    assert_eq!(
+        templates,
+        [
+            (None, 1),
+            (Some(String::from("/users/me")), 1),
+            (Some(String::from("/users/{id}")), 2),
+        ]
+    );
+}
+
+#[test]
+fn counts_the_redirects_followed_with_the_original_and_final_hosts() {
+    let metrics = TestMetrics::new();
+    let layer = ClientMetricsLayer::new(&metrics.meter()).with_redirect_tracking(true);
+    // follows two redirects, to another host and then to a path on it
+    let redirected = layer.layer(tower::service_fn(|req: http::Request<_>| async move {
+        let redirects = req.extensions().get::<ClientRedirects>().unwrap();
+        redirects.follow(&http::Uri::from_static("https://CDN.example.net/a"));
+        redirects.follow(&http::Uri::from_static("/b"));
+        Ok::<_, Infallible>(http::Response::new(String::new()))
+    }));
+    let request = http::Request::get("https://api.example.com/a")
+        .body(String::new())
+        .unwrap();
+    block_on(redirected.oneshot(request)).unwrap();
+    let direct = layer.layer(tower::service_fn(|_: http::Request<_>| async {
+        Ok::<_, Infallible>(http::Response::new(String::new()))
+    }));
+    let request = http::Request::get("https://api.example.com/b")
+        .body(String::new())
+        .unwrap();
+    block_on(direct.oneshot(request)).unwrap();
+
+    let redirects = metrics.points::<u64>("http.client.redirects");
+    assert_eq!(redirects.len(), 1);
+    assert_eq!(redirects[0].value, 2);
+    assert_eq!(
+        redirects[0]
+            .attribute("http.redirect.original_host")
+            .unwrap(),
+        "api.example.com"
+    );
+    assert_eq!(
+        redirects[0].attribute("http.redirect.final_host").unwrap(),
+        "cdn.example.net"
+    );
+
+    let mut final_hosts: Vec<_> = metrics
+        .histogram::<f64>(DURATION)
+        .iter()
+        .map(|point| point.attribute("http.redirect.final_host"))
+        .collect();
+    final_hosts.sort();
+    assert_eq!(final_hosts, [None, Some(String::from("cdn.example.net"))]);
+}
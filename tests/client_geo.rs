@@ -0,0 +1,63 @@
+//! `geo.*` attributes are read from CDN geolocation headers.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+/// The `geo.country.iso_code` and `geo.region.iso_code` of a request carrying `headers`.
+fn geo_attributes(
+    enabled: bool,
+    headers: &[(&'static str, &'static str)],
+) -> (Option<String>, Option<String>) {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_client_geo_attributes(enabled)
+        .build()
+        .unwrap();
+    let mut request = http::Request::builder();
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    send(&layer, request.body(String::new()).unwrap(), |_| {
+        http::Response::new(String::new())
+    });
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    (
+        duration[0].attribute("geo.country.iso_code"),
+        duration[0].attribute("geo.region.iso_code"),
+    )
+}
+
+#[test]
+fn cdn_headers_are_recorded_uppercased() {
+    assert_eq!(
+        geo_attributes(true, &[("cf-ipcountry", "de"), ("cf-region-code", "by")]),
+        (Some(String::from("DE")), Some(String::from("BY")))
+    );
+    assert_eq!(
+        geo_attributes(true, &[("fastly-client-country", "US")]),
+        (Some(String::from("US")), None)
+    );
+}
+
+#[test]
+fn malformed_codes_are_other() {
+    assert_eq!(
+        geo_attributes(true, &[("cf-ipcountry", "Germany")]),
+        (Some(String::from("_OTHER")), None)
+    );
+}
+
+#[test]
+fn attributes_are_disabled_by_default() {
+    assert_eq!(
+        geo_attributes(false, &[("cf-ipcountry", "DE")]),
+        (None, None)
+    );
+}
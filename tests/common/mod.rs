@@ -0,0 +1,202 @@
+//! Helpers shared by the integration tests: an in-memory meter and a minimal executor.
+
+#![allow(dead_code)]
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll, Waker};
+
+use opentelemetry::metrics::{Meter, MeterProvider};
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::metrics::data::{self, ResourceMetrics};
+use opentelemetry_sdk::metrics::reader::MetricReader;
+use opentelemetry_sdk::metrics::{
+    InstrumentKind, ManualReader, MetricResult, Pipeline, SdkMeterProvider, Temporality,
+};
+use opentelemetry_sdk::Resource;
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::HTTPMetricsLayer;
+
+#[derive(Clone, Debug)]
+struct SharedReader(Arc<ManualReader>);
+
+impl MetricReader for SharedReader {
+    fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+        self.0.register_pipeline(pipeline)
+    }
+
+    fn collect(&self, rm: &mut ResourceMetrics) -> MetricResult<()> {
+        self.0.collect(rm)
+    }
+
+    fn force_flush(&self) -> MetricResult<()> {
+        self.0.force_flush()
+    }
+
+    fn shutdown(&self) -> MetricResult<()> {
+        self.0.shutdown()
+    }
+
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        self.0.temporality(kind)
+    }
+}
+
+/// Meter provider whose metrics are collected on demand.
+pub struct TestMetrics {
+    reader: SharedReader,
+    provider: SdkMeterProvider,
+}
+
+/// Data point of a sum or gauge, or the count and sum of a histogram data point.
+#[derive(Debug)]
+pub struct Point<T> {
+    pub attributes: Vec<KeyValue>,
+    pub value: T,
+    pub count: u64,
+}
+
+impl<T> Point<T> {
+    /// The value of the attribute `key` as a string, if present.
+    pub fn attribute(&self, key: &str) -> Option<String> {
+        self.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.to_string())
+    }
+}
+
+impl TestMetrics {
+    pub fn new() -> Self {
+        let reader = SharedReader(Arc::new(ManualReader::builder().build()));
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .build();
+        TestMetrics { reader, provider }
+    }
+
+    pub fn meter(&self) -> Meter {
+        self.provider.meter("test")
+    }
+
+    pub fn provider(&self) -> &SdkMeterProvider {
+        &self.provider
+    }
+
+    fn collect(&self) -> ResourceMetrics {
+        let mut rm = ResourceMetrics {
+            resource: Resource::empty(),
+            scope_metrics: Vec::new(),
+        };
+        self.reader.collect(&mut rm).unwrap();
+        rm
+    }
+
+    /// The data points of the sums and gauges named `name`.
+    pub fn points<T: Copy + 'static>(&self, name: &str) -> Vec<Point<T>> {
+        let rm = self.collect();
+        let mut points = Vec::new();
+        for metric in rm.scope_metrics.iter().flat_map(|scope| &scope.metrics) {
+            if metric.name != name {
+                continue;
+            }
+            let data = metric.data.as_any();
+            let data_points = if let Some(sum) = data.downcast_ref::<data::Sum<T>>() {
+                &sum.data_points
+            } else if let Some(gauge) = data.downcast_ref::<data::Gauge<T>>() {
+                &gauge.data_points
+            } else {
+                panic!("{name} is not a sum or gauge of the expected type");
+            };
+            points.extend(data_points.iter().map(|point| Point {
+                attributes: point.attributes.clone(),
+                value: point.value,
+                count: 1,
+            }));
+        }
+        points
+    }
+
+    /// The data points of the histograms named `name`, with their counts and sums.
+    pub fn histogram<T: Copy + 'static>(&self, name: &str) -> Vec<Point<T>> {
+        let rm = self.collect();
+        let mut points = Vec::new();
+        for metric in rm.scope_metrics.iter().flat_map(|scope| &scope.metrics) {
+            if metric.name != name {
+                continue;
+            }
+            let histogram = metric
+                .data
+                .as_any()
+                .downcast_ref::<data::Histogram<T>>()
+                .unwrap_or_else(|| panic!("{name} is not a histogram of the expected type"));
+            points.extend(histogram.data_points.iter().map(|point| Point {
+                attributes: point.attributes.clone(),
+                value: point.sum,
+                count: point.count,
+            }));
+        }
+        points
+    }
+
+    /// The unit and bucket boundaries of the histogram named `name`.
+    pub fn histogram_layout<T: Copy + 'static>(&self, name: &str) -> (String, Vec<f64>) {
+        let rm = self.collect();
+        let metric = rm
+            .scope_metrics
+            .iter()
+            .flat_map(|scope| &scope.metrics)
+            .find(|metric| metric.name == name)
+            .unwrap_or_else(|| panic!("{name} reported no data"));
+        let histogram = metric
+            .data
+            .as_any()
+            .downcast_ref::<data::Histogram<T>>()
+            .unwrap_or_else(|| panic!("{name} is not a histogram of the expected type"));
+        (
+            metric.unit.to_string(),
+            histogram.data_points[0].bounds.clone(),
+        )
+    }
+
+    /// The names of all instruments which reported data.
+    pub fn names(&self) -> Vec<String> {
+        let rm = self.collect();
+        rm.scope_metrics
+            .iter()
+            .flat_map(|scope| &scope.metrics)
+            .map(|metric| metric.name.to_string())
+            .collect()
+    }
+}
+
+/// Poll a future which needs no runtime to completion.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// Send `request` through `layer` to a handler answering with `respond`.
+pub fn send<F>(
+    layer: &HTTPMetricsLayer,
+    request: http::Request<String>,
+    respond: F,
+) -> http::Response<String>
+where
+    F: FnOnce(http::Request<String>) -> http::Response<String>,
+{
+    let mut respond = Some(respond);
+    let service = layer.layer(tower::service_fn(move |req| {
+        let response = respond.take().expect("the handler is called once")(req);
+        async move { Ok::<_, Infallible>(response) }
+    }));
+    block_on(service.oneshot(request)).unwrap()
+}
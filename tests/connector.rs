@@ -0,0 +1,81 @@
+//! Failed connections and lookups get a bounded `error.type`.
+#![cfg(feature = "connector")]
+
+mod common;
+
+use std::error::Error;
+use std::fmt;
+use std::future::ready;
+use std::io;
+
+use http::Uri;
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::connector::{ConnectorMetricsLayer, ResolverMetricsLayer};
+
+use common::{block_on, TestMetrics};
+
+/// Connector error wrapping its cause, as hyper-util's connector does.
+#[derive(Debug)]
+struct ConnectError(&'static str, io::Error);
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl Error for ConnectError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.1)
+    }
+}
+
+fn connect_error_type(err: ConnectError) -> Option<String> {
+    let metrics = TestMetrics::new();
+    let mut err = Some(err);
+    let connector =
+        ConnectorMetricsLayer::new(&metrics.meter()).layer(tower::service_fn(move |_: Uri| {
+            ready(Err::<(), _>(err.take().unwrap()))
+        }));
+    let dst = Uri::from_static("http://example.com");
+    assert!(block_on(connector.oneshot(dst)).is_err());
+
+    let connect = metrics.histogram::<f64>("http.client.connect.duration");
+    assert_eq!(connect.len(), 1);
+    connect[0].attribute("error.type")
+}
+
+#[test]
+fn io_error_kinds_are_classified() {
+    let cases = [
+        (io::ErrorKind::ConnectionRefused, "connection_refused"),
+        (io::ErrorKind::ConnectionReset, "connection_reset"),
+        (io::ErrorKind::TimedOut, "timeout"),
+        (io::ErrorKind::InvalidData, "tls_error"),
+    ];
+    for (kind, error_type) in cases {
+        let err = ConnectError("tcp connect error", io::Error::from(kind));
+        assert_eq!(connect_error_type(err).as_deref(), Some(error_type));
+    }
+}
+
+#[test]
+fn messages_are_not_classified() {
+    let err = ConnectError("dns error", io::Error::other("timed out resolving host"));
+    assert_eq!(connect_error_type(err).as_deref(), Some("_OTHER"));
+}
+
+#[test]
+fn lookup_errors_are_other() {
+    let metrics = TestMetrics::new();
+    let resolver =
+        ResolverMetricsLayer::new(&metrics.meter()).layer(tower::service_fn(|_: String| {
+            ready(Err::<(), _>(io::Error::from(io::ErrorKind::TimedOut)))
+        }));
+    assert!(block_on(resolver.oneshot(String::from("example.com"))).is_err());
+
+    let lookup = metrics.histogram::<f64>("dns.lookup.duration");
+    assert_eq!(lookup.len(), 1);
+    assert_eq!(lookup[0].attribute("error.type").as_deref(), Some("_OTHER"));
+}
@@ -0,0 +1,61 @@
+//! Handlers supply values for custom histograms through a response extension.
+
+mod common;
+
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, RecordValues};
+
+use common::{send, TestMetrics};
+
+#[test]
+fn values_are_recorded_with_the_duration_attributes() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_custom_histogram("app.db.duration", "s")
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        let mut response = http::Response::new(String::new());
+        response.extensions_mut().insert(
+            RecordValues::new()
+                .with_value("app.db.duration", 0.25)
+                .with_value("app.db.duration", 0.5)
+                .with_value("app.unregistered", 1.0),
+        );
+        response
+    });
+
+    let db_duration = metrics.histogram::<f64>("app.db.duration");
+    assert_eq!(db_duration.len(), 1);
+    assert_eq!(db_duration[0].count, 2);
+    assert_eq!(db_duration[0].value, 0.75);
+    assert_eq!(
+        db_duration[0].attribute("http.request.method").unwrap(),
+        "GET"
+    );
+    assert_eq!(
+        db_duration[0]
+            .attribute("http.response.status_code")
+            .unwrap(),
+        "200"
+    );
+    assert!(!metrics
+        .names()
+        .iter()
+        .any(|name| name == "app.unregistered"));
+}
+
+#[test]
+fn responses_without_values_record_nothing() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_custom_histogram("app.db.duration", "s")
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    assert!(metrics.histogram::<f64>("app.db.duration").is_empty());
+}
@@ -0,0 +1,84 @@
+//! Custom instruments record values extracted from the response.
+
+mod common;
+
+use tower_otel_http_metrics::{CustomInstrument, HTTPMetricsLayerBuilder};
+
+use common::{send, TestMetrics};
+
+/// The `x-item-count` response header as a number.
+fn item_count(parts: &http::response::Parts) -> Option<f64> {
+    parts
+        .headers
+        .get("x-item-count")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+#[test]
+fn extracted_values_are_recorded_with_the_duration_attributes() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_custom_instrument(CustomInstrument::counter(
+            "app.items.total",
+            "{item}",
+            item_count,
+        ))
+        .with_custom_instrument(CustomInstrument::histogram(
+            "app.items.returned",
+            "{item}",
+            item_count,
+        ))
+        .build()
+        .unwrap();
+    for count in ["3", "4"] {
+        let request = http::Request::post("/items").body(String::new()).unwrap();
+        send(&layer, request, |_| {
+            http::Response::builder()
+                .status(http::StatusCode::CREATED)
+                .header("x-item-count", count)
+                .body(String::new())
+                .unwrap()
+        });
+    }
+
+    let total = metrics.points::<f64>("app.items.total");
+    assert_eq!(total.len(), 1);
+    assert_eq!(total[0].value, 7.0);
+    assert_eq!(total[0].attribute("http.request.method").unwrap(), "POST");
+    assert_eq!(
+        total[0].attribute("http.response.status_code").unwrap(),
+        "201"
+    );
+
+    let returned = metrics.histogram::<f64>("app.items.returned");
+    assert_eq!(returned.len(), 1);
+    assert_eq!(returned[0].count, 2);
+    assert_eq!(returned[0].value, 7.0);
+    assert_eq!(
+        returned[0].attribute("http.request.method").unwrap(),
+        "POST"
+    );
+}
+
+#[test]
+fn responses_without_a_value_record_nothing() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_custom_instrument(CustomInstrument::histogram(
+            "app.items.returned",
+            "{item}",
+            item_count,
+        ))
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    assert!(metrics.histogram::<f64>("app.items.returned").is_empty());
+}
@@ -0,0 +1,35 @@
+//! The generated dashboard queries the metrics the layer records.
+#![cfg(feature = "dashboard")]
+
+mod common;
+
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, NamingConvention};
+
+use common::{send, TestMetrics};
+
+#[test]
+fn dashboard_follows_the_recorded_names() {
+    let metrics = TestMetrics::new();
+    let builder = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_naming_convention(NamingConvention::Datadog)
+        .with_route_extractor(|_| Some("/users/:id"));
+    let dashboard = builder.grafana_dashboard("Users API");
+    let layer = builder.build().unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    let duration = metrics.histogram::<f64>("trace.http.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(
+        duration[0].attribute("resource_name").unwrap(),
+        "GET /users/:id"
+    );
+
+    assert!(dashboard.contains(r#""title":"Users API""#));
+    assert!(dashboard.contains("trace_http_request_duration_seconds_bucket"));
+    assert!(dashboard.contains("resource_name"));
+    assert!(!dashboard.contains("http_server_request_duration_seconds"));
+}
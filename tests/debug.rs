@@ -0,0 +1,46 @@
+//! Debug output of the public types does not leak request attributes.
+
+mod common;
+
+use std::convert::Infallible;
+
+use tower::{Service, ServiceExt};
+use tower_layer::Layer;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{block_on, TestMetrics};
+
+#[test]
+fn debug_output_leaves_out_caller_attributes() {
+    let metrics = TestMetrics::new();
+    let builder = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_usage_counter("app.usage", "{unit}")
+        .with_usage_tenant_header(http::HeaderName::from_static("x-tenant-id"));
+    assert!(format!("{builder:?}").starts_with("HTTPMetricsLayerBuilder"));
+    let layer = builder.build().unwrap();
+    assert_eq!(format!("{layer:?}"), "HTTPMetricsLayer { .. }");
+
+    let mut service = layer.layer(tower::service_fn(|_: http::Request<String>| async {
+        Ok::<_, Infallible>(http::Response::new(String::new()))
+    }));
+    assert!(format!("{service:?}").starts_with("HTTPMetricsService { inner_service: "));
+
+    let request = http::Request::delete("/accounts/1")
+        .header("x-tenant-id", "acme")
+        .body(String::new())
+        .unwrap();
+    let future = block_on(service.ready()).unwrap().call(request);
+    let debug = format!("{future:?}");
+    assert!(debug.contains("DELETE"));
+    assert!(!debug.contains("acme"));
+    block_on(future).unwrap();
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(
+        duration[0].attribute("http.request.method").unwrap(),
+        "DELETE"
+    );
+}
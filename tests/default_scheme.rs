@@ -0,0 +1,32 @@
+//! Requests in origin form take the configured default `url.scheme`.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+#[test]
+fn default_scheme_applies_to_requests_without_one() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_default_url_scheme("https")
+        .build()
+        .unwrap();
+    for uri in ["/users", "/users?page=2", "http://localhost/users"] {
+        let request = http::Request::get(uri).body(String::new()).unwrap();
+        send(&layer, request, |_| http::Response::new(String::new()));
+    }
+
+    let mut schemes: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("url.scheme").unwrap(), point.count))
+        .collect();
+    schemes.sort();
+    assert_eq!(
+        schemes,
+        [(String::from("http"), 1), (String::from("https"), 2)]
+    );
+}
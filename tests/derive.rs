@@ -0,0 +1,102 @@
+//! `#[derive(HttpMetricsAttributes)]` records the fields of a struct as attributes.
+#![cfg(feature = "derive")]
+
+mod common;
+
+use std::convert::Infallible;
+
+use opentelemetry::{KeyValue, Value};
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{AttributeExtractor, HTTPMetricsLayerBuilder, HttpMetricsAttributes};
+
+use common::{block_on, TestMetrics};
+
+#[derive(Clone, HttpMetricsAttributes)]
+struct AuthContext {
+    #[http_metrics(key = "app.plan")]
+    plan: &'static str,
+    region: String,
+    seats: i64,
+    trial: Option<bool>,
+    #[http_metrics(skip)]
+    #[allow(dead_code)]
+    user_id: u64,
+}
+
+#[derive(HttpMetricsAttributes)]
+struct Tagged<T: Clone + Into<Value>> {
+    tag: T,
+}
+
+fn attributes(value: &impl HttpMetricsAttributes) -> Vec<KeyValue> {
+    let mut attributes = Vec::new();
+    value.attributes(&mut attributes);
+    attributes
+}
+
+fn auth_context(trial: Option<bool>) -> AuthContext {
+    AuthContext {
+        plan: "pro",
+        region: String::from("eu"),
+        seats: 3,
+        trial,
+        user_id: 42,
+    }
+}
+
+#[test]
+fn fields_are_recorded_under_their_keys() {
+    assert_eq!(
+        attributes(&auth_context(Some(true))),
+        [
+            KeyValue::new("app.plan", "pro"),
+            KeyValue::new("region", "eu"),
+            KeyValue::new("seats", 3),
+            KeyValue::new("trial", true),
+        ]
+    );
+}
+
+#[test]
+fn none_fields_are_not_recorded() {
+    assert_eq!(
+        attributes(&auth_context(None)),
+        [
+            KeyValue::new("app.plan", "pro"),
+            KeyValue::new("region", "eu"),
+            KeyValue::new("seats", 3),
+        ]
+    );
+}
+
+#[test]
+fn generic_structs_are_supported() {
+    assert_eq!(
+        attributes(&Tagged { tag: "blue" }),
+        [KeyValue::new("tag", "blue")]
+    );
+}
+
+#[test]
+fn derived_extensions_are_extracted() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_attribute_extractor(AttributeExtractor::request_extension_attributes::<
+            AuthContext,
+        >())
+        .build()
+        .unwrap();
+    let service = layer.layer(tower::service_fn(|_: http::Request<String>| async {
+        Ok::<_, Infallible>(http::Response::new(String::new()))
+    }));
+    let mut request = http::Request::new(String::new());
+    request.extensions_mut().insert(auth_context(None));
+    block_on(service.oneshot(request)).unwrap();
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration[0].attribute("app.plan").as_deref(), Some("pro"));
+    assert_eq!(duration[0].attribute("seats").as_deref(), Some("3"));
+    assert_eq!(duration[0].attribute("user_id"), None);
+}
@@ -0,0 +1,72 @@
+//! The diagnostics endpoint reports the state of the layer's metrics.
+#![cfg(feature = "diagnostics")]
+
+mod common;
+
+use std::sync::Arc;
+
+use tower::ServiceExt;
+use tower_otel_http_metrics::{DiagnosticsService, HTTPMetricsLayerBuilder};
+
+use common::{block_on, send, TestMetrics};
+
+/// The report of the diagnostics endpoint.
+fn report(diagnostics: &DiagnosticsService) -> String {
+    let request = http::Request::get("/.well-known/otel-metrics-status")
+        .body(String::new())
+        .unwrap();
+    let response = block_on(diagnostics.clone().oneshot(request)).unwrap();
+    assert_eq!(
+        response.headers()[http::header::CONTENT_TYPE],
+        "application/json"
+    );
+    response.into_body()
+}
+
+#[test]
+fn reports_the_provider_instruments_and_collections() {
+    let metrics = TestMetrics::new();
+    let provider = Arc::new(metrics.provider().clone());
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter_provider(&provider)
+        .build()
+        .unwrap();
+    let diagnostics = layer.diagnostics_service();
+
+    let report_before = report(&diagnostics);
+    assert!(report_before.starts_with(r#"{"meter_provider":"live","dry_run":false,"#));
+    assert!(report_before.contains(r#""http.server.request.duration""#));
+    assert!(report_before.contains(r#""http.server.active_requests""#));
+    assert!(report_before.ends_with(r#""last_collection_seconds_ago":null}"#));
+
+    send(&layer, http::Request::new(String::new()), |_| {
+        assert!(report(&diagnostics).contains(r#""active_requests":1,"#));
+        http::Response::new(String::new())
+    });
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("http.request.method").unwrap(), "GET");
+
+    let report_after = report(&diagnostics);
+    assert!(report_after.contains(r#""active_requests":0,"#));
+    assert!(!report_after.ends_with(r#""last_collection_seconds_ago":null}"#));
+
+    drop(provider);
+    assert!(report(&diagnostics).starts_with(r#"{"meter_provider":"dropped","#));
+}
+
+#[test]
+fn lists_only_the_enabled_instruments() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_slo_threshold("/search", std::time::Duration::from_millis(300))
+        .build()
+        .unwrap();
+    let report = report(&layer.diagnostics_service());
+
+    assert!(report.starts_with(r#"{"meter_provider":"untracked","#));
+    assert!(report.contains(r#""http.server.request.slow""#));
+    assert!(!report.contains(r#""http.server.request.body.size.malformed""#));
+    assert!(!report.contains("http.server.diagnostics.collection"));
+}
@@ -0,0 +1,50 @@
+//! Dry-run layers summarize their measurements instead of recording them.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+#[test]
+fn measurements_are_summarized_and_not_recorded() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_dry_run(true)
+        .build()
+        .unwrap();
+    for uri in ["/a", "/a", "/b"] {
+        let request = http::Request::get(uri).body(String::new()).unwrap();
+        send(&layer, request, |_| http::Response::new(String::new()));
+    }
+    let request = http::Request::post("/a").body(String::new()).unwrap();
+    send(&layer, request, |_| http::Response::new(String::new()));
+
+    assert!(metrics.names().is_empty());
+    let instruments = layer.dry_run_summary().unwrap().instruments();
+    let duration = instruments
+        .iter()
+        .find(|instrument| instrument.name() == "http.server.request.duration")
+        .unwrap();
+    assert_eq!(duration.measurements(), 4);
+    assert_eq!(duration.attribute_sets(), 2);
+}
+
+#[test]
+fn recording_layers_have_no_summary() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    assert!(layer.dry_run_summary().is_none());
+    assert_eq!(
+        metrics.histogram::<f64>("http.server.request.duration")[0].count,
+        1
+    );
+}
@@ -0,0 +1,70 @@
+//! Request durations can be recorded at a finer resolution than the semconv buckets.
+
+mod common;
+
+use std::time::Duration;
+
+use tower_otel_http_metrics::{DurationResolution, HTTPMetricsLayerBuilder};
+
+use common::{send, TestMetrics};
+
+const DURATION: &str = "http.server.request.duration";
+
+const HANDLER_TIME: Duration = Duration::from_millis(2);
+
+/// Serve one request through a layer recording durations at `resolution`.
+fn served(metrics: &TestMetrics, resolution: DurationResolution) {
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_duration_resolution(resolution)
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        std::thread::sleep(HANDLER_TIME);
+        http::Response::new(String::new())
+    });
+}
+
+#[test]
+fn seconds_use_the_semconv_boundaries() {
+    let metrics = TestMetrics::new();
+    served(&metrics, DurationResolution::Seconds);
+
+    let duration = metrics.histogram::<f64>(DURATION);
+    assert_eq!(duration[0].count, 1);
+    assert!(duration[0].value >= HANDLER_TIME.as_secs_f64());
+    let (unit, bounds) = metrics.histogram_layout::<f64>(DURATION);
+    assert_eq!(unit, "s");
+    assert_eq!(bounds[0], 0.005);
+}
+
+#[test]
+fn fine_seconds_start_at_a_microsecond() {
+    let metrics = TestMetrics::new();
+    served(&metrics, DurationResolution::FineSeconds);
+
+    let duration = metrics.histogram::<f64>(DURATION);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("http.request.method").unwrap(), "GET");
+    assert!(duration[0].value >= HANDLER_TIME.as_secs_f64());
+    let (unit, bounds) = metrics.histogram_layout::<f64>(DURATION);
+    assert_eq!(unit, "s");
+    assert_eq!(bounds.first(), Some(&0.000_001));
+    assert_eq!(bounds.last(), Some(&10.0));
+}
+
+#[test]
+fn nanoseconds_are_recorded_as_integers() {
+    let metrics = TestMetrics::new();
+    served(&metrics, DurationResolution::Nanoseconds);
+
+    let duration = metrics.histogram::<u64>(DURATION);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("http.request.method").unwrap(), "GET");
+    assert!(duration[0].value >= HANDLER_TIME.as_nanos() as u64);
+    assert!(duration[0].value < Duration::from_secs(1).as_nanos() as u64);
+    let (unit, bounds) = metrics.histogram_layout::<u64>(DURATION);
+    assert_eq!(unit, "ns");
+    assert_eq!(bounds.first(), Some(&1_000.0));
+    assert_eq!(bounds.last(), Some(&10_000_000_000.0));
+}
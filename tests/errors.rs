@@ -0,0 +1,186 @@
+//! Requests which fail or are cancelled before a response are recorded with an `error.type`.
+
+mod common;
+
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt;
+use std::future::{pending, ready, Future};
+use std::io;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayer, HTTPMetricsLayerBuilder};
+use tower_service::Service;
+
+use common::{block_on, TestMetrics};
+
+const DURATION: &str = "http.server.request.duration";
+const ACTIVE_REQUESTS: &str = "http.server.active_requests";
+
+fn layer(metrics: &TestMetrics, cancelled_requests: bool) -> HTTPMetricsLayer {
+    HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_cancelled_requests(cancelled_requests)
+        .build()
+        .unwrap()
+}
+
+/// Poll the response future of a request once, then drop it.
+fn cancel<S>(service: S)
+where
+    S: Service<http::Request<String>>,
+{
+    let mut service = service;
+    let mut response = pin!(service.call(http::Request::new(String::new())));
+    let pending = response
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+        .is_pending();
+    assert!(pending);
+}
+
+fn pending_service(
+) -> impl Service<http::Request<String>, Response = http::Response<String>, Error = Infallible> + Clone
+{
+    tower::service_fn(|_req: http::Request<String>| pending())
+}
+
+#[test]
+fn cancelled_requests_are_not_recorded_by_default() {
+    let metrics = TestMetrics::new();
+    cancel(layer(&metrics, false).layer(pending_service()));
+
+    assert!(metrics.histogram::<f64>(DURATION).is_empty());
+    let active_requests = metrics.points::<i64>(ACTIVE_REQUESTS);
+    assert_eq!(active_requests[0].value, 0);
+}
+
+#[test]
+fn cancelled_requests_are_recorded_when_enabled() {
+    let metrics = TestMetrics::new();
+    cancel(layer(&metrics, true).layer(pending_service()));
+
+    let duration = metrics.histogram::<f64>(DURATION);
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(
+        duration[0].attribute("error.type").as_deref(),
+        Some("cancelled")
+    );
+    assert_eq!(duration[0].attribute("http.response.status_code"), None);
+    let active_requests = metrics.points::<i64>(ACTIVE_REQUESTS);
+    assert_eq!(active_requests[0].value, 0);
+}
+
+#[test]
+fn errors_are_recorded_as_other() {
+    let metrics = TestMetrics::new();
+    let service = layer(&metrics, false).layer(tower::service_fn(|_req: http::Request<String>| {
+        ready(Err::<http::Response<String>, _>(io::Error::from(
+            io::ErrorKind::ConnectionReset,
+        )))
+    }));
+
+    assert!(block_on(service.oneshot(http::Request::new(String::new()))).is_err());
+    let duration = metrics.histogram::<f64>(DURATION);
+    assert_eq!(
+        duration[0].attribute("error.type").as_deref(),
+        Some("_OTHER")
+    );
+    let active_requests = metrics.points::<i64>(ACTIVE_REQUESTS);
+    assert_eq!(active_requests[0].value, 0);
+}
+
+/// Error wrapping the cause of a failed request, as frameworks commonly do.
+#[derive(Debug)]
+struct HandlerError(io::Error);
+
+impl fmt::Display for HandlerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("handler failed")
+    }
+}
+
+impl Error for HandlerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// `error.type` recorded for a request failing with `err`, with client aborts classified.
+fn classified_error_type<E>(err: E) -> Option<String>
+where
+    E: Into<tower::BoxError> + Clone + Send + 'static,
+{
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_client_abort_error_type(true)
+        .build()
+        .unwrap();
+    let service = tower::ServiceBuilder::new()
+        .layer(layer.clone())
+        .layer(layer.error_type_layer())
+        .service_fn(move |_req: http::Request<String>| {
+            ready(Err::<http::Response<String>, _>(err.clone()))
+        });
+
+    assert!(block_on(service.oneshot(http::Request::new(String::new()))).is_err());
+    let duration = metrics.histogram::<f64>(DURATION);
+    assert_eq!(duration.len(), 1);
+    duration[0].attribute("error.type")
+}
+
+#[derive(Clone, Debug)]
+struct Cause(io::ErrorKind);
+
+impl From<Cause> for tower::BoxError {
+    fn from(Cause(kind): Cause) -> Self {
+        Box::new(HandlerError(io::Error::from(kind)))
+    }
+}
+
+#[test]
+fn client_aborts_are_classified_with_the_error_type_layer() {
+    for kind in [
+        io::ErrorKind::ConnectionReset,
+        io::ErrorKind::ConnectionAborted,
+        io::ErrorKind::BrokenPipe,
+    ] {
+        assert_eq!(
+            classified_error_type(Cause(kind)).as_deref(),
+            Some("client_abort"),
+            "{kind:?}"
+        );
+    }
+}
+
+#[test]
+fn other_errors_stay_other_with_the_error_type_layer() {
+    assert_eq!(
+        classified_error_type(Cause(io::ErrorKind::InvalidData)).as_deref(),
+        Some("_OTHER")
+    );
+}
+
+#[test]
+fn errors_need_not_be_static() {
+    fn wrap<'a>(message: &'a str) -> Poll<Result<(), &'a str>> {
+        let metrics = TestMetrics::new();
+        let mut service =
+            layer(&metrics, false).layer(tower::service_fn(move |_req: http::Request<String>| {
+                ready(Err::<http::Response<String>, &'a str>(message))
+            }));
+        let mut response = pin!(service.call(http::Request::new(String::new())));
+        response
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+            .map(|result| result.map(|_| ()))
+    }
+
+    let message = String::from("not static");
+    assert_eq!(wrap(&message), Poll::Ready(Err("not static")));
+}
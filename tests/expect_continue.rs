@@ -0,0 +1,72 @@
+//! `http.server.request.continue.duration` covers the upload after `100 Continue`.
+
+mod common;
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures_util::stream::{self, StreamExt};
+use http_body::Frame;
+use http_body_util::{BodyExt, StreamBody};
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, HTTPMetricsRequestBody};
+
+use common::{block_on, TestMetrics};
+
+const CONTINUE_DURATION: &str = "http.server.request.continue.duration";
+const UPLOAD_TIME: Duration = Duration::from_millis(20);
+
+/// Metrics of a layer handling an upload, with or without `Expect: 100-continue`.
+fn upload_metrics(expect_continue: bool) -> TestMetrics {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_expect_continue_duration(true)
+        .build()
+        .unwrap();
+    let service = layer
+        .request_body_layer()
+        .layer(layer.layer(tower::service_fn(
+            |req: http::Request<HTTPMetricsRequestBody<_>>| async move {
+                req.into_body().collect().await.unwrap();
+                Ok::<_, Infallible>(http::Response::new(String::new()))
+            },
+        )));
+    // the client sends the body slowly, once it gets 100 Continue
+    let body = StreamBody::new(stream::iter(["up", "load"]).map(|chunk| {
+        std::thread::sleep(UPLOAD_TIME / 2);
+        Ok::<_, Infallible>(Frame::data(Bytes::from_static(chunk.as_bytes())))
+    }));
+    let mut request = http::Request::post("/upload");
+    if expect_continue {
+        request = request.header(http::header::EXPECT, "100-continue");
+    }
+    block_on(service.oneshot(request.body(body).unwrap())).unwrap();
+    metrics
+}
+
+#[test]
+fn time_from_continue_to_the_full_body_is_recorded() {
+    let metrics = upload_metrics(true);
+
+    let continue_duration = metrics.histogram::<f64>(CONTINUE_DURATION);
+    assert_eq!(continue_duration.len(), 1);
+    assert_eq!(continue_duration[0].count, 1);
+    assert!(continue_duration[0].value >= UPLOAD_TIME.as_secs_f64());
+    assert!(continue_duration[0].value < 1.0);
+    assert_eq!(
+        continue_duration[0]
+            .attribute("http.request.method")
+            .unwrap(),
+        "POST"
+    );
+}
+
+#[test]
+fn requests_without_expect_are_not_recorded() {
+    let metrics = upload_metrics(false);
+
+    assert!(metrics.histogram::<f64>(CONTINUE_DURATION).is_empty());
+}
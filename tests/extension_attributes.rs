@@ -0,0 +1,65 @@
+//! Attributes are mapped from typed request and response extensions.
+
+mod common;
+
+use tower_otel_http_metrics::{AttributeExtractor, HTTPMetricsLayerBuilder};
+
+use common::{send, TestMetrics};
+
+#[derive(Clone)]
+struct AuthContext {
+    plan: &'static str,
+}
+
+#[derive(Clone)]
+struct CacheTier(i64);
+
+#[test]
+fn extension_values_are_recorded() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_attribute_extractor(AttributeExtractor::request_extension(
+            "app.plan",
+            |auth: &AuthContext| auth.plan,
+        ))
+        .with_attribute_extractor(AttributeExtractor::response_extension(
+            "app.cache.tier",
+            |tier: &CacheTier| tier.0,
+        ))
+        .build()
+        .unwrap();
+    let mut request = http::Request::new(String::new());
+    request
+        .extensions_mut()
+        .insert(AuthContext { plan: "enterprise" });
+    send(&layer, request, |_| {
+        let mut response = http::Response::new(String::new());
+        response.extensions_mut().insert(CacheTier(2));
+        response
+    });
+    // neither extension
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    let mut attributes: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| {
+            (
+                point.attribute("app.plan"),
+                point.attribute("app.cache.tier"),
+                point.count,
+            )
+        })
+        .collect();
+    attributes.sort();
+    assert_eq!(
+        attributes,
+        [
+            (None, None, 1),
+            (Some(String::from("enterprise")), Some(String::from("2")), 1),
+        ]
+    );
+}
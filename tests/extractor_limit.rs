@@ -0,0 +1,49 @@
+//! Attributes beyond the maximum per request are dropped and counted.
+
+mod common;
+
+use opentelemetry::KeyValue;
+use tower_otel_http_metrics::{AttributeExtractor, HTTPMetricsLayerBuilder};
+
+use common::{send, TestMetrics};
+
+#[test]
+fn excess_attributes_are_dropped_and_counted() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_attribute_extractor(AttributeExtractor::request(|_| {
+            (0..5).map(|i| KeyValue::new(format!("app.request.{i}"), i))
+        }))
+        .with_attribute_extractor(AttributeExtractor::response(|_| {
+            (0..2).map(|i| KeyValue::new(format!("app.response.{i}"), i))
+        }))
+        .with_max_extractor_attributes(4)
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    let extracted: Vec<_> = duration[0]
+        .attributes
+        .iter()
+        .map(|kv| kv.key.as_str())
+        .filter(|key| key.starts_with("app."))
+        .collect();
+    assert_eq!(
+        extracted,
+        [
+            "app.request.0",
+            "app.request.1",
+            "app.request.2",
+            "app.request.3"
+        ]
+    );
+
+    let dropped = metrics.points::<u64>("http.server.extractor.attributes.dropped");
+    assert_eq!(dropped.len(), 1);
+    assert_eq!(dropped[0].value, 3);
+}
@@ -0,0 +1,39 @@
+//! Panicking extractors lose their attributes without failing the request.
+
+mod common;
+
+use opentelemetry::KeyValue;
+use tower_otel_http_metrics::{AttributeExtractor, HTTPMetricsLayerBuilder};
+
+use common::{send, TestMetrics};
+
+#[test]
+fn panics_are_counted_per_extractor_kind() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_attribute_extractor(AttributeExtractor::request(|parts| {
+            let tenant = parts.headers["x-tenant-id"].to_str().unwrap().to_owned();
+            Some(KeyValue::new("app.tenant", tenant))
+        }))
+        .with_attribute_extractor(AttributeExtractor::response(|_| {
+            Some(KeyValue::new("app.served_by", "edge"))
+        }))
+        .build()
+        .unwrap();
+    // the request extractor panics on the missing header
+    let response = send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::from("ok"))
+    });
+    assert_eq!(response.body(), "ok");
+
+    let panics = metrics.points::<u64>("http.server.extractor.panics");
+    assert_eq!(panics.len(), 1);
+    assert_eq!(panics[0].value, 1);
+    assert_eq!(panics[0].attribute("extractor.kind").unwrap(), "request");
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].attribute("app.tenant"), None);
+    assert_eq!(duration[0].attribute("app.served_by").unwrap(), "edge");
+}
@@ -0,0 +1,39 @@
+//! Function invocations are recorded with their trigger and cold start.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+#[test]
+fn only_the_first_invocation_is_a_cold_start() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_faas_attributes(true)
+        .build()
+        .unwrap();
+    for _ in 0..3 {
+        let request = http::Request::get("/")
+            .header("x-forwarded-proto", "https")
+            .body(String::new())
+            .unwrap();
+        send(&layer, request, |_| http::Response::new(String::new()));
+    }
+
+    let mut invocations: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| {
+            assert_eq!(point.attribute("faas.trigger").unwrap(), "http");
+            assert_eq!(point.attribute("url.scheme").unwrap(), "https");
+            (point.attribute("faas.coldstart").unwrap(), point.count)
+        })
+        .collect();
+    invocations.sort();
+    assert_eq!(
+        invocations,
+        [(String::from("false"), 2), (String::from("true"), 1)]
+    );
+}
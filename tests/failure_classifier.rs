@@ -0,0 +1,57 @@
+//! The tower-http failure classifier of traces sets the `error.type` of metrics.
+#![cfg(feature = "tower-http")]
+
+mod common;
+
+use tower_http::classify::{ServerErrorsAsFailures, ServerErrorsFailureClass, SharedClassifier};
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+#[test]
+fn failure_classes_are_recorded_as_error_types() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_failure_classifier(SharedClassifier::new(ServerErrorsAsFailures::new()))
+        .build()
+        .unwrap();
+    for status in [
+        http::StatusCode::OK,
+        http::StatusCode::NOT_FOUND,
+        http::StatusCode::BAD_GATEWAY,
+    ] {
+        send(&layer, http::Request::new(String::new()), |_| {
+            http::Response::builder()
+                .status(status)
+                .body(String::new())
+                .unwrap()
+        });
+    }
+
+    let mut error_types: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| {
+            (
+                point.attribute("http.response.status_code").unwrap(),
+                point.attribute("error.type"),
+            )
+        })
+        .collect();
+    error_types.sort();
+    // 4xx responses are not failures to this classifier
+    assert_eq!(
+        error_types,
+        [
+            (String::from("200"), None),
+            (String::from("404"), None),
+            (
+                String::from("502"),
+                Some(
+                    ServerErrorsFailureClass::StatusCode(http::StatusCode::BAD_GATEWAY).to_string()
+                )
+            ),
+        ]
+    );
+}
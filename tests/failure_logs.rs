@@ -0,0 +1,140 @@
+//! Failed requests emit a log event carrying the attributes of their metric.
+#![cfg(feature = "failure-logs")]
+
+mod common;
+
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, Severity};
+use opentelemetry::Key;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+/// Log record keeping what the layer sets on it.
+#[derive(Debug, Default)]
+struct Event {
+    name: &'static str,
+    severity: Option<Severity>,
+    body: Option<AnyValue>,
+    attributes: Vec<(Key, AnyValue)>,
+}
+
+impl Event {
+    /// Value of the attribute `key`.
+    fn attribute(&self, key: &str) -> Option<&AnyValue> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k.as_str() == key)
+            .map(|(_, value)| value)
+    }
+}
+
+impl LogRecord for Event {
+    fn set_event_name(&mut self, name: &'static str) {
+        self.name = name;
+    }
+
+    fn set_target<T>(&mut self, _: T)
+    where
+        T: Into<Cow<'static, str>>,
+    {
+    }
+
+    fn set_timestamp(&mut self, _: SystemTime) {}
+
+    fn set_observed_timestamp(&mut self, _: SystemTime) {}
+
+    fn set_severity_text(&mut self, _: &'static str) {}
+
+    fn set_severity_number(&mut self, number: Severity) {
+        self.severity = Some(number);
+    }
+
+    fn set_body(&mut self, body: AnyValue) {
+        self.body = Some(body);
+    }
+
+    fn add_attributes<I, K, V>(&mut self, attributes: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<Key>,
+        V: Into<AnyValue>,
+    {
+        for (key, value) in attributes {
+            self.add_attribute(key, value);
+        }
+    }
+
+    fn add_attribute<K, V>(&mut self, key: K, value: V)
+    where
+        K: Into<Key>,
+        V: Into<AnyValue>,
+    {
+        self.attributes.push((key.into(), value.into()));
+    }
+}
+
+/// Logger keeping the events emitted.
+#[derive(Clone, Default)]
+struct RecordingLogger(Arc<Mutex<Vec<Event>>>);
+
+impl Logger for RecordingLogger {
+    type LogRecord = Event;
+
+    fn create_log_record(&self) -> Event {
+        Event::default()
+    }
+
+    fn emit(&self, record: Event) {
+        self.0.lock().unwrap().push(record);
+    }
+}
+
+#[test]
+fn only_failed_requests_are_logged() {
+    let metrics = TestMetrics::new();
+    let logger = RecordingLogger::default();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|_| Some("/users/:id"))
+        .with_failure_logs(logger.clone())
+        .build()
+        .unwrap();
+    for status in [200, 404, 502] {
+        send(&layer, http::Request::new(String::new()), |_| {
+            http::Response::builder()
+                .status(status)
+                .body(String::new())
+                .unwrap()
+        });
+    }
+
+    let failed = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .into_iter()
+        .find(|point| point.attribute("http.response.status_code").unwrap() == "502")
+        .unwrap();
+    assert_eq!(failed.count, 1);
+
+    let events = logger.0.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.name, "http.server.request.failure");
+    assert_eq!(event.severity, Some(Severity::Error));
+    assert_eq!(event.body, Some(AnyValue::from("502 Bad Gateway")));
+    assert_eq!(
+        event.attribute("http.route"),
+        Some(&AnyValue::from("/users/:id"))
+    );
+    assert_eq!(
+        event.attribute("http.response.status_code"),
+        Some(&AnyValue::from(502))
+    );
+    assert_eq!(
+        event.attribute("http.server.request.duration"),
+        Some(&AnyValue::from(failed.value))
+    );
+}
@@ -0,0 +1,64 @@
+//! Durations measured with the TSC clock match those of the OS clock.
+#![cfg(feature = "fast-time")]
+
+mod common;
+
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{AcceptTimeService, HTTPMetricsLayerBuilder, RequestMetricsContext};
+
+use common::{block_on, TestMetrics};
+
+const HANDLER_TIME: Duration = Duration::from_millis(20);
+
+#[test]
+fn request_duration_covers_the_handler() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_request_context_extension(true)
+        .build()
+        .unwrap();
+    let service = layer.layer(tower::service_fn(|req: http::Request<String>| async move {
+        std::thread::sleep(HANDLER_TIME);
+        // the context hands out the start as an OS clock instant
+        let context = req.extensions().get::<RequestMetricsContext>().unwrap();
+        assert!(context.start() <= Instant::now());
+        assert!(context.elapsed() >= HANDLER_TIME);
+        Ok::<_, Infallible>(http::Response::new(String::new()))
+    }));
+    block_on(service.oneshot(http::Request::new(String::new()))).unwrap();
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("http.request.method").unwrap(), "GET");
+    assert!(duration[0].value >= HANDLER_TIME.as_secs_f64());
+    assert!(duration[0].value < 1.0);
+}
+
+#[test]
+fn request_duration_starts_at_the_accept_time() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_duration_from_accept_time(true)
+        .build()
+        .unwrap();
+    let accepted_at = Instant::now();
+    std::thread::sleep(HANDLER_TIME);
+    let service = AcceptTimeService::new(
+        layer.layer(tower::service_fn(|_: http::Request<String>| async {
+            Ok::<_, Infallible>(http::Response::new(String::new()))
+        })),
+        accepted_at,
+    );
+    block_on(service.oneshot(http::Request::new(String::new()))).unwrap();
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration[0].count, 1);
+    assert!(duration[0].value >= HANDLER_TIME.as_secs_f64());
+}
@@ -0,0 +1,83 @@
+//! The messages of streaming gRPC calls are counted per call.
+
+mod common;
+
+use std::convert::Infallible;
+use std::vec;
+
+use bytes::Bytes;
+use futures_util::stream::{self, Iter};
+use http_body::Frame;
+use http_body_util::{BodyExt, StreamBody};
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, HTTPMetricsRequestBody};
+
+use common::{block_on, TestMetrics};
+
+type Streamed = StreamBody<Iter<vec::IntoIter<Result<Frame<Bytes>, Infallible>>>>;
+
+/// Body streaming `messages` with the gRPC message framing, split across frames at `split`.
+fn grpc_body(messages: &[&str], split: usize) -> Streamed {
+    let mut framed = Vec::new();
+    for message in messages {
+        framed.push(0);
+        framed.extend((message.len() as u32).to_be_bytes());
+        framed.extend(message.as_bytes());
+    }
+    let rest = framed.split_off(split);
+    StreamBody::new(stream::iter(vec![
+        Ok(Frame::data(Bytes::from(framed))),
+        Ok(Frame::data(Bytes::from(rest))),
+    ]))
+}
+
+fn grpc_request<B>(body: B) -> http::Request<B> {
+    http::Request::post("/helloworld.Greeter/SayHello")
+        .header(http::header::CONTENT_TYPE, "application/grpc")
+        .body(body)
+        .unwrap()
+}
+
+#[test]
+fn streamed_messages_are_counted_per_call() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_grpc_message_counts(true)
+        .build()
+        .unwrap();
+    let service = layer
+        .response_body_layer()
+        .layer(
+            layer
+                .request_body_layer()
+                .layer(layer.layer(tower::service_fn(
+                    |req: http::Request<HTTPMetricsRequestBody<Streamed>>| async {
+                        req.into_body().collect().await.unwrap();
+                        Ok::<_, Infallible>(http::Response::new(grpc_body(&["a", "bb", "ccc"], 7)))
+                    },
+                ))),
+        );
+    let response = block_on(service.oneshot(grpc_request(grpc_body(&["hi", "there"], 3)))).unwrap();
+    block_on(response.into_body().collect()).unwrap();
+
+    for (name, messages) in [
+        ("rpc.server.requests_per_rpc", 2),
+        ("rpc.server.responses_per_rpc", 3),
+    ] {
+        let per_rpc = metrics.histogram::<u64>(name);
+        assert_eq!(per_rpc.len(), 1, "{name}");
+        assert_eq!(
+            (per_rpc[0].count, per_rpc[0].value),
+            (1, messages),
+            "{name}"
+        );
+        assert_eq!(per_rpc[0].attribute("rpc.system").unwrap(), "grpc");
+        assert_eq!(
+            per_rpc[0].attribute("rpc.service").unwrap(),
+            "helloworld.Greeter"
+        );
+        assert_eq!(per_rpc[0].attribute("rpc.method").unwrap(), "SayHello");
+    }
+}
@@ -0,0 +1,50 @@
+//! gRPC calls are attributed to their service.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+#[test]
+fn calls_are_attributed_to_their_service() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_grpc_service_attributes(true)
+        .build()
+        .unwrap();
+    let request = http::Request::post("/helloworld.Greeter/SayHello")
+        .header(http::header::CONTENT_TYPE, "application/grpc+proto")
+        .body(String::new())
+        .unwrap();
+    send(&layer, request, |_| http::Response::new(String::new()));
+    // not a gRPC call
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    let mut services: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| {
+            (
+                point.attribute("rpc.system"),
+                point.attribute("rpc.service"),
+                point.count,
+            )
+        })
+        .collect();
+    services.sort();
+    assert_eq!(
+        services,
+        [
+            (None, None, 1),
+            (
+                Some(String::from("grpc")),
+                Some(String::from("helloworld.Greeter")),
+                1
+            ),
+        ]
+    );
+}
@@ -0,0 +1,99 @@
+//! Informational responses are counted, and interim ones never stand in for the final response.
+
+mod common;
+
+use std::convert::Infallible;
+use std::vec;
+
+use bytes::Bytes;
+use futures_util::stream::{self, Iter};
+use http_body::Frame;
+use http_body_util::{BodyExt, StreamBody};
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, HTTPMetricsRequestBody};
+
+use common::{block_on, send, TestMetrics};
+
+const INFORMATIONAL: &str = "http.server.informational_responses";
+
+type Streamed = StreamBody<Iter<vec::IntoIter<Result<Frame<Bytes>, Infallible>>>>;
+
+#[test]
+fn switching_protocols_responses_are_counted() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_informational_responses_counter(true)
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::builder()
+            .status(http::StatusCode::SWITCHING_PROTOCOLS)
+            .body(String::new())
+            .unwrap()
+    });
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    let informational = metrics.points::<u64>(INFORMATIONAL);
+    assert_eq!(informational.len(), 1);
+    assert_eq!(informational[0].value, 1);
+    assert_eq!(
+        informational[0]
+            .attribute("http.response.status_code")
+            .unwrap(),
+        "101"
+    );
+    assert_eq!(
+        metrics
+            .histogram::<f64>("http.server.request.duration")
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn continue_is_counted_and_the_final_status_recorded() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_informational_responses_counter(true)
+        .with_expect_continue_duration(true)
+        .build()
+        .unwrap();
+    let service = layer
+        .request_body_layer()
+        .layer(layer.layer(tower::service_fn(
+            |req: http::Request<HTTPMetricsRequestBody<Streamed>>| async move {
+                req.into_body().collect().await.unwrap();
+                let mut response = http::Response::new(String::new());
+                *response.status_mut() = http::StatusCode::CREATED;
+                Ok::<_, Infallible>(response)
+            },
+        )));
+    let body = StreamBody::new(stream::iter(vec![Ok(Frame::data(Bytes::from_static(
+        b"upload",
+    )))]));
+    let request = http::Request::put("/upload")
+        .header(http::header::EXPECT, "100-continue")
+        .body(body)
+        .unwrap();
+    block_on(service.oneshot(request)).unwrap();
+
+    let informational = metrics.points::<u64>(INFORMATIONAL);
+    assert_eq!(informational.len(), 1);
+    assert_eq!(
+        informational[0]
+            .attribute("http.response.status_code")
+            .unwrap(),
+        "100"
+    );
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(
+        duration[0].attribute("http.response.status_code").unwrap(),
+        "201"
+    );
+}
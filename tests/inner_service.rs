@@ -0,0 +1,57 @@
+//! The wrapped service is reachable through the metrics service.
+
+mod common;
+
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::task::{Context, Poll};
+
+use tower::{Service, ServiceExt};
+use tower_layer::Layer;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{block_on, TestMetrics};
+
+/// Service counting the requests it answered.
+#[derive(Debug, Default)]
+struct CountingService {
+    calls: usize,
+}
+
+impl Service<http::Request<String>> for CountingService {
+    type Response = http::Response<String>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: http::Request<String>) -> Self::Future {
+        self.calls += 1;
+        ready(Ok(http::Response::new(String::new())))
+    }
+}
+
+#[test]
+fn inner_service_is_reachable_after_recording() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    let mut service = layer.layer(CountingService::default());
+    for _ in 0..2 {
+        let ready = block_on(service.ready()).unwrap();
+        block_on(ready.call(http::Request::new(String::new()))).unwrap();
+    }
+
+    assert_eq!(service.get_ref().calls, 2);
+    service.get_mut().calls = 0;
+    assert_eq!(service.into_inner().calls, 0);
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 2);
+    assert_eq!(duration[0].attribute("http.request.method").unwrap(), "GET");
+}
@@ -0,0 +1,60 @@
+//! Known routes are counted from startup, at zero until requested.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+const REQUEST_COUNT: &str = "http.server.request.count";
+
+/// The request counts of the known routes, by method and route.
+fn request_counts(metrics: &TestMetrics) -> Vec<(String, String, u64)> {
+    let mut counts: Vec<_> = metrics
+        .points::<u64>(REQUEST_COUNT)
+        .iter()
+        .map(|point| {
+            (
+                point.attribute("http.request.method").unwrap(),
+                point.attribute("http.route").unwrap(),
+                point.value,
+            )
+        })
+        .collect();
+    counts.sort();
+    counts
+}
+
+#[test]
+fn known_routes_are_reported_before_their_first_request() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|parts| (parts.uri.path() == "/users").then_some("/users"))
+        .with_known_route(http::Method::GET, "/users")
+        .with_known_route(http::Method::POST, "/users")
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        request_counts(&metrics),
+        [
+            (String::from("GET"), String::from("/users"), 0),
+            (String::from("POST"), String::from("/users"), 0),
+        ]
+    );
+
+    let request = http::Request::get("/users").body(String::new()).unwrap();
+    send(&layer, request, |_| http::Response::new(String::new()));
+    // unknown routes are not counted
+    let request = http::Request::get("/health").body(String::new()).unwrap();
+    send(&layer, request, |_| http::Response::new(String::new()));
+
+    assert_eq!(
+        request_counts(&metrics),
+        [
+            (String::from("GET"), String::from("/users"), 1),
+            (String::from("POST"), String::from("/users"), 0),
+        ]
+    );
+}
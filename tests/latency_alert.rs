@@ -0,0 +1,58 @@
+//! Latency alerts fire once the quantile of a route crosses the threshold.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+const THRESHOLD: Duration = Duration::from_millis(2);
+// evaluated once per quarter of the window
+const WINDOW: Duration = Duration::from_millis(400);
+
+#[test]
+fn slow_route_fires_an_alert() {
+    let metrics = TestMetrics::new();
+    let alerts = Arc::new(Mutex::new(Vec::new()));
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|_| Some("/reports"))
+        .with_latency_alert(0.99, THRESHOLD, WINDOW, {
+            let alerts = alerts.clone();
+            move |alert| {
+                alerts.lock().unwrap().push((
+                    alert.route().map(str::to_owned),
+                    alert.firing(),
+                    alert.latency(),
+                ))
+            }
+        })
+        .build()
+        .unwrap();
+    for _ in 0..3 {
+        send(&layer, http::Request::new(String::new()), |_| {
+            std::thread::sleep(THRESHOLD * 3);
+            http::Response::new(String::new())
+        });
+    }
+    // the next request opens a new slot, evaluating the slow ones
+    std::thread::sleep(WINDOW / 4 + Duration::from_millis(10));
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    let alerts = alerts.lock().unwrap();
+    assert_eq!(alerts.len(), 1);
+    let (route, firing, latency) = &alerts[0];
+    assert_eq!(route.as_deref(), Some("/reports"));
+    assert!(firing);
+    assert!(*latency > THRESHOLD);
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 4);
+    assert_eq!(duration[0].attribute("http.route").unwrap(), "/reports");
+}
@@ -0,0 +1,98 @@
+//! Metered services report their load to balancers from the requests they track.
+#![cfg(feature = "load")]
+
+mod common;
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use tower::load::Load;
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, LoadMeasure};
+use tower_service::Service;
+
+use common::{block_on, TestMetrics};
+
+const HANDLER_TIME: Duration = Duration::from_millis(10);
+
+/// Handler answering after `HANDLER_TIME`.
+fn handler(
+) -> impl Service<http::Request<String>, Response = http::Response<String>, Error = Infallible> + Clone
+{
+    tower::service_fn(|_: http::Request<String>| async {
+        std::thread::sleep(HANDLER_TIME);
+        Ok::<_, Infallible>(http::Response::new(String::new()))
+    })
+}
+
+#[test]
+fn active_requests_are_the_load() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_load_measure(LoadMeasure::ActiveRequests)
+        .build()
+        .unwrap();
+    let mut service = layer.layer(handler());
+    assert_eq!(service.load(), 0.0);
+
+    block_on(service.ready()).unwrap();
+    let response = service.call(http::Request::new(String::new()));
+    // clones share the load
+    assert_eq!(service.clone().load(), 1.0);
+    let active_requests = metrics.points::<i64>("http.server.active_requests");
+    assert_eq!(active_requests[0].value, 1);
+
+    block_on(response).unwrap();
+    assert_eq!(service.load(), 0.0);
+    assert_eq!(
+        metrics.points::<i64>("http.server.active_requests")[0].value,
+        0
+    );
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("http.request.method").unwrap(), "GET");
+}
+
+#[test]
+fn latency_ewma_follows_the_request_durations() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_load_measure(LoadMeasure::LatencyEwma {
+            decay: Duration::from_secs(60),
+        })
+        .build()
+        .unwrap();
+    let service = layer.layer(handler());
+    // unseeded until the first request completes
+    assert_eq!(service.load(), 0.0);
+
+    block_on(service.clone().oneshot(http::Request::new(String::new()))).unwrap();
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration[0].count, 1);
+    let load = service.load();
+    assert!(load >= HANDLER_TIME.as_secs_f64() * 0.9);
+    assert!(load <= duration[0].value * 1.1);
+}
+
+#[test]
+fn services_without_a_measure_have_no_load() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    let mut service = layer.layer(handler());
+    block_on(service.ready()).unwrap();
+    let response = service.call(http::Request::new(String::new()));
+    assert_eq!(service.load(), 0.0);
+    block_on(response).unwrap();
+
+    assert_eq!(
+        metrics.histogram::<f64>("http.server.request.duration")[0].count,
+        1
+    );
+}
@@ -0,0 +1,66 @@
+//! Rejected requests are counted with the reason and the configured default scheme.
+#![cfg(feature = "load-shed")]
+
+mod common;
+
+use std::future::{ready, Ready};
+use std::task::{Context, Poll};
+
+use tower::load_shed::error::Overloaded;
+use tower::BoxError;
+use tower_layer::Layer;
+use tower_otel_http_metrics::load_shed::RejectedRequestsLayer;
+use tower_service::Service;
+
+use common::{block_on, TestMetrics};
+
+/// Inner service shedding every request.
+struct Shedding;
+
+impl Service<http::Request<String>> for Shedding {
+    type Response = http::Response<String>;
+    type Error = BoxError;
+    type Future = Ready<Result<Self::Response, BoxError>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: http::Request<String>) -> Self::Future {
+        ready(Err(Overloaded::new().into()))
+    }
+}
+
+fn rejected_scheme(layer: RejectedRequestsLayer, metrics: &TestMetrics) -> Option<String> {
+    let mut service = layer.layer(Shedding);
+    let request = http::Request::builder()
+        .uri("/path")
+        .body(String::new())
+        .unwrap();
+    assert!(block_on(service.call(request)).is_err());
+
+    let rejected = metrics.points::<u64>("http.server.rejected_requests");
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(rejected[0].value, 1);
+    assert_eq!(
+        rejected[0].attribute("rejection.reason").as_deref(),
+        Some("overloaded")
+    );
+    rejected[0].attribute("url.scheme")
+}
+
+#[test]
+fn rejections_get_the_default_url_scheme() {
+    let metrics = TestMetrics::new();
+    let layer = RejectedRequestsLayer::new(&metrics.meter()).with_default_url_scheme("https");
+    assert_eq!(rejected_scheme(layer, &metrics).as_deref(), Some("https"));
+}
+
+#[test]
+fn classifier_keeps_the_default_url_scheme() {
+    let metrics = TestMetrics::new();
+    let layer = RejectedRequestsLayer::new(&metrics.meter())
+        .with_default_url_scheme("https")
+        .with_classifier(|_| None);
+    assert_eq!(rejected_scheme(layer, &metrics).as_deref(), Some("https"));
+}
@@ -0,0 +1,43 @@
+//! Services whose futures are not `Send` can be wrapped, as on thread-per-core runtimes.
+
+mod common;
+
+use std::convert::Infallible;
+use std::rc::Rc;
+
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{block_on, TestMetrics};
+
+#[test]
+fn non_send_services_are_recorded() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    // the service and its futures hold an Rc, so neither is Send
+    let greeting = Rc::new(String::from("hello"));
+    let service = layer.layer(tower::service_fn(move |_: http::Request<String>| {
+        let greeting = greeting.clone();
+        async move {
+            let response = http::Response::builder()
+                .status(http::StatusCode::ACCEPTED)
+                .body(greeting.to_string())
+                .unwrap();
+            Ok::<_, Infallible>(response)
+        }
+    }));
+    let response = block_on(service.oneshot(http::Request::new(String::new()))).unwrap();
+    assert_eq!(response.body(), "hello");
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(
+        duration[0].attribute("http.response.status_code").unwrap(),
+        "202"
+    );
+}
@@ -0,0 +1,79 @@
+//! Requests with a malformed `Content-Length` are counted, and their body size is not recorded.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+const REQUEST_BODY_SIZE: &str = "http.server.request.body.size";
+const MALFORMED: &str = "http.server.request.body.size.malformed";
+
+/// POST `/upload` request with the `Content-Length` values `lengths`.
+fn upload(lengths: &[&'static str]) -> http::Request<String> {
+    let mut request = http::Request::post("https://example.com/upload");
+    for length in lengths {
+        request = request.header(http::header::CONTENT_LENGTH, *length);
+    }
+    request.body(String::new()).unwrap()
+}
+
+#[test]
+fn malformed_content_lengths_are_counted_instead_of_recorded() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|parts| (parts.uri.path() == "/upload").then_some("/upload"))
+        .with_malformed_content_length_counter(true)
+        .with_max_content_length(1024)
+        .build()
+        .unwrap();
+    for lengths in [
+        &["5"][..],
+        &["+5"],
+        &["5, 6"],
+        &["5", "6"],
+        &["99999999999999999999"],
+        &["2048"],
+        &["7, 7"],
+    ] {
+        send(&layer, upload(lengths), |_| {
+            http::Response::new(String::new())
+        });
+    }
+
+    let malformed = metrics.points::<u64>(MALFORMED);
+    assert_eq!(malformed.len(), 1);
+    assert_eq!(malformed[0].value, 5);
+    assert_eq!(
+        malformed[0].attribute("http.request.method").unwrap(),
+        "POST"
+    );
+    assert_eq!(malformed[0].attribute("url.scheme").unwrap(), "https");
+    assert_eq!(malformed[0].attribute("http.route").unwrap(), "/upload");
+
+    // only the valid lengths are recorded
+    let body_size = metrics.histogram::<u64>(REQUEST_BODY_SIZE);
+    assert_eq!(body_size.len(), 1);
+    assert_eq!(body_size[0].count, 2);
+    assert_eq!(body_size[0].value, 12);
+}
+
+#[test]
+fn malformed_content_lengths_are_not_counted_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    send(&layer, upload(&["five"]), |_| {
+        http::Response::new(String::new())
+    });
+
+    assert!(metrics.points::<u64>(MALFORMED).is_empty());
+    assert!(metrics.histogram::<u64>(REQUEST_BODY_SIZE).is_empty());
+    assert_eq!(
+        metrics.histogram::<f64>("http.server.request.duration")[0].count,
+        1
+    );
+}
@@ -0,0 +1,42 @@
+//! Durations above the configured maximum are clamped and counted.
+
+mod common;
+
+use std::time::Duration;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+const MAX_DURATION: Duration = Duration::from_millis(10);
+
+#[test]
+fn long_requests_are_clamped_and_counted() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_max_request_duration(MAX_DURATION)
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        std::thread::sleep(MAX_DURATION * 3);
+        http::Response::new(String::new())
+    });
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 2);
+    assert!(duration[0].value <= 2.0 * MAX_DURATION.as_secs_f64());
+
+    let overflow = metrics.points::<u64>("http.server.request.duration.overflow");
+    assert_eq!(overflow.len(), 1);
+    assert_eq!(overflow[0].value, 1);
+    assert_eq!(overflow[0].attribute("http.request.method").unwrap(), "GET");
+    assert_eq!(
+        overflow[0].attribute("http.response.status_code").unwrap(),
+        "200"
+    );
+}
@@ -0,0 +1,80 @@
+//! `405 Method Not Allowed` responses are attributed to the known route matching their path.
+
+mod common;
+
+use tower_otel_http_metrics::{HTTPMetricsLayer, HTTPMetricsLayerBuilder};
+
+use common::{send, TestMetrics};
+
+/// Layer knowing the `GET /users/:id` route.
+fn layer(metrics: &TestMetrics) -> HTTPMetricsLayer {
+    HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_known_route(http::Method::GET, "/users/:id")
+        .build()
+        .unwrap()
+}
+
+/// Send a `method` request to `uri`, answered with `status`.
+fn respond(layer: &HTTPMetricsLayer, method: http::Method, uri: &str, status: http::StatusCode) {
+    let request = http::Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(String::new())
+        .unwrap();
+    send(layer, request, |_| {
+        http::Response::builder()
+            .status(status)
+            .body(String::new())
+            .unwrap()
+    });
+}
+
+#[test]
+fn method_not_allowed_is_attributed_to_the_known_route() {
+    let metrics = TestMetrics::new();
+    let layer = layer(&metrics);
+    respond(
+        &layer,
+        http::Method::DELETE,
+        "/users/1",
+        http::StatusCode::METHOD_NOT_ALLOWED,
+    );
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("http.route").unwrap(), "/users/:id");
+    assert_eq!(
+        duration[0].attribute("http.request.method").unwrap(),
+        "DELETE"
+    );
+    assert_eq!(
+        duration[0].attribute("http.response.status_code").unwrap(),
+        "405"
+    );
+}
+
+#[test]
+fn other_responses_and_unknown_paths_keep_no_route() {
+    let metrics = TestMetrics::new();
+    let layer = layer(&metrics);
+    respond(
+        &layer,
+        http::Method::DELETE,
+        "/users/1",
+        http::StatusCode::NOT_FOUND,
+    );
+    respond(
+        &layer,
+        http::Method::DELETE,
+        "/orders/1",
+        http::StatusCode::METHOD_NOT_ALLOWED,
+    );
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 2);
+    assert!(duration
+        .iter()
+        .all(|point| point.attribute("http.route").is_none()));
+}
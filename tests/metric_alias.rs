@@ -0,0 +1,42 @@
+//! Measurements are duplicated under the legacy names of their instrument.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+#[test]
+fn aliases_record_the_same_measurements() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_metric_alias(
+            "http.server.request.duration",
+            "http_request_duration_seconds",
+        )
+        .with_metric_alias("http.server.request.duration", "legacy.request.duration")
+        .build()
+        .unwrap();
+    let request = http::Request::put("/items/1").body(String::new()).unwrap();
+    send(&layer, request, |_| {
+        http::Response::builder()
+            .status(http::StatusCode::NO_CONTENT)
+            .body(String::new())
+            .unwrap()
+    });
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    for alias in ["http_request_duration_seconds", "legacy.request.duration"] {
+        let aliased = metrics.histogram::<f64>(alias);
+        assert_eq!(aliased.len(), 1, "{alias}");
+        assert_eq!(aliased[0].count, 1);
+        assert_eq!(aliased[0].value, duration[0].value);
+        assert_eq!(aliased[0].attribute("http.request.method").unwrap(), "PUT");
+        assert_eq!(
+            aliased[0].attribute("http.response.status_code").unwrap(),
+            "204"
+        );
+    }
+}
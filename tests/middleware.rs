@@ -0,0 +1,39 @@
+//! The function middleware records like the layer, within axum's `from_fn_with_state`.
+#![cfg(feature = "axum")]
+
+mod common;
+
+use axum::body::Body;
+use axum::routing::post;
+use axum::{middleware, Router};
+use tower::ServiceExt;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{block_on, TestMetrics};
+
+#[test]
+fn middleware_records_routed_requests() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    let router = Router::new()
+        .route("/users/:id", post(|| async { http::StatusCode::CREATED }))
+        .route_layer(middleware::from_fn_with_state(
+            layer,
+            tower_otel_http_metrics::middleware,
+        ));
+    let request = http::Request::post("/users/1").body(Body::empty()).unwrap();
+    let response = block_on(router.oneshot(request)).unwrap();
+    assert_eq!(response.status(), http::StatusCode::CREATED);
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("http.route").unwrap(), "/users/:id");
+    assert_eq!(
+        duration[0].attribute("http.response.status_code").unwrap(),
+        "201"
+    );
+}
@@ -0,0 +1,95 @@
+//! Fast requests of chatty routes are counted rather than recorded into the duration histogram.
+
+mod common;
+
+use std::time::Duration;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+const DURATION: &str = "http.server.request.duration";
+const FAST_REQUESTS: &str = "http.server.request.fast";
+
+const THRESHOLD: Duration = Duration::from_millis(10);
+
+#[test]
+fn fast_requests_of_the_route_are_only_counted() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|parts| match parts.uri.path() {
+            "/cache" => Some("/cache"),
+            _ => Some("/users"),
+        })
+        .with_min_recorded_duration("/cache", THRESHOLD)
+        .build()
+        .unwrap();
+    for (path, handler_time) in [
+        ("/cache", Duration::ZERO),
+        ("/cache", Duration::ZERO),
+        ("/cache", THRESHOLD * 2),
+        ("/users", Duration::ZERO),
+    ] {
+        let request = http::Request::post(path)
+            .header(http::header::CONTENT_LENGTH, "3")
+            .body(String::from("abc"))
+            .unwrap();
+        send(&layer, request, |_| {
+            std::thread::sleep(handler_time);
+            http::Response::new(String::new())
+        });
+    }
+
+    let fast = metrics.points::<u64>(FAST_REQUESTS);
+    assert_eq!(fast.len(), 1);
+    assert_eq!(fast[0].value, 2);
+    assert_eq!(fast[0].attribute("http.route").unwrap(), "/cache");
+    assert_eq!(fast[0].attribute("http.request.method").unwrap(), "POST");
+    assert_eq!(
+        fast[0].attribute("http.response.status_code").unwrap(),
+        "200"
+    );
+
+    let mut durations: Vec<_> = metrics
+        .histogram::<f64>(DURATION)
+        .iter()
+        .map(|point| (point.attribute("http.route").unwrap(), point.count))
+        .collect();
+    durations.sort();
+    assert_eq!(
+        durations,
+        [(String::from("/cache"), 1), (String::from("/users"), 1)]
+    );
+    let slow = metrics
+        .histogram::<f64>(DURATION)
+        .into_iter()
+        .find(|point| point.attribute("http.route").unwrap() == "/cache")
+        .unwrap();
+    assert!(slow.value >= (THRESHOLD * 2).as_secs_f64());
+
+    // other instruments still record the fast requests
+    let body_size = metrics.histogram::<u64>("http.server.request.body.size");
+    let cache_body_size = body_size
+        .iter()
+        .find(|point| point.attribute("http.route").unwrap() == "/cache")
+        .unwrap();
+    assert_eq!(cache_body_size.count, 3);
+}
+
+#[test]
+fn requests_are_all_recorded_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    for _ in 0..3 {
+        send(&layer, http::Request::new(String::new()), |_| {
+            http::Response::new(String::new())
+        });
+    }
+
+    assert!(metrics.points::<u64>(FAST_REQUESTS).is_empty());
+    assert_eq!(metrics.histogram::<f64>(DURATION)[0].count, 3);
+}
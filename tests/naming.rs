@@ -0,0 +1,96 @@
+//! Metrics and attributes are recorded under the names of the configured convention.
+
+mod common;
+
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, NamingConvention};
+
+use common::{send, TestMetrics};
+
+/// Metrics of a layer using `convention` handling a request to a route with a request body.
+fn named_metrics(convention: NamingConvention) -> TestMetrics {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_naming_convention(convention)
+        .with_route_extractor(|_| Some("/users/:id"))
+        .build()
+        .unwrap();
+    let request = http::Request::put("/users/1")
+        .header(http::header::CONTENT_LENGTH, "5")
+        .body(String::from("hello"))
+        .unwrap();
+    send(&layer, request, |_| {
+        http::Response::builder()
+            .status(http::StatusCode::ACCEPTED)
+            .body(String::new())
+            .unwrap()
+    });
+    metrics
+}
+
+#[test]
+fn elastic_common_schema_names() {
+    let metrics = named_metrics(NamingConvention::ElasticCommonSchema);
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(
+        duration[0].attribute("transaction.name").unwrap(),
+        "PUT /users/:id"
+    );
+    assert_eq!(duration[0].attribute("http.version").unwrap(), "1.1");
+    assert_eq!(duration[0].attribute("network.protocol").unwrap(), "http");
+    assert_eq!(duration[0].attribute("http.route"), None);
+    assert_eq!(duration[0].attribute("network.protocol.version"), None);
+
+    let body_size = metrics.histogram::<u64>("http.server.request.body.bytes");
+    assert_eq!(body_size.len(), 1);
+    assert_eq!(body_size[0].value, 5);
+    assert!(metrics
+        .histogram::<u64>("http.server.request.body.size")
+        .is_empty());
+}
+
+#[test]
+fn datadog_names() {
+    let metrics = named_metrics(NamingConvention::Datadog);
+
+    assert!(metrics
+        .histogram::<f64>("http.server.request.duration")
+        .is_empty());
+    let duration = metrics.histogram::<f64>("trace.http.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(
+        duration[0].attribute("resource_name").unwrap(),
+        "PUT /users/:id"
+    );
+    assert_eq!(duration[0].attribute("http.method").unwrap(), "PUT");
+    assert_eq!(duration[0].attribute("http.status_code").unwrap(), "202");
+    assert_eq!(duration[0].attribute("http.request.method"), None);
+}
+
+#[test]
+fn axum_prometheus_names() {
+    let metrics = named_metrics(NamingConvention::AxumPrometheus);
+
+    let duration = metrics.histogram::<f64>("axum_http_requests_duration_seconds");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    let total = metrics.points::<u64>("axum_http_requests_total");
+    assert_eq!(total.len(), 1);
+    assert_eq!(total[0].value, 1);
+    for point in [&duration[0].attributes, &total[0].attributes] {
+        let mut keys: Vec<_> = point.iter().map(|kv| kv.key.as_str()).collect();
+        keys.sort();
+        assert_eq!(keys, ["endpoint", "method", "status"]);
+    }
+    assert_eq!(total[0].attribute("method").unwrap(), "PUT");
+    assert_eq!(total[0].attribute("endpoint").unwrap(), "/users/:id");
+    assert_eq!(total[0].attribute("status").unwrap(), "202");
+
+    let pending = metrics.points::<i64>("axum_http_requests_pending");
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].value, 0);
+}
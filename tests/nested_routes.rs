@@ -0,0 +1,96 @@
+//! Services nested in axum routers record their route with the mount path.
+#![cfg(feature = "axum")]
+
+mod common;
+
+use std::convert::Infallible;
+
+use axum::body::Body;
+use axum::routing::get;
+use axum::Router;
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayer, HTTPMetricsLayerBuilder};
+
+use common::{block_on, TestMetrics};
+
+/// The `http.route` values recorded for requests to `uris` through `router`.
+fn routes(metrics: &TestMetrics, router: Router, uris: &[&str]) -> Vec<(String, u64)> {
+    for uri in uris {
+        let request = http::Request::get(*uri).body(Body::empty()).unwrap();
+        block_on(router.clone().oneshot(request)).unwrap();
+    }
+    let mut routes: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("http.route").unwrap(), point.count))
+        .collect();
+    routes.sort();
+    routes
+}
+
+fn assets(layer: &HTTPMetricsLayer) -> Router {
+    let service = layer.layer(tower::service_fn(|_: http::Request<Body>| async {
+        Ok::<_, Infallible>(http::Response::new(Body::empty()))
+    }));
+    Router::new().nest_service("/assets", service)
+}
+
+#[test]
+fn nested_routers_record_the_full_route() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    let api = Router::new()
+        .route("/users/:id", get(|| async {}))
+        .layer(layer);
+    let router = Router::new().nest("/api", api);
+
+    assert_eq!(
+        routes(&metrics, router, &["/api/users/1", "/api/users/2"]),
+        [(String::from("/api/users/:id"), 2)]
+    );
+}
+
+#[test]
+fn nested_services_record_their_mount_path() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        routes(
+            &metrics,
+            assets(&layer),
+            &["/assets/app.js", "/assets/img/logo.png"]
+        ),
+        [(String::from("/assets/*path"), 2)]
+    );
+}
+
+#[cfg(feature = "route-patterns")]
+#[test]
+fn patterns_match_the_original_uri_of_nested_services() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_pattern("/assets/img/:file")
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        routes(
+            &metrics,
+            assets(&layer),
+            &["/assets/app.js", "/assets/img/logo.png"]
+        ),
+        [
+            (String::from("/assets/*path"), 1),
+            (String::from("/assets/img/:file"), 1)
+        ]
+    );
+}
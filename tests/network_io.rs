@@ -0,0 +1,61 @@
+//! `http.server.network.io` counts the header and body bytes in each direction.
+
+mod common;
+
+use std::convert::Infallible;
+
+use bytes::Bytes;
+use http_body::Frame;
+use http_body_util::{BodyExt, StreamBody};
+use tower::ServiceExt;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{block_on, TestMetrics};
+
+#[test]
+fn header_and_body_bytes_are_counted_per_direction() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_network_io_counter(true)
+        .build()
+        .unwrap();
+    let service = tower::ServiceBuilder::new()
+        .layer(layer.response_body_layer())
+        .layer(layer)
+        .service_fn(|_req: http::Request<String>| async {
+            let frames = ["abc", "de"].map(|chunk| {
+                Ok::<_, Infallible>(Frame::data(Bytes::from_static(chunk.as_bytes())))
+            });
+            Ok::<_, Infallible>(http::Response::new(StreamBody::new(
+                futures_util::stream::iter(frames),
+            )))
+        });
+    let request = http::Request::post("/upload")
+        .header(http::header::CONTENT_LENGTH, "5")
+        .body(String::from("hello"))
+        .unwrap();
+    let response = block_on(service.oneshot(request)).unwrap();
+    block_on(response.into_body().collect()).unwrap();
+
+    let mut io: Vec<_> = metrics
+        .points::<u64>("http.server.network.io")
+        .iter()
+        .map(|point| {
+            assert_eq!(point.attributes.len(), 1);
+            (
+                point.attribute("network.io.direction").unwrap(),
+                point.value,
+            )
+        })
+        .collect();
+    io.sort();
+    // "POST /upload HTTP/1.1\r\ncontent-length: 5\r\n\r\nhello" and "HTTP/1.1 200 OK\r\n\r\nabcde"
+    assert_eq!(
+        io,
+        [
+            (String::from("receive"), 49),
+            (String::from("transmit"), 24)
+        ]
+    );
+}
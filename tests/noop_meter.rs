@@ -0,0 +1,105 @@
+//! With no-op meter detection, requests pass through layers whose meter records nothing.
+
+mod common;
+
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use opentelemetry::metrics::{AsyncInstrumentBuilder, InstrumentProvider, Meter, ObservableGauge};
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, TimingCheckpoints};
+
+use common::{block_on, TestMetrics};
+
+/// Whether a request reached the inner service with the timing checkpoints extension,
+/// which is only inserted when the request is measured.
+fn measured(meter: Meter, noop_meter_detection: bool) -> bool {
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(meter)
+        .with_timing_checkpoints(true)
+        .with_noop_meter_detection(noop_meter_detection)
+        .build()
+        .unwrap();
+    let service = layer.layer(tower::service_fn(|req: http::Request<String>| async move {
+        let measured = req.extensions().get::<TimingCheckpoints>().is_some();
+        let response = http::Response::builder()
+            .header("x-measured", measured.to_string())
+            .body(String::new());
+        Ok::<_, Infallible>(response.unwrap())
+    }));
+    let response = block_on(service.oneshot(http::Request::new(String::new()))).unwrap();
+    response.headers()["x-measured"] == "true"
+}
+
+#[test]
+fn noop_meters_are_measured_by_default() {
+    assert!(measured(opentelemetry::global::meter("test"), false));
+}
+
+#[test]
+fn noop_meters_pass_through_when_detected() {
+    assert!(!measured(opentelemetry::global::meter("test"), true));
+}
+
+#[test]
+fn sdk_meters_are_measured_when_detecting() {
+    let metrics = TestMetrics::new();
+    assert!(measured(metrics.meter(), true));
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration[0].count, 1);
+    // the probe never reports a value, so it is not exported
+    assert!(!metrics
+        .names()
+        .iter()
+        .any(|name| name == "http.server.meter.probe"));
+}
+
+/// Meter recording nothing, counting the no-op meter probes registered with it.
+#[derive(Default)]
+struct CountingInstruments(AtomicUsize);
+
+impl InstrumentProvider for CountingInstruments {
+    fn u64_observable_gauge(
+        &self,
+        builder: AsyncInstrumentBuilder<'_, ObservableGauge<u64>, u64>,
+    ) -> ObservableGauge<u64> {
+        if builder.name == "http.server.meter.probe" {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+        ObservableGauge::new()
+    }
+}
+
+/// The number of probes registered with a meter when building a layer with `builder`.
+fn probes(builder: HTTPMetricsLayerBuilder) -> usize {
+    let instruments = Arc::new(CountingInstruments::default());
+    builder
+        .with_meter(Meter::new(instruments.clone()))
+        .build()
+        .unwrap();
+    instruments.0.load(Ordering::Relaxed)
+}
+
+#[test]
+fn meters_are_probed_once_when_detecting() {
+    assert_eq!(
+        probes(HTTPMetricsLayerBuilder::new().with_noop_meter_detection(true)),
+        1
+    );
+}
+
+#[test]
+fn meters_are_not_probed_without_detection() {
+    assert_eq!(probes(HTTPMetricsLayerBuilder::new()), 0);
+}
+
+#[test]
+fn meters_are_not_probed_when_requests_are_handled_in_full() {
+    let builder = HTTPMetricsLayerBuilder::new()
+        .with_noop_meter_detection(true)
+        .with_request_context_extension(true);
+    assert_eq!(probes(builder), 0);
+}
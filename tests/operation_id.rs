@@ -0,0 +1,102 @@
+//! Requests are recorded with the OpenAPI operation which handled them.
+
+mod common;
+
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, OperationId};
+
+use common::{send, TestMetrics};
+
+#[test]
+fn operation_ids_come_from_the_response_or_the_registered_routes() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|parts| {
+            parts
+                .uri
+                .path()
+                .starts_with("/users/")
+                .then_some("/users/:id")
+        })
+        .with_operation_id(http::Method::GET, "/users/:id", "getUser")
+        .with_operation_id(http::Method::DELETE, "/users/:id", "deleteUser")
+        .build()
+        .unwrap();
+    let requests = [
+        (http::Method::GET, "/users/1", None),
+        (http::Method::DELETE, "/users/1", None),
+        // the response extension takes precedence
+        (http::Method::GET, "/users/2", Some("getUserV2")),
+        // neither registered nor reported
+        (http::Method::PUT, "/users/1", None),
+        (http::Method::GET, "/health", None),
+    ];
+    for (method, uri, reported) in requests {
+        let request = http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(String::new())
+            .unwrap();
+        send(&layer, request, |_| {
+            let mut response = http::Response::new(String::new());
+            if let Some(operation_id) = reported {
+                response
+                    .extensions_mut()
+                    .insert(OperationId::new(operation_id));
+            }
+            response
+        });
+    }
+
+    let mut operations: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| {
+            (
+                point.attribute("http.request.method").unwrap(),
+                point.attribute("http.route"),
+                point.attribute("openapi.operation_id"),
+            )
+        })
+        .collect();
+    operations.sort();
+    let users = || Some(String::from("/users/:id"));
+    assert_eq!(
+        operations,
+        [
+            (
+                String::from("DELETE"),
+                users(),
+                Some(String::from("deleteUser"))
+            ),
+            (String::from("GET"), None, None),
+            (String::from("GET"), users(), Some(String::from("getUser"))),
+            (
+                String::from("GET"),
+                users(),
+                Some(String::from("getUserV2"))
+            ),
+            (String::from("PUT"), users(), None),
+        ]
+    );
+}
+
+#[test]
+fn operation_ids_are_not_recorded_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        let mut response = http::Response::new(String::new());
+        response
+            .extensions_mut()
+            .insert(OperationId::new("getUser"));
+        response
+    });
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("openapi.operation_id"), None);
+}
@@ -0,0 +1,91 @@
+//! A layer placed where axum never reports the matched route is pointed out.
+#![cfg(feature = "axum")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use axum::routing::get;
+use axum::Router;
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayer, HTTPMetricsLayerBuilder};
+
+use common::{block_on, TestMetrics};
+
+/// Layer collecting its placement warnings into `warnings`.
+fn warning_layer(metrics: &TestMetrics, warnings: &Arc<Mutex<Vec<String>>>) -> HTTPMetricsLayer {
+    let warnings = warnings.clone();
+    HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_placement_warning(2, move |message| {
+            warnings.lock().unwrap().push(message.to_owned())
+        })
+        .build()
+        .unwrap()
+}
+
+fn users() -> Router {
+    Router::new().route("/users/:id", get(|| async {}))
+}
+
+/// Send GET requests to `uris` through `service`.
+fn get_all<S>(service: S, uris: &[&str])
+where
+    S: tower_service::Service<http::Request<Body>> + Clone,
+    S::Error: std::fmt::Debug,
+{
+    for uri in uris {
+        let request = http::Request::get(*uri).body(Body::empty()).unwrap();
+        block_on(service.clone().oneshot(request)).unwrap();
+    }
+}
+
+/// The `http.route` values recorded, with their request count.
+fn routes(metrics: &TestMetrics) -> Vec<(Option<String>, u64)> {
+    metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("http.route"), point.count))
+        .collect()
+}
+
+#[test]
+fn a_layer_around_the_router_warns_once() {
+    let metrics = TestMetrics::new();
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let service = warning_layer(&metrics, &warnings).layer(users());
+    get_all(service, &["/users/1", "/users/2", "/users/3"]);
+
+    let warnings = warnings.lock().unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("Router::layer"));
+    assert_eq!(routes(&metrics), [(None, 3)]);
+}
+
+#[test]
+fn a_layer_applied_to_the_router_does_not_warn() {
+    let metrics = TestMetrics::new();
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let router = users().layer(warning_layer(&metrics, &warnings));
+    // the fallback serves requests without a matched route once one was routed
+    get_all(router, &["/users/1", "/missing", "/missing", "/missing"]);
+
+    assert!(warnings.lock().unwrap().is_empty());
+    let mut routes = routes(&metrics);
+    routes.sort();
+    assert_eq!(routes, [(None, 3), (Some(String::from("/users/:id")), 1)]);
+}
+
+#[test]
+#[should_panic(expected = "MatchedPath")]
+fn strict_placement_panics_on_a_layer_around_the_router() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_strict_placement(true)
+        .build()
+        .unwrap();
+    get_all(layer.layer(users()), &["/users/1"]);
+}
@@ -0,0 +1,84 @@
+//! The polls of each response future are counted, exposing spurious wakeups.
+
+mod common;
+
+use std::convert::Infallible;
+use std::future::poll_fn;
+use std::task::Poll;
+
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayer, HTTPMetricsLayerBuilder};
+
+use common::{block_on, TestMetrics};
+
+const POLLS: &str = "http.server.request.polls";
+
+/// Serve a request to `uri` by a handler pending `pending` times before answering.
+fn serve(layer: &HTTPMetricsLayer, uri: &'static str, pending: usize) {
+    let service = layer.layer(tower::service_fn(move |_: http::Request<String>| {
+        let mut pending = pending;
+        poll_fn(move |cx| {
+            if pending == 0 {
+                return Poll::Ready(Ok::<_, Infallible>(http::Response::new(String::new())));
+            }
+            pending -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+    }));
+    let request = http::Request::get(uri).body(String::new()).unwrap();
+    block_on(service.oneshot(request)).unwrap();
+}
+
+#[test]
+fn polls_of_each_request_are_recorded_per_route() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|parts| Some(parts.uri.path().to_owned()))
+        .with_poll_count_histogram(true)
+        .build()
+        .unwrap();
+    serve(&layer, "/ready", 0);
+    serve(&layer, "/busy", 4);
+    serve(&layer, "/busy", 2);
+
+    let mut polls: Vec<_> = metrics
+        .histogram::<u64>(POLLS)
+        .iter()
+        .map(|point| {
+            assert_eq!(point.attribute("http.request.method").unwrap(), "GET");
+            (
+                point.attribute("http.route").unwrap(),
+                point.count,
+                point.value,
+            )
+        })
+        .collect();
+    polls.sort();
+    // the final poll answering the request counts as well
+    assert_eq!(
+        polls,
+        [
+            (String::from("/busy"), 2, 5 + 3),
+            (String::from("/ready"), 1, 1),
+        ]
+    );
+}
+
+#[test]
+fn polls_are_not_recorded_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    serve(&layer, "/busy", 2);
+
+    assert!(metrics.histogram::<u64>(POLLS).is_empty());
+    assert_eq!(
+        metrics.histogram::<f64>("http.server.request.duration")[0].count,
+        1
+    );
+}
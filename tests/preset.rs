@@ -0,0 +1,155 @@
+//! The metrics recorded under each preset.
+
+mod common;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use tower::ServiceExt;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, Preset};
+
+use common::{block_on, TestMetrics};
+
+/// The names of the metrics recorded for one request under `preset`.
+fn recorded(preset: Preset) -> Vec<String> {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_preset(preset)
+        .build()
+        .unwrap();
+    let service = tower::ServiceBuilder::new()
+        .layer(layer.response_body_layer())
+        .layer(layer)
+        .service_fn(|_: http::Request<String>| async {
+            Ok::<_, Infallible>(http::Response::new(String::from("hello")))
+        });
+    let request = http::Request::builder()
+        .header(http::header::CONTENT_LENGTH, "5")
+        .body(String::from("hello"))
+        .unwrap();
+    let response = block_on(service.oneshot(request)).unwrap();
+    drop(response);
+
+    let mut names = metrics.names();
+    names.sort();
+    names
+}
+
+#[test]
+fn minimal_records_the_duration_only() {
+    assert_eq!(recorded(Preset::Minimal), ["http.server.request.duration"]);
+}
+
+#[test]
+fn standard_records_the_semconv_metrics() {
+    assert_eq!(
+        recorded(Preset::Standard),
+        [
+            "http.server.active_requests",
+            "http.server.request.body.size",
+            "http.server.request.duration",
+        ]
+    );
+}
+
+#[test]
+fn full_adds_the_size_metrics() {
+    assert_eq!(
+        recorded(Preset::Full),
+        [
+            "http.server.active_requests",
+            "http.server.request.body.size",
+            "http.server.request.duration",
+            "http.server.request.size",
+            "http.server.response.body.size",
+        ]
+    );
+}
+
+/// The attributes of `http.server.request.duration` for one request under `preset`, sorted by key.
+fn duration_attributes(preset: Preset) -> Vec<(String, String)> {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_preset(preset)
+        .build()
+        .unwrap();
+    let service =
+        tower::ServiceBuilder::new()
+            .layer(layer)
+            .service_fn(|_: http::Request<String>| async {
+                let response = http::Response::builder()
+                    .status(http::StatusCode::UNAUTHORIZED)
+                    .header(http::header::WWW_AUTHENTICATE, "Bearer")
+                    .header("cf-cache-status", "MISS")
+                    .body(String::new())
+                    .unwrap();
+                Ok::<_, Infallible>(response)
+            });
+    let mut request = http::Request::builder()
+        .uri("/users")
+        .header(http::header::HOST, "api.example.com:8080")
+        .header(
+            http::header::USER_AGENT,
+            "Googlebot/2.1 (+http://www.google.com/bot.html)",
+        )
+        .header("cf-ipcountry", "DE")
+        .body(String::new())
+        .unwrap();
+    request
+        .extensions_mut()
+        .insert(SocketAddr::from(([192, 0, 2, 7], 54321)));
+    block_on(service.oneshot(request)).unwrap();
+
+    let points = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(points.len(), 1);
+    let mut attributes: Vec<_> = points[0]
+        .attributes
+        .iter()
+        .map(|kv| (kv.key.to_string(), kv.value.to_string()))
+        .collect();
+    attributes.sort();
+    attributes
+}
+
+#[test]
+fn standard_records_the_semconv_attributes() {
+    let keys: Vec<_> = duration_attributes(Preset::Standard)
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(
+        keys,
+        [
+            "http.request.method",
+            "http.response.status_code",
+            "network.protocol.name",
+            "network.protocol.version",
+            "url.scheme",
+        ]
+    );
+}
+
+#[test]
+fn full_adds_every_opt_in_attribute() {
+    let attributes = duration_attributes(Preset::Full);
+    let value = |key: &str| {
+        attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    };
+    assert_eq!(value("server.address"), Some("api.example.com"));
+    assert_eq!(value("server.port"), Some("8080"));
+    assert_eq!(value("network.peer.address"), Some("192.0.2.7"));
+    assert_eq!(value("geo.country.iso_code"), Some("DE"));
+    assert_eq!(value("faas.trigger"), Some("http"));
+    assert_eq!(value("http.cache.status"), Some("miss"));
+    assert_eq!(value("http.auth.outcome"), Some("unauthenticated"));
+    assert_eq!(value("http.auth.scheme"), Some("bearer"));
+    assert_eq!(value("user.authenticated"), Some("false"));
+    assert_eq!(value("http.route.fallback"), Some("false"));
+    #[cfg(feature = "user-agent")]
+    assert_eq!(value("user_agent.device.category"), Some("bot"));
+}
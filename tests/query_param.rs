@@ -0,0 +1,81 @@
+//! Query parameter attributes match percent-decoded names and values against the allowlist.
+
+mod common;
+
+use std::convert::Infallible;
+
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{block_on, TestMetrics};
+
+/// The `url.query.format` attribute recorded for a request to `uri`.
+fn format_attribute(uri: &str) -> Option<String> {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_query_param_attribute("format", ["text/csv", "plain text"])
+        .build()
+        .unwrap();
+    let service = layer.layer(tower::service_fn(|_: http::Request<String>| async {
+        Ok::<_, Infallible>(http::Response::new(String::new()))
+    }));
+    let request = http::Request::builder()
+        .uri(uri)
+        .body(String::new())
+        .unwrap();
+    block_on(service.oneshot(request)).unwrap();
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    duration[0].attribute("url.query.format")
+}
+
+#[test]
+fn values_are_percent_decoded() {
+    assert_eq!(
+        format_attribute("/export?format=text%2Fcsv").as_deref(),
+        Some("text/csv")
+    );
+    assert_eq!(
+        format_attribute("/export?format=text%2fcsv").as_deref(),
+        Some("text/csv")
+    );
+}
+
+#[test]
+fn plus_signs_are_spaces() {
+    assert_eq!(
+        format_attribute("/export?format=plain+text").as_deref(),
+        Some("plain text")
+    );
+    assert_eq!(
+        format_attribute("/export?format=plain%20text").as_deref(),
+        Some("plain text")
+    );
+}
+
+#[test]
+fn names_are_percent_decoded() {
+    assert_eq!(
+        format_attribute("/export?%66ormat=text/csv").as_deref(),
+        Some("text/csv")
+    );
+}
+
+#[test]
+fn malformed_and_unknown_values_are_other() {
+    assert_eq!(
+        format_attribute("/export?format=text%2").as_deref(),
+        Some("_OTHER")
+    );
+    assert_eq!(
+        format_attribute("/export?format=text%+Fcsv").as_deref(),
+        Some("_OTHER")
+    );
+    assert_eq!(
+        format_attribute("/export?format=%FF").as_deref(),
+        Some("_OTHER")
+    );
+    assert_eq!(format_attribute("/export?other=1"), None);
+}
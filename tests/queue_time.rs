@@ -0,0 +1,71 @@
+//! `http.server.queue_time` records the time spent before reaching the application.
+
+mod common;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+const QUEUE_TIME: &str = "http.server.queue_time";
+
+/// Metrics of a layer with the queue time histogram handling a request with `request_start`.
+fn queue_time_metrics(enabled: bool, request_start: Option<String>) -> TestMetrics {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_queue_time_histogram(enabled)
+        .build()
+        .unwrap();
+    let mut request = http::Request::builder();
+    if let Some(request_start) = request_start {
+        request = request.header("x-request-start", request_start);
+    }
+    send(&layer, request.body(String::new()).unwrap(), |_| {
+        http::Response::new(String::new())
+    });
+    metrics
+}
+
+/// The Unix time `ago` in the past.
+fn unix_time(ago: Duration) -> Duration {
+    (SystemTime::now() - ago)
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+}
+
+#[test]
+fn millisecond_and_microsecond_timestamps_are_recorded() {
+    let queued = Duration::from_millis(250);
+    for request_start in [
+        format!("t={}", unix_time(queued).as_millis()),
+        unix_time(queued).as_micros().to_string(),
+    ] {
+        let metrics = queue_time_metrics(true, Some(request_start));
+
+        let queue_time = metrics.histogram::<f64>(QUEUE_TIME);
+        assert_eq!(queue_time.len(), 1);
+        assert_eq!(queue_time[0].count, 1);
+        assert!(queue_time[0].value >= 0.249 && queue_time[0].value < 5.0);
+        assert_eq!(
+            queue_time[0].attribute("http.request.method").unwrap(),
+            "GET"
+        );
+    }
+}
+
+#[test]
+fn requests_without_the_header_are_not_recorded() {
+    let metrics = queue_time_metrics(true, None);
+    assert!(metrics.histogram::<f64>(QUEUE_TIME).is_empty());
+    let metrics = queue_time_metrics(true, Some(String::from("t=garbage")));
+    assert!(metrics.histogram::<f64>(QUEUE_TIME).is_empty());
+}
+
+#[test]
+fn histogram_is_disabled_by_default() {
+    let start = unix_time(Duration::from_millis(10)).as_millis().to_string();
+    let metrics = queue_time_metrics(false, Some(start));
+    assert!(!metrics.names().iter().any(|name| name == QUEUE_TIME));
+}
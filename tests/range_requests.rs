@@ -0,0 +1,102 @@
+//! Requests for byte ranges are counted by outcome, with the size of the ranges served.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+const RANGE_REQUESTS: &str = "http.server.range_requests";
+const RANGE_SIZE: &str = "http.server.response.range.size";
+
+/// GET `/videos/1` request, for `range` if any.
+fn video(range: Option<&'static str>) -> http::Request<String> {
+    let mut request = http::Request::get("/videos/1");
+    if let Some(range) = range {
+        request = request.header(http::header::RANGE, range);
+    }
+    request.body(String::new()).unwrap()
+}
+
+#[test]
+fn range_requests_are_counted_by_status_with_their_size() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|_| Some("/videos/:id"))
+        .with_range_request_metrics(true)
+        .build()
+        .unwrap();
+    let responses = [
+        (Some("bytes=0-499"), 206, Some("bytes 0-499/1234")),
+        (Some("bytes=500-"), 206, Some("bytes 500-1233/1234")),
+        // the range is ignored, and the whole content served
+        (Some("bytes=0-99"), 200, None),
+        (Some("bytes=5000-"), 416, Some("bytes */1234")),
+        // not a range request
+        (None, 200, None),
+    ];
+    for (range, status, content_range) in responses {
+        send(&layer, video(range), |_| {
+            let mut response = http::Response::builder().status(status);
+            if let Some(content_range) = content_range {
+                response = response.header(http::header::CONTENT_RANGE, content_range);
+            }
+            response.body(String::new()).unwrap()
+        });
+    }
+
+    let mut range_requests: Vec<_> = metrics
+        .points::<u64>(RANGE_REQUESTS)
+        .iter()
+        .map(|point| {
+            assert_eq!(point.attribute("http.request.method").unwrap(), "GET");
+            assert_eq!(point.attribute("http.route").unwrap(), "/videos/:id");
+            (
+                point.attribute("http.response.status_code").unwrap(),
+                point.value,
+            )
+        })
+        .collect();
+    range_requests.sort();
+    assert_eq!(
+        range_requests,
+        [
+            (String::from("200"), 1),
+            (String::from("206"), 2),
+            (String::from("416"), 1),
+        ]
+    );
+
+    let range_size = metrics.histogram::<u64>(RANGE_SIZE);
+    assert_eq!(range_size.len(), 1);
+    assert_eq!(range_size[0].count, 2);
+    assert_eq!(range_size[0].value, 500 + 734);
+    assert_eq!(
+        range_size[0].attribute("http.route").unwrap(),
+        "/videos/:id"
+    );
+}
+
+#[test]
+fn range_requests_are_not_counted_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    send(&layer, video(Some("bytes=0-499")), |_| {
+        http::Response::builder()
+            .status(http::StatusCode::PARTIAL_CONTENT)
+            .header(http::header::CONTENT_RANGE, "bytes 0-499/1234")
+            .body(String::new())
+            .unwrap()
+    });
+
+    assert!(metrics.points::<u64>(RANGE_REQUESTS).is_empty());
+    assert!(metrics.histogram::<u64>(RANGE_SIZE).is_empty());
+    assert_eq!(
+        metrics.histogram::<f64>("http.server.request.duration")[0].count,
+        1
+    );
+}
@@ -0,0 +1,91 @@
+//! Rare status codes can be collapsed into `_OTHER` to bound the status dimension.
+
+mod common;
+
+use opentelemetry::Value;
+use tower_otel_http_metrics::{HTTPMetricsLayer, HTTPMetricsLayerBuilder};
+
+use common::{send, TestMetrics};
+
+/// The `http.response.status_code` values recorded for `statuses`, with their request count.
+fn status_codes(
+    metrics: &TestMetrics,
+    layer: &HTTPMetricsLayer,
+    statuses: &[u16],
+) -> Vec<(String, u64)> {
+    for status in statuses {
+        send(layer, http::Request::new(String::new()), |_| {
+            http::Response::builder()
+                .status(*status)
+                .body(String::new())
+                .unwrap()
+        });
+    }
+    let mut status_codes: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| {
+            let status_code = point
+                .attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == "http.response.status_code")
+                .unwrap();
+            (format!("{:?}", status_code.value), point.count)
+        })
+        .collect();
+    status_codes.sort();
+    status_codes
+}
+
+/// Debug rendering of an attribute value, telling integers from strings.
+fn rendered(value: impl Into<Value>) -> String {
+    format!("{:?}", value.into())
+}
+
+#[test]
+fn rare_status_codes_are_collapsed_into_other() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_rare_status_codes_collapsed(true)
+        .build()
+        .unwrap();
+
+    let mut expected = vec![
+        (rendered(200), 2),
+        (rendered(404), 1),
+        (rendered("_OTHER"), 3),
+    ];
+    expected.sort();
+    assert_eq!(
+        status_codes(&metrics, &layer, &[200, 200, 404, 418, 507, 226]),
+        expected
+    );
+}
+
+#[test]
+fn kept_status_codes_replace_the_common_ones() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_kept_status_codes([http::StatusCode::IM_A_TEAPOT])
+        .build()
+        .unwrap();
+
+    let mut expected = vec![(rendered(418), 1), (rendered("_OTHER"), 2)];
+    expected.sort();
+    assert_eq!(status_codes(&metrics, &layer, &[418, 200, 404]), expected);
+}
+
+#[test]
+fn status_codes_are_kept_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+
+    let mut expected = vec![(rendered(200), 1), (rendered(418), 1)];
+    expected.sort();
+    assert_eq!(status_codes(&metrics, &layer, &[200, 418]), expected);
+}
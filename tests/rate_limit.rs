@@ -0,0 +1,67 @@
+//! Rate limit response headers are exported as gauges.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+const LIMIT: &str = "http.server.rate_limit.limit";
+const REMAINING: &str = "http.server.rate_limit.remaining";
+
+/// Metrics of a layer with the rate limit gauges answering with `headers`.
+fn rate_limit_metrics(enabled: bool, headers: &[(&'static str, &'static str)]) -> TestMetrics {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_rate_limit_gauges(enabled)
+        .build()
+        .unwrap();
+    let request = http::Request::post("/orders").body(String::new()).unwrap();
+    send(&layer, request, |_| {
+        let mut response = http::Response::builder();
+        for (name, value) in headers {
+            response = response.header(*name, *value);
+        }
+        response.body(String::new()).unwrap()
+    });
+    metrics
+}
+
+#[test]
+fn standard_headers_are_recorded() {
+    let metrics = rate_limit_metrics(
+        true,
+        &[
+            ("ratelimit-limit", "100, 100;w=60"),
+            ("ratelimit-remaining", "42"),
+        ],
+    );
+
+    let limit = metrics.points::<u64>(LIMIT);
+    assert_eq!(limit.len(), 1);
+    assert_eq!(limit[0].value, 100);
+    assert_eq!(limit[0].attribute("http.request.method").unwrap(), "POST");
+    let remaining = metrics.points::<u64>(REMAINING);
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].value, 42);
+    assert_eq!(
+        remaining[0].attribute("http.request.method").unwrap(),
+        "POST"
+    );
+}
+
+#[test]
+fn legacy_headers_are_recorded() {
+    let metrics = rate_limit_metrics(true, &[("x-ratelimit-remaining", "0")]);
+
+    assert_eq!(metrics.points::<u64>(REMAINING)[0].value, 0);
+    assert!(metrics.points::<u64>(LIMIT).is_empty());
+}
+
+#[test]
+fn gauges_are_disabled_by_default() {
+    let metrics = rate_limit_metrics(false, &[("ratelimit-limit", "100")]);
+
+    assert!(!metrics.names().iter().any(|name| name == LIMIT));
+}
@@ -0,0 +1,84 @@
+//! Request body sizes counted as the body is read are recorded with the response status.
+
+mod common;
+
+use std::convert::Infallible;
+use std::vec;
+
+use bytes::Bytes;
+use futures_util::stream::{self, Iter};
+use http_body::Frame;
+use http_body_util::{BodyExt, StreamBody};
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, HTTPMetricsRequestBody};
+
+use common::{block_on, TestMetrics};
+
+type Streamed = StreamBody<Iter<vec::IntoIter<Result<Frame<Bytes>, Infallible>>>>;
+
+/// Body of unknown size streaming `chunks`.
+fn streamed(chunks: &[&'static str]) -> Streamed {
+    let frames: Vec<_> = chunks
+        .iter()
+        .map(|chunk| Ok(Frame::data(Bytes::from_static(chunk.as_bytes()))))
+        .collect();
+    StreamBody::new(stream::iter(frames))
+}
+
+#[test]
+fn body_read_before_the_response_gets_its_status() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    let service = layer
+        .request_body_layer()
+        .layer(layer.layer(tower::service_fn(
+            |req: http::Request<HTTPMetricsRequestBody<Streamed>>| async move {
+                let body = req.into_body().collect().await.unwrap().to_bytes();
+                assert_eq!(body.len(), 11);
+                let mut response = http::Response::new(String::new());
+                *response.status_mut() = http::StatusCode::CREATED;
+                Ok::<_, Infallible>(response)
+            },
+        )));
+    let request = http::Request::new(streamed(&["hello", " world"]));
+    block_on(service.oneshot(request)).unwrap();
+
+    let body_size = metrics.histogram::<u64>("http.server.request.body.size");
+    assert_eq!(body_size.len(), 1);
+    assert_eq!(body_size[0].count, 1);
+    assert_eq!(body_size[0].value, 11);
+    assert_eq!(
+        body_size[0]
+            .attribute("http.response.status_code")
+            .as_deref(),
+        Some("201")
+    );
+}
+
+#[test]
+fn body_of_a_failed_request_is_recorded_without_status() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    let service = layer
+        .request_body_layer()
+        .layer(layer.layer(tower::service_fn(
+            |req: http::Request<HTTPMetricsRequestBody<Streamed>>| async move {
+                req.into_body().collect().await.unwrap();
+                Err::<http::Response<String>, _>("failed")
+            },
+        )));
+    let request = http::Request::new(streamed(&["hello"]));
+    assert!(block_on(service.oneshot(request)).is_err());
+
+    let body_size = metrics.histogram::<u64>("http.server.request.body.size");
+    assert_eq!(body_size.len(), 1);
+    assert_eq!(body_size[0].value, 5);
+    assert_eq!(body_size[0].attribute("http.response.status_code"), None);
+}
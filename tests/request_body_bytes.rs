@@ -0,0 +1,63 @@
+//! `http.server.request.body.bytes` counts upload progress frame by frame.
+
+mod common;
+
+use std::convert::Infallible;
+use std::vec;
+
+use bytes::Bytes;
+use futures_util::stream::{self, Iter};
+use http_body::Frame;
+use http_body_util::{BodyExt, StreamBody};
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, HTTPMetricsRequestBody};
+
+use common::{block_on, TestMetrics};
+
+const BODY_BYTES: &str = "http.server.request.body.bytes";
+
+type Streamed = StreamBody<Iter<vec::IntoIter<Result<Frame<Bytes>, Infallible>>>>;
+
+/// The bytes counted so far, with the request method.
+fn counted(metrics: &TestMetrics) -> u64 {
+    let body_bytes = metrics.points::<u64>(BODY_BYTES);
+    assert_eq!(body_bytes.len(), 1);
+    assert_eq!(
+        body_bytes[0].attribute("http.request.method").unwrap(),
+        "PUT"
+    );
+    body_bytes[0].value
+}
+
+#[test]
+fn bytes_are_counted_as_frames_are_read() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_request_body_bytes_counter(true)
+        .build()
+        .unwrap();
+    let service = layer
+        .request_body_layer()
+        .layer(layer.layer(tower::service_fn(
+            |req: http::Request<HTTPMetricsRequestBody<Streamed>>| async {
+                let mut body = req.into_body();
+                body.frame().await.unwrap().unwrap();
+                assert_eq!(counted(&metrics), 5);
+                body.frame().await.unwrap().unwrap();
+                assert_eq!(counted(&metrics), 11);
+                Ok::<_, Infallible>(http::Response::new(String::new()))
+            },
+        )));
+    let frames: Vec<_> = ["hello", " world"]
+        .iter()
+        .map(|chunk| Ok(Frame::data(Bytes::from_static(chunk.as_bytes()))))
+        .collect();
+    let request = http::Request::put("/upload")
+        .body(StreamBody::new(stream::iter(frames)))
+        .unwrap();
+    block_on(service.oneshot(request)).unwrap();
+
+    assert_eq!(counted(&metrics), 11);
+}
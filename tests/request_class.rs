@@ -0,0 +1,48 @@
+//! Requests are recorded with the class their classifier maps them to.
+
+mod common;
+
+use http::request::Parts;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+fn classify(parts: &Parts) -> Option<&'static str> {
+    let path = parts.uri.path();
+    if path.starts_with("/api/") {
+        Some("api")
+    } else if path.starts_with("/hooks/") {
+        Some("webhook")
+    } else {
+        None
+    }
+}
+
+#[test]
+fn requests_are_recorded_per_class() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_request_classifier(classify)
+        .build()
+        .unwrap();
+    for uri in ["/api/users", "/api/orders", "/hooks/stripe", "/favicon.ico"] {
+        let request = http::Request::get(uri).body(String::new()).unwrap();
+        send(&layer, request, |_| http::Response::new(String::new()));
+    }
+
+    let mut classes: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("http.request.class"), point.count))
+        .collect();
+    classes.sort();
+    assert_eq!(
+        classes,
+        [
+            (None, 1),
+            (Some(String::from("api")), 2),
+            (Some(String::from("webhook")), 1),
+        ]
+    );
+}
@@ -0,0 +1,69 @@
+//! Middleware inside the layer reuses the request data the layer resolved.
+
+mod common;
+
+use std::convert::Infallible;
+
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, RequestMetricsContext};
+
+use common::{block_on, TestMetrics};
+
+/// Handle a request, returning the method and route of its context when it carries one.
+fn handle(request_context_extension: bool) -> (TestMetrics, Option<(String, Option<String>)>) {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|_| Some("/orders/{id}"))
+        .with_request_context_extension(request_context_extension)
+        .build()
+        .unwrap();
+    let service = layer.layer(tower::service_fn(|req: http::Request<String>| async move {
+        let context = req
+            .extensions()
+            .get::<RequestMetricsContext>()
+            .map(|context| {
+                let elapsed = context.elapsed();
+                assert!(context.start().elapsed() >= elapsed);
+                (
+                    context.method().to_owned(),
+                    context.route().map(str::to_owned),
+                )
+            });
+        Ok::<_, Infallible>(http::Response::new(context))
+    }));
+    let request = http::Request::patch("/orders/7")
+        .body(String::new())
+        .unwrap();
+    let context = block_on(service.oneshot(request)).unwrap().into_body();
+    (metrics, context)
+}
+
+#[test]
+fn context_matches_the_recorded_attributes() {
+    let (metrics, context) = handle(true);
+    let (method, route) = context.unwrap();
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(method, "PATCH");
+    assert_eq!(
+        duration[0].attribute("http.request.method").unwrap(),
+        method
+    );
+    assert_eq!(route.as_deref(), Some("/orders/{id}"));
+    assert_eq!(duration[0].attribute("http.route"), route);
+}
+
+#[test]
+fn context_is_disabled_by_default() {
+    let (metrics, context) = handle(false);
+
+    assert_eq!(context, None);
+    assert_eq!(
+        metrics.histogram::<f64>("http.server.request.duration")[0].count,
+        1
+    );
+}
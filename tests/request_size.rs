@@ -0,0 +1,114 @@
+//! `http.server.request.size` adds the HTTP/1.1 header section to the body size, when known.
+
+mod common;
+
+use std::convert::Infallible;
+
+use bytes::Bytes;
+use http_body::Frame;
+use http_body_util::StreamBody;
+use tower::ServiceExt;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, HTTPMetricsRequestBody};
+
+use common::{block_on, TestMetrics};
+
+const REQUEST_SIZE: &str = "http.server.request.size";
+const NETWORK_IO: &str = "http.server.network.io";
+
+// "GET /path HTTP/1.1\r\n" and "host: a\r\n", then the empty line ending the section
+const HEADER_SECTION_SIZE: u64 = 20 + 9 + 2;
+// "content-length: 5\r\n"
+const CONTENT_LENGTH_FIELD_SIZE: u64 = 19;
+
+fn serve<B>(metrics: &TestMetrics, req: http::Request<B>)
+where
+    B: http_body::Body,
+{
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_request_size(true)
+        .with_network_io_counter(true)
+        .build()
+        .unwrap();
+    // the request body layer reports the exact size of bodies without a Content-Length
+    let service = tower::ServiceBuilder::new()
+        .layer(layer.request_body_layer())
+        .layer(layer)
+        .service_fn(|_req: http::Request<HTTPMetricsRequestBody<B>>| async {
+            Ok::<_, Infallible>(http::Response::new(String::new()))
+        });
+    block_on(service.oneshot(req)).unwrap();
+}
+
+fn request() -> http::request::Builder {
+    http::Request::get("/path").header("host", "a")
+}
+
+fn request_sizes(metrics: &TestMetrics) -> Vec<(u64, u64)> {
+    metrics
+        .histogram::<u64>(REQUEST_SIZE)
+        .iter()
+        .map(|point| (point.count, point.value))
+        .collect()
+}
+
+#[test]
+fn content_length_is_added_to_the_header_section() {
+    let metrics = TestMetrics::new();
+    serve(
+        &metrics,
+        request()
+            .header("content-length", "5")
+            .body(String::from("hello"))
+            .unwrap(),
+    );
+
+    let size = HEADER_SECTION_SIZE + CONTENT_LENGTH_FIELD_SIZE + 5;
+    assert_eq!(request_sizes(&metrics), [(1, size)]);
+    let received = metrics
+        .points::<u64>(NETWORK_IO)
+        .into_iter()
+        .find(|point| point.attribute("network.io.direction").as_deref() == Some("receive"))
+        .unwrap();
+    assert_eq!(received.value, size);
+}
+
+#[test]
+fn bodies_of_exact_size_are_measured_without_content_length() {
+    let metrics = TestMetrics::new();
+    serve(&metrics, request().body(String::from("hello")).unwrap());
+
+    assert_eq!(request_sizes(&metrics), [(1, HEADER_SECTION_SIZE + 5)]);
+}
+
+#[test]
+fn bodiless_requests_are_measured() {
+    let metrics = TestMetrics::new();
+    serve(&metrics, request().body(String::new()).unwrap());
+
+    assert_eq!(request_sizes(&metrics), [(1, HEADER_SECTION_SIZE)]);
+}
+
+#[test]
+fn bodies_of_unknown_size_are_not_measured() {
+    let metrics = TestMetrics::new();
+    let frames =
+        futures_util::stream::iter([Ok::<_, Infallible>(Frame::data(Bytes::from("hello")))]);
+    serve(&metrics, request().body(StreamBody::new(frames)).unwrap());
+
+    assert!(request_sizes(&metrics).is_empty());
+}
+
+#[test]
+fn malformed_content_lengths_are_not_measured() {
+    let metrics = TestMetrics::new();
+    serve(
+        &metrics,
+        request()
+            .header("content-length", "five")
+            .body(String::from("hello"))
+            .unwrap(),
+    );
+
+    assert!(request_sizes(&metrics).is_empty());
+}
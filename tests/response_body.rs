@@ -0,0 +1,119 @@
+//! Response bodies are only observed by the response body layer, when a body metric needs it.
+
+mod common;
+
+use std::convert::Infallible;
+
+use bytes::Bytes;
+use http_body::Frame;
+use http_body_util::{BodyExt, Full, StreamBody};
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, ResponseBodySizeSource};
+
+use common::{block_on, TestMetrics};
+
+type Frames = futures_util::stream::Iter<std::vec::IntoIter<Result<Frame<Bytes>, Infallible>>>;
+
+fn streamed(chunks: &[&'static str]) -> StreamBody<Frames> {
+    let frames: Vec<_> = chunks
+        .iter()
+        .map(|chunk| Ok(Frame::data(Bytes::from_static(chunk.as_bytes()))))
+        .collect();
+    StreamBody::new(futures_util::stream::iter(frames))
+}
+
+#[test]
+fn streamed_frames_are_observed() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_response_body_size(true)
+        .with_response_body_frame_metrics(true)
+        .build()
+        .unwrap();
+    let service = tower::ServiceBuilder::new()
+        .layer(layer.response_body_layer())
+        .layer(layer)
+        .service_fn(|_req: http::Request<String>| async {
+            Ok::<_, Infallible>(http::Response::new(streamed(&["a", "bb", "ccc"])))
+        });
+
+    let response = block_on(service.oneshot(http::Request::new(String::new()))).unwrap();
+    assert!(format!("{response:?}").contains("observed: true"));
+    let body = block_on(response.into_body().collect()).unwrap().to_bytes();
+    assert_eq!(body, "abbccc");
+
+    let frame_size = metrics.histogram::<u64>("http.server.response.body.frame.size");
+    assert_eq!((frame_size[0].count, frame_size[0].value), (3, 6));
+    let frames = metrics.histogram::<u64>("http.server.response.body.frames");
+    assert_eq!((frames[0].count, frames[0].value), (1, 3));
+    let size = metrics.histogram::<u64>("http.server.response.body.size");
+    assert_eq!((size[0].count, size[0].value), (1, 6));
+}
+
+#[test]
+fn bodies_of_known_size_pass_through() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_response_body_size(true)
+        .build()
+        .unwrap();
+    let service = tower::ServiceBuilder::new()
+        .layer(layer.response_body_layer())
+        .layer(layer)
+        .service_fn(|_req: http::Request<String>| async {
+            Ok::<_, Infallible>(http::Response::new(Full::new(Bytes::from_static(b"hello"))))
+        });
+
+    let response = block_on(service.oneshot(http::Request::new(String::new()))).unwrap();
+    assert!(format!("{response:?}").contains("observed: false"));
+    // recorded from the size hint before the body is streamed
+    let size = metrics.histogram::<u64>("http.server.response.body.size");
+    assert_eq!((size[0].count, size[0].value), (1, 5));
+}
+
+#[test]
+fn bodies_pass_through_without_body_metrics() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    let service = tower::ServiceBuilder::new()
+        .layer(layer.response_body_layer())
+        .layer(layer)
+        .service_fn(|_req: http::Request<String>| async {
+            Ok::<_, Infallible>(http::Response::new(streamed(&["a", "bb"])))
+        });
+
+    let response = block_on(service.oneshot(http::Request::new(String::new()))).unwrap();
+    assert!(format!("{response:?}").contains("observed: false"));
+}
+
+#[test]
+fn responses_are_unchanged_without_the_response_body_layer() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_response_body_size(true)
+        .with_response_body_size_source(ResponseBodySizeSource::ContentLength)
+        .build()
+        .unwrap();
+    let service = layer.layer(tower::service_fn(|_req: http::Request<String>| async {
+        let response = http::Response::builder()
+            .header(http::header::CONTENT_LENGTH, "6")
+            .body(streamed(&["a", "bb", "ccc"]))
+            .unwrap();
+        Ok::<_, Infallible>(response)
+    }));
+
+    let response: http::Response<StreamBody<Frames>> =
+        block_on(service.oneshot(http::Request::new(String::new()))).unwrap();
+    let body = block_on(response.into_body().collect()).unwrap().to_bytes();
+    assert_eq!(body, "abbccc");
+    // recorded from the Content-Length, which needs no access to the body
+    let size = metrics.histogram::<u64>("http.server.response.body.size");
+    assert_eq!((size[0].count, size[0].value), (1, 6));
+}
@@ -0,0 +1,53 @@
+//! `http.server.response.body.bytes` counts the bytes sent per route.
+
+mod common;
+
+use std::convert::Infallible;
+
+use bytes::Bytes;
+use http_body::Frame;
+use http_body_util::{BodyExt, StreamBody};
+use tower::ServiceExt;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{block_on, TestMetrics};
+
+#[test]
+fn bytes_sent_are_counted_with_the_method_and_route() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|_| Some("/reports/{id}"))
+        .with_response_body_bytes_counter(true)
+        .build()
+        .unwrap();
+    let service = tower::ServiceBuilder::new()
+        .layer(layer.response_body_layer())
+        .layer(layer)
+        .service_fn(|_req: http::Request<String>| async {
+            let frames = ["abc", "de"].map(|chunk| {
+                Ok::<_, Infallible>(Frame::data(Bytes::from_static(chunk.as_bytes())))
+            });
+            Ok::<_, Infallible>(http::Response::new(StreamBody::new(
+                futures_util::stream::iter(frames),
+            )))
+        });
+    for _ in 0..2 {
+        let response =
+            block_on(service.clone().oneshot(http::Request::new(String::new()))).unwrap();
+        block_on(response.into_body().collect()).unwrap();
+    }
+
+    let body_bytes = metrics.points::<u64>("http.server.response.body.bytes");
+    assert_eq!(body_bytes.len(), 1);
+    assert_eq!(body_bytes[0].value, 10);
+    assert_eq!(body_bytes[0].attributes.len(), 2);
+    assert_eq!(
+        body_bytes[0].attribute("http.request.method").unwrap(),
+        "GET"
+    );
+    assert_eq!(
+        body_bytes[0].attribute("http.route").unwrap(),
+        "/reports/{id}"
+    );
+}
@@ -0,0 +1,50 @@
+//! Durations are recorded a second time with the rollup attribute set.
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+#[test]
+fn rollup_keeps_the_status_class_and_listed_keys() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_duration_rollup(["http.request.method"])
+        .build()
+        .unwrap();
+    for status in [
+        http::StatusCode::OK,
+        http::StatusCode::CREATED,
+        http::StatusCode::NOT_FOUND,
+    ] {
+        send(&layer, http::Request::new(String::new()), |_| {
+            http::Response::builder()
+                .status(status)
+                .body(String::new())
+                .unwrap()
+        });
+    }
+
+    assert_eq!(
+        metrics
+            .histogram::<f64>("http.server.request.duration")
+            .len(),
+        3
+    );
+    let mut rollup: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration.rollup")
+        .iter()
+        .map(|point| {
+            assert_eq!(point.attributes.len(), 2);
+            assert_eq!(point.attribute("http.request.method").unwrap(), "GET");
+            (
+                point.attribute("http.response.status_class").unwrap(),
+                point.count,
+            )
+        })
+        .collect();
+    rollup.sort();
+    assert_eq!(rollup, [(String::from("2xx"), 2), (String::from("4xx"), 1)]);
+}
@@ -0,0 +1,66 @@
+//! The route cache is bounded and counts its evictions.
+#![cfg(feature = "route-cache")]
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+const EVICTIONS: &str = "http.server.route_cache.evictions";
+
+#[test]
+fn least_recently_used_routes_are_evicted_and_counted() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|parts| Some(parts.uri.path().to_owned()))
+        .with_route_cache_capacity(2)
+        .build()
+        .unwrap();
+    // "/c" evicts "/b", the least recently used, then "/b" evicts "/a"
+    for uri in ["/a", "/b", "/a", "/c", "/b"] {
+        let request = http::Request::get(uri).body(String::new()).unwrap();
+        send(&layer, request, |_| http::Response::new(String::new()));
+    }
+
+    let evictions = metrics.points::<u64>(EVICTIONS);
+    assert_eq!(evictions.len(), 1);
+    assert_eq!(evictions[0].value, 2);
+    assert!(evictions[0].attributes.is_empty());
+
+    let mut routes: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("http.route").unwrap(), point.count))
+        .collect();
+    routes.sort();
+    assert_eq!(
+        routes,
+        [
+            (String::from("/a"), 2),
+            (String::from("/b"), 2),
+            (String::from("/c"), 1)
+        ]
+    );
+}
+
+#[test]
+fn cached_routes_are_not_evicted() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|parts| Some(parts.uri.path().to_owned()))
+        .with_route_cache_capacity(2)
+        .build()
+        .unwrap();
+    for uri in ["/a", "/b", "/a", "/b"] {
+        let request = http::Request::get(uri).body(String::new()).unwrap();
+        send(&layer, request, |_| http::Response::new(String::new()));
+    }
+
+    assert!(metrics
+        .points::<u64>(EVICTIONS)
+        .iter()
+        .all(|point| point.value == 0));
+}
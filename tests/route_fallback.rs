@@ -0,0 +1,51 @@
+//! `http.route.fallback` follows the `RouteFallback` marker of the response.
+
+mod common;
+
+use std::convert::Infallible;
+
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, RouteFallback};
+
+use common::{block_on, TestMetrics};
+
+/// The `http.route.fallback` of a request answered with or without the marker.
+fn route_fallback(enabled: bool, fallback: bool) -> Option<String> {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_fallback_attribute(enabled)
+        .build()
+        .unwrap();
+    let service = layer.layer(tower::service_fn(
+        move |_: http::Request<String>| async move {
+            let mut response = http::Response::new(String::new());
+            *response.status_mut() = http::StatusCode::NOT_FOUND;
+            if fallback {
+                response.extensions_mut().insert(RouteFallback);
+            }
+            Ok::<_, Infallible>(response)
+        },
+    ));
+    block_on(service.oneshot(http::Request::new(String::new()))).unwrap();
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    duration[0].attribute("http.route.fallback")
+}
+
+#[test]
+fn fallback_responses_are_marked() {
+    assert_eq!(route_fallback(true, true).as_deref(), Some("true"));
+}
+
+#[test]
+fn unmarked_not_found_responses_are_not_fallbacks() {
+    assert_eq!(route_fallback(true, false).as_deref(), Some("false"));
+}
+
+#[test]
+fn attribute_is_disabled_by_default() {
+    assert_eq!(route_fallback(false, true), None);
+}
@@ -0,0 +1,61 @@
+//! `http.route` is resolved by the first stage of the pipeline which knows the route.
+#![cfg(feature = "route-patterns")]
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+#[test]
+fn each_stage_resolves_what_the_previous_ones_did_not() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|parts| {
+            parts
+                .uri
+                .path()
+                .starts_with("/admin/")
+                .then_some("/admin/*rest")
+        })
+        .with_route_pattern("/users/{id}")
+        .with_fallback_route("/unmatched")
+        .build()
+        .unwrap();
+    for uri in ["/admin/users/1", "/users/1", "/users/2", "/health"] {
+        let request = http::Request::get(uri).body(String::new()).unwrap();
+        send(&layer, request, |_| http::Response::new(String::new()));
+    }
+
+    let mut routes: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("http.route").unwrap(), point.count))
+        .collect();
+    routes.sort();
+    assert_eq!(
+        routes,
+        [
+            (String::from("/admin/*rest"), 1),
+            (String::from("/unmatched"), 1),
+            (String::from("/users/{id}"), 2),
+        ]
+    );
+}
+
+#[test]
+fn requests_without_a_route_have_no_attribute_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_pattern("/users/{id}")
+        .build()
+        .unwrap();
+    let request = http::Request::get("/health").body(String::new()).unwrap();
+    send(&layer, request, |_| http::Response::new(String::new()));
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].attribute("http.route"), None);
+}
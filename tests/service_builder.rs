@@ -0,0 +1,35 @@
+//! The layer is added to a `ServiceBuilder` in the tower layering style.
+#![cfg(feature = "service-builder")]
+
+mod common;
+
+use std::convert::Infallible;
+
+use tower::{ServiceBuilder, ServiceExt};
+use tower_otel_http_metrics::ServiceBuilderExt;
+
+use common::{block_on, TestMetrics};
+
+#[test]
+fn service_builder_layer_records_requests() {
+    let metrics = TestMetrics::new();
+    let service = ServiceBuilder::new()
+        .otel_http_metrics(metrics.meter())
+        .service_fn(|_: http::Request<String>| async {
+            Ok::<_, Infallible>(http::Response::new(String::new()))
+        });
+    let request = http::Request::head("/").body(String::new()).unwrap();
+    block_on(service.oneshot(request)).unwrap();
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(
+        duration[0].attribute("http.request.method").unwrap(),
+        "HEAD"
+    );
+    assert_eq!(
+        duration[0].attribute("http.response.status_code").unwrap(),
+        "200"
+    );
+}
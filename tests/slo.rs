@@ -0,0 +1,81 @@
+//! Requests slower than the latency objective of their route are counted and marked.
+
+mod common;
+
+use std::time::Duration;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+const DURATION: &str = "http.server.request.duration";
+const SLOW_REQUESTS: &str = "http.server.request.slow";
+
+const HANDLER_TIME: Duration = Duration::from_millis(20);
+
+#[test]
+fn requests_are_judged_against_the_threshold_of_their_route() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|parts| match parts.uri.path() {
+            "/search" => Some("/search"),
+            "/reports" => Some("/reports"),
+            "/health" => Some("/health"),
+            _ => None,
+        })
+        .with_slo_threshold("/search", Duration::from_millis(1))
+        .with_slo_threshold("/reports", Duration::from_secs(60))
+        .build()
+        .unwrap();
+    for path in ["/search", "/search", "/reports", "/health"] {
+        let request = http::Request::get(path).body(String::new()).unwrap();
+        send(&layer, request, |_| {
+            std::thread::sleep(HANDLER_TIME);
+            http::Response::new(String::new())
+        });
+    }
+
+    let slow = metrics.points::<u64>(SLOW_REQUESTS);
+    assert_eq!(slow.len(), 1);
+    assert_eq!(slow[0].value, 2);
+    assert_eq!(slow[0].attribute("http.route").unwrap(), "/search");
+    assert_eq!(slow[0].attribute("slo.violated"), None);
+
+    let mut violations: Vec<_> = metrics
+        .histogram::<f64>(DURATION)
+        .iter()
+        .map(|point| {
+            (
+                point.attribute("http.route").unwrap(),
+                point.attribute("slo.violated"),
+                point.count,
+            )
+        })
+        .collect();
+    violations.sort();
+    assert_eq!(
+        violations,
+        [
+            (String::from("/health"), None, 1),
+            (String::from("/reports"), Some(String::from("false")), 1),
+            (String::from("/search"), Some(String::from("true")), 2),
+        ]
+    );
+}
+
+#[test]
+fn slow_requests_are_not_counted_without_thresholds() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    assert!(!metrics.names().contains(&String::from(SLOW_REQUESTS)));
+    let duration = metrics.histogram::<f64>(DURATION);
+    assert_eq!(duration[0].attribute("slo.violated"), None);
+}
@@ -0,0 +1,156 @@
+//! The measured duration and outcome of requests are recorded on their trace span.
+#![cfg(feature = "span-attributes")]
+
+mod common;
+
+use std::borrow::Cow;
+use std::future::ready;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use opentelemetry::trace::{SpanContext, Status, TraceContextExt};
+use opentelemetry::{Context, KeyValue, Value};
+use tower::ServiceExt;
+use tower_layer::Layer;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{block_on, send, TestMetrics};
+
+/// Recording span keeping the attributes set on it.
+#[derive(Clone, Debug)]
+struct RecordingSpan {
+    span_context: SpanContext,
+    attributes: Arc<Mutex<Vec<KeyValue>>>,
+}
+
+impl RecordingSpan {
+    fn new() -> Self {
+        RecordingSpan {
+            span_context: SpanContext::empty_context(),
+            attributes: Arc::default(),
+        }
+    }
+
+    /// Value of the attribute `key` set on the span.
+    fn attribute(&self, key: &str) -> Option<Value> {
+        self.attributes
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    }
+}
+
+impl opentelemetry::trace::Span for RecordingSpan {
+    fn add_event_with_timestamp<T>(&mut self, _: T, _: SystemTime, _: Vec<KeyValue>)
+    where
+        T: Into<Cow<'static, str>>,
+    {
+    }
+
+    fn span_context(&self) -> &SpanContext {
+        &self.span_context
+    }
+
+    fn is_recording(&self) -> bool {
+        true
+    }
+
+    fn set_attribute(&mut self, attribute: KeyValue) {
+        self.attributes.lock().unwrap().push(attribute);
+    }
+
+    fn set_status(&mut self, _: Status) {}
+
+    fn update_name<T>(&mut self, _: T)
+    where
+        T: Into<Cow<'static, str>>,
+    {
+    }
+
+    fn add_link(&mut self, _: SpanContext, _: Vec<KeyValue>) {}
+
+    fn end_with_timestamp(&mut self, _: SystemTime) {}
+}
+
+/// Request whose context holds `span`.
+fn traced_request(span: &RecordingSpan) -> http::Request<String> {
+    let mut request = http::Request::new(String::new());
+    request
+        .extensions_mut()
+        .insert(Context::new().with_span(span.clone()));
+    request
+}
+
+#[test]
+fn records_the_metric_duration_and_status_on_the_span() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_span_attributes(true)
+        .build()
+        .unwrap();
+    let span = RecordingSpan::new();
+    send(&layer, traced_request(&span), |_| {
+        http::Response::builder()
+            .status(http::StatusCode::SERVICE_UNAVAILABLE)
+            .body(String::new())
+            .unwrap()
+    });
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(
+        span.attribute("http.server.request.duration"),
+        Some(Value::F64(duration[0].value))
+    );
+    assert_eq!(
+        span.attribute("http.response.status_code"),
+        Some(Value::I64(503))
+    );
+    assert_eq!(span.attribute("error.type"), None);
+}
+
+#[test]
+fn records_the_error_type_of_failed_requests_on_the_span() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_span_attributes(true)
+        .build()
+        .unwrap();
+    let service = layer.layer(tower::service_fn(|_: http::Request<String>| {
+        ready(Err::<http::Response<String>, _>(io::Error::from(
+            io::ErrorKind::ConnectionReset,
+        )))
+    }));
+    let span = RecordingSpan::new();
+    assert!(block_on(service.oneshot(traced_request(&span))).is_err());
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration[0].attribute("error.type").unwrap(), "_OTHER");
+    assert_eq!(span.attribute("error.type"), Some(Value::from("_OTHER")));
+    assert_eq!(span.attribute("http.response.status_code"), None);
+}
+
+#[test]
+fn leaves_the_span_alone_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    let span = RecordingSpan::new();
+    send(&layer, traced_request(&span), |_| {
+        http::Response::new(String::new())
+    });
+
+    assert_eq!(
+        metrics.histogram::<f64>("http.server.request.duration")[0].count,
+        1
+    );
+    assert!(span.attributes.lock().unwrap().is_empty());
+}
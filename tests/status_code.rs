@@ -0,0 +1,45 @@
+//! `http.response.status_code` is an integer attribute unless strings are requested.
+
+mod common;
+
+use opentelemetry::Value;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+/// The `http.response.status_code` of a `404` response.
+fn status_code(string_status_code: bool) -> Value {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_string_status_code(string_status_code)
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::builder()
+            .status(http::StatusCode::NOT_FOUND)
+            .body(String::new())
+            .unwrap()
+    });
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    duration[0]
+        .attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == "http.response.status_code")
+        .unwrap()
+        .value
+        .clone()
+}
+
+#[test]
+fn status_code_is_an_integer() {
+    assert_eq!(status_code(false), Value::I64(404));
+}
+
+#[test]
+fn string_status_code_has_the_reason_phrase() {
+    assert_eq!(status_code(true), Value::from("404 Not Found"));
+}
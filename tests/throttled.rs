@@ -0,0 +1,62 @@
+//! Throttled requests are counted per route and status, with the policy which fired.
+
+mod common;
+
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, ThrottlePolicy};
+
+use common::{send, TestMetrics};
+
+#[test]
+fn throttled_responses_are_counted() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|_| Some("/search"))
+        .with_throttled_requests_counter(true)
+        .build()
+        .unwrap();
+    let responses = [
+        (http::StatusCode::TOO_MANY_REQUESTS, false, Some("per-user")),
+        (http::StatusCode::TOO_MANY_REQUESTS, false, Some("per-user")),
+        (http::StatusCode::SERVICE_UNAVAILABLE, true, None),
+        // not throttled: unavailable without Retry-After, and served
+        (http::StatusCode::SERVICE_UNAVAILABLE, false, None),
+        (http::StatusCode::OK, false, None),
+    ];
+    for (status, retry_after, policy) in responses {
+        send(&layer, http::Request::new(String::new()), |_| {
+            let mut response = http::Response::builder().status(status);
+            if retry_after {
+                response = response.header(http::header::RETRY_AFTER, "30");
+            }
+            let mut response = response.body(String::new()).unwrap();
+            if let Some(policy) = policy {
+                response
+                    .extensions_mut()
+                    .insert(ThrottlePolicy::new(policy));
+            }
+            response
+        });
+    }
+
+    let mut throttled: Vec<_> = metrics
+        .points::<u64>("http.server.request.throttled")
+        .iter()
+        .map(|point| {
+            assert_eq!(point.attribute("http.route").unwrap(), "/search");
+            (
+                point.attribute("http.response.status_code").unwrap(),
+                point.attribute("http.server.throttle.policy"),
+                point.value,
+            )
+        })
+        .collect();
+    throttled.sort();
+    assert_eq!(
+        throttled,
+        [
+            (String::from("429"), Some(String::from("per-user")), 2),
+            (String::from("503"), None, 1),
+        ]
+    );
+}
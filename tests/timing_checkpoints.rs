@@ -0,0 +1,70 @@
+//! Stages marked on the timing checkpoints of a request are recorded with their duration.
+
+mod common;
+
+use std::time::Duration;
+
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, TimingCheckpoints};
+
+use common::{send, TestMetrics};
+
+const STAGE_DURATION: &str = "http.server.request.stage.duration";
+
+const STAGE_TIME: Duration = Duration::from_millis(10);
+
+#[test]
+fn stages_are_recorded_from_checkpoint_to_checkpoint() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|_| Some("/users/:id"))
+        .with_timing_checkpoints(true)
+        .build()
+        .unwrap();
+    let mut late = None;
+    send(&layer, http::Request::new(String::new()), |req| {
+        let checkpoints = req.extensions().get::<TimingCheckpoints>().unwrap();
+        std::thread::sleep(STAGE_TIME);
+        checkpoints.mark("load_user");
+        checkpoints.mark("render");
+        late = Some(checkpoints.clone());
+        http::Response::new(String::new())
+    });
+    // marked once the response is ready, e.g. while its body streams
+    late.unwrap().mark("stream");
+
+    let mut stages: Vec<_> = metrics
+        .histogram::<f64>(STAGE_DURATION)
+        .into_iter()
+        .map(|point| {
+            assert_eq!(point.attribute("http.request.method").unwrap(), "GET");
+            assert_eq!(point.attribute("http.route").unwrap(), "/users/:id");
+            assert_eq!(point.count, 1);
+            (
+                point.attribute("http.server.request.stage").unwrap(),
+                point.value,
+            )
+        })
+        .collect();
+    stages.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(stages.len(), 2);
+    assert_eq!(stages[0].0, "load_user");
+    assert!(stages[0].1 >= STAGE_TIME.as_secs_f64());
+    assert_eq!(stages[1].0, "render");
+    assert!(stages[1].1 < STAGE_TIME.as_secs_f64());
+}
+
+#[test]
+fn checkpoints_are_not_inserted_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    send(&layer, http::Request::new(String::new()), |req| {
+        assert!(req.extensions().get::<TimingCheckpoints>().is_none());
+        http::Response::new(String::new())
+    });
+
+    assert!(metrics.histogram::<f64>(STAGE_DURATION).is_empty());
+}
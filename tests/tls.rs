@@ -0,0 +1,83 @@
+//! TLS handshakes are timed, with failures classified by their `error.type`.
+
+mod common;
+
+use std::future::{pending, ready, Future};
+use std::io;
+use std::pin::pin;
+use std::task::{Context, Waker};
+use std::time::Duration;
+
+use tower_otel_http_metrics::TlsHandshakeMetrics;
+
+use common::{block_on, TestMetrics};
+
+const HANDSHAKE_DURATION: &str = "tls.server.handshake.duration";
+
+/// The `error.type` of the recorded handshakes, with their count.
+fn error_types(metrics: &TestMetrics) -> Vec<(Option<String>, u64)> {
+    let mut error_types: Vec<_> = metrics
+        .histogram::<f64>(HANDSHAKE_DURATION)
+        .iter()
+        .map(|point| (point.attribute("error.type"), point.count))
+        .collect();
+    error_types.sort();
+    error_types
+}
+
+#[test]
+fn completed_handshakes_are_recorded_without_error_type() {
+    let metrics = TestMetrics::new();
+    let tls = TlsHandshakeMetrics::new(&metrics.meter());
+    let stream = block_on(tls.handshake(async {
+        std::thread::sleep(Duration::from_millis(5));
+        Ok::<_, io::Error>("stream")
+    }))
+    .unwrap();
+    assert_eq!(stream, "stream");
+
+    let duration = metrics.histogram::<f64>(HANDSHAKE_DURATION);
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert!(duration[0].attributes.is_empty());
+    assert!(duration[0].value >= 0.005);
+}
+
+#[test]
+fn failed_handshakes_are_recorded_with_their_error_type() {
+    let metrics = TestMetrics::new();
+    let tls = TlsHandshakeMetrics::new(&metrics.meter());
+    for kind in [
+        io::ErrorKind::UnexpectedEof,
+        io::ErrorKind::ConnectionReset,
+        io::ErrorKind::InvalidData,
+        io::ErrorKind::TimedOut,
+        io::ErrorKind::PermissionDenied,
+    ] {
+        let handshake = ready(Err::<(), _>(io::Error::from(kind)));
+        block_on(tls.handshake(handshake)).unwrap_err();
+    }
+
+    assert_eq!(
+        error_types(&metrics),
+        [
+            (Some(String::from("_OTHER")), 1),
+            (Some(String::from("connection_reset")), 2),
+            (Some(String::from("timeout")), 1),
+            (Some(String::from("tls_error")), 1),
+        ]
+    );
+}
+
+#[test]
+fn abandoned_handshakes_are_recorded_as_timeouts() {
+    let metrics = TestMetrics::new();
+    let tls = TlsHandshakeMetrics::new(&metrics.meter());
+    {
+        let handshake = pin!(tls.handshake(pending::<io::Result<()>>()));
+        let polled = handshake.poll(&mut Context::from_waker(Waker::noop()));
+        assert!(polled.is_pending());
+    }
+
+    assert_eq!(error_types(&metrics), [(Some(String::from("timeout")), 1)]);
+}
@@ -0,0 +1,73 @@
+//! Recording follows the sampling decision of the request's trace.
+#![cfg(feature = "trace-sampling")]
+
+mod common;
+
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use opentelemetry::{Context, KeyValue};
+use tower_otel_http_metrics::{AttributeExtractor, HTTPMetricsLayerBuilder, TraceSampling};
+
+use common::{send, TestMetrics};
+
+/// Request within a trace whose sampling decision is `sampled`.
+fn traced_request(sampled: bool) -> http::Request<String> {
+    let flags = if sampled {
+        TraceFlags::SAMPLED
+    } else {
+        TraceFlags::default()
+    };
+    let span_context = SpanContext::new(
+        TraceId::from_bytes([1; 16]),
+        SpanId::from_bytes([1; 8]),
+        flags,
+        true,
+        TraceState::default(),
+    );
+    let mut request = http::Request::new(String::new());
+    request
+        .extensions_mut()
+        .insert(Context::new().with_remote_span_context(span_context));
+    request
+}
+
+/// Metrics of a layer with `sampling` handling a sampled and an unsampled request.
+fn sampled_metrics(sampling: TraceSampling) -> TestMetrics {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_attribute_extractor(AttributeExtractor::request(|_| {
+            Some(KeyValue::new("app.tenant", "acme"))
+        }))
+        .with_trace_sampling(sampling)
+        .build()
+        .unwrap();
+    for sampled in [true, false] {
+        send(&layer, traced_request(sampled), |_| {
+            http::Response::new(String::new())
+        });
+    }
+    metrics
+}
+
+#[test]
+fn only_sampled_requests_are_measured() {
+    let metrics = sampled_metrics(TraceSampling::Measurements);
+
+    let duration = metrics.histogram::<f64>("http.server.request.duration");
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("app.tenant").unwrap(), "acme");
+}
+
+#[test]
+fn only_sampled_requests_get_extractor_attributes() {
+    let metrics = sampled_metrics(TraceSampling::ExtractorAttributes);
+
+    let mut tenants: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("app.tenant"), point.count))
+        .collect();
+    tenants.sort();
+    assert_eq!(tenants, [(None, 1), (Some(String::from("acme")), 1)]);
+}
@@ -0,0 +1,70 @@
+//! `deployment.variant` is read from a request header or baggage entry.
+
+mod common;
+
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, TrafficSplitSource};
+
+use common::{send, TestMetrics};
+
+/// The `deployment.variant` values recorded for requests carrying `headers`, with their counts.
+fn variants(
+    source: TrafficSplitSource,
+    headers: &[Option<(&'static str, &'static str)>],
+) -> Vec<(Option<String>, u64)> {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_traffic_split_attribute(source, ["canary", "stable"])
+        .build()
+        .unwrap();
+    for header in headers {
+        let mut request = http::Request::builder();
+        if let Some((name, value)) = header {
+            request = request.header(*name, *value);
+        }
+        send(&layer, request.body(String::new()).unwrap(), |_| {
+            http::Response::new(String::new())
+        });
+    }
+
+    let mut variants: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("deployment.variant"), point.count))
+        .collect();
+    variants.sort();
+    variants
+}
+
+#[test]
+fn header_variants_are_recorded() {
+    assert_eq!(
+        variants(
+            TrafficSplitSource::Header(http::HeaderName::from_static("x-canary")),
+            &[
+                Some(("x-canary", "canary")),
+                Some(("x-canary", "stable")),
+                Some(("x-canary", "stable")),
+                Some(("x-canary", "blue")),
+                None,
+            ],
+        ),
+        [
+            (None, 1),
+            (Some(String::from("_OTHER")), 1),
+            (Some(String::from("canary")), 1),
+            (Some(String::from("stable")), 2),
+        ]
+    );
+}
+
+#[test]
+fn baggage_variants_are_recorded() {
+    assert_eq!(
+        variants(
+            TrafficSplitSource::Baggage("deployment.variant".into()),
+            &[Some(("baggage", "user.id=1, deployment.variant=canary"))],
+        ),
+        [(Some(String::from("canary")), 1)]
+    );
+}
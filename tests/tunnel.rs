@@ -0,0 +1,80 @@
+//! `CONNECT` requests are recorded as tunnels, counted for as long as they stay open.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, OpenTunnel};
+
+use common::{send, TestMetrics};
+
+const DURATION: &str = "http.server.request.duration";
+const OPEN_TUNNELS: &str = "http.server.open_tunnels";
+
+/// `CONNECT` request for a tunnel to `authority`.
+fn connect(authority: &str) -> http::Request<String> {
+    http::Request::connect(authority)
+        .body(String::new())
+        .unwrap()
+}
+
+#[test]
+fn connect_requests_have_no_route_or_scheme() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_extractor(|_| Some("/{*path}"))
+        .with_default_url_scheme("https")
+        .with_connect_tunnels(true)
+        .build()
+        .unwrap();
+    send(&layer, connect("example.com:443"), |_| {
+        http::Response::new(String::new())
+    });
+
+    let duration = metrics.histogram::<f64>(DURATION);
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(
+        duration[0].attribute("http.request.method").unwrap(),
+        "CONNECT"
+    );
+    assert_eq!(
+        duration[0].attribute("http.response.status_code").unwrap(),
+        "200"
+    );
+    assert_eq!(duration[0].attribute("http.route"), None);
+    assert_eq!(duration[0].attribute("url.scheme"), None);
+}
+
+#[test]
+fn established_tunnels_are_counted_until_dropped() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_open_tunnels_counter(true)
+        .build()
+        .unwrap();
+    let tunnel = Arc::new(Mutex::new(None));
+    let serving = tunnel.clone();
+    send(&layer, connect("example.com:443"), move |mut req| {
+        *serving.lock().unwrap() = req.extensions_mut().remove::<OpenTunnel>();
+        http::Response::new(String::new())
+    });
+    // rejected tunnels are never counted
+    send(&layer, connect("internal:22"), |_| {
+        http::Response::builder()
+            .status(http::StatusCode::FORBIDDEN)
+            .body(String::new())
+            .unwrap()
+    });
+
+    let open_tunnels = metrics.points::<i64>(OPEN_TUNNELS);
+    assert_eq!(open_tunnels.len(), 1);
+    assert_eq!(open_tunnels[0].value, 1);
+    assert!(open_tunnels[0].attributes.is_empty());
+
+    tunnel.lock().unwrap().take();
+    assert_eq!(metrics.points::<i64>(OPEN_TUNNELS)[0].value, 0);
+    assert_eq!(metrics.histogram::<f64>(DURATION).len(), 2);
+}
@@ -0,0 +1,62 @@
+//! Typed paths keep their route template as `http.route` by registering their `PATH`.
+#![cfg(feature = "route-patterns")]
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+/// Stand-in for axum-extra's `TypedPath`, which carries the route template of a path type.
+trait TypedPath {
+    const PATH: &'static str;
+}
+
+/// Typed path of a user, as derived with `#[typed_path("/users/:id")]`.
+struct UserPath;
+
+impl TypedPath for UserPath {
+    const PATH: &'static str = "/users/:id";
+}
+
+/// Typed path of a user's posts, as derived with `#[typed_path("/users/:id/posts/:post_id")]`.
+struct UserPostPath;
+
+impl TypedPath for UserPostPath {
+    const PATH: &'static str = "/users/:id/posts/:post_id";
+}
+
+#[test]
+fn typed_paths_are_recorded_as_their_route_template() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_route_pattern(UserPath::PATH)
+        .with_route_pattern(UserPostPath::PATH)
+        .build()
+        .unwrap();
+    for uri in [
+        "/users/1",
+        "/users/2?full=true",
+        "/users/1/posts/7",
+        "/users",
+    ] {
+        let request = http::Request::get(uri).body(String::new()).unwrap();
+        send(&layer, request, |_| http::Response::new(String::new()));
+    }
+
+    let mut routes: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("http.route"), point.count))
+        .collect();
+    routes.sort();
+    assert_eq!(
+        routes,
+        [
+            (None, 1),
+            (Some(String::from("/users/:id")), 2),
+            (Some(String::from("/users/:id/posts/:post_id")), 1),
+        ]
+    );
+}
@@ -0,0 +1,46 @@
+//! Unbinding a layer from its meter provider, e.g. once the provider is shut down.
+
+mod common;
+
+use std::convert::Infallible;
+
+use tower::{Service, ServiceExt};
+use tower_layer::Layer;
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{block_on, TestMetrics};
+
+fn request_count(metrics: &TestMetrics) -> u64 {
+    metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| point.count)
+        .sum()
+}
+
+#[test]
+fn unbound_services_record_nothing_until_rebound() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    let mut service = layer.layer(tower::service_fn(|_: http::Request<String>| async {
+        Ok::<_, Infallible>(http::Response::new(String::new()))
+    }));
+    let mut request = || {
+        let service = block_on(service.ready()).unwrap();
+        block_on(service.call(http::Request::new(String::new()))).unwrap();
+    };
+
+    request();
+    assert_eq!(request_count(&metrics), 1);
+
+    layer.unbind();
+    request();
+    assert_eq!(request_count(&metrics), 1);
+
+    layer.rebind(metrics.meter());
+    request();
+    assert_eq!(request_count(&metrics), 2);
+}
@@ -0,0 +1,68 @@
+//! `url.path` records request paths through a mandatory sanitizer.
+
+mod common;
+
+use tower_otel_http_metrics::{mask_path_ids, HTTPMetricsLayerBuilder};
+
+use common::{send, TestMetrics};
+
+/// The `url.path` values recorded for requests to `uris`.
+fn url_paths<F>(sanitizer: F, uris: &[&str]) -> Vec<(String, u64)>
+where
+    F: Fn(&str) -> String + Send + Sync + 'static,
+{
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_url_path_attribute(sanitizer)
+        .build()
+        .unwrap();
+    for uri in uris {
+        let request = http::Request::get(*uri).body(String::new()).unwrap();
+        send(&layer, request, |_| http::Response::new(String::new()));
+    }
+
+    let mut paths: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| (point.attribute("url.path").unwrap(), point.count))
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn identifiers_are_masked() {
+    assert_eq!(
+        url_paths(
+            mask_path_ids,
+            &[
+                "/users/42/files/6ba7b810-9dad-11d1-80b4-00c04fd430c8",
+                "/users/7/files/00000000-0000-0000-0000-000000000000?download=1",
+                "/users/me",
+            ],
+        ),
+        [
+            (String::from("/users/me"), 1),
+            (String::from("/users/{id}/files/{uuid}"), 2),
+        ]
+    );
+}
+
+#[test]
+fn custom_sanitizers_bound_the_paths() {
+    let sanitizer = |path: &str| {
+        if path.starts_with("/internal/") {
+            String::from(path)
+        } else {
+            String::from("other")
+        }
+    };
+    assert_eq!(
+        url_paths(sanitizer, &["/internal/health", "/a", "/b"]),
+        [
+            (String::from("/internal/health"), 1),
+            (String::from("other"), 2),
+        ]
+    );
+}
@@ -0,0 +1,50 @@
+//! Handler-supplied usage units are metered per tenant.
+
+mod common;
+
+use tower_otel_http_metrics::{HTTPMetricsLayerBuilder, UsageUnits};
+
+use common::{send, TestMetrics};
+
+#[test]
+fn units_are_added_per_tenant() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_usage_counter("app.usage.tokens", "{token}")
+        .with_usage_tenant_header(http::HeaderName::from_static("x-tenant-id"))
+        .build()
+        .unwrap();
+    for (tenant, units) in [
+        (Some("acme"), Some(10)),
+        (Some("acme"), Some(5)),
+        (None, Some(1)),
+    ] {
+        let mut request = http::Request::post("/completions");
+        if let Some(tenant) = tenant {
+            request = request.header("x-tenant-id", tenant);
+        }
+        send(&layer, request.body(String::new()).unwrap(), |_| {
+            let mut response = http::Response::new(String::new());
+            if let Some(units) = units {
+                response.extensions_mut().insert(UsageUnits(units));
+            }
+            response
+        });
+    }
+    // responses without units are not metered
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    let mut usage: Vec<_> = metrics
+        .points::<u64>("app.usage.tokens")
+        .iter()
+        .map(|point| {
+            assert_eq!(point.attribute("http.request.method").unwrap(), "POST");
+            (point.attribute("tenant.id"), point.value)
+        })
+        .collect();
+    usage.sort();
+    assert_eq!(usage, [(None, 1), (Some(String::from("acme")), 15)]);
+}
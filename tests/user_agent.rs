@@ -0,0 +1,57 @@
+//! `user_agent.device.category` classifies the user agent of requests.
+#![cfg(feature = "user-agent")]
+
+mod common;
+
+use tower_otel_http_metrics::HTTPMetricsLayerBuilder;
+
+use common::{send, TestMetrics};
+
+const DESKTOP: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) \
+     Chrome/120.0.0.0 Safari/537.36";
+const MOBILE: &str =
+    "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, \
+     like Gecko) Version/17.0 Mobile/15E148 Safari/604.1";
+const BOT: &str = "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)";
+
+#[test]
+fn requests_are_counted_per_device_category() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_user_agent_device_category(true)
+        .build()
+        .unwrap();
+    for user_agent in [Some(DESKTOP), Some(MOBILE), Some(MOBILE), Some(BOT), None] {
+        let mut request = http::Request::builder();
+        if let Some(user_agent) = user_agent {
+            request = request.header(http::header::USER_AGENT, user_agent);
+        }
+        send(&layer, request.body(String::new()).unwrap(), |_| {
+            http::Response::new(String::new())
+        });
+    }
+
+    let mut categories: Vec<_> = metrics
+        .histogram::<f64>("http.server.request.duration")
+        .iter()
+        .map(|point| {
+            assert_eq!(point.attribute("user_agent.original"), None);
+            (
+                point.attribute("user_agent.device.category").unwrap(),
+                point.count,
+            )
+        })
+        .collect();
+    categories.sort();
+    assert_eq!(
+        categories,
+        [
+            (String::from("bot"), 1),
+            (String::from("desktop"), 1),
+            (String::from("mobile"), 2),
+            (String::from("other"), 1),
+        ]
+    );
+}
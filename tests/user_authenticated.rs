@@ -0,0 +1,90 @@
+//! Requests marked by auth middleware are told apart from anonymous ones.
+
+mod common;
+
+use tower_otel_http_metrics::{Authenticated, HTTPMetricsLayerBuilder};
+
+use common::{send, TestMetrics};
+
+const DURATION: &str = "http.server.request.duration";
+
+#[test]
+fn authenticated_requests_are_marked_from_request_or_response_extensions() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_user_authenticated_attribute(true)
+        .build()
+        .unwrap();
+    // marked by middleware around the layer
+    let mut request = http::Request::new(String::new());
+    request.extensions_mut().insert(Authenticated::new());
+    send(&layer, request, |_| http::Response::new(String::new()));
+    // marked by middleware inside the layer
+    send(&layer, http::Request::new(String::new()), |_| {
+        let mut response = http::Response::new(String::new());
+        response.extensions_mut().insert(Authenticated::new());
+        response
+    });
+    send(&layer, http::Request::new(String::new()), |_| {
+        http::Response::new(String::new())
+    });
+
+    let mut authenticated: Vec<_> = metrics
+        .histogram::<f64>(DURATION)
+        .iter()
+        .map(|point| {
+            assert_eq!(point.attribute("user.principal.bucket"), None);
+            (point.attribute("user.authenticated").unwrap(), point.count)
+        })
+        .collect();
+    authenticated.sort();
+    assert_eq!(
+        authenticated,
+        [(String::from("false"), 1), (String::from("true"), 2)]
+    );
+}
+
+#[test]
+fn principals_are_hashed_into_stable_buckets() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .with_principal_buckets(8)
+        .build()
+        .unwrap();
+    for _ in 0..3 {
+        let mut request = http::Request::new(String::new());
+        request
+            .extensions_mut()
+            .insert(Authenticated::new().with_principal("user-1234"));
+        send(&layer, request, |_| http::Response::new(String::new()));
+    }
+
+    let duration = metrics.histogram::<f64>(DURATION);
+    assert_eq!(duration.len(), 1);
+    assert_eq!(duration[0].count, 3);
+    assert_eq!(duration[0].attribute("user.authenticated").unwrap(), "true");
+    let bucket: u32 = duration[0]
+        .attribute("user.principal.bucket")
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(bucket < 8);
+}
+
+#[test]
+fn requests_are_not_marked_by_default() {
+    let metrics = TestMetrics::new();
+    let layer = HTTPMetricsLayerBuilder::new()
+        .with_meter(metrics.meter())
+        .build()
+        .unwrap();
+    let mut request = http::Request::new(String::new());
+    request.extensions_mut().insert(Authenticated::new());
+    send(&layer, request, |_| http::Response::new(String::new()));
+
+    let duration = metrics.histogram::<f64>(DURATION);
+    assert_eq!(duration[0].count, 1);
+    assert_eq!(duration[0].attribute("user.authenticated"), None);
+}